@@ -0,0 +1,50 @@
+// GhostWire Client - Contact Aliases
+// Local nicknames for contacts, stored in `<data dir>/ghostwire/aliases.json`.
+// Aliases never leave this machine; peers always see real usernames.
+
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// File name of the alias store inside the data directory
+const ALIASES_FILE: &str = "aliases.json";
+
+/// Maximum length of an alias, in characters
+pub const MAX_ALIAS_LEN: usize = 32;
+
+/// Username -> alias map
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Aliases(BTreeMap<String, String>);
+
+impl Aliases {
+    /// Load aliases from the data directory
+    pub fn load() -> Self {
+        storage::load_json(ALIASES_FILE)
+    }
+
+    /// Write aliases back to the data directory
+    pub fn save(&self) -> anyhow::Result<()> {
+        storage::save_json(ALIASES_FILE, self)
+    }
+
+    /// Name to show for a user: their alias if set, otherwise the username
+    pub fn display<'a>(&'a self, username: &'a str) -> &'a str {
+        self.0.get(username).map(String::as_str).unwrap_or(username)
+    }
+
+    /// Whether a user has an alias
+    pub fn contains(&self, username: &str) -> bool {
+        self.0.contains_key(username)
+    }
+
+    /// Set or replace a user's alias
+    pub fn set(&mut self, username: &str, alias: String) {
+        self.0.insert(username.to_string(), alias);
+    }
+
+    /// Remove a user's alias, returning it if there was one
+    pub fn remove(&mut self, username: &str) -> Option<String> {
+        self.0.remove(username)
+    }
+}
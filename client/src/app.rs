@@ -1,73 +1,225 @@
 // GhostWire Client - Application State
 // This module manages the core application state and business logic
 
+use crate::aliases::Aliases;
+use crate::favorites::ChannelOrder;
+use crate::autoreply::{AutoAction, AutoReplies};
+use crate::blocked::Blocked;
+use crate::cmdline::CommandLine;
+use crate::config::{Config, PresenceFilter, SidebarSort};
+use crate::crypto::{self, SecretKey};
+use crate::e2e::E2e;
+use crate::export::TranscriptMessage;
+use crate::glyphs::{self, Glyphs};
+use crate::history;
+use crate::i18n::{self, fill, Strings};
+use crate::logging::LogBuffer;
+use crate::metrics::MetricsWriter;
+use crate::notify::DoNotDisturb;
+use crate::relays::split_channel;
+use crate::reminders::Reminders;
+use crate::replay::ReplayWindow;
+use crate::sanitize;
+use crate::search::{self, SearchHit};
+use crate::session::{SavedChannel, SessionState};
+use crate::starred::{Starred, StarredMessage};
+use crate::uri::LinkTarget;
+use crate::verify;
+use anyhow::Context;
 use chrono::{DateTime, Utc};
+use ghostwire_protocol::e2e::{Envelope, RoomEnvelope};
+use ghostwire_protocol::group::{GroupError, GroupEvent, GroupInfo};
+use ghostwire_protocol::hello::ServerInfo;
+use ghostwire_protocol::whois::WhoisInfo;
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
-
-/// Maximum number of messages to keep in memory
-const MAX_MESSAGES: usize = 1000;
-
-/// Maximum number of users to display
-const MAX_USERS: usize = 100;
-
-/// Message types for the GhostWire protocol
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum MessageType {
-    #[serde(rename = "MSG")]
-    Message,
-    #[serde(rename = "AUTH")]
-    Auth,
-    #[serde(rename = "SYS")]
-    System,
-}
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::warn;
+use zeroize::{Zeroize, Zeroizing};
 
-/// Metadata for each message
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MessageMeta {
-    pub sender: String,
-    pub timestamp: i64,
-}
+/// Number of recent message IDs remembered per channel for deduplication
+const RECENT_IDS: usize = 256;
 
-/// Wire protocol message structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct WireMessage {
-    #[serde(rename = "type")]
-    pub msg_type: MessageType,
-    pub payload: String,
-    /// Channel ID: "global", "dm:user1:user2", or "group:name"
-    #[serde(default = "default_channel")]
-    pub channel: String,
-    pub meta: MessageMeta,
-}
+/// Window for the telemetry "most active channels" list, in seconds
+const ACTIVITY_WINDOW_SECS: i64 = 300;
 
-/// Default channel is global for backward compatibility
-fn default_channel() -> String {
-    "global".to_string()
-}
+/// Number of latency measurements kept for the sparkline
+const LATENCY_SAMPLES: usize = 60;
+
+/// ID of the virtual, read-only channel aggregating starred messages.
+/// The `~` prefix can never pass wire channel validation.
+pub const STARRED_CHANNEL: &str = "~starred";
+
+/// ID of the virtual, read-only channel collecting messages that mention us
+/// or a watch word during this session
+pub const MENTIONS_CHANNEL: &str = "~mentions";
+
+/// ID of the virtual, read-only channel collecting join, leave, rename and
+/// topic notices from every channel (unless `activity_channel` is off)
+pub const ACTIVITY_CHANNEL: &str = "~activity";
 
 /// Internal chat message representation
 #[derive(Debug, Clone)]
 pub struct ChatMessage {
-    pub sender: String,
-    pub content: String,
+    /// Wire message ID (None for system notices and peers without IDs)
+    pub id: Option<String>,
+    /// Shared with every other message from the same sender (see `Senders`)
+    pub sender: Arc<str>,
+    /// Shared with starred copies and exports rather than cloned
+    pub content: Arc<str>,
     pub timestamp: DateTime<Utc>,
     pub is_system: bool,
+    /// Raw wire frame this message was decoded from (None for local messages)
+    pub raw: Option<String>,
+    /// Control sequences were stripped from the sender or content
+    pub sanitized: bool,
+    /// Sender's clock was far off, so the timestamp was replaced with receive time
+    pub clock_skewed: bool,
+    /// Starred locally
+    pub starred: bool,
+    /// Relay message of the day, drawn as a banner (content keeps its line breaks)
+    pub motd: bool,
+    /// A `/remind` reminder, shown even when system messages are hidden
+    pub reminder: bool,
+    /// Client and relay versions may not work together; drawn as a red
+    /// banner and shown even when system messages are hidden
+    pub compat: bool,
+    /// Mentions us or a watch word
+    pub highlighted: bool,
+    /// What we know about the delivery of a message we sent
+    pub delivery: Delivery,
+    /// End-to-end encrypted by a sender whose identity key we verified
+    pub verified: bool,
+    /// ||Spoilers|| are shown rather than blacked out (`x` toggles)
+    pub revealed: bool,
+    /// An action (`/roll`, `/flip`, `/shrug`), drawn as "* sender content"
+    pub action: bool,
+}
+
+/// Delivery state of one of our own messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Delivery {
+    /// Sent, with no news from the relay since
+    #[default]
+    Sent,
+    /// The recipient is offline and the relay holds the message for them
+    Queued,
+    /// The recipient's client reported receiving it
+    Delivered,
+    /// The recipient has seen it
+    Read,
+    /// The relay reported the recipient offline and dropped the message
+    Undelivered,
+    /// It never left this client: writing it to the relay failed, or it
+    /// could not be encrypted
+    Failed,
+}
+
+/// What the connection to the relay is doing, shown in the chat title
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// First attempt, before anything has failed
+    Connecting,
+    Connected,
+    /// An attempt failed; attempt number `attempt` starts at `next_retry`
+    Reconnecting { attempt: u32, next_retry: DateTime<Utc> },
+    /// The connection dropped, or no relay could be reached
+    Disconnected { reason: String },
+    /// The relay kicked us; we stay disconnected until restarted
+    Kicked { reason: String },
 }
 
 impl ChatMessage {
-    pub fn new(sender: String, content: String, is_system: bool) -> Self {
+    pub fn new(sender: impl Into<Arc<str>>, content: impl Into<Arc<str>>, is_system: bool) -> Self {
         Self {
-            sender,
-            content,
+            id: None,
+            sender: sender.into(),
+            content: content.into(),
             timestamp: Utc::now(),
             is_system,
+            raw: None,
+            sanitized: false,
+            clock_skewed: false,
+            starred: false,
+            motd: false,
+            reminder: false,
+            compat: false,
+            highlighted: false,
+            delivery: Delivery::Sent,
+            verified: false,
+            revealed: false,
+            action: false,
         }
     }
 
     pub fn system(content: String) -> Self {
-        Self::new("SYSTEM".to_string(), content, true)
+        Self::new("SYSTEM", content, true)
+    }
+
+    /// The relay's message of the day
+    pub fn motd(lines: Vec<String>) -> Self {
+        let mut msg = Self::system(lines.join("\n"));
+        msg.motd = true;
+        msg
+    }
+
+    /// Warning that this client and the relay may not work together
+    pub fn compat(content: String) -> Self {
+        let mut msg = Self::system(content);
+        msg.compat = true;
+        msg
+    }
+
+    /// A reminder set with `/remind` that has come due
+    pub fn reminder(content: String) -> Self {
+        let mut msg = Self::system(content);
+        msg.reminder = true;
+        msg
+    }
+}
+
+/// Pool of sender names, so a long history holds one copy of each name
+/// instead of one per message
+#[derive(Debug, Clone, Default)]
+pub struct Senders(HashSet<Arc<str>>);
+
+impl Senders {
+    /// The pooled copy of `name`, added if it is new
+    pub fn intern(&mut self, name: &str) -> Arc<str> {
+        if let Some(pooled) = self.0.get(name) {
+            return Arc::clone(pooled);
+        }
+        let pooled: Arc<str> = Arc::from(name);
+        self.0.insert(Arc::clone(&pooled));
+        pooled
+    }
+}
+
+/// Bounded set of recently seen message IDs, evicting the oldest first
+#[derive(Debug, Clone, Default)]
+pub struct RecentIds {
+    order: VecDeque<String>,
+    ids: HashSet<String>,
+}
+
+impl RecentIds {
+    /// Check whether an ID has been seen recently
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids.contains(id)
+    }
+    
+    /// Remember an ID, forgetting the oldest once `RECENT_IDS` is exceeded
+    pub fn insert(&mut self, id: String) {
+        if !self.ids.insert(id.clone()) {
+            return;
+        }
+        self.order.push_back(id);
+        if self.order.len() > RECENT_IDS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
     }
 }
 
@@ -100,18 +252,74 @@ impl User {
         
         time_since_activity > idle_threshold
     }
+    
+    /// Roster section the user is listed under
+    pub fn status(&self) -> UserStatus {
+        if !self.is_online {
+            UserStatus::Offline
+        } else if self.is_idle() {
+            UserStatus::Idle
+        } else {
+            UserStatus::Active
+        }
+    }
 }
 
-/// Channel type variants
+/// Roster sections, in display order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UserStatus {
+    Active,
+    Idle,
+    Offline,
+}
+
+impl UserStatus {
+    pub const ALL: [UserStatus; 3] = [UserStatus::Active, UserStatus::Idle, UserStatus::Offline];
+}
+
+/// A selectable roster row. Users are kept by name, so the selection stays
+/// on them when the roster re-sorts.
 #[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RosterKey {
+    Section(UserStatus),
+    User(String),
+}
+
+/// A row of the sorted roster
+#[derive(Debug, Clone, Copy)]
+pub enum RosterRow<'a> {
+    /// Section header with the number of users in it
+    Section { status: UserStatus, count: usize, collapsed: bool },
+    User(&'a User),
+}
+
+impl RosterRow<'_> {
+    pub fn key(&self) -> RosterKey {
+        match self {
+            RosterRow::Section { status, .. } => RosterKey::Section(*status),
+            RosterRow::User(user) => RosterKey::User(user.username.clone()),
+        }
+    }
+}
+
+/// Channel type variants
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
 pub enum ChannelType {
     /// Global channel - all users
     Global,
     /// Direct message with another user
     DirectMessage { other_user: String },
-    /// Group channel with multiple users
-    #[allow(dead_code)]
-    Group { name: String, members: Vec<String> },
+    /// Group channel with members and operators
+    Group { name: String, info: GroupInfo },
+    /// Open room anyone can `/join`; `encrypted` if joined with a passphrase
+    Room { name: String, encrypted: bool },
+    /// Virtual channel listing starred messages from every channel
+    Starred,
+    /// Virtual channel collecting mentions and watch words from every channel
+    Mentions,
+    /// Virtual channel collecting join/leave/rename/topic notices
+    Activity,
 }
 
 /// A chat channel
@@ -125,6 +333,19 @@ pub struct Channel {
     pub messages: VecDeque<ChatMessage>,
     /// Number of unread messages
     pub unread_count: usize,
+    /// IDs of recently added messages, for dropping replayed duplicates
+    pub recent_ids: RecentIds,
+    /// IDs of pinned messages, oldest pin first
+    pub pinned: Vec<String>,
+    /// Per-channel override of `Config::hide_system_messages`
+    pub hide_system: Option<bool>,
+    /// Timestamp of the newest message seen here; older arrivals (relay
+    /// replays after a restart) do not count as unread
+    pub read_marker: Option<DateTime<Utc>>,
+    /// ID of the message selected when we left the channel scrolled up
+    pub scroll_anchor: Option<String>,
+    /// Stored history may hold messages older than the buffer's
+    pub older_history: bool,
 }
 
 impl Channel {
@@ -133,47 +354,195 @@ impl Channel {
         Self {
             id: "global".to_string(),
             channel_type: ChannelType::Global,
-            messages: VecDeque::with_capacity(MAX_MESSAGES),
+            messages: VecDeque::new(),
+            unread_count: 0,
+            recent_ids: RecentIds::default(),
+            pinned: Vec::new(),
+            hide_system: None,
+            read_marker: None,
+            scroll_anchor: None,
+            older_history: false,
+        }
+    }
+    
+    /// Create the virtual starred-messages channel
+    pub fn starred() -> Self {
+        Self {
+            id: STARRED_CHANNEL.to_string(),
+            channel_type: ChannelType::Starred,
+            messages: VecDeque::new(),
+            unread_count: 0,
+            recent_ids: RecentIds::default(),
+            pinned: Vec::new(),
+            hide_system: None,
+            read_marker: None,
+            scroll_anchor: None,
+            older_history: false,
+        }
+    }
+    
+    /// Create the virtual mentions channel
+    pub fn mentions() -> Self {
+        Self {
+            id: MENTIONS_CHANNEL.to_string(),
+            channel_type: ChannelType::Mentions,
+            messages: VecDeque::new(),
+            unread_count: 0,
+            recent_ids: RecentIds::default(),
+            pinned: Vec::new(),
+            hide_system: None,
+            read_marker: None,
+            scroll_anchor: None,
+            older_history: false,
+        }
+    }
+    
+    /// Create the virtual activity channel
+    pub fn activity() -> Self {
+        Self {
+            id: ACTIVITY_CHANNEL.to_string(),
+            channel_type: ChannelType::Activity,
+            messages: VecDeque::new(),
             unread_count: 0,
+            recent_ids: RecentIds::default(),
+            pinned: Vec::new(),
+            hide_system: None,
+            read_marker: None,
+            scroll_anchor: None,
+            older_history: false,
         }
     }
     
     /// Create a new DM channel
     pub fn dm(current_user: &str, other_user: String) -> Self {
-        // Sort usernames alphabetically for consistent channel IDs
-        let (user1, user2) = if current_user < other_user.as_str() {
-            (current_user, other_user.as_str())
-        } else {
-            (other_user.as_str(), current_user)
-        };
-        
         Self {
-            id: format!("dm:{}:{}", user1, user2),
+            id: dm_channel_id(current_user, &other_user),
             channel_type: ChannelType::DirectMessage { other_user },
-            messages: VecDeque::with_capacity(MAX_MESSAGES),
+            messages: VecDeque::new(),
             unread_count: 0,
+            recent_ids: RecentIds::default(),
+            pinned: Vec::new(),
+            hide_system: None,
+            read_marker: None,
+            scroll_anchor: None,
+            older_history: false,
         }
     }
     
-    /// Create a new group channel    
-    /// Create a group channel (reserved for future use)
-    #[allow(dead_code)]
-    pub fn group(name: String, members: Vec<String>) -> Self {
+    /// Create a group channel
+    pub fn group(name: String, info: GroupInfo) -> Self {
         Self {
             id: format!("group:{}", name),
-            channel_type: ChannelType::Group { name: name.clone(), members },
-            messages: VecDeque::with_capacity(MAX_MESSAGES),
+            channel_type: ChannelType::Group { name: name.clone(), info },
+            messages: VecDeque::new(),
+            unread_count: 0,
+            recent_ids: RecentIds::default(),
+            pinned: Vec::new(),
+            hide_system: None,
+            read_marker: None,
+            scroll_anchor: None,
+            older_history: false,
+        }
+    }
+    
+    /// Create a room channel
+    pub fn room(name: String, encrypted: bool) -> Self {
+        Self {
+            id: format!("room:{}", name),
+            channel_type: ChannelType::Room { name, encrypted },
+            messages: VecDeque::new(),
             unread_count: 0,
+            recent_ids: RecentIds::default(),
+            pinned: Vec::new(),
+            hide_system: None,
+            read_marker: None,
+            scroll_anchor: None,
+            older_history: false,
         }
     }
     
-    /// Add a message to this channel
-    pub fn add_message(&mut self, message: ChatMessage) {
+    /// Move this channel into an extra relay's namespace (`relay/id`)
+    pub fn on_relay(mut self, relay: &str) -> Self {
+        self.id = format!("{}/{}", relay, self.id);
+        self
+    }
+    
+    /// Add a message to this channel, dropping the oldest beyond `limit`
+    pub fn add_message(&mut self, message: ChatMessage, limit: usize) {
+        if let Some(id) = &message.id {
+            self.recent_ids.insert(id.clone());
+        }
         self.messages.push_back(message);
         
-        // Keep only the last MAX_MESSAGES
-        if self.messages.len() > MAX_MESSAGES {
-            self.messages.pop_front();
+        while self.messages.len() > limit {
+            // Stored messages can be read back when scrolled to
+            if self.messages.pop_front().is_some_and(|msg| !msg.is_system) {
+                self.older_history = true;
+            }
+        }
+    }
+    
+    /// Put messages read from the stored history in front of the buffer
+    fn prepend_stored(&mut self, stored: Vec<TranscriptMessage>, senders: &mut Senders) {
+        for stored in stored.into_iter().rev() {
+            let mut msg = ChatMessage::new(senders.intern(&stored.sender), stored.content, false);
+            msg.timestamp = stored.timestamp;
+            msg.action = stored.action;
+            if let Some(id) = &stored.id {
+                self.recent_ids.insert(id.clone());
+            }
+            msg.id = stored.id;
+            self.messages.push_front(msg);
+        }
+    }
+    
+    /// Move the read marker up to the newest message
+    pub fn mark_read(&mut self) {
+        let newest = self.messages.iter().filter(|m| !m.is_system).map(|m| m.timestamp).max();
+        self.read_marker = self.read_marker.max(newest);
+    }
+    
+    /// Whether a message from this time was already seen
+    pub fn is_read(&self, timestamp: DateTime<Utc>) -> bool {
+        self.read_marker.is_some_and(|marker| timestamp <= marker)
+    }
+    
+    /// Buffer indices of the messages to render, skipping system notices if hidden
+    pub fn visible_indices(&self, hide_system: bool) -> Vec<usize> {
+        self.messages
+            .iter()
+            .enumerate()
+            .filter(|(_, msg)| !hide_system || !msg.is_system || msg.motd || msg.reminder || msg.compat)
+            .map(|(i, _)| i)
+            .collect()
+    }
+    
+    /// Pin or unpin a message by ID; returns false if nothing changed
+    pub fn set_pinned(&mut self, message_id: &str, pinned: bool) -> bool {
+        let position = self.pinned.iter().position(|id| id == message_id);
+        match (pinned, position) {
+            (true, None) => self.pinned.push(message_id.to_string()),
+            (false, Some(i)) => {
+                self.pinned.remove(i);
+            }
+            _ => return false,
+        }
+        true
+    }
+    
+    /// Pinned messages still in the buffer, oldest pin first
+    pub fn pinned_messages(&self) -> Vec<&ChatMessage> {
+        self.pinned
+            .iter()
+            .filter_map(|id| self.messages.iter().find(|m| m.id.as_deref() == Some(id)))
+            .collect()
+    }
+    
+    /// Membership and settings, if this is a group channel
+    pub fn group_info(&self) -> Option<&GroupInfo> {
+        match &self.channel_type {
+            ChannelType::Group { info, .. } => Some(info),
+            _ => None,
         }
     }
     
@@ -182,11 +551,33 @@ impl Channel {
         match &self.channel_type {
             ChannelType::Global => "# global".to_string(),
             ChannelType::DirectMessage { other_user } => format!("@ {}", other_user),
-            ChannelType::Group { name, .. } => format!("# {}", name),
+            ChannelType::Group { name, .. } | ChannelType::Room { name, .. } => format!("# {}", name),
+            ChannelType::Starred => "★ starred".to_string(),
+            ChannelType::Mentions => "@ mentions".to_string(),
+            ChannelType::Activity => "~ activity".to_string(),
         }
     }
 }
 
+/// Build the ID of the DM channel between two users
+pub fn dm_channel_id(user_a: &str, user_b: &str) -> String {
+    // Sort usernames alphabetically for consistent channel IDs
+    let (user1, user2) = if user_a < user_b {
+        (user_a, user_b)
+    } else {
+        (user_b, user_a)
+    };
+    format!("dm:{}:{}", user1, user2)
+}
+
+/// A DELIVERED or READ receipt waiting to be sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    pub channel_id: String,
+    pub message_id: String,
+    pub read: bool,
+}
+
 /// Telemetry data for monitoring
 #[derive(Debug, Clone)]
 pub struct Telemetry {
@@ -196,8 +587,18 @@ pub struct Telemetry {
     pub bytes_received: u64,
     pub connection_uptime: u64, // seconds
     pub latency_ms: u64,
+    /// Last `LATENCY_SAMPLES` round-trip measurements, oldest first
+    pub latency_history: VecDeque<u64>,
     /// Network activity history (messages per second over last 60 seconds)
     pub network_activity: Vec<u64>,
+    /// Times the connection was established (1 + reconnects)
+    pub connections: u64,
+    /// Encrypted messages dropped as replays
+    pub replays_dropped: u64,
+    /// Messages sent and received per channel ID
+    pub channel_counts: std::collections::HashMap<String, u64>,
+    /// Channel of every message in the last `ACTIVITY_WINDOW_SECS`, oldest first
+    recent_channel_events: VecDeque<(DateTime<Utc>, String)>,
 }
 
 impl Default for Telemetry {
@@ -209,7 +610,70 @@ impl Default for Telemetry {
             bytes_received: 0,
             connection_uptime: 0,
             latency_ms: 0,
+            latency_history: VecDeque::with_capacity(LATENCY_SAMPLES),
             network_activity: vec![0; 60], // 60 seconds of history
+            connections: 0,
+            replays_dropped: 0,
+            channel_counts: std::collections::HashMap::new(),
+            recent_channel_events: VecDeque::new(),
+        }
+    }
+}
+
+impl Telemetry {
+    /// Connections after the first one
+    pub fn reconnects(&self) -> u64 {
+        self.connections.saturating_sub(1)
+    }
+
+    /// Count a message we sent to `channel_id`
+    pub fn record_sent(&mut self, channel_id: &str) {
+        self.messages_sent += 1;
+        self.record_channel_event(channel_id);
+    }
+
+    /// Count a message received in `channel_id`
+    pub fn record_received(&mut self, channel_id: &str) {
+        self.messages_received += 1;
+        self.record_channel_event(channel_id);
+    }
+
+    fn record_channel_event(&mut self, channel_id: &str) {
+        *self.channel_counts.entry(channel_id.to_string()).or_default() += 1;
+        let now = Utc::now();
+        self.recent_channel_events.push_back((now, channel_id.to_string()));
+        self.prune_channel_events(now);
+    }
+
+    /// Drop channel events older than the activity window
+    fn prune_channel_events(&mut self, now: DateTime<Utc>) {
+        let cutoff = now - chrono::Duration::seconds(ACTIVITY_WINDOW_SECS);
+        while self.recent_channel_events.front().is_some_and(|(at, _)| *at < cutoff) {
+            self.recent_channel_events.pop_front();
+        }
+    }
+
+    /// Up to `n` channels with the most messages in the activity window,
+    /// busiest first (ties broken by channel ID)
+    pub fn most_active_channels(&self, n: usize) -> Vec<(&str, usize)> {
+        let cutoff = Utc::now() - chrono::Duration::seconds(ACTIVITY_WINDOW_SECS);
+        let mut counts: std::collections::HashMap<&str, usize> = std::collections::HashMap::new();
+        for (_, channel_id) in self.recent_channel_events.iter().filter(|(at, _)| *at >= cutoff) {
+            *counts.entry(channel_id.as_str()).or_default() += 1;
+        }
+        let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        counts.truncate(n);
+        counts
+    }
+
+    /// Move stats to a re-keyed channel
+    pub fn rename_channel(&mut self, old: &str, new: &str) {
+        if let Some(count) = self.channel_counts.remove(old) {
+            self.channel_counts.insert(new.to_string(), count);
+        }
+        for (_, channel_id) in self.recent_channel_events.iter_mut().filter(|(_, id)| id == old) {
+            *channel_id = new.to_string();
         }
     }
 }
@@ -219,6 +683,38 @@ impl Default for Telemetry {
 pub enum InputMode {
     Normal,   // Navigation mode
     Editing,  // Typing a message
+    Command,  // Typing a `:` command line
+}
+
+/// Modal popup drawn on top of the main layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Popup {
+    /// Decoded wire frame of the selected message
+    Inspector,
+    /// Pinned messages of the active channel
+    Pins,
+    /// Short authentication string to compare with `user`
+    Verify { user: String, code: [usize; verify::SAS_LEN] },
+    /// Profile card of a roster user, with quick actions
+    Profile { user: String },
+    /// Command palette: the search text and the highlighted row
+    Palette { query: String, selected: usize },
+    /// `/search` results across every channel, newest first
+    Search { query: String, hits: Vec<SearchHit>, selected: usize },
+    /// A message that would go out in plaintext in a channel that was
+    /// encrypted, waiting for confirmation
+    Downgrade { channel_id: String, content: String, action: bool },
+}
+
+/// How well the messages of a channel are protected on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Security {
+    /// The relay can read them
+    Plaintext,
+    /// End-to-end encrypted (DMs, and rooms joined with a passphrase)
+    Encrypted,
+    /// An encrypted DM whose partner's identity key we verified
+    Verified,
 }
 
 /// Main application state
@@ -226,29 +722,50 @@ pub struct App {
     /// Current username
     pub username: String,
     
+    /// User configuration
+    pub config: Config,
+    
     /// All channels (keyed by channel ID)
     pub channels: std::collections::HashMap<String, Channel>,
     
+    /// Sender names shared by the messages in `channels`
+    pub senders: Senders,
+    
     /// Currently active channel ID
     pub active_channel: String,
     
+    /// Channel that was active before it, for `` ` `` and Alt+Tab
+    pub previous_channel: Option<String>,
+    
     /// Selected channel index in sidebar
     pub selected_channel: usize,
     
     /// Current input buffer
     pub input: String,
     
-    /// Input cursor position
+    /// Input cursor position, as a byte offset on a char boundary
     pub input_cursor: usize,
     
     /// Current input mode
     pub input_mode: InputMode,
     
+    /// The `:` command line and its history
+    pub command_line: CommandLine,
+    
     /// User roster (all known users)
     pub users: Vec<User>,
     
-    /// Selected user index in roster (for creating DMs)
-    pub selected_user: usize,
+    /// Selected roster row (for creating DMs)
+    pub selected_user: Option<RosterKey>,
+    
+    /// Roster sections folded down to their header (`z`)
+    pub collapsed_sections: HashSet<UserStatus>,
+    
+    /// The Users pane lists everyone even in channels whose members we know (`u`)
+    pub global_roster: bool,
+    
+    /// The last key moved the roster selection, so Enter acts on it
+    pub roster_focused: bool,
     
     /// Chat scroll position (for active channel)
     pub scroll_position: usize,
@@ -257,7 +774,104 @@ pub struct App {
     pub telemetry: Telemetry,
     
     /// Connection status
-    pub is_connected: bool,
+    pub connection: ConnectionState,
+    
+    /// Currently open popup (if any)
+    pub popup: Option<Popup>,
+    
+    /// Captured client log lines
+    pub logs: LogBuffer,
+    
+    /// Whether the debug log panel is visible
+    pub show_logs: bool,
+    
+    /// UI strings for the selected locale
+    pub strings: &'static Strings,
+    
+    /// Local nicknames for contacts
+    pub aliases: Aliases,
+    
+    /// Users whose messages are dropped
+    pub blocked: Blocked,
+    
+    /// Favorite channels and the hand-arranged sidebar order
+    pub channel_order: ChannelOrder,
+    
+    /// Locally starred messages, mirrored into the starred channel
+    pub starred: Starred,
+    
+    /// Pending `/remind` reminders
+    pub reminders: Reminders,
+    
+    /// Rules that answer incoming DMs
+    pub autoreply: AutoReplies,
+    
+    /// URL of the relay we are (or were last) connected to
+    pub server_url: Option<String>,
+    
+    /// Relay identity from its HELLO (None for relays that predate it)
+    pub server_info: Option<ServerInfo>,
+    
+    /// The relay's latest `/whois` answer, shown on that user's profile card
+    pub whois: Option<WhoisInfo>,
+    
+    /// Channel whose next older page of history is read on the next loop
+    /// iteration, once "loading" has been drawn
+    pub pending_history: Option<String>,
+    
+    /// Telemetry snapshot file, if exporting (`/telemetry export`)
+    pub metrics: Option<MetricsWriter>,
+    
+    /// Do-not-disturb state (`/dnd`)
+    pub dnd: DoNotDisturb,
+    
+    /// When we last posted in each channel, for group slow mode
+    pub last_sent: HashMap<String, Instant>,
+    
+    /// Whether we send READ receipts (`/receipts`, `read_receipts` in config)
+    pub read_receipts: bool,
+    
+    /// Whose join/leave notices are shown (`/presence`, `presence` in config)
+    pub presence: PresenceFilter,
+    
+    /// Receipts queued for the network task
+    pub pending_receipts: Vec<Receipt>,
+    
+    /// Auto-replies to send and commands to run, by the DM channel that
+    /// triggered them
+    pub pending_autoreplies: Vec<(String, AutoAction)>,
+    
+    /// Newest message we sent a READ for, per DM channel
+    pub read_sent: HashMap<String, String>,
+    
+    /// Identity key and encrypted DM sessions
+    pub e2e: E2e,
+    
+    /// Keys of the passphrase rooms we joined, by channel ID (never saved)
+    pub room_keys: HashMap<String, SecretKey>,
+    
+    /// When the next cover message is due (`[padding]` with cover traffic)
+    pub next_cover: Option<Instant>,
+    
+    /// Nonces of recently opened passphrase room messages, by channel ID
+    pub room_replay: HashMap<String, ReplayWindow>,
+    
+    /// Channels that have been encrypted, so sending plaintext there is
+    /// confirmed first
+    pub encrypted_before: HashSet<String>,
+    
+    /// Room whose passphrase the input box is asking for; input is masked
+    pub passphrase_prompt: Option<String>,
+    
+    /// Relay clock minus local clock, measured during the heartbeat
+    pub clock_offset_ms: i64,
+    
+    /// Senders already warned about a skewed clock
+    pub skew_warned: HashSet<String>,
+    
+    /// Last sequence number seen from each sender in each channel, for gap
+    /// detection
+    pub last_seq: std::collections::HashMap<(String, String), u64>,
     
     /// Should quit the application
     pub should_quit: bool,
@@ -265,74 +879,186 @@ pub struct App {
 
 impl App {
     /// Create a new application instance
-    pub fn new(username: String) -> Self {
+    pub fn new(username: String, config: Config) -> Self {
+        let strings = i18n::select(config.locale.as_deref());
+        
         // Create global channel
         let mut global_channel = Channel::global();
-        global_channel.add_message(ChatMessage::system(
-            format!("Welcome to GhostWire, {}!", username)
-        ));
+        global_channel.add_message(
+            ChatMessage::system(fill(strings.welcome, &[&username])),
+            config.history_limit("global"),
+        );
         
         // Initialize channels map
         let mut channels = std::collections::HashMap::new();
         channels.insert("global".to_string(), global_channel);
+        channels.insert(STARRED_CHANNEL.to_string(), Channel::starred());
+        channels.insert(MENTIONS_CHANNEL.to_string(), Channel::mentions());
+        if config.activity_channel {
+            channels.insert(ACTIVITY_CHANNEL.to_string(), Channel::activity());
+        }
         
         Self {
+            read_receipts: config.read_receipts,
+            presence: config.presence,
+            e2e: E2e::default(),
+            room_keys: HashMap::new(),
+            next_cover: config.padding.next_cover().map(|wait| Instant::now() + wait),
+            room_replay: HashMap::new(),
+            encrypted_before: HashSet::new(),
+            passphrase_prompt: None,
             username,
+            config,
             channels,
+            senders: Senders::default(),
             active_channel: "global".to_string(),
+            previous_channel: None,
             selected_channel: 0,
             input: String::new(),
             input_cursor: 0,
             input_mode: InputMode::Normal,
-            users: Vec::with_capacity(MAX_USERS),
-            selected_user: 0,
+            command_line: CommandLine::default(),
+            users: Vec::new(),
+            selected_user: None,
+            collapsed_sections: HashSet::new(),
+            global_roster: false,
+            roster_focused: false,
             scroll_position: 0,
             telemetry: Telemetry::default(),
-            is_connected: false,
+            connection: ConnectionState::Connecting,
+            popup: None,
+            logs: LogBuffer::default(),
+            show_logs: false,
+            strings,
+            aliases: Aliases::default(),
+            blocked: Blocked::default(),
+            channel_order: ChannelOrder::default(),
+            starred: Starred::default(),
+            reminders: Reminders::default(),
+            autoreply: AutoReplies::default(),
+            server_url: None,
+            server_info: None,
+            whois: None,
+            pending_history: None,
+            metrics: None,
+            dnd: DoNotDisturb::Off,
+            last_sent: HashMap::new(),
+            pending_receipts: Vec::new(),
+            pending_autoreplies: Vec::new(),
+            read_sent: HashMap::new(),
+            clock_offset_ms: 0,
+            skew_warned: HashSet::new(),
+            last_seq: std::collections::HashMap::new(),
             should_quit: false,
         }
     }
     
     /// Add a message to the active channel
-    pub fn add_message(&mut self, message: ChatMessage) {
+    pub fn add_message(&mut self, mut message: ChatMessage) {
+        message.sender = self.senders.intern(&message.sender);
+        let limit = self.config.history_limit(&self.active_channel);
         if let Some(channel) = self.channels.get_mut(&self.active_channel) {
-            channel.add_message(message);
+            channel.add_message(message, limit);
+            channel.mark_read();
             // Auto-scroll to bottom
             self.scroll_to_bottom();
         }
     }
     
     /// Add a message to a specific channel
-    pub fn add_message_to_channel(&mut self, channel_id: &str, message: ChatMessage) {
-        // Auto-create DM channel if it doesn't exist
-        if channel_id.starts_with("dm:") && !self.channels.contains_key(channel_id) {
-            // Extract the other user's name from the channel ID
-            // Format: "dm:user1:user2"
-            let parts: Vec<&str> = channel_id.split(':').collect();
-            if parts.len() == 3 {
-                let other_user = if parts[1] == self.username {
-                    parts[2].to_string()
-                } else {
-                    parts[1].to_string()
-                };
-                
-                let channel = Channel::dm(&self.username, other_user);
+    pub fn add_message_to_channel(&mut self, channel_id: &str, mut message: ChatMessage) {
+        message.sender = self.senders.intern(&message.sender);
+        // Auto-create DM channels and other relays' channels
+        if !self.channels.contains_key(channel_id) {
+            if let Some(channel) = self.new_channel(channel_id) {
                 self.channels.insert(channel_id.to_string(), channel);
             }
         }
         
+        let limit = self.config.history_limit(channel_id);
         if let Some(channel) = self.channels.get_mut(channel_id) {
-            channel.add_message(message);
+            // Our own messages (auto-replies, for one) are never unread, and
+            // neither is the activity channel's background noise
+            let seen = *message.sender == *self.username
+                || channel.channel_type == ChannelType::Activity
+                || (!message.is_system && channel.is_read(message.timestamp));
+            channel.add_message(message, limit);
             
             // Increment unread count if not active channel
             if channel_id != self.active_channel {
-                channel.unread_count += 1;
+                if !seen {
+                    channel.unread_count += 1;
+                }
             } else {
+                channel.mark_read();
                 self.scroll_to_bottom();
             }
         }
     }
     
+    /// Post the reminders that have come due, each into the channel it was
+    /// set in, or the active channel if that one is gone
+    pub fn post_due_reminders(&mut self, now: DateTime<Utc>) {
+        let due = self.reminders.take_due(now);
+        if due.is_empty() {
+            return;
+        }
+        for reminder in due {
+            let msg = ChatMessage::reminder(fill(self.strings.reminder, &[&reminder.text]));
+            if self.channels.contains_key(&reminder.channel) {
+                self.add_message_to_channel(&reminder.channel, msg);
+            } else {
+                self.add_message(msg);
+            }
+        }
+        if let Err(e) = self.reminders.save() {
+            warn!("Failed to save reminders: {}", e);
+        }
+    }
+    
+    /// Build a channel for a DM or extra-relay channel ID seen for the first time
+    fn new_channel(&self, channel_id: &str) -> Option<Channel> {
+        let (relay, wire_id) = split_channel(channel_id);
+        let channel = if let Some(users) = wire_id.strip_prefix("dm:") {
+            // Extract the other user's name from the channel ID
+            // Format: "dm:user1:user2"
+            let (user1, user2) = users.split_once(':')?;
+            let other_user = if user1 == self.username { user2 } else { user1 };
+            Channel::dm(&self.username, other_user.to_string())
+        } else if wire_id == "global" && relay.is_some() {
+            Channel::global()
+        } else {
+            return None;
+        };
+        Some(match relay {
+            Some(relay) => channel.on_relay(relay),
+            None => channel,
+        })
+    }
+    
+    /// Check whether a message ID was already added to a channel
+    pub fn is_duplicate(&self, channel_id: &str, id: &str) -> bool {
+        self.channels
+            .get(channel_id)
+            .map(|channel| channel.recent_ids.contains(id))
+            .unwrap_or(false)
+    }
+    
+    /// Record a sender's sequence number in a channel, returning how many
+    /// frames were skipped since the last one seen. Numbers at or below the
+    /// last one are repeats and change nothing, except 1: the sender
+    /// restarted, so tracking starts over.
+    pub fn record_seq(&mut self, sender: &str, channel_id: &str, seq: u64) -> Option<u64> {
+        let key = (sender.to_string(), channel_id.to_string());
+        let last = self.last_seq.get(&key).copied();
+        if last.is_some_and(|last| seq <= last && seq != 1) {
+            return None;
+        }
+        self.last_seq.insert(key, seq);
+        let last = last?;
+        (seq > last + 1).then(|| seq - last - 1)
+    }
+    
     /// Add a user to the roster
     pub fn add_user(&mut self, user: User) {
         // Don't add yourself
@@ -342,10 +1068,155 @@ impl App {
         
         // Check if user already exists
         if !self.users.iter().any(|u| u.username == user.username) {
-            self.users.push(user.clone());
-            self.add_message(ChatMessage::system(
-                format!("{} joined the chat", user.username)
-            ));
+            if self.users.len() < self.config.max_users {
+                self.users.push(user.clone());
+            }
+            if self.shows_presence(&user.username) {
+                self.add_activity(None, fill(self.strings.user_joined, &[&user.username]));
+            }
+        }
+    }
+    
+    /// Turn the activity channel on or off for this session
+    pub fn set_activity_channel(&mut self, enabled: bool) {
+        self.config.activity_channel = enabled;
+        if enabled {
+            self.channels.entry(ACTIVITY_CHANNEL.to_string()).or_insert_with(Channel::activity);
+        } else {
+            self.channels.remove(ACTIVITY_CHANNEL);
+            if self.active_channel == ACTIVITY_CHANNEL {
+                self.switch_channel("global".to_string());
+            }
+        }
+    }
+    
+    /// Post a join/leave/rename/topic notice: to the activity channel, tagged
+    /// with the channel it happened in, or with `activity_channel` off, into
+    /// that channel (the active one for relay-wide events)
+    pub fn add_activity(&mut self, channel_id: Option<&str>, notice: String) {
+        if !self.config.activity_channel {
+            match channel_id {
+                Some(id) => self.add_message_to_channel(id, ChatMessage::system(notice)),
+                None => self.add_message(ChatMessage::system(notice)),
+            }
+            return;
+        }
+        let notice = match channel_id.and_then(|id| self.channels.get(id)) {
+            Some(channel) => format!("[{}] {}", self.channel_name(channel), notice),
+            None => notice,
+        };
+        self.add_message_to_channel(ACTIVITY_CHANNEL, ChatMessage::system(notice));
+    }
+    
+    /// Whether join/leave notices for a user pass the presence filter
+    pub fn shows_presence(&self, username: &str) -> bool {
+        match self.presence {
+            PresenceFilter::All => true,
+            PresenceFilter::Contacts => self.aliases.contains(username) || self.e2e.is_verified(username),
+            PresenceFilter::Dms => {
+                self.e2e.knows(username)
+                    || self.channels.values().any(|channel| {
+                        matches!(&channel.channel_type, ChannelType::DirectMessage { other_user } if other_user == username)
+                    })
+            }
+            PresenceFilter::None => false,
+        }
+    }
+    
+    /// Replace the roster with the relay's list of authenticated users,
+    /// keeping what we already know about users who are still present
+    pub fn set_roster(&mut self, usernames: Vec<String>) {
+        let mut users: Vec<User> = usernames
+            .into_iter()
+            .filter(|name| *name != self.username)
+            .take(self.config.max_users)
+            .map(|name| {
+                self.users
+                    .iter()
+                    .find(|u| u.username == name)
+                    .cloned()
+                    .unwrap_or_else(|| User::new(name))
+            })
+            .collect();
+        users.dedup_by(|a, b| a.username == b.username);
+        self.users = users;
+    }
+    
+    /// Apply a nickname change accepted by the relay everywhere the old
+    /// name appears: our own name, the roster, DM channel IDs and senders
+    pub fn rename_user(&mut self, old: &str, new: &str) {
+        if self.username == old {
+            self.username = new.to_string();
+        }
+        if let Some(user) = self.users.iter_mut().find(|u| u.username == old) {
+            user.username = new.to_string();
+        }
+        
+        // DM channel IDs embed both usernames, so affected channels are re-keyed
+        let affected: Vec<String> = self
+            .channels
+            .keys()
+            .filter(|id| id.strip_prefix("dm:").is_some_and(|users| users.split(':').any(|u| u == old)))
+            .cloned()
+            .collect();
+        for id in affected {
+            let Some(mut channel) = self.channels.remove(&id) else {
+                continue;
+            };
+            if let ChannelType::DirectMessage { other_user } = &mut channel.channel_type {
+                if other_user == old {
+                    *other_user = new.to_string();
+                }
+                channel.id = dm_channel_id(&self.username, other_user);
+            }
+            if self.active_channel == id {
+                self.active_channel = channel.id.clone();
+            }
+            if self.previous_channel.as_ref() == Some(&id) {
+                self.previous_channel = Some(channel.id.clone());
+            }
+            self.channel_order.rename(&id, &channel.id);
+            self.save_channel_order();
+            self.telemetry.rename_channel(&id, &channel.id);
+            self.channels.insert(channel.id.clone(), channel);
+        }
+        
+        let new_sender = self.senders.intern(new);
+        for channel in self.channels.values_mut() {
+            if let ChannelType::Group { info, .. } = &mut channel.channel_type {
+                for names in [&mut info.members, &mut info.operators] {
+                    if names.remove(old) {
+                        names.insert(new.to_string());
+                    }
+                }
+            }
+            for msg in channel.messages.iter_mut().filter(|m| !m.is_system && &*m.sender == old) {
+                msg.sender = Arc::clone(&new_sender);
+            }
+        }
+        let renamed: Vec<_> = self.last_seq.keys().filter(|(sender, _)| sender == old).cloned().collect();
+        for key in renamed {
+            if let Some(seq) = self.last_seq.remove(&key) {
+                self.last_seq.insert((new.to_string(), key.1), seq);
+            }
+        }
+        if self.skew_warned.remove(old) {
+            self.skew_warned.insert(new.to_string());
+        }
+        self.blocked.rename(old, new);
+        if let Some(alias) = self.aliases.remove(old) {
+            self.aliases.set(new, alias);
+            if let Err(e) = self.aliases.save() {
+                warn!("Failed to save aliases: {}", e);
+            }
+        }
+        
+        // Our own rename answers /nick, so it stays where we typed it
+        let notice = fill(self.strings.user_renamed, &[&old, &new]);
+        if new == self.username {
+            self.add_message(ChatMessage::system(notice));
+        } else if self.shows_presence(new) {
+            self.add_activity(None, notice);
         }
     }
     
@@ -353,13 +1224,8 @@ impl App {
     pub fn remove_user(&mut self, username: &str) {
         if let Some(pos) = self.users.iter().position(|u| u.username == username) {
             self.users.remove(pos);
-            self.add_message(ChatMessage::system(
-                format!("{} left the chat", username)
-            ));
-            
-            // Adjust selected user if necessary
-            if self.selected_user >= self.users.len() && self.selected_user > 0 {
-                self.selected_user = self.users.len() - 1;
+            if self.shows_presence(username) {
+                self.add_activity(None, fill(self.strings.user_left, &[&username]));
             }
         }
     }
@@ -391,93 +1257,1020 @@ impl App {
         self.input_mode = InputMode::Normal;
     }
     
+    /// Open an empty `:` command line
+    pub fn open_command_line(&mut self) {
+        self.command_line.open();
+        self.input_mode = InputMode::Command;
+    }
+    
     /// Add a character to the input buffer
     pub fn input_char(&mut self, c: char) {
         self.input.insert(self.input_cursor, c);
-        self.input_cursor += 1;
+        self.input_cursor += c.len_utf8();
+    }
+    
+    /// Insert pasted text at the cursor. Line breaks are kept, except in a
+    /// passphrase prompt; escape sequences and other controls are dropped.
+    pub fn input_paste(&mut self, text: &str) {
+        let clean = Zeroizing::new(if self.passphrase_prompt.is_some() {
+            sanitize::sanitize(text).text
+        } else {
+            sanitize::sanitize_multiline(text).text
+        });
+        self.input.insert_str(self.input_cursor, &clean);
+        self.input_cursor += clean.len();
     }
     
     /// Delete character before cursor
     pub fn input_backspace(&mut self) {
-        if self.input_cursor > 0 {
-            self.input.remove(self.input_cursor - 1);
-            self.input_cursor -= 1;
+        if let Some(c) = self.input[..self.input_cursor].chars().next_back() {
+            self.input_cursor -= c.len_utf8();
+            self.input.remove(self.input_cursor);
         }
     }
     
     /// Move cursor left
     pub fn input_cursor_left(&mut self) {
-        if self.input_cursor > 0 {
-            self.input_cursor -= 1;
+        if let Some(c) = self.input[..self.input_cursor].chars().next_back() {
+            self.input_cursor -= c.len_utf8();
         }
     }
     
     /// Move cursor right
     pub fn input_cursor_right(&mut self) {
-        if self.input_cursor < self.input.len() {
-            self.input_cursor += 1;
+        if let Some(c) = self.input[self.input_cursor..].chars().next() {
+            self.input_cursor += c.len_utf8();
         }
     }
     
     /// Get the current input and clear the buffer
     pub fn take_input(&mut self) -> String {
-        let input = self.input.clone();
-        self.input.clear();
+        // Move the buffer out rather than clone + clear, which would leave the
+        // typed plaintext behind in the old allocation
         self.input_cursor = 0;
-        input
+        std::mem::take(&mut self.input)
     }
     
     /// Scroll chat up
     pub fn scroll_up(&mut self) {
         if self.scroll_position > 0 {
             self.scroll_position -= 1;
+        } else {
+            self.request_older_history();
         }
     }
     
     /// Scroll chat down
     pub fn scroll_down(&mut self) {
-        if let Some(channel) = self.channels.get(&self.active_channel) {
-            let max_scroll = channel.messages.len().saturating_sub(1);
-            if self.scroll_position < max_scroll {
-                self.scroll_position += 1;
-            }
+        let max_scroll = self.visible_indices().len().saturating_sub(1);
+        if self.scroll_position < max_scroll {
+            self.scroll_position += 1;
         }
     }
     
     /// Scroll to bottom of chat
     pub fn scroll_to_bottom(&mut self) {
-        if let Some(channel) = self.channels.get(&self.active_channel) {
-            self.scroll_position = channel.messages.len().saturating_sub(1);
+        self.scroll_position = self.visible_indices().len().saturating_sub(1);
+    }
+    
+    /// Whether a channel currently hides system notices
+    pub fn hides_system(&self, channel: &Channel) -> bool {
+        // The activity channel is nothing but notices
+        if channel.channel_type == ChannelType::Activity {
+            return false;
         }
+        channel.hide_system.unwrap_or(self.config.hide_system_messages)
     }
     
-    /// Get list of channel IDs sorted for display
-    pub fn get_channel_list(&self) -> Vec<String> {
-        let mut channels: Vec<String> = self.channels.keys().cloned().collect();
-        channels.sort_by(|a, b| {
-            // Global first, then DMs alphabetically
-            match (a.as_str(), b.as_str()) {
-                ("global", _) => std::cmp::Ordering::Less,
-                (_, "global") => std::cmp::Ordering::Greater,
-                _ => a.cmp(b),
-            }
-        });
-        channels
+    /// Toggle hiding system notices in the active channel
+    pub fn toggle_system_messages(&mut self) {
+        let default = self.config.hide_system_messages;
+        if let Some(channel) = self.channels.get_mut(&self.active_channel) {
+            channel.hide_system = Some(!channel.hide_system.unwrap_or(default));
+        }
+        self.scroll_to_bottom();
     }
     
-    /// Switch to a different channel
+    /// Buffer indices of the rendered messages in the active channel; the
+    /// selection cursor (`scroll_position`) indexes into this list
+    pub fn visible_indices(&self) -> Vec<usize> {
+        self.channels
+            .get(&self.active_channel)
+            .map(|channel| channel.visible_indices(self.hides_system(channel)))
+            .unwrap_or_default()
+    }
+    
+    /// Buffer index of the message under the selection cursor
+    fn selected_index(&self) -> Option<usize> {
+        self.visible_indices().get(self.scroll_position).copied()
+    }
+    
+    /// Get the message under the selection cursor in the active channel
+    pub fn selected_message(&self) -> Option<&ChatMessage> {
+        let index = self.selected_index()?;
+        self.channels
+            .get(&self.active_channel)
+            .and_then(|channel| channel.messages.get(index))
+    }
+    
+    /// Take the selected message out of the active channel if it is one of
+    /// ours that never arrived, returning its text and whether it was an action
+    pub fn take_failed_selected(&mut self) -> Option<(String, bool)> {
+        let index = self.selected_index()?;
+        let channel = self.channels.get_mut(&self.active_channel)?;
+        let msg = channel.messages.get(index)?;
+        let failed = matches!(msg.delivery, Delivery::Failed | Delivery::Undelivered);
+        if msg.is_system || *msg.sender != *self.username || !failed {
+            return None;
+        }
+        let msg = channel.messages.remove(index)?;
+        Some((msg.content.to_string(), msg.action))
+    }
+    
+    /// Show or hide the spoilers of the selected message
+    pub fn toggle_spoilers_selected(&mut self) {
+        let Some(index) = self.selected_index() else {
+            return;
+        };
+        if let Some(msg) = self
+            .channels
+            .get_mut(&self.active_channel)
+            .and_then(|channel| channel.messages.get_mut(index))
+        {
+            msg.revealed = !msg.revealed;
+        }
+    }
+    
+    /// Open the raw message inspector for the selected message
+    pub fn open_inspector(&mut self) {
+        if self.selected_message().is_some() {
+            self.popup = Some(Popup::Inspector);
+        }
+    }
+    
+    /// Open the command palette with an empty search
+    pub fn open_palette(&mut self) {
+        self.popup = Some(Popup::Palette { query: String::new(), selected: 0 });
+    }
+    
+    /// Search every open channel, buffered messages and stored history, and
+    /// list the hits
+    pub fn open_search(&mut self, query: String) {
+        let needle = query.to_lowercase();
+        let mut hits = Vec::new();
+        for channel in self.channels.values() {
+            if matches!(channel.channel_type, ChannelType::Starred | ChannelType::Mentions | ChannelType::Activity) {
+                continue;
+            }
+            let buffered: Vec<SearchHit> = channel
+                .messages
+                .iter()
+                .filter(|msg| !msg.is_system && search::matches(&needle, &msg.content))
+                .map(|msg| SearchHit::buffered(&channel.id, msg))
+                .collect();
+            let stored: Vec<SearchHit> = history::load(&channel.id)
+                .iter()
+                .filter(|msg| search::matches(&needle, &msg.content))
+                .filter(|msg| !buffered.iter().any(|hit| hit.is_stored(msg)))
+                .map(|msg| SearchHit::stored(&channel.id, msg))
+                .collect();
+            hits.extend(buffered);
+            hits.extend(stored);
+        }
+        hits.sort_by_key(|hit| std::cmp::Reverse(hit.timestamp));
+        hits.truncate(search::MAX_HITS);
+        self.popup = Some(Popup::Search { query, hits, selected: 0 });
+    }
+    
+    /// Switch to a search hit's channel and select the message, reading
+    /// older pages of its stored history until it is in the buffer
+    pub fn jump_to_hit(&mut self, hit: &SearchHit) {
+        if !self.channels.contains_key(&hit.channel_id) {
+            return;
+        }
+        self.switch_channel(hit.channel_id.clone());
+        while !self.channels[&hit.channel_id].messages.iter().any(|msg| hit.is_message(msg)) {
+            if self.load_older_history(&hit.channel_id) == 0 {
+                break;
+            }
+        }
+        let Some(channel) = self.channels.get(&hit.channel_id) else {
+            return;
+        };
+        let position = self
+            .visible_indices()
+            .iter()
+            .position(|&i| channel.messages.get(i).is_some_and(|msg| hit.is_message(msg)));
+        if let Some(position) = position {
+            self.scroll_position = position;
+        }
+    }
+    
+    /// Open the list of pinned messages in the active channel
+    pub fn open_pins(&mut self) {
+        self.popup = Some(Popup::Pins);
+    }
+    
+    /// Show the verification code for a user we have a key for; otherwise
+    /// return the notice explaining why not
+    pub fn start_verification(&mut self, user: &str) -> Result<(), String> {
+        let Some((ours, theirs)) = self.e2e.identities(user) else {
+            return Err(fill(self.strings.verify_no_key, &[&user]));
+        };
+        self.popup = Some(Popup::Verify {
+            user: user.to_string(),
+            code: verify::sas(&ours, &theirs),
+        });
+        Ok(())
+    }
+    
+    /// Close the verification popup, recording whether the codes matched
+    pub fn finish_verification(&mut self, matched: bool) {
+        let Some(Popup::Verify { user, .. }) = self.popup.take() else {
+            return;
+        };
+        self.e2e.set_verified(&user, matched);
+        let notice = if matched {
+            fill(self.strings.verify_matched, &[&user])
+        } else {
+            fill(self.strings.verify_mismatch, &[&user])
+        };
+        self.add_message(ChatMessage::system(notice));
+    }
+    
+    /// Close any open popup
+    pub fn close_popup(&mut self) {
+        self.popup = None;
+    }
+    
+    /// Toggle the debug log panel
+    pub fn toggle_logs(&mut self) {
+        self.show_logs = !self.show_logs;
+    }
+    
+    /// Name to show for a user, honoring local aliases
+    pub fn display_name<'a>(&'a self, username: &'a str) -> &'a str {
+        self.aliases.display(username)
+    }
+    
+    /// Symbols to draw with, per the `ascii_only` setting
+    pub fn glyphs(&self) -> &'static Glyphs {
+        if self.config.ascii_only {
+            &glyphs::ASCII
+        } else {
+            &glyphs::UNICODE
+        }
+    }
+    
+    /// Name to show for a channel, honoring local aliases for DMs
+    pub fn channel_name(&self, channel: &Channel) -> String {
+        let name = match &channel.channel_type {
+            ChannelType::DirectMessage { other_user } => {
+                format!("@ {}", self.display_name(other_user))
+            }
+            ChannelType::Starred => format!("{} {}", self.glyphs().star, self.strings.starred),
+            ChannelType::Mentions => format!("{} {}", self.glyphs().mention, self.strings.mentions),
+            ChannelType::Activity => format!("{} {}", self.glyphs().activity, self.strings.activity),
+            ChannelType::Room { .. } if self.room_keys.contains_key(&channel.id) => {
+                format!("{} {}", channel.display_name(), self.glyphs().locked)
+            }
+            _ => channel.display_name(),
+        };
+        match split_channel(&channel.id) {
+            (Some(relay), _) => format!("{}: {}", relay, name),
+            (None, _) => name,
+        }
+    }
+    
+    /// Get list of channel IDs sorted for display
+    pub fn get_channel_list(&self) -> Vec<String> {
+        let mut channels: Vec<String> = self.channels.keys().cloned().collect();
+        channels.sort_by_cached_key(|id| self.sidebar_key(id));
+        channels
+    }
+    
+    /// Where a channel sorts in the sidebar: favorites first, in their own
+    /// order. With `sidebar_sort = "unread"`, channels with unread mentions
+    /// and then other unread channels come next (not during do-not-disturb,
+    /// which hides unread counts). Then the primary relay, then each extra
+    /// relay as a group. Within a group: global, starred, mentions, activity,
+    /// then the rest, hand-arranged ones first and the others alphabetically.
+    /// Channels with the same section (the first field) may trade places.
+    fn sidebar_key(&self, channel_id: &str) -> ((bool, u8, Option<String>, u8), usize, String) {
+        if let Some(position) = self.channel_order.favorite(channel_id) {
+            return ((false, 0, None, 0), position, channel_id.to_string());
+        }
+        let urgency = match self.channels.get(channel_id) {
+            Some(channel) if self.config.sidebar_sort == SidebarSort::Unread && !self.dnd.is_on() => {
+                match channel.unread_count {
+                    0 => 2,
+                    unread if channel.messages.iter().rev().take(unread).any(|msg| msg.highlighted) => 0,
+                    _ => 1,
+                }
+            }
+            _ => 2,
+        };
+        let (relay, wire_id) = split_channel(channel_id);
+        let rank = match wire_id {
+            "global" => 0,
+            STARRED_CHANNEL => 1,
+            MENTIONS_CHANNEL => 2,
+            ACTIVITY_CHANNEL => 3,
+            _ => 4,
+        };
+        let position = self.channel_order.position(channel_id).unwrap_or(usize::MAX);
+        ((true, urgency, relay.map(str::to_string), rank), position, channel_id.to_string())
+    }
+    
+    /// Add the highlighted channel to the favorites, or remove it
+    pub fn toggle_favorite_selected(&mut self) {
+        let Some(channel_id) = self.get_channel_list().get(self.selected_channel).cloned() else {
+            return;
+        };
+        let favorite = self.channel_order.toggle_favorite(&channel_id);
+        self.save_channel_order();
+        // Keep the same channel highlighted in its new place
+        if let Some(index) = self.get_channel_list().iter().position(|id| *id == channel_id) {
+            self.selected_channel = index;
+        }
+        let name = self.channels.get(&channel_id).map(|channel| self.channel_name(channel)).unwrap_or(channel_id);
+        let template = if favorite { self.strings.favorite_added } else { self.strings.favorite_removed };
+        self.add_message(ChatMessage::system(fill(template, &[&name])));
+    }
+    
+    /// Move the highlighted channel one place up or down its section of the sidebar
+    pub fn move_selected_channel(&mut self, up: bool) {
+        let mut channels = self.get_channel_list();
+        let from = self.selected_channel;
+        let Some(to) = (if up { from.checked_sub(1) } else { Some(from + 1) }) else {
+            return;
+        };
+        let (Some(a), Some(b)) = (channels.get(from), channels.get(to)) else {
+            return;
+        };
+        if self.sidebar_key(a).0 != self.sidebar_key(b).0 {
+            return;
+        }
+        channels.swap(from, to);
+        let (favorites, order) = channels.into_iter().partition(|id| self.channel_order.favorite(id).is_some());
+        self.channel_order.favorites = favorites;
+        self.channel_order.order = order;
+        self.selected_channel = to;
+        self.save_channel_order();
+    }
+    
+    fn save_channel_order(&self) {
+        if let Err(e) = self.channel_order.save() {
+            warn!("Failed to save the channel order: {}", e);
+        }
+    }
+    
+    /// Switch to a different channel
     pub fn switch_channel(&mut self, channel_id: String) {
         if self.channels.contains_key(&channel_id) {
-            self.active_channel = channel_id.clone();
+            // Remember where we were, unless following the newest message
+            let at_bottom = self.scroll_position + 1 >= self.visible_indices().len();
+            let anchor = self.selected_message().filter(|_| !at_bottom).and_then(|msg| msg.id.clone());
+            if let Some(channel) = self.channels.get_mut(&self.active_channel) {
+                channel.scroll_anchor = anchor;
+            }
+            
+            if channel_id != self.active_channel {
+                self.previous_channel = Some(std::mem::replace(&mut self.active_channel, channel_id.clone()));
+            }
             self.scroll_to_bottom();
+            self.restore_scroll();
             
             // Clear unread count
             if let Some(channel) = self.channels.get_mut(&channel_id) {
                 channel.unread_count = 0;
+                channel.mark_read();
+            }
+        }
+    }
+    
+    /// Select the active channel's scroll anchor again, if it is still buffered
+    fn restore_scroll(&mut self) {
+        let Some(channel) = self.channels.get(&self.active_channel) else {
+            return;
+        };
+        let Some(anchor) = &channel.scroll_anchor else {
+            return;
+        };
+        let position = self
+            .visible_indices()
+            .iter()
+            .position(|&i| channel.messages.get(i).and_then(|msg| msg.id.as_ref()) == Some(anchor));
+        if let Some(position) = position {
+            self.scroll_position = position;
+        }
+    }
+    
+    /// Open channels, read markers and scroll anchors to save on exit
+    pub fn session_state(&self) -> SessionState {
+        let at_bottom = self.scroll_position + 1 >= self.visible_indices().len();
+        let active_anchor = self.selected_message().filter(|_| !at_bottom).and_then(|msg| msg.id.clone());
+        let channels = self
+            .get_channel_list()
+            .iter()
+            .filter_map(|id| self.channels.get(id))
+            .filter(|channel| !matches!(channel.channel_type, ChannelType::Starred | ChannelType::Mentions | ChannelType::Activity))
+            .map(|channel| SavedChannel {
+                id: channel.id.clone(),
+                channel_type: channel.channel_type.clone(),
+                read_marker: channel.read_marker,
+                scroll_anchor: if channel.id == self.active_channel {
+                    active_anchor.clone()
+                } else {
+                    channel.scroll_anchor.clone()
+                },
+                hide_system: channel.hide_system,
+                encrypted_before: self.encrypted_before.contains(&channel.id),
+            })
+            .collect();
+        SessionState {
+            username: self.username.clone(),
+            active_channel: self.active_channel.clone(),
+            channels,
+        }
+    }
+    
+    /// Put the newest page of stored history of every channel we have one
+    /// for in front of its messages (older pages are read when scrolled
+    /// to, see `request_older_history`). Channels that are not open
+    /// are reopened where the ID says enough (DMs, rooms, other relays'
+    /// global); groups need their membership, so only open ones get history.
+    pub fn load_history(&mut self) {
+        for channel_id in history::channels() {
+            if !self.channels.contains_key(&channel_id) {
+                let channel = match split_channel(&channel_id) {
+                    (None, wire_id) if wire_id.starts_with("room:") => {
+                        Some(Channel::room(wire_id["room:".len()..].to_string(), false))
+                    }
+                    _ => self.new_channel(&channel_id),
+                };
+                // Another user's DMs (say, from an imported transcript) stay on disk
+                let Some(channel) = channel.filter(|channel| channel.id == channel_id) else {
+                    continue;
+                };
+                self.channels.insert(channel_id.clone(), channel);
+            }
+            
+            let count = self.config.history_limit(&channel_id).min(history::PAGE_SIZE);
+            let (stored, older) = history::load_recent(&channel_id, count);
+            let Some(channel) = self.channels.get_mut(&channel_id) else {
+                continue;
+            };
+            channel.prepend_stored(stored, &mut self.senders);
+            channel.older_history = older;
+        }
+    }
+    
+    /// Ask for the next older page of the active channel's stored history;
+    /// the loop reads it after drawing the loading indicator
+    fn request_older_history(&mut self) {
+        let older = self.channels.get(&self.active_channel).is_some_and(|channel| channel.older_history);
+        if older && self.config.save_history {
+            self.pending_history = Some(self.active_channel.clone());
+        }
+    }
+    
+    /// Read the page asked for by `request_older_history`, keeping the
+    /// selection on the same message
+    pub fn load_pending_history(&mut self) {
+        let Some(channel_id) = self.pending_history.take() else {
+            return;
+        };
+        let loaded = self.load_older_history(&channel_id);
+        if channel_id == self.active_channel {
+            self.scroll_position += loaded;
+        }
+    }
+    
+    /// Put the next older page of a channel's stored history in front of its
+    /// buffer, returning how many messages that was
+    fn load_older_history(&mut self, channel_id: &str) -> usize {
+        let Some(channel) = self.channels.get_mut(channel_id) else {
+            return 0;
+        };
+        if !channel.older_history {
+            return 0;
+        }
+        // Stored messages from before the oldest one shown come next
+        let before = channel.messages.iter().find(|msg| !msg.is_system).map_or_else(Utc::now, |msg| msg.timestamp);
+        let messages = &channel.messages;
+        let recent_ids = &channel.recent_ids;
+        let (stored, more) = history::load_older(channel_id, before, history::PAGE_SIZE, |stored| match &stored.id {
+            Some(id) => recent_ids.contains(id),
+            None => messages.iter().any(|msg| {
+                msg.timestamp == stored.timestamp && msg.sender == stored.sender && msg.content == stored.content
+            }),
+        });
+        let loaded = stored.len();
+        channel.older_history = more;
+        channel.prepend_stored(stored, &mut self.senders);
+        loaded
+    }
+    
+    /// Merge every channel's messages into the stored history, leaving out
+    /// those already past the retention age
+    pub fn save_history(&self) {
+        let cutoff = self.config.retention.cutoff(Utc::now());
+        for channel in self.channels.values() {
+            if matches!(channel.channel_type, ChannelType::Starred | ChannelType::Mentions | ChannelType::Activity) {
+                continue;
+            }
+            let messages: Vec<TranscriptMessage> = channel
+                .messages
+                .iter()
+                .filter(|msg| !msg.is_system && cutoff.is_none_or(|cutoff| msg.timestamp >= cutoff))
+                .map(|msg| TranscriptMessage {
+                    id: msg.id.clone(),
+                    timestamp: msg.timestamp,
+                    sender: msg.sender.clone(),
+                    content: msg.content.clone(),
+                    is_system: false,
+                    action: msg.action,
+                })
+                .collect();
+            if messages.is_empty() {
+                continue;
+            }
+            if let Err(e) = history::merge(&channel.id, messages) {
+                warn!("Failed to save the history of {}: {}", channel.id, e);
+            }
+        }
+    }
+    
+    /// The channel a `/purge` argument names: a channel ID (open or only
+    /// stored), `#name` for global, a room or a group, or `@user` for a DM
+    pub fn find_channel(&self, spec: &str) -> Option<String> {
+        let known = |id: &str| self.channels.contains_key(id) || history::channels().iter().any(|stored| stored == id);
+        if let Some(user) = spec.strip_prefix('@') {
+            return Some(dm_channel_id(&self.username, user)).filter(|id| known(id));
+        }
+        if let Some(name) = spec.strip_prefix('#') {
+            let candidates = [name.to_string(), format!("room:{}", name), format!("group:{}", name)];
+            return candidates.into_iter().find(|id| known(id));
+        }
+        Some(spec.to_string()).filter(|id| known(id))
+    }
+    
+    /// `/purge`: delete a channel's stored messages, all of them or those
+    /// older than `older_than`, and drop them from its buffer so they are
+    /// not saved again on exit
+    pub fn purge_history(&mut self, channel_id: &str, older_than: Option<chrono::Duration>) {
+        let name = match self.channels.get(channel_id) {
+            Some(channel) if matches!(channel.channel_type, ChannelType::Starred | ChannelType::Mentions | ChannelType::Activity) => {
+                let notice = fill(self.strings.purge_virtual, &[&self.channel_name(channel)]);
+                self.add_message(ChatMessage::system(notice));
+                return;
+            }
+            Some(channel) => self.channel_name(channel),
+            None => channel_id.to_string(),
+        };
+        let cutoff = older_than.map(|age| Utc::now() - age);
+        let notice = match history::purge(channel_id, cutoff) {
+            Ok(deleted) => fill(self.strings.purge_done, &[&deleted, &name]),
+            Err(e) => fill(self.strings.purge_failed, &[&e]),
+        };
+        if let Some(channel) = self.channels.get_mut(channel_id) {
+            channel
+                .messages
+                .retain(|msg| msg.is_system || cutoff.is_some_and(|cutoff| msg.timestamp >= cutoff));
+            channel.older_history = false;
+        }
+        if channel_id == self.active_channel {
+            self.scroll_to_bottom();
+        }
+        self.add_message(ChatMessage::system(notice));
+    }
+    
+    /// Reopen the channels of a saved session and switch to its active
+    /// channel. Room keys are never saved, so passphrase rooms come back
+    /// locked with a reminder to rejoin.
+    pub fn restore_session(&mut self, state: SessionState) {
+        if state.username != self.username {
+            return;
+        }
+        for saved in state.channels {
+            let channel = self.channels.entry(saved.id.clone()).or_insert_with(|| Channel {
+                id: saved.id.clone(),
+                channel_type: saved.channel_type.clone(),
+                messages: VecDeque::new(),
+                unread_count: 0,
+                recent_ids: RecentIds::default(),
+                pinned: Vec::new(),
+                hide_system: None,
+                read_marker: None,
+                scroll_anchor: None,
+                older_history: false,
+            });
+            channel.read_marker = saved.read_marker;
+            channel.scroll_anchor = saved.scroll_anchor;
+            channel.hide_system = saved.hide_system;
+            if saved.encrypted_before {
+                self.encrypted_before.insert(saved.id.clone());
+            }
+            if let ChannelType::Room { name, encrypted: true } = &saved.channel_type {
+                self.encrypted_before.insert(saved.id.clone());
+                let notice = fill(self.strings.room_rejoin, &[&name]);
+                channel.add_message(ChatMessage::system(notice), self.config.history_limit(&saved.id));
+            }
+        }
+        if self.channels.contains_key(&state.active_channel) {
+            self.switch_channel(state.active_channel);
+        }
+    }
+    
+    /// Record what the relay reported about one of our messages; returns
+    /// false if it is not in the channel's buffer
+    pub fn mark_delivery(&mut self, channel_id: &str, message_id: &str, delivery: Delivery) -> bool {
+        let Some(channel) = self.channels.get_mut(channel_id) else {
+            return false;
+        };
+        let own = channel
+            .messages
+            .iter_mut()
+            .rev()
+            .find(|msg| msg.id.as_deref() == Some(message_id) && *msg.sender == *self.username);
+        match own {
+            Some(msg) => {
+                msg.delivery = delivery;
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// The other user of a DM channel we are part of
+    fn dm_partner(&self, channel_id: &str) -> Option<&str> {
+        match &self.channels.get(channel_id)?.channel_type {
+            ChannelType::DirectMessage { other_user }
+                if dm_channel_id(&self.username, other_user) == split_channel(channel_id).1 =>
+            {
+                Some(other_user)
+            }
+            _ => None,
+        }
+    }
+    
+    /// The other user of a DM channel ID we are part of; unlike
+    /// `dm_partner` the channel does not have to exist yet
+    pub fn dm_peer<'a>(&self, channel_id: &'a str) -> Option<&'a str> {
+        let (a, b) = split_channel(channel_id).1.strip_prefix("dm:")?.split_once(':')?;
+        match (a == self.username, b == self.username) {
+            (true, false) => Some(b),
+            (false, true) => Some(a),
+            _ => None,
+        }
+    }
+    
+    /// Decrypt an encrypted DM payload
+    pub fn open_envelope(&mut self, channel_id: &str, payload: &str) -> anyhow::Result<Zeroizing<String>> {
+        let envelope = Envelope::from_payload(payload).context("Malformed encrypted payload")?;
+        self.e2e.decrypt(channel_id, &envelope)
+    }
+    
+    /// Join (or rejoin) a room and switch to it. With a key, messages are
+    /// encrypted; without one, the room is plaintext.
+    pub fn join_room(&mut self, channel_id: String, name: String, key: Option<SecretKey>) {
+        let encrypted = key.is_some();
+        if encrypted {
+            self.encrypted_before.insert(channel_id.clone());
+        }
+        match key {
+            Some(key) => self.room_keys.insert(channel_id.clone(), key),
+            None => self.room_keys.remove(&channel_id),
+        };
+        let channel = self.channels.entry(channel_id.clone()).or_insert_with(|| {
+            let mut channel = Channel::room(name.clone(), encrypted);
+            channel.id = channel_id.clone();
+            channel
+        });
+        channel.channel_type = ChannelType::Room { name, encrypted };
+        self.switch_channel(channel_id);
+    }
+    
+    /// Encrypted channels a cover message could go to right now: DMs with a
+    /// ready session and an online partner, and rooms we hold the key of
+    pub fn cover_channels(&self) -> Vec<String> {
+        self.channels
+            .values()
+            .filter(|channel| match &channel.channel_type {
+                ChannelType::DirectMessage { other_user } => {
+                    self.e2e.is_established(&channel.id)
+                        && !self.e2e.identity_changed(other_user)
+                        && self.users.iter().any(|u| u.username == *other_user && u.status() != UserStatus::Offline)
+                }
+                ChannelType::Room { .. } => self.room_keys.contains_key(&channel.id),
+                _ => false,
+            })
+            .map(|channel| channel.id.clone())
+            .collect()
+    }
+    
+    /// How messages we send to a channel are protected; `None` for the
+    /// virtual channels, where nothing is sent
+    pub fn security(&self, channel_id: &str) -> Option<Security> {
+        match &self.channels.get(channel_id)?.channel_type {
+            ChannelType::Starred | ChannelType::Mentions | ChannelType::Activity => None,
+            // DMs are held until the session is set up, never sent in plaintext
+            ChannelType::DirectMessage { other_user } if self.e2e.is_verified(other_user) => Some(Security::Verified),
+            ChannelType::DirectMessage { .. } => Some(Security::Encrypted),
+            _ if self.room_keys.contains_key(channel_id) => Some(Security::Encrypted),
+            _ => Some(Security::Plaintext),
+        }
+    }
+    
+    /// Whether a message sent to this channel now would go out in plaintext
+    /// although the channel was encrypted before (a room whose key was lost
+    /// on restart, or where others still send encrypted messages)
+    pub fn is_downgraded(&self, channel_id: &str) -> bool {
+        self.security(channel_id) == Some(Security::Plaintext) && self.encrypted_before.contains(channel_id)
+    }
+    
+    /// Ask before sending a message that `is_downgraded` would put on the
+    /// wire in plaintext
+    pub fn confirm_downgrade(&mut self, channel_id: &str, content: String, action: bool) {
+        self.popup = Some(Popup::Downgrade {
+            channel_id: channel_id.to_string(),
+            content,
+            action,
+        });
+    }
+    
+    /// Close the downgrade warning. Accepting it keeps the channel plaintext
+    /// from now on and returns the message to send; otherwise the message goes
+    /// back into the input box
+    pub fn finish_downgrade(&mut self, accepted: bool) -> Option<(String, String, bool)> {
+        let Some(Popup::Downgrade { channel_id, content, action }) = self.popup.take() else {
+            return None;
+        };
+        if accepted {
+            self.encrypted_before.remove(&channel_id);
+            return Some((channel_id, content, action));
+        }
+        if !action && self.input.is_empty() {
+            self.input = content;
+            self.input_cursor = self.input.len();
+        }
+        None
+    }
+    
+    /// Encrypt a message for a passphrase room we joined; `None` for other
+    /// channels
+    pub fn seal_for_room(&self, channel_id: &str, content: &str) -> Option<anyhow::Result<String>> {
+        let key = self.room_keys.get(channel_id)?;
+        let room_id = split_channel(channel_id).1;
+        let sent = self.server_now().timestamp();
+        Some(crypto::seal_room_message(key, room_id, &self.username, content, sent).map(|envelope| envelope.to_payload()))
+    }
+    
+    /// Decrypt a passphrase room message; one opened before, or sealed
+    /// outside the replay window, fails with a `Replay` error
+    pub fn open_room_message(&mut self, channel_id: &str, sender: &str, payload: &str) -> anyhow::Result<Zeroizing<String>> {
+        let key = self.room_keys.get(channel_id).context("Joined without a passphrase")?;
+        let envelope = RoomEnvelope::from_payload(payload).context("Malformed encrypted payload")?;
+        let plaintext = crypto::open_room_message(key, split_channel(channel_id).1, sender, &envelope)?;
+        // Checked once it authenticated, so forged nonces cannot fill the window
+        let now = self.server_now().timestamp();
+        self.room_replay
+            .entry(channel_id.to_string())
+            .or_default()
+            .check(envelope.sent, &envelope.nonce, now)?;
+        Ok(plaintext)
+    }
+    
+    /// Queue a DELIVERED receipt for a DM we just received
+    pub fn queue_delivered(&mut self, channel_id: &str, sender: &str, message_id: &str) {
+        if sender != self.username && self.dm_partner(channel_id) == Some(sender) {
+            self.pending_receipts.push(Receipt {
+                channel_id: channel_id.to_string(),
+                message_id: message_id.to_string(),
+                read: false,
+            });
+        }
+    }
+    
+    /// Queue a READ receipt if the active channel is a DM scrolled to the
+    /// bottom whose newest message from our partner has not been marked read
+    pub fn queue_read(&mut self) {
+        if !self.read_receipts
+            || self.scroll_position + 1 < self.visible_indices().len()
+        {
+            return;
+        }
+        let Some(partner) = self.dm_partner(&self.active_channel) else {
+            return;
+        };
+        let newest = self.channels[&self.active_channel]
+            .messages
+            .iter()
+            .rev()
+            .find(|msg| *msg.sender == *partner && msg.id.is_some())
+            .and_then(|msg| msg.id.clone());
+        let Some(message_id) = newest else {
+            return;
+        };
+        if self.read_sent.get(&self.active_channel) == Some(&message_id) {
+            return;
+        }
+        self.read_sent.insert(self.active_channel.clone(), message_id.clone());
+        self.pending_receipts.push(Receipt {
+            channel_id: self.active_channel.clone(),
+            message_id,
+            read: true,
+        });
+    }
+    
+    /// Apply a receipt from our DM partner. READ covers every one of our
+    /// messages up to the one named; DELIVERED never downgrades a READ.
+    pub fn apply_receipt(&mut self, channel_id: &str, sender: &str, message_id: &str, read: bool) {
+        if self.dm_partner(channel_id) != Some(sender) {
+            return;
+        }
+        let username = self.username.clone();
+        let Some(channel) = self.channels.get_mut(channel_id) else {
+            return;
+        };
+        let Some(index) = channel
+            .messages
+            .iter()
+            .rposition(|msg| msg.id.as_deref() == Some(message_id) && *msg.sender == *username)
+        else {
+            return;
+        };
+        if read {
+            // Messages the relay dropped never reached them
+            let reached = |msg: &&mut ChatMessage| {
+                *msg.sender == *username
+                    && !matches!(msg.delivery, Delivery::Undelivered | Delivery::Failed)
+            };
+            for msg in channel.messages.range_mut(..=index).filter(reached) {
+                msg.delivery = Delivery::Read;
             }
+        } else if channel.messages[index].delivery != Delivery::Read {
+            channel.messages[index].delivery = Delivery::Delivered;
         }
     }
     
+    /// Group info of the active channel, if it is a group
+    pub fn active_group(&self) -> Option<&GroupInfo> {
+        self.channels.get(&self.active_channel)?.group_info()
+    }
+    
+    /// Members of the active channel, when we know them: a group's members,
+    /// or us and the other side of a DM. The relay does not report who is in
+    /// global or a room, so those have none.
+    pub fn channel_members(&self) -> Option<Vec<&str>> {
+        match &self.channels.get(&self.active_channel)?.channel_type {
+            ChannelType::Group { info, .. } => Some(info.members.iter().map(String::as_str).collect()),
+            ChannelType::DirectMessage { other_user } => Some(vec![self.username.as_str(), other_user.as_str()]),
+            _ => None,
+        }
+    }
+    
+    /// Switch the Users pane between the active channel's members and everyone
+    pub fn toggle_global_roster(&mut self) {
+        if self.channel_members().is_none() {
+            self.add_message(ChatMessage::system(self.strings.roster_unscoped.to_string()));
+            return;
+        }
+        self.global_roster = !self.global_roster;
+    }
+    
+    /// Whether `sender` may post in `channel_id`: everyone may, except in
+    /// groups, which only take messages from their members, and only from
+    /// operators and announcers in announcement mode. `sender` is the name
+    /// the relay delivered the message under.
+    pub fn may_post(&self, channel_id: &str, sender: &str) -> bool {
+        match self.channels.get(channel_id).and_then(Channel::group_info) {
+            Some(info) => info.may_post(sender),
+            None => true,
+        }
+    }
+    
+    /// Seconds we still have to wait before posting in a slow-mode group.
+    /// Operators are exempt.
+    pub fn slow_mode_wait(&self, channel_id: &str) -> Option<u64> {
+        let info = self.channels.get(channel_id)?.group_info()?;
+        if info.slow_mode_secs == 0 || info.is_operator(&self.username) {
+            return None;
+        }
+        let elapsed = self.last_sent.get(channel_id)?.elapsed().as_secs();
+        let secs = u64::from(info.slow_mode_secs);
+        (elapsed < secs).then(|| secs - elapsed)
+    }
+    
+    /// Create a group with us as its operator and switch to it. Returns the
+    /// channel ID and the event announcing it.
+    pub fn create_group(
+        &mut self,
+        channel_id: String,
+        name: String,
+        members: Vec<String>,
+    ) -> Result<(String, GroupEvent), GroupError> {
+        if self.channels.contains_key(&channel_id) {
+            return Err(GroupError::AlreadyExists);
+        }
+        let info = GroupInfo::new(&self.username, &members)?;
+        let mut channel = Channel::group(name, info);
+        channel.id = channel_id.clone();
+        self.channels.insert(channel_id.clone(), channel);
+        self.switch_channel(channel_id.clone());
+        Ok((channel_id, GroupEvent::Create { members }))
+    }
+    
+    /// Apply a group event, ours or a peer's, and return the notice
+    /// describing it. Events for groups we are not in are ignored unless
+    /// they add us; events that break the group's rules are dropped.
+    pub fn apply_group_event(
+        &mut self,
+        channel_id: &str,
+        sender: &str,
+        event: &GroupEvent,
+    ) -> Result<Option<String>, GroupError> {
+        let Some(channel) = self.channels.get_mut(channel_id) else {
+            return Ok(self.join_group(channel_id, sender, event));
+        };
+        let ChannelType::Group { name, info } = &mut channel.channel_type else {
+            return Ok(None);
+        };
+        info.apply(sender, event)?;
+        let name = name.clone();
+        
+        let by = self.display_name(sender).to_string();
+        let notice = match event {
+            GroupEvent::Create { .. } => return Ok(None),
+            GroupEvent::Kick { user } if *user == self.username => {
+                // We are out: drop the channel and say why in global
+                self.channels.remove(channel_id);
+                if self.active_channel == channel_id {
+                    self.switch_channel("global".to_string());
+                }
+                let notice = fill(self.strings.group_removed_you, &[&by, &name]);
+                self.add_message_to_channel("global", ChatMessage::system(notice));
+                return Ok(None);
+            }
+            // Membership and topic changes are activity; settings stay in the group
+            GroupEvent::Invite { user, .. } => {
+                self.add_activity(Some(channel_id), fill(self.strings.group_invited, &[&by, &self.display_name(user)]));
+                return Ok(None);
+            }
+            GroupEvent::Kick { user } => {
+                self.add_activity(Some(channel_id), fill(self.strings.group_kicked, &[&by, &self.display_name(user)]));
+                return Ok(None);
+            }
+            GroupEvent::Topic { topic } => {
+                let notice = if topic.is_empty() {
+                    fill(self.strings.group_topic_cleared, &[&by])
+                } else {
+                    fill(self.strings.group_topic, &[&by, topic])
+                };
+                self.add_activity(Some(channel_id), notice);
+                return Ok(None);
+            }
+            GroupEvent::SlowMode { secs: 0 } => fill(self.strings.group_slow_off, &[&by]),
+            GroupEvent::SlowMode { secs } => fill(self.strings.group_slow_on, &[&by, secs]),
+            GroupEvent::Announce { on: true } => fill(self.strings.group_announce_on, &[&by]),
+            GroupEvent::Announce { on: false } => fill(self.strings.group_announce_off, &[&by]),
+            GroupEvent::Announcer { user, allowed: true } => {
+                fill(self.strings.group_announcer_added, &[&by, &self.display_name(user)])
+            }
+            GroupEvent::Announcer { user, allowed: false } => {
+                fill(self.strings.group_announcer_removed, &[&by, &self.display_name(user)])
+            }
+        };
+        Ok(Some(notice))
+    }
+    
+    /// Create a group channel for a CREATE or INVITE that adds us
+    fn join_group(&mut self, channel_id: &str, sender: &str, event: &GroupEvent) -> Option<String> {
+        let (_, wire_id) = split_channel(channel_id);
+        let name = wire_id.strip_prefix("group:")?.to_string();
+        let info = match event {
+            GroupEvent::Create { members } if members.contains(&self.username) => {
+                GroupInfo::new(sender, members)
+            }
+            GroupEvent::Invite { user, group } if *user == self.username => {
+                GroupInfo::from_invite(sender, user, group)
+            }
+            _ => return None,
+        };
+        let info = match info {
+            Ok(info) => info,
+            Err(e) => {
+                warn!("Ignoring group {} from {}: {}", channel_id, sender, e);
+                return None;
+            }
+        };
+        let mut channel = Channel::group(name.clone(), info);
+        channel.id = channel_id.to_string();
+        self.channels.insert(channel_id.to_string(), channel);
+        Some(fill(self.strings.group_joined, &[&self.display_name(sender), &name]))
+    }
+    
     /// Create or switch to a DM channel
     pub fn open_dm(&mut self, other_user: String) {
         let channel = Channel::dm(&self.username, other_user.clone());
@@ -491,6 +2284,112 @@ impl App {
         // Switch to it
         self.switch_channel(channel_id);
     }
+
+    /// Open the channel of a `ghostwire://` link, on the primary relay
+    pub fn open_link(&mut self, target: LinkTarget) {
+        match target {
+            LinkTarget::Global => self.switch_channel("global".to_string()),
+            LinkTarget::Dm(user) if user != self.username => self.open_dm(user),
+            LinkTarget::Dm(_) => {}
+            LinkTarget::Room { name, passphrase: true } => {
+                self.passphrase_prompt = Some(format!("room:{}", name));
+                self.add_message(ChatMessage::system(fill(self.strings.room_passphrase_prompt, &[&name])));
+            }
+            LinkTarget::Room { name, passphrase: false } => {
+                self.join_room(format!("room:{}", name), name.clone(), None);
+                self.add_message(ChatMessage::system(fill(self.strings.room_joined, &[&name])));
+            }
+        }
+    }
+
+    /// Star or unstar the selected message. In the starred channel this
+    /// always unstars.
+    pub fn toggle_star_selected(&mut self) {
+        if self.active_channel == STARRED_CHANNEL {
+            if self.scroll_position >= self.starred.0.len() {
+                return;
+            }
+            let removed = self.starred.0.remove(self.scroll_position);
+            for channel in self.channels.values_mut() {
+                for msg in channel.messages.iter_mut().filter(|m| removed.matches(m)) {
+                    msg.starred = false;
+                }
+            }
+        } else {
+            let Some(index) = self.selected_index() else {
+                return;
+            };
+            let channel_id = &self.active_channel;
+            let Some(msg) = self
+                .channels
+                .get_mut(channel_id)
+                .and_then(|channel| channel.messages.get_mut(index))
+            else {
+                return;
+            };
+            if msg.is_system {
+                return;
+            }
+            
+            msg.starred = !msg.starred;
+            if msg.starred {
+                self.starred.0.push(StarredMessage::from_message(channel_id, msg));
+            } else {
+                self.starred.0.retain(|starred| !starred.matches(msg));
+            }
+        }
+        
+        if let Err(e) = self.starred.save() {
+            warn!("Failed to save starred messages: {}", e);
+        }
+        self.refresh_starred_channel();
+    }
+    
+    /// Copy a message posted in `channel_id` that mentions us or a watch
+    /// word into the mentions channel
+    pub fn collect_mention(&mut self, channel_id: &str, msg: &ChatMessage) {
+        let origin = self
+            .channels
+            .get(channel_id)
+            .map(|channel| self.channel_name(channel))
+            .unwrap_or_else(|| channel_id.to_string());
+        let mut copy = ChatMessage::new(msg.sender.clone(), format!("[{}] {}", origin, msg.content), false);
+        copy.timestamp = msg.timestamp;
+        copy.verified = msg.verified;
+        copy.action = msg.action;
+        self.add_message_to_channel(MENTIONS_CHANNEL, copy);
+    }
+    
+    /// Rebuild the starred channel from the starred store
+    pub fn refresh_starred_channel(&mut self) {
+        let messages: VecDeque<ChatMessage> = self
+            .starred
+            .0
+            .iter()
+            .map(|starred| {
+                let origin = self
+                    .channels
+                    .get(&starred.channel)
+                    .map(|channel| self.channel_name(channel))
+                    .unwrap_or_else(|| starred.channel.clone());
+                let mut msg = ChatMessage::new(
+                    starred.sender.clone(),
+                    format!("[{}] {}", origin, starred.content),
+                    false,
+                );
+                msg.timestamp = starred.timestamp;
+                msg.starred = true;
+                msg
+            })
+            .collect();
+        
+        if let Some(channel) = self.channels.get_mut(STARRED_CHANNEL) {
+            channel.messages = messages;
+        }
+        if self.active_channel == STARRED_CHANNEL {
+            self.scroll_position = self.scroll_position.min(self.starred.0.len().saturating_sub(1));
+        }
+    }
     
     /// Select previous channel
     pub fn select_previous_channel(&mut self) {
@@ -507,6 +2406,22 @@ impl App {
         }
     }
     
+    /// Switch back to the channel that was active before this one
+    pub fn switch_to_previous_channel(&mut self) {
+        if let Some(channel_id) = self.previous_channel.clone() {
+            self.switch_channel(channel_id);
+        }
+    }
+    
+    /// Switch to the `n`th channel of the sidebar, counting from 1
+    pub fn switch_to_nth_channel(&mut self, n: usize) {
+        let channels = self.get_channel_list();
+        if let Some(channel_id) = n.checked_sub(1).and_then(|index| channels.get(index)) {
+            self.selected_channel = n - 1;
+            self.switch_channel(channel_id.clone());
+        }
+    }
+    
     /// Switch to selected channel
     pub fn activate_selected_channel(&mut self) {
         let channels = self.get_channel_list();
@@ -515,29 +2430,152 @@ impl App {
         }
     }
     
-    /// Select previous user in roster
-    pub fn select_previous_user(&mut self) {
-        if self.selected_user > 0 {
-            self.selected_user -= 1;
+    /// The roster as shown: active, idle and offline sections, each sorted
+    /// by name, with the users of collapsed sections left out
+    pub fn roster(&self) -> Vec<RosterRow<'_>> {
+        let mut rows = Vec::new();
+        for status in UserStatus::ALL {
+            let mut users: Vec<&User> = self.users.iter().filter(|u| u.status() == status).collect();
+            if users.is_empty() {
+                continue;
+            }
+            users.sort_by_cached_key(|u| (self.display_name(&u.username).to_lowercase(), u.username.clone()));
+            let collapsed = self.collapsed_sections.contains(&status);
+            rows.push(RosterRow::Section { status, count: users.len(), collapsed });
+            if !collapsed {
+                rows.extend(users.into_iter().map(RosterRow::User));
+            }
         }
+        rows
     }
     
-    /// Select next user in roster
+    /// Position of the selection in `roster()`, if it is shown
+    pub fn selected_roster_row(&self, rows: &[RosterRow]) -> Option<usize> {
+        let selected = self.selected_user.as_ref()?;
+        rows.iter().position(|row| row.key() == *selected)
+    }
+    
+    /// The selected user, if the selection is on one
+    pub fn selected_roster_user(&self) -> Option<&User> {
+        match self.selected_user.as_ref()? {
+            RosterKey::User(name) => self.users.iter().find(|u| u.username == *name),
+            RosterKey::Section(_) => None,
+        }
+    }
+    
+    /// Move the roster selection by one row; a selection that is no longer
+    /// shown starts over from the top
+    fn move_roster_selection(&mut self, forward: bool) {
+        let rows = self.roster();
+        let next = match self.selected_roster_row(&rows) {
+            Some(i) if forward => (i + 1).min(rows.len() - 1),
+            Some(i) => i.saturating_sub(1),
+            None => 0,
+        };
+        self.selected_user = rows.get(next).map(RosterRow::key);
+    }
+    
+    /// Select previous row in roster
+    pub fn select_previous_user(&mut self) {
+        self.move_roster_selection(false);
+        self.roster_focused = true;
+    }
+    
+    /// Select next row in roster
     pub fn select_next_user(&mut self) {
-        if self.selected_user < self.users.len().saturating_sub(1) {
-            self.selected_user += 1;
+        self.move_roster_selection(true);
+        self.roster_focused = true;
+    }
+    
+    /// Enter on the roster: open the selected user's profile card, or fold
+    /// the selected section
+    pub fn activate_roster_row(&mut self) {
+        match &self.selected_user {
+            Some(RosterKey::User(_)) => {
+                if let Some(user) = self.selected_roster_user() {
+                    self.open_profile(user.username.clone());
+                }
+            }
+            Some(RosterKey::Section(_)) => self.toggle_roster_section(),
+            None => {}
+        }
+    }
+    
+    /// Open a user's profile card, dropping a who-is answer about someone else
+    pub fn open_profile(&mut self, username: String) {
+        if self.whois.as_ref().is_some_and(|info| info.username != username) {
+            self.whois = None;
         }
+        self.popup = Some(Popup::Profile { user: username });
+    }
+    
+    /// Channels we share with a user: our DM with them, groups they are a
+    /// member of, and channels they have posted in
+    pub fn shared_channels(&self, username: &str) -> Vec<String> {
+        self.get_channel_list()
+            .iter()
+            .filter_map(|id| self.channels.get(id))
+            .filter(|channel| match &channel.channel_type {
+                ChannelType::Starred | ChannelType::Mentions | ChannelType::Activity => false,
+                ChannelType::DirectMessage { other_user } => other_user == username,
+                ChannelType::Group { info, .. } if info.members.contains(username) => true,
+                _ => channel.messages.iter().any(|m| !m.is_system && *m.sender == *username),
+            })
+            .map(|channel| self.channel_name(channel))
+            .collect()
     }
     
-    /// Update connection status
-    pub fn set_connected(&mut self, connected: bool) {
-        if connected != self.is_connected {
-            self.is_connected = connected;
-            let status = if connected { "Connected" } else { "Disconnected" };
-            self.add_message(ChatMessage::system(status.to_string()));
+    /// Block or unblock a user and save the block list
+    pub fn toggle_block(&mut self, username: &str) {
+        let notice = if self.blocked.toggle(username) {
+            fill(self.strings.user_blocked, &[&username])
+        } else {
+            fill(self.strings.user_unblocked, &[&username])
+        };
+        match self.blocked.save() {
+            Ok(()) => self.add_message(ChatMessage::system(notice)),
+            Err(e) => self.add_message(ChatMessage::system(fill(self.strings.block_save_failed, &[&e]))),
         }
     }
     
+    /// Fold or unfold the roster section of the selection
+    pub fn toggle_roster_section(&mut self) {
+        self.roster_focused = true;
+        let status = match &self.selected_user {
+            Some(RosterKey::Section(status)) => *status,
+            Some(RosterKey::User(_)) => match self.selected_roster_user() {
+                Some(user) => user.status(),
+                None => return,
+            },
+            None => return,
+        };
+        if !self.collapsed_sections.remove(&status) {
+            self.collapsed_sections.insert(status);
+            // The selected user is hidden now; keep the selection visible
+            self.selected_user = Some(RosterKey::Section(status));
+        }
+    }
+    
+    /// Whether we are connected to the relay
+    pub fn is_connected(&self) -> bool {
+        self.connection == ConnectionState::Connected
+    }
+    
+    /// Update connection status, noting connects and disconnects in the chat
+    pub fn set_connection(&mut self, state: ConnectionState) {
+        match &state {
+            ConnectionState::Connected if !self.is_connected() => {
+                self.telemetry.connections += 1;
+                self.add_message(ChatMessage::system(self.strings.connected.to_string()));
+            }
+            ConnectionState::Disconnected { reason } if self.connection != state => {
+                self.add_message(ChatMessage::system(fill(self.strings.disconnected, &[reason])));
+            }
+            _ => {}
+        }
+        self.connection = state;
+    }
+    
     /// Update telemetry (for future batch updates)
     #[allow(dead_code)]
     pub fn update_telemetry(&mut self, telemetry: Telemetry) {
@@ -566,14 +2604,46 @@ impl App {
         }
     }
     
-    /// Update network latency (for future ping/pong implementation)
-    #[allow(dead_code)]
+    /// Update network latency from a heartbeat round trip
     pub fn update_latency(&mut self, latency_ms: u64) {
         self.telemetry.latency_ms = latency_ms;
+        if self.telemetry.latency_history.len() == LATENCY_SAMPLES {
+            self.telemetry.latency_history.pop_front();
+        }
+        self.telemetry.latency_history.push_back(latency_ms);
+    }
+    
+    /// Current time on the relay's clock
+    pub fn server_now(&self) -> DateTime<Utc> {
+        Utc::now() + chrono::Duration::milliseconds(self.clock_offset_ms)
     }
     
     /// Quit the application
     pub fn quit(&mut self) {
         self.should_quit = true;
     }
+    
+    /// Overwrite message text, input and keys before exit (`--ephemeral`).
+    /// Keys wipe themselves when dropped.
+    pub fn wipe(&mut self) {
+        // Starred copies share message text; they are already on disk
+        self.starred.0.clear();
+        let messages: Vec<ChatMessage> = self
+            .channels
+            .values_mut()
+            .flat_map(|channel| channel.messages.drain(..))
+            .collect();
+        for mut msg in messages {
+            // Shared text is overwritten by the last message holding it
+            if let Some(content) = Arc::get_mut(&mut msg.content) {
+                content.zeroize();
+            }
+            if let Some(raw) = &mut msg.raw {
+                raw.zeroize();
+            }
+        }
+        self.input.zeroize();
+        self.room_keys.clear();
+        self.e2e = E2e::ephemeral();
+    }
 }
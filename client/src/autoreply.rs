@@ -0,0 +1,192 @@
+// GhostWire Client - Auto-Reply Rules
+// Local rules that answer incoming DMs: when a message matches a rule's
+// pattern, its reply is sent back, or run as a slash command if it starts
+// with '/'. Rules come from `[[autoreply.rules]]` in config.toml and from
+// `/autoreply add`, which are kept in `<data dir>/ghostwire/autoreplies.json`.
+//
+// Two clients auto-replying to each other would loop, so each DM partner
+// gets at most one auto-reply per cooldown, and none at all after
+// `MAX_UNANSWERED` in a row until we write to them ourselves.
+
+use crate::storage;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// File name of the `/autoreply add` rule store inside the data directory
+const AUTOREPLIES_FILE: &str = "autoreplies.json";
+
+/// Auto-replies sent to one partner in a row before we must write ourselves
+pub const MAX_UNANSWERED: u32 = 3;
+
+/// Maximum length of a rule's reply, in characters
+pub const MAX_REPLY_LEN: usize = 500;
+
+/// Auto-reply settings (`[autoreply]` in config.toml)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoReplyConfig {
+    /// Answer matching DMs at all (`/autoreply on|off` changes it for the session)
+    pub enabled: bool,
+    /// Seconds before the same partner can get another auto-reply
+    pub cooldown_secs: u64,
+    /// Rules, tried in order (`[[autoreply.rules]]`)
+    pub rules: Vec<AutoReplyRule>,
+}
+
+impl Default for AutoReplyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cooldown_secs: 300,
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// A pattern and what to do when a DM matches it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutoReplyRule {
+    /// Regular expression searched for in the message, e.g. `(?i)\bping\b`
+    pub pattern: String,
+    /// Text sent back, with `$1` or `$name` replaced by the pattern's
+    /// captures (`$$` for a literal `$`); a reply starting with '/' is run
+    /// as a slash command instead, exactly as written
+    pub reply: String,
+    /// Only while do-not-disturb is on
+    #[serde(default)]
+    pub dnd_only: bool,
+}
+
+/// What a matching rule asks for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AutoAction {
+    /// Send this text back in the DM
+    Reply(String),
+    /// Run this slash command
+    Command(String),
+}
+
+/// A rule with its pattern compiled
+struct Compiled {
+    rule: AutoReplyRule,
+    regex: Regex,
+    /// Added with `/autoreply add` rather than read from config.toml
+    added: bool,
+}
+
+/// The rules in effect and who got an auto-reply recently
+#[derive(Default)]
+pub struct AutoReplies {
+    pub enabled: bool,
+    cooldown: Duration,
+    rules: Vec<Compiled>,
+    /// When each DM channel last got an auto-reply
+    last_reply: HashMap<String, Instant>,
+    /// Auto-replies sent to each DM channel since we last wrote there
+    unanswered: HashMap<String, u32>,
+}
+
+impl AutoReplies {
+    /// Compile the config's rules and those added with `/autoreply add`.
+    /// Rules whose pattern does not compile are logged and skipped.
+    pub fn new(config: &AutoReplyConfig) -> Self {
+        let added: Vec<AutoReplyRule> = storage::load_json(AUTOREPLIES_FILE);
+        let mut rules = Vec::new();
+        let all = config.rules.iter().map(|rule| (rule, false)).chain(added.iter().map(|rule| (rule, true)));
+        for (rule, added) in all {
+            match Regex::new(&rule.pattern) {
+                Ok(regex) => rules.push(Compiled { rule: rule.clone(), regex, added }),
+                Err(e) => warn!("Skipping auto-reply rule {:?}: {}", rule.pattern, e),
+            }
+        }
+        Self {
+            enabled: config.enabled,
+            cooldown: Duration::from_secs(config.cooldown_secs),
+            rules,
+            last_reply: HashMap::new(),
+            unanswered: HashMap::new(),
+        }
+    }
+
+    /// What to do about a DM from our partner in `channel_id`, if anything.
+    /// A match counts against the channel's cooldown and loop limit.
+    pub fn check(&mut self, channel_id: &str, text: &str, dnd: bool, now: Instant) -> Option<AutoAction> {
+        if !self.enabled {
+            return None;
+        }
+        if self.unanswered.get(channel_id).copied().unwrap_or(0) >= MAX_UNANSWERED {
+            return None;
+        }
+        if self
+            .last_reply
+            .get(channel_id)
+            .is_some_and(|last| now.duration_since(*last) < self.cooldown)
+        {
+            return None;
+        }
+
+        let (rule, captures) = self
+            .rules
+            .iter()
+            .filter(|compiled| dnd || !compiled.rule.dnd_only)
+            .find_map(|compiled| Some((&compiled.rule, compiled.regex.captures(text)?)))?;
+        let action = if rule.reply.starts_with('/') {
+            AutoAction::Command(rule.reply.clone())
+        } else {
+            let mut reply = String::new();
+            captures.expand(&rule.reply, &mut reply);
+            if reply.trim().is_empty() {
+                return None;
+            }
+            AutoAction::Reply(reply.chars().take(MAX_REPLY_LEN).collect())
+        };
+
+        self.last_reply.insert(channel_id.to_string(), now);
+        *self.unanswered.entry(channel_id.to_string()).or_default() += 1;
+        Some(action)
+    }
+
+    /// We wrote in `channel_id` ourselves, so auto-replies may resume there
+    pub fn wrote(&mut self, channel_id: &str) {
+        self.unanswered.remove(channel_id);
+    }
+
+    /// Rules in the order they are tried, and whether each was added with
+    /// `/autoreply add`
+    pub fn rules(&self) -> impl Iterator<Item = (&AutoReplyRule, bool)> {
+        self.rules.iter().map(|compiled| (&compiled.rule, compiled.added))
+    }
+
+    /// Add a rule after the others and save the added rules
+    pub fn add(&mut self, rule: AutoReplyRule) -> anyhow::Result<()> {
+        let regex = Regex::new(&rule.pattern)?;
+        self.rules.push(Compiled { rule, regex, added: true });
+        self.save()
+    }
+
+    /// Remove the rule at `index` (0-based, as listed) if it was added with
+    /// `/autoreply add`, and save the rest; config.toml rules stay
+    pub fn remove(&mut self, index: usize) -> anyhow::Result<AutoReplyRule> {
+        let compiled = self.rules.get(index).ok_or_else(|| anyhow::anyhow!("no rule {}", index + 1))?;
+        if !compiled.added {
+            anyhow::bail!("rule {} is set in config.toml", index + 1);
+        }
+        let removed = self.rules.remove(index).rule;
+        self.save()?;
+        Ok(removed)
+    }
+
+    /// Write the rules added with `/autoreply add` to the data directory
+    fn save(&self) -> anyhow::Result<()> {
+        let added: Vec<&AutoReplyRule> = self
+            .rules
+            .iter()
+            .filter(|compiled| compiled.added)
+            .map(|compiled| &compiled.rule)
+            .collect();
+        storage::save_json(AUTOREPLIES_FILE, &added)
+    }
+}
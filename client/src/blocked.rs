@@ -0,0 +1,49 @@
+// GhostWire Client - Blocked Users
+// Users whose messages and DM handshakes are dropped, stored in
+// `<data dir>/ghostwire/blocked.json`. Blocking is local: the relay still
+// delivers their frames and they are not told.
+
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// File name of the block list inside the data directory
+const BLOCKED_FILE: &str = "blocked.json";
+
+/// Set of blocked usernames
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Blocked(BTreeSet<String>);
+
+impl Blocked {
+    /// Load the block list from the data directory
+    pub fn load() -> Self {
+        storage::load_json(BLOCKED_FILE)
+    }
+
+    /// Write the block list back to the data directory
+    pub fn save(&self) -> anyhow::Result<()> {
+        storage::save_json(BLOCKED_FILE, self)
+    }
+
+    /// Whether a user is blocked
+    pub fn contains(&self, username: &str) -> bool {
+        self.0.contains(username)
+    }
+
+    /// Block or unblock a user, returning whether they are blocked now
+    pub fn toggle(&mut self, username: &str) -> bool {
+        if self.0.remove(username) {
+            false
+        } else {
+            self.0.insert(username.to_string())
+        }
+    }
+
+    /// Carry a block over to a user's new name
+    pub fn rename(&mut self, old: &str, new: &str) {
+        if self.0.remove(old) {
+            self.0.insert(new.to_string());
+        }
+    }
+}
@@ -0,0 +1,151 @@
+// GhostWire Client - Command Line Interface
+// Positional arguments keep the original `ghostwire [username] [server_url]` form;
+// maintenance tasks are subcommands (`ghostwire keys ...`). Either positional
+// may be a `ghostwire://` link instead (see `uri.rs`).
+
+use crate::uri::{self, Link};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Default server URL (can be overridden via CLI args)
+const DEFAULT_SERVER_URL: &str = "wss://ghost.jcyrus.com/ws";
+
+/// Ephemeral TUI chat client with a dumb relay server
+#[derive(Debug, Parser)]
+#[command(name = "ghostwire", version, about)]
+pub struct Cli {
+    /// Username to connect as (`username` from the config, or random
+    /// ghost_xxxxxxxx, if omitted)
+    pub username: Option<String>,
+
+    /// Relay WebSocket URL, tried before any `servers` from the config, or a
+    /// ghostwire://<server>/<channel> link to open
+    pub server_url: Option<String>,
+
+    /// Config file (default: <config dir>/ghostwire/config.toml)
+    #[arg(long, value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Also append client logs to this file
+    #[arg(long, value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// Append telemetry snapshots to this file (.csv for CSV, otherwise JSON lines)
+    #[arg(long, value_name = "PATH")]
+    pub metrics_file: Option<PathBuf>,
+
+    /// Run this shell command when a relay connects, reconnects, closes the
+    /// connection, disconnects or kicks us; GHOSTWIRE_EVENT and friends
+    /// describe what happened
+    #[arg(long, value_name = "CMD")]
+    pub on_event: Option<String>,
+
+    /// One-off anonymous session: random username, throwaway identity key,
+    /// nothing read from or written to disk, memory wiped on exit. Takes the
+    /// relay URL as its only positional argument.
+    #[arg(long, conflicts_with = "server_url")]
+    pub ephemeral: bool,
+
+    /// Stay connected in the background without a UI; `ghostwire attach`
+    /// opens the chat. Stops on Ctrl+C or SIGTERM, not when the terminal closes.
+    #[arg(long, conflicts_with = "ephemeral")]
+    pub daemon: bool,
+
+    /// Daemon socket (default: <runtime dir>/ghostwire/daemon.sock)
+    #[arg(long, value_name = "PATH", global = true)]
+    pub socket: Option<PathBuf>,
+
+    /// Prompt for a relay auth token, store it in the keyring (or encrypted file), and exit
+    #[arg(long)]
+    pub save_token: bool,
+
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+/// Subcommands that run instead of the chat client
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    /// Back up or restore the identity key and trust store
+    Keys {
+        #[command(subcommand)]
+        action: KeysAction,
+    },
+    /// Open the chat on a running `--daemon`; quitting detaches and leaves it connected
+    Attach,
+    /// Merge a transcript written by `/export json` into the local message
+    /// history, under the channel it was exported from
+    Import { file: PathBuf },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum KeysAction {
+    /// Write a passphrase-encrypted backup to FILE
+    Export { file: PathBuf },
+    /// Restore a backup made with `keys export`
+    Import {
+        file: PathBuf,
+        /// Replace a different identity key already on this machine
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+impl Cli {
+    /// Get the username: the argument, else `configured`, else a random one
+    /// (always random in ephemeral mode). A link in the username's place
+    /// (as desktop URI handlers pass it) is not a username.
+    pub fn username(&self, configured: Option<&str>) -> String {
+        self.username
+            .clone()
+            .filter(|arg| !uri::is_link(arg))
+            .or_else(|| configured.map(str::to_string))
+            .filter(|_| !self.ephemeral)
+            .unwrap_or_else(|| format!("ghost_{}", &uuid::Uuid::new_v4().to_string()[..8]))
+    }
+
+    /// The `ghostwire://` link among the positional arguments, parsed
+    pub fn link(&self) -> anyhow::Result<Option<Link>> {
+        [&self.username, &self.server_url]
+            .into_iter()
+            .flatten()
+            .find(|arg| uri::is_link(arg))
+            .map(|arg| uri::parse(arg))
+            .transpose()
+    }
+
+    /// Relay URL argument; `--ephemeral` takes no username, so its first
+    /// positional argument is the URL. Links are handled by `link`.
+    fn server_url_arg(&self) -> Option<&String> {
+        let arg = if self.ephemeral {
+            self.username.as_ref()
+        } else {
+            self.server_url.as_ref()
+        };
+        arg.filter(|arg| !uri::is_link(arg))
+    }
+
+    /// Socket of the daemon, from `--socket` or the default location
+    pub fn socket_path(&self) -> anyhow::Result<PathBuf> {
+        match &self.socket {
+            Some(path) => Ok(path.clone()),
+            None => crate::daemon::default_socket_path(),
+        }
+    }
+
+    /// Relays to try, in order: the link's relay or the URL argument, then
+    /// the configured list, falling back to the public relay when none is given
+    pub fn server_urls(&self, link: Option<&Link>, configured: &[String]) -> Vec<String> {
+        let mut urls: Vec<String> = Vec::new();
+        let link_url = link.map(|link| &link.server_url);
+        for url in link_url.into_iter().chain(self.server_url_arg()).chain(configured) {
+            if !urls.contains(url) {
+                urls.push(url.clone());
+            }
+        }
+        if urls.is_empty() {
+            urls.push(DEFAULT_SERVER_URL.to_string());
+        }
+        urls
+    }
+}
@@ -0,0 +1,256 @@
+// GhostWire Client - Command Line Mode
+// `:` in normal mode opens a vim-style command line in the input box: `:q`,
+// `:set option=value`, and every slash command without its slash
+// (`:join #room`). It has its own buffer, so a message draft is kept, plus a
+// history browsed with ↑/↓ and Tab completion of names and values.
+
+use crate::commands::COMMANDS;
+
+/// Most command lines remembered for ↑/↓
+const MAX_HISTORY: usize = 100;
+
+/// Ways of writing quit
+const QUIT: &[&str] = &["q", "q!", "quit", "qa", "qa!", "wq", "x"];
+
+/// Options `:set` understands and the values they take (none: on/off)
+pub const OPTIONS: &[(&str, &[&str])] = &[
+    ("timestamps", &["absolute", "relative"]),
+    ("sidebar", &["arranged", "unread"]),
+    ("presence", &["all", "contacts", "dms", "none"]),
+    ("markdown", &[]),
+    ("highlight", &[]),
+    ("ascii", &[]),
+    ("receipts", &[]),
+    ("activity", &[]),
+];
+
+/// A parsed command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExCommand {
+    Quit,
+    /// `:set` alone lists the options
+    Set(Option<SetArg>),
+    /// Anything else is a slash command, slash included
+    Slash(String),
+}
+
+/// One `:set` argument
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetArg {
+    pub name: String,
+    pub value: SetValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetValue {
+    /// `:set name?`, or `:set name` for an option that is not on/off
+    Show,
+    /// `:set name`
+    On,
+    /// `:set noname`
+    Off,
+    /// `:set name=value`
+    To(String),
+}
+
+/// Parse a command line (without the leading `:`)
+pub fn parse(line: &str) -> ExCommand {
+    let line = line.trim();
+    let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    if QUIT.contains(&name) && rest.is_empty() {
+        return ExCommand::Quit;
+    }
+    if name == "set" || name == "se" {
+        return ExCommand::Set(parse_set(rest.trim()));
+    }
+    ExCommand::Slash(format!("/{}", line))
+}
+
+fn parse_set(arg: &str) -> Option<SetArg> {
+    if arg.is_empty() {
+        return None;
+    }
+    let (name, value) = if let Some(name) = arg.strip_suffix('?') {
+        (name, SetValue::Show)
+    } else if let Some((name, value)) = arg.split_once('=') {
+        (name, SetValue::To(value.to_string()))
+    } else if let Some(name) = arg.strip_prefix("no").filter(|name| is_toggle(name)) {
+        (name, SetValue::Off)
+    } else if is_toggle(arg) {
+        (arg, SetValue::On)
+    } else {
+        (arg, SetValue::Show)
+    };
+    Some(SetArg { name: name.to_string(), value })
+}
+
+/// Whether an option is on/off rather than taking a value
+fn is_toggle(name: &str) -> bool {
+    OPTIONS.iter().any(|(option, values)| *option == name && values.is_empty())
+}
+
+/// Candidates being cycled through with Tab
+#[derive(Debug)]
+struct Completion {
+    /// Byte offset where the completed word starts
+    start: usize,
+    candidates: Vec<String>,
+    index: usize,
+}
+
+/// The command line being edited, and its history
+#[derive(Debug, Default)]
+pub struct CommandLine {
+    pub text: String,
+    /// Cursor position, as a byte offset on a char boundary
+    pub cursor: usize,
+    history: Vec<String>,
+    /// History entry shown while browsing with ↑/↓
+    browsing: Option<usize>,
+    completion: Option<Completion>,
+}
+
+impl CommandLine {
+    /// Start a new, empty line
+    pub fn open(&mut self) {
+        self.set_text(String::new());
+        self.browsing = None;
+    }
+
+    /// Take the finished line, remembering it in the history
+    pub fn submit(&mut self) -> String {
+        let line = std::mem::take(&mut self.text);
+        self.cursor = 0;
+        self.completion = None;
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+        line
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.completion = None;
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    /// Delete before the cursor; false if the line was already empty, which
+    /// closes it as in vim
+    pub fn backspace(&mut self) -> bool {
+        if self.text.is_empty() {
+            return false;
+        }
+        self.completion = None;
+        if let Some(c) = self.text[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
+            self.text.remove(self.cursor);
+        }
+        true
+    }
+
+    pub fn move_left(&mut self) {
+        if let Some(c) = self.text[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if let Some(c) = self.text[self.cursor..].chars().next() {
+            self.cursor += c.len_utf8();
+        }
+    }
+
+    /// Show the previous (older) history entry
+    pub fn history_previous(&mut self) {
+        let index = match self.browsing {
+            Some(0) => return,
+            Some(index) => index - 1,
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+        self.browsing = Some(index);
+        self.set_text(self.history[index].clone());
+    }
+
+    /// Show the next (newer) history entry, or an empty line after the newest
+    pub fn history_next(&mut self) {
+        let Some(index) = self.browsing else { return };
+        if index + 1 < self.history.len() {
+            self.browsing = Some(index + 1);
+            self.set_text(self.history[index + 1].clone());
+        } else {
+            self.browsing = None;
+            self.set_text(String::new());
+        }
+    }
+
+    /// Complete the word before the cursor: a command name first, then
+    /// `:set` options and their values. Pressing Tab again cycles through
+    /// the other candidates.
+    pub fn complete(&mut self) {
+        if let Some(completion) = &mut self.completion {
+            completion.index = (completion.index + 1) % completion.candidates.len();
+            let start = completion.start;
+            let candidate = completion.candidates[completion.index].clone();
+            self.text.truncate(start);
+            self.text.push_str(&candidate);
+            self.cursor = self.text.len();
+            return;
+        }
+
+        self.text.truncate(self.cursor);
+        let start = self.text.rfind(' ').map_or(0, |at| at + 1);
+        let word = &self.text[start..];
+        let mut candidates: Vec<String> = if start == 0 {
+            QUIT.iter()
+                .copied()
+                .chain(["set"])
+                .chain(COMMANDS.iter().map(|spec| spec.name))
+                .filter(|name| name.starts_with(word))
+                .map(str::to_string)
+                .collect()
+        } else if self.text.split_whitespace().next() == Some("set") {
+            set_candidates(word)
+        } else {
+            Vec::new()
+        };
+        candidates.sort();
+        candidates.dedup();
+        let Some(first) = candidates.first().cloned() else { return };
+
+        self.text.truncate(start);
+        self.text.push_str(&first);
+        self.cursor = self.text.len();
+        self.completion = Some(Completion { start, candidates, index: 0 });
+    }
+
+    fn set_text(&mut self, text: String) {
+        self.cursor = text.len();
+        self.text = text;
+        self.completion = None;
+    }
+}
+
+/// Option names, `no` forms of the on/off ones, or an option's values after `=`
+fn set_candidates(word: &str) -> Vec<String> {
+    if let Some((name, partial)) = word.split_once('=') {
+        return OPTIONS
+            .iter()
+            .filter(|(option, _)| *option == name)
+            .flat_map(|(_, values)| values.iter())
+            .filter(|value| value.starts_with(partial))
+            .map(|value| format!("{}={}", name, value))
+            .collect();
+    }
+    OPTIONS
+        .iter()
+        .flat_map(|(name, values)| {
+            let negated = values.is_empty().then(|| format!("no{}", name));
+            std::iter::once(name.to_string()).chain(negated)
+        })
+        .filter(|name| name.starts_with(word))
+        .collect()
+}
@@ -0,0 +1,249 @@
+// GhostWire Client - Code Blocks
+// Splits messages into prose and ``` fenced code blocks, and tokenizes code
+// lines for highlighting. The highlighter is a small keyword/string/comment
+// lexer working one line at a time, not a full grammar: it keeps the client
+// free of heavy dependencies and is plenty for chat snippets.
+
+/// A piece of a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment<'a> {
+    /// Prose, possibly spanning several lines
+    Text(&'a str),
+    /// The body of a fenced block and the language named after the opening fence
+    Code { lang: Option<&'a str>, code: &'a str },
+}
+
+/// Split a message into prose and fenced code blocks. A fence is a line
+/// starting with ```; an unclosed block runs to the end of the message.
+pub fn segments(content: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    // Opening fence's language and the byte offset where its body starts
+    let mut open: Option<(Option<&str>, usize)> = None;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let fence = line.trim().strip_prefix("```");
+        match (open, fence) {
+            (None, Some(lang)) => {
+                if offset > text_start {
+                    segments.push(Segment::Text(trim_newline(&content[text_start..offset])));
+                }
+                let lang = Some(lang.trim()).filter(|lang| !lang.is_empty());
+                open = Some((lang, offset + line.len()));
+            }
+            (Some((lang, start)), Some(_)) => {
+                segments.push(Segment::Code { lang, code: trim_newline(&content[start..offset]) });
+                open = None;
+                text_start = offset + line.len();
+            }
+            _ => {}
+        }
+        offset += line.len();
+    }
+
+    match open {
+        Some((lang, start)) => {
+            segments.push(Segment::Code { lang, code: trim_newline(&content[start..]) });
+        }
+        None if text_start < content.len() => segments.push(Segment::Text(&content[text_start..])),
+        None => {}
+    }
+    segments
+}
+
+fn trim_newline(text: &str) -> &str {
+    text.strip_suffix('\n').unwrap_or(text)
+}
+
+/// Bodies of every code block in a message, joined by blank lines
+pub fn code_of(content: &str) -> Option<String> {
+    let blocks: Vec<&str> = segments(content)
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Code { code, .. } => Some(code),
+            Segment::Text(_) => None,
+        })
+        .collect();
+    (!blocks.is_empty()).then(|| blocks.join("\n\n"))
+}
+
+/// What a stretch of code is, for choosing its color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Plain,
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+/// Keywords and line-comment marker of a language
+struct Language {
+    keywords: &'static [&'static str],
+    comment: &'static str,
+    /// `'` starts a lifetime unless it is a char literal like `'x'` or `'\n'`
+    lifetimes: bool,
+}
+
+const RUST: Language = Language {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "false",
+        "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+        "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+        "where", "while",
+    ],
+    comment: "//",
+    lifetimes: true,
+};
+
+const PYTHON: Language = Language {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif",
+        "else", "except", "False", "finally", "for", "from", "global", "if", "import", "in", "is",
+        "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True", "try", "while",
+        "with", "yield",
+    ],
+    comment: "#",
+    lifetimes: false,
+};
+
+const JAVASCRIPT: Language = Language {
+    keywords: &[
+        "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "else",
+        "export", "extends", "false", "finally", "for", "function", "if", "import", "in", "instanceof",
+        "interface", "let", "new", "null", "of", "return", "switch", "this", "throw", "true", "try",
+        "type", "typeof", "undefined", "var", "while",
+    ],
+    comment: "//",
+    lifetimes: false,
+};
+
+const GO: Language = Language {
+    keywords: &[
+        "break", "case", "chan", "const", "continue", "default", "defer", "else", "false", "for",
+        "func", "go", "if", "import", "interface", "map", "nil", "package", "range", "return",
+        "select", "struct", "switch", "true", "type", "var",
+    ],
+    comment: "//",
+    lifetimes: false,
+};
+
+const C: Language = Language {
+    keywords: &[
+        "auto", "bool", "break", "case", "char", "class", "const", "continue", "default", "do",
+        "double", "else", "enum", "extern", "false", "float", "for", "if", "include", "int", "long",
+        "namespace", "nullptr", "return", "short", "sizeof", "static", "struct", "switch", "template",
+        "true", "typedef", "union", "unsigned", "void", "while",
+    ],
+    comment: "//",
+    lifetimes: false,
+};
+
+const SHELL: Language = Language {
+    keywords: &[
+        "case", "do", "done", "echo", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+        "in", "local", "return", "then", "while",
+    ],
+    comment: "#",
+    lifetimes: false,
+};
+
+const CONFIG: Language = Language {
+    keywords: &["true", "false", "null"],
+    comment: "#",
+    lifetimes: false,
+};
+
+const SQL: Language = Language {
+    keywords: &[
+        "AND", "AS", "BY", "CREATE", "DELETE", "FROM", "GROUP", "INSERT", "INTO", "JOIN", "LIMIT",
+        "NOT", "NULL", "ON", "OR", "ORDER", "SELECT", "SET", "TABLE", "UPDATE", "VALUES", "WHERE",
+    ],
+    comment: "--",
+    lifetimes: false,
+};
+
+fn language(lang: &str) -> Option<&'static Language> {
+    Some(match lang.to_ascii_lowercase().as_str() {
+        "rust" | "rs" => &RUST,
+        "python" | "py" => &PYTHON,
+        "javascript" | "js" | "typescript" | "ts" | "jsx" | "tsx" => &JAVASCRIPT,
+        "go" | "golang" => &GO,
+        "c" | "h" | "cpp" | "c++" | "hpp" | "cc" => &C,
+        "sh" | "bash" | "shell" | "zsh" | "console" => &SHELL,
+        "toml" | "yaml" | "yml" | "ini" | "json" => &CONFIG,
+        "sql" => &SQL,
+        _ => return None,
+    })
+}
+
+/// Split one line of code into tokens. Unknown or missing languages are
+/// left plain.
+pub fn highlight<'a>(lang: Option<&str>, line: &'a str) -> Vec<(Token, &'a str)> {
+    let Some(language) = lang.and_then(language) else {
+        return vec![(Token::Plain, line)];
+    };
+
+    let mut tokens = Vec::new();
+    let mut plain_start = 0;
+    let mut rest = line.char_indices().peekable();
+    while let Some((i, c)) = rest.next() {
+        let token_end = if line[i..].starts_with(language.comment) {
+            Some((Token::Comment, line.len()))
+        } else if c == '\'' && language.lifetimes && !is_char_literal(&line[i..]) {
+            None
+        } else if c == '"' || c == '\'' || c == '`' {
+            // Up to the matching unescaped quote, or the end of the line
+            let mut end = line.len();
+            let mut escaped = false;
+            for (j, d) in line[i + 1..].char_indices() {
+                if !escaped && d == c {
+                    end = i + 1 + j + 1;
+                    break;
+                }
+                escaped = !escaped && d == '\\';
+            }
+            Some((Token::String, end))
+        } else if c.is_alphanumeric() || c == '_' {
+            let end = line[i..]
+                .find(|d: char| !(d.is_alphanumeric() || d == '_' || d == '.' && c.is_ascii_digit()))
+                .map_or(line.len(), |len| i + len);
+            let word = &line[i..end];
+            if c.is_ascii_digit() {
+                Some((Token::Number, end))
+            } else if language.keywords.contains(&word) {
+                Some((Token::Keyword, end))
+            } else {
+                // Step over the rest of the identifier
+                while rest.next_if(|&(j, _)| j < end).is_some() {}
+                None
+            }
+        } else {
+            None
+        };
+
+        if let Some((token, end)) = token_end {
+            if i > plain_start {
+                tokens.push((Token::Plain, &line[plain_start..i]));
+            }
+            tokens.push((token, &line[i..end]));
+            plain_start = end;
+            while rest.next_if(|&(j, _)| j < end).is_some() {}
+        }
+    }
+    if plain_start < line.len() {
+        tokens.push((Token::Plain, &line[plain_start..]));
+    }
+    tokens
+}
+
+/// Whether text starting at a `'` is a char literal rather than a lifetime
+fn is_char_literal(text: &str) -> bool {
+    let mut chars = text.chars().skip(1);
+    match chars.next() {
+        Some('\\') => true,
+        Some(_) => chars.next() == Some('\''),
+        None => false,
+    }
+}
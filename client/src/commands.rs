@@ -0,0 +1,647 @@
+// GhostWire Client - Slash Commands
+// Input starting with '/' is parsed here instead of being sent as a message
+
+use crate::aliases::MAX_ALIAS_LEN;
+use crate::autoreply::{AutoReplyRule, MAX_REPLY_LEN};
+use crate::config::PresenceFilter;
+use crate::export::ExportFormat;
+use crate::fun::Dice;
+use crate::reminders::MAX_REMINDER_LEN;
+use chrono::Duration;
+use ghostwire_protocol::group::{MAX_SLOW_MODE_SECS, MAX_TOPIC_LEN};
+use ghostwire_protocol::validate::{validate_group_name, validate_username};
+use std::path::PathBuf;
+
+/// Description of a slash command, used for parsing and help text
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub description: &'static str,
+}
+
+/// Registry of all slash commands
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "help",
+        usage: "/help",
+        description: "List available commands",
+    },
+    CommandSpec {
+        name: "export",
+        usage: "/export [md|txt|json] [--since 2h] [--out PATH]",
+        description: "Write the active channel's history to a file",
+    },
+    CommandSpec {
+        name: "purge",
+        usage: "/purge [channel] [--older-than 30d]",
+        description: "Delete the stored history of the active (or given) channel, or just its older messages",
+    },
+    CommandSpec {
+        name: "nick",
+        usage: "/nick <newname>",
+        description: "Change your username",
+    },
+    CommandSpec {
+        name: "alias",
+        usage: "/alias <user> [name]",
+        description: "Show a contact under a local nickname (omit name to clear)",
+    },
+    CommandSpec {
+        name: "pin",
+        usage: "/pin",
+        description: "Pin the selected message for everyone in the channel",
+    },
+    CommandSpec {
+        name: "unpin",
+        usage: "/unpin",
+        description: "Unpin the selected message",
+    },
+    CommandSpec {
+        name: "pins",
+        usage: "/pins",
+        description: "List pinned messages in the active channel",
+    },
+    CommandSpec {
+        name: "search",
+        usage: "/search <text>",
+        description: "Find messages containing the text in every channel and its stored history",
+    },
+    CommandSpec {
+        name: "system",
+        usage: "/system",
+        description: "Show or hide system messages in this channel",
+    },
+    CommandSpec {
+        name: "who",
+        usage: "/who",
+        description: "Refresh the user roster from the relay",
+    },
+    CommandSpec {
+        name: "telemetry",
+        usage: "/telemetry export <path> | /telemetry stop",
+        description: "Append telemetry snapshots to a .csv or JSON-lines file",
+    },
+    CommandSpec {
+        name: "dnd",
+        usage: "/dnd [30m|2h|off]",
+        description: "Toggle do-not-disturb, or turn it on for a while",
+    },
+    CommandSpec {
+        name: "remind",
+        usage: "/remind <30m|2h|1d> <text>",
+        description: "Post a reminder into this channel after a while (only you see it)",
+    },
+    CommandSpec {
+        name: "autoreply",
+        usage: "/autoreply [on|off] | /autoreply add [--dnd] <regex> <reply> | /autoreply remove <n>",
+        description: "List, toggle, add or remove rules that answer incoming DMs",
+    },
+    CommandSpec {
+        name: "receipts",
+        usage: "/receipts [on|off]",
+        description: "Toggle telling DM partners when you have read their messages",
+    },
+    CommandSpec {
+        name: "presence",
+        usage: "/presence [all|contacts|dms|none]",
+        description: "Choose whose join/leave notices are shown, or show the current choice",
+    },
+    CommandSpec {
+        name: "rekey",
+        usage: "/rekey",
+        description: "Start a new encrypted session in the active DM",
+    },
+    CommandSpec {
+        name: "verify",
+        usage: "/verify <user>",
+        description: "Compare a security code with a DM partner to verify their identity key",
+    },
+    CommandSpec {
+        name: "trust",
+        usage: "/trust <user>",
+        description: "Accept a DM partner's changed identity key and send the DMs held for them",
+    },
+    CommandSpec {
+        name: "dm",
+        usage: "/dm <user>",
+        description: "Open a DM with any user, including offline ones not in the roster",
+    },
+    CommandSpec {
+        name: "whois",
+        usage: "/whois <user>",
+        description: "Show a user's profile with what the relay shares about their connection",
+    },
+    CommandSpec {
+        name: "join",
+        usage: "/join #<room> [--passphrase]",
+        description: "Join an open room, optionally encrypted with a shared passphrase",
+    },
+    CommandSpec {
+        name: "group",
+        usage: "/group <name> <user>...",
+        description: "Create a group channel with you as its operator",
+    },
+    CommandSpec {
+        name: "invite",
+        usage: "/invite <user>",
+        description: "Add someone to the active group (operators)",
+    },
+    CommandSpec {
+        name: "kick",
+        usage: "/kick <user>",
+        description: "Remove someone from the active group (operators)",
+    },
+    CommandSpec {
+        name: "topic",
+        usage: "/topic [text]",
+        description: "Set the active group's topic, or clear it (operators)",
+    },
+    CommandSpec {
+        name: "slow",
+        usage: "/slow <30s|2m|off>",
+        description: "Limit how often members may post in the active group (operators)",
+    },
+    CommandSpec {
+        name: "announce",
+        usage: "/announce <on|off>",
+        description: "Let only operators and announcers post in the active group (operators)",
+    },
+    CommandSpec {
+        name: "announcer",
+        usage: "/announcer <user> [off]",
+        description: "Let a member post in announcement mode, or stop letting them (operators)",
+    },
+    CommandSpec {
+        name: "roll",
+        usage: "/roll [NdM[+K]]",
+        description: "Roll dice (1d6 by default) and show everyone the result",
+    },
+    CommandSpec {
+        name: "flip",
+        usage: "/flip",
+        description: "Flip a coin and show everyone the result",
+    },
+    CommandSpec {
+        name: "shrug",
+        usage: "/shrug",
+        description: "Shrug at the channel",
+    },
+];
+
+/// A parsed slash command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// List available commands
+    Help,
+    /// Export the active channel transcript
+    Export {
+        format: ExportFormat,
+        since: Option<Duration>,
+        out: Option<PathBuf>,
+    },
+    /// Delete stored history of a channel (`None`: the active one), all of
+    /// it or what is older than `older_than`
+    Purge {
+        channel: Option<String>,
+        older_than: Option<Duration>,
+    },
+    /// Change our username
+    Nick(String),
+    /// Set (or clear, if `None`) a local nickname for a contact
+    Alias { username: String, alias: Option<String> },
+    /// Pin (or unpin) the selected message
+    Pin { pinned: bool },
+    /// Show pinned messages
+    Pins,
+    /// Search every channel for messages containing the text
+    Search(String),
+    /// Toggle system messages in the active channel
+    System,
+    /// Request the current roster from the relay
+    Who,
+    /// Start (with a path) or stop (`None`) exporting telemetry snapshots
+    Telemetry(Option<PathBuf>),
+    /// Do-not-disturb: toggle, turn on for a duration, or turn off
+    Dnd(DndRequest),
+    /// Post `text` into the active channel, locally, after a while
+    Remind { after: Duration, text: String },
+    /// List, toggle, add or remove auto-reply rules
+    AutoReply(AutoReplyRequest),
+    /// Turn sending read receipts on or off, or toggle it (`None`)
+    Receipts(Option<bool>),
+    /// Set the presence filter, or show it (`None`)
+    Presence(Option<PresenceFilter>),
+    /// Replace the encrypted session of the active DM
+    Rekey,
+    /// Compare identity keys with a user
+    Verify(String),
+    /// Accept a user's changed identity key
+    Trust(String),
+    /// Open a user's profile and ask the relay about their connection
+    Whois(String),
+    /// Open (or switch to) the DM channel with a user
+    Dm(String),
+    /// Join a room; with `passphrase`, prompt for the passphrase first
+    Join { name: String, passphrase: bool },
+    /// Create a group channel with these members
+    CreateGroup { name: String, members: Vec<String> },
+    /// Operator action in the active group
+    Moderate(GroupCommand),
+    /// Roll dice and send the result as an action
+    Roll(Dice),
+    /// Flip a coin and send the result as an action
+    Flip,
+    /// Send a shrug as an action
+    Shrug,
+}
+
+/// What an operator asked to change in the active group
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupCommand {
+    Invite(String),
+    Kick(String),
+    /// Empty clears the topic
+    Topic(String),
+    /// Slow-mode interval in seconds; 0 turns it off
+    SlowMode(u32),
+    /// Announcement mode on or off
+    Announce(bool),
+    /// Whether a member may post in announcement mode
+    Announcer { user: String, allowed: bool },
+}
+
+/// What `/autoreply` was asked to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum AutoReplyRequest {
+    List,
+    Enable(bool),
+    Add(AutoReplyRule),
+    /// 0-based index into the listed rules
+    Remove(usize),
+}
+
+/// What `/dnd` was asked to do
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DndRequest {
+    Toggle,
+    For(Duration),
+    Off,
+}
+
+/// Parse a line of input.
+///
+/// Returns `None` if the input is not a command (plain message), otherwise the
+/// parsed command or a user-facing error explaining what was wrong.
+pub fn parse(input: &str) -> Option<Result<Command, String>> {
+    let rest = input.strip_prefix('/')?;
+    let mut args = rest.split_whitespace();
+    let name = args.next().unwrap_or_default();
+    let args: Vec<&str> = args.collect();
+
+    let Some(spec) = COMMANDS.iter().find(|spec| spec.name == name) else {
+        return Some(Err(format!("Unknown command: /{} (try /help)", name)));
+    };
+
+    let result = match name {
+        "help" => Ok(Command::Help),
+        "export" => parse_export(&args),
+        "purge" => parse_purge(&args),
+        "nick" => parse_nick(&args),
+        "alias" => parse_alias(&args),
+        "pin" => Ok(Command::Pin { pinned: true }),
+        "unpin" => Ok(Command::Pin { pinned: false }),
+        "pins" => Ok(Command::Pins),
+        "search" => parse_search(&args),
+        "system" => Ok(Command::System),
+        "who" => Ok(Command::Who),
+        "telemetry" => parse_telemetry(&args),
+        "dnd" => parse_dnd(&args),
+        "remind" => parse_remind(&args),
+        "autoreply" => parse_autoreply(&args),
+        "receipts" => parse_receipts(&args),
+        "presence" => parse_presence(&args),
+        "rekey" => Ok(Command::Rekey),
+        "verify" => parse_member(&args).map(Command::Verify),
+        "trust" => parse_member(&args).map(Command::Trust),
+        "whois" => parse_member(&args).map(Command::Whois),
+        "dm" => parse_member(&args).map(Command::Dm),
+        "join" => parse_join(&args),
+        "group" => parse_group(&args),
+        "invite" => parse_member(&args).map(|user| Command::Moderate(GroupCommand::Invite(user))),
+        "kick" => parse_member(&args).map(|user| Command::Moderate(GroupCommand::Kick(user))),
+        "topic" => parse_topic(&args),
+        "slow" => parse_slow(&args),
+        "announce" => parse_announce(&args),
+        "announcer" => parse_announcer(&args),
+        "roll" => parse_roll(&args),
+        "flip" => Ok(Command::Flip),
+        "shrug" => Ok(Command::Shrug),
+        _ => Err(format!("/{} is not implemented", name)),
+    };
+    Some(result.map_err(|e| format!("{} - usage: {}", e, spec.usage)))
+}
+
+/// Parse `/nick <newname>`
+fn parse_nick(args: &[&str]) -> Result<Command, String> {
+    let [name] = args else {
+        return Err("Expected exactly one name".to_string());
+    };
+    validate_username(name).map_err(|e| format!("Invalid nickname: {}", e))?;
+    Ok(Command::Nick(name.to_string()))
+}
+
+/// Parse `/search <text...>`
+fn parse_search(args: &[&str]) -> Result<Command, String> {
+    let text = args.join(" ");
+    if text.is_empty() {
+        return Err("Expected something to search for".to_string());
+    }
+    Ok(Command::Search(text))
+}
+
+/// Parse `/telemetry export <path>` or `/telemetry stop`
+fn parse_telemetry(args: &[&str]) -> Result<Command, String> {
+    match args {
+        ["export", path] => Ok(Command::Telemetry(Some(PathBuf::from(path)))),
+        ["export"] => Err("export needs a file path".to_string()),
+        ["stop"] => Ok(Command::Telemetry(None)),
+        _ => Err("Expected export <path> or stop".to_string()),
+    }
+}
+
+/// Parse `/dnd [DURATION|off]`
+fn parse_dnd(args: &[&str]) -> Result<Command, String> {
+    let request = match args {
+        [] => DndRequest::Toggle,
+        ["off"] => DndRequest::Off,
+        [value] => DndRequest::For(
+            parse_duration(value).ok_or_else(|| format!("Invalid duration: {}", value))?,
+        ),
+        _ => return Err("Expected at most one argument".to_string()),
+    };
+    Ok(Command::Dnd(request))
+}
+
+/// Parse `/remind <DURATION> <text...>`
+fn parse_remind(args: &[&str]) -> Result<Command, String> {
+    let [value, text @ ..] = args else {
+        return Err("Expected a duration".to_string());
+    };
+    let after = parse_duration(value)
+        .filter(|after| *after > Duration::zero())
+        .ok_or_else(|| format!("Invalid duration: {}", value))?;
+    let text = text.join(" ");
+    if text.is_empty() {
+        return Err("Expected something to be reminded of".to_string());
+    }
+    if text.chars().count() > MAX_REMINDER_LEN {
+        return Err(format!("Reminders must be at most {} characters", MAX_REMINDER_LEN));
+    }
+    Ok(Command::Remind { after, text })
+}
+
+/// Parse `/autoreply [on|off]`, `/autoreply add [--dnd] <regex> <reply...>`
+/// or `/autoreply remove <n>`
+fn parse_autoreply(args: &[&str]) -> Result<Command, String> {
+    let request = match args {
+        [] => AutoReplyRequest::List,
+        ["on"] => AutoReplyRequest::Enable(true),
+        ["off"] => AutoReplyRequest::Enable(false),
+        ["remove", n] => {
+            let n: usize = n.parse().ok().filter(|&n| n > 0).ok_or_else(|| format!("Invalid rule number: {}", n))?;
+            AutoReplyRequest::Remove(n - 1)
+        }
+        ["add", rest @ ..] => {
+            let (dnd_only, rest) = match rest {
+                ["--dnd", rest @ ..] => (true, rest),
+                rest => (false, rest),
+            };
+            let [pattern, reply @ ..] = rest else {
+                return Err("add needs a pattern and a reply".to_string());
+            };
+            // Syntax errors span several lines; the last one says what is wrong
+            regex::Regex::new(pattern).map_err(|e| {
+                format!("Invalid pattern: {}", e.to_string().lines().last().unwrap_or_default())
+            })?;
+            let reply = reply.join(" ");
+            if reply.is_empty() {
+                return Err("add needs a reply".to_string());
+            }
+            if reply.chars().count() > MAX_REPLY_LEN {
+                return Err(format!("Replies must be at most {} characters", MAX_REPLY_LEN));
+            }
+            AutoReplyRequest::Add(AutoReplyRule {
+                pattern: pattern.to_string(),
+                reply,
+                dnd_only,
+            })
+        }
+        _ => return Err("Expected on, off, add, remove or nothing".to_string()),
+    };
+    Ok(Command::AutoReply(request))
+}
+
+/// Parse `/receipts [on|off]`
+fn parse_receipts(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [] => Ok(Command::Receipts(None)),
+        ["on"] => Ok(Command::Receipts(Some(true))),
+        ["off"] => Ok(Command::Receipts(Some(false))),
+        _ => Err("Expected on, off or nothing".to_string()),
+    }
+}
+
+/// Parse `/presence [all|contacts|dms|none]`
+fn parse_presence(args: &[&str]) -> Result<Command, String> {
+    let filter = match args {
+        [] => None,
+        ["all"] => Some(PresenceFilter::All),
+        ["contacts"] => Some(PresenceFilter::Contacts),
+        ["dms"] => Some(PresenceFilter::Dms),
+        ["none"] => Some(PresenceFilter::None),
+        _ => return Err("Expected all, contacts, dms, none or nothing".to_string()),
+    };
+    Ok(Command::Presence(filter))
+}
+
+/// Parse `/join #<room> [--passphrase]`
+fn parse_join(args: &[&str]) -> Result<Command, String> {
+    let (name, passphrase) = match args {
+        [name] => (name, false),
+        [name, "--passphrase"] | ["--passphrase", name] => (name, true),
+        _ => return Err("Expected a room name and optionally --passphrase".to_string()),
+    };
+    let name = name.strip_prefix('#').unwrap_or(name);
+    validate_group_name(name).map_err(|e| format!("Invalid room name: {}", e))?;
+    Ok(Command::Join {
+        name: name.to_string(),
+        passphrase,
+    })
+}
+
+/// Parse `/group <name> <user>...`
+fn parse_group(args: &[&str]) -> Result<Command, String> {
+    let [name, members @ ..] = args else {
+        return Err("Expected a group name".to_string());
+    };
+    validate_group_name(name).map_err(|e| format!("Invalid group name: {}", e))?;
+    if members.is_empty() {
+        return Err("Expected at least one member".to_string());
+    }
+    for member in members {
+        validate_username(member).map_err(|e| format!("Invalid username: {}", e))?;
+    }
+    Ok(Command::CreateGroup {
+        name: name.to_string(),
+        members: members.iter().map(|m| m.to_string()).collect(),
+    })
+}
+
+/// Parse the single username of `/invite` and `/kick`
+fn parse_member(args: &[&str]) -> Result<String, String> {
+    let [user] = args else {
+        return Err("Expected exactly one username".to_string());
+    };
+    validate_username(user).map_err(|e| format!("Invalid username: {}", e))?;
+    Ok(user.to_string())
+}
+
+/// Parse `/topic [text...]`
+fn parse_topic(args: &[&str]) -> Result<Command, String> {
+    let topic = args.join(" ");
+    if topic.chars().count() > MAX_TOPIC_LEN {
+        return Err(format!("Topic must be at most {} characters", MAX_TOPIC_LEN));
+    }
+    Ok(Command::Moderate(GroupCommand::Topic(topic)))
+}
+
+/// Parse `/slow <DURATION|off>`
+fn parse_slow(args: &[&str]) -> Result<Command, String> {
+    let secs = match args {
+        ["off"] => 0,
+        [value] => {
+            let duration = parse_duration(value).ok_or_else(|| format!("Invalid duration: {}", value))?;
+            u32::try_from(duration.num_seconds())
+                .ok()
+                .filter(|&secs| secs <= MAX_SLOW_MODE_SECS)
+                .ok_or_else(|| format!("Slow mode must be at most {} seconds", MAX_SLOW_MODE_SECS))?
+        }
+        _ => return Err("Expected a duration or off".to_string()),
+    };
+    Ok(Command::Moderate(GroupCommand::SlowMode(secs)))
+}
+
+/// Parse `/announce <on|off>`
+fn parse_announce(args: &[&str]) -> Result<Command, String> {
+    match args {
+        ["on"] => Ok(Command::Moderate(GroupCommand::Announce(true))),
+        ["off"] => Ok(Command::Moderate(GroupCommand::Announce(false))),
+        _ => Err("Expected on or off".to_string()),
+    }
+}
+
+/// Parse `/announcer <user> [off]`
+fn parse_announcer(args: &[&str]) -> Result<Command, String> {
+    let (user, allowed) = match args {
+        [user] => (user, true),
+        [user, "off"] => (user, false),
+        _ => return Err("Expected a username, optionally followed by off".to_string()),
+    };
+    validate_username(user).map_err(|e| format!("Invalid username: {}", e))?;
+    Ok(Command::Moderate(GroupCommand::Announcer { user: user.to_string(), allowed }))
+}
+
+/// Parse `/alias <user> [name...]`
+fn parse_alias(args: &[&str]) -> Result<Command, String> {
+    let [username, alias @ ..] = args else {
+        return Err("Expected a username".to_string());
+    };
+    validate_username(username).map_err(|e| format!("Invalid username: {}", e))?;
+
+    let alias = alias.join(" ");
+    if alias.chars().count() > MAX_ALIAS_LEN {
+        return Err(format!("Alias must be at most {} characters", MAX_ALIAS_LEN));
+    }
+    Ok(Command::Alias {
+        username: username.to_string(),
+        alias: (!alias.is_empty()).then_some(alias),
+    })
+}
+
+/// Parse `/export [format] [--since DURATION] [--out PATH]`
+fn parse_export(args: &[&str]) -> Result<Command, String> {
+    let mut format = ExportFormat::Markdown;
+    let mut since = None;
+    let mut out = None;
+
+    let mut iter = args.iter();
+    while let Some(&arg) = iter.next() {
+        match arg {
+            "--since" => {
+                let value = iter.next().ok_or("--since needs a duration, e.g. 2h")?;
+                since = Some(
+                    parse_duration(value).ok_or_else(|| format!("Invalid duration: {}", value))?,
+                );
+            }
+            "--out" => {
+                let value = iter.next().ok_or("--out needs a file path")?;
+                out = Some(PathBuf::from(value));
+            }
+            other => {
+                format = other
+                    .parse()
+                    .map_err(|_| format!("Unknown export format: {} (use md, txt or json)", other))?;
+            }
+        }
+    }
+
+    Ok(Command::Export { format, since, out })
+}
+
+/// Parse `/purge [channel] [--older-than DURATION]`
+fn parse_purge(args: &[&str]) -> Result<Command, String> {
+    let mut channel = None;
+    let mut older_than = None;
+
+    let mut iter = args.iter();
+    while let Some(&arg) = iter.next() {
+        match arg {
+            "--older-than" => {
+                let value = iter.next().ok_or("--older-than needs a duration, e.g. 30d")?;
+                older_than = Some(
+                    parse_duration(value)
+                        .filter(|age| *age > Duration::zero())
+                        .ok_or_else(|| format!("Invalid duration: {}", value))?,
+                );
+            }
+            other if channel.is_none() => channel = Some(other.to_string()),
+            other => return Err(format!("Unexpected argument: {}", other)),
+        }
+    }
+
+    Ok(Command::Purge { channel, older_than })
+}
+
+/// Parse a short duration like `45s`, `30m`, `2h` or `7d`
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let unit_at = s.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = s.split_at(unit_at);
+    let amount: i64 = amount.parse().ok()?;
+
+    match unit {
+        "s" => Duration::try_seconds(amount),
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        _ => None,
+    }
+}
+
+/// Parse `/roll [NdM[+K]]`
+fn parse_roll(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [] => Ok(Command::Roll(Dice { count: 1, sides: 6, modifier: 0 })),
+        [notation] => Dice::parse(notation).map(Command::Roll),
+        _ => Err("Expected at most one roll".to_string()),
+    }
+}
@@ -0,0 +1,203 @@
+// GhostWire Client - Configuration
+// User settings loaded from `<config dir>/ghostwire/config.toml`.
+// Every field has a default, so a missing or partial file is fine.
+
+use crate::autoreply::AutoReplyConfig;
+use crate::e2e::RekeyConfig;
+use crate::history::RetentionConfig;
+use crate::notify::NotificationConfig;
+use crate::padding::PaddingConfig;
+use crate::relays::RelayConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// File name of the config file inside the config directory
+const CONFIG_FILE: &str = "config.toml";
+
+/// Messages kept in memory per channel unless configured otherwise
+pub const DEFAULT_MAX_MESSAGES: usize = 1000;
+
+/// Users shown in the roster unless configured otherwise
+pub const DEFAULT_MAX_USERS: usize = 100;
+
+/// User configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Username when none is given on the command line, e.g. when opening
+    /// a ghostwire:// link
+    pub username: Option<String>,
+    /// Mark messages that had terminal control sequences stripped
+    pub show_sanitized_marker: bool,
+    /// Hide join/leave/system notices in channels that have not toggled it
+    pub hide_system_messages: bool,
+    /// Draw status icons and decorations with ASCII instead of symbols/emoji
+    pub ascii_only: bool,
+    /// UI language, e.g. "es" (default: from LC_ALL / LC_MESSAGES / LANG)
+    pub locale: Option<String>,
+    /// Mention/DM alerts (`[notifications]` table)
+    pub notifications: NotificationConfig,
+    /// Rules that answer incoming DMs (`[autoreply]` table)
+    pub autoreply: AutoReplyConfig,
+    /// Fallback relays, tried in order after the URL given on the command line
+    pub servers: Vec<String>,
+    /// Extra relays to stay connected to at the same time (`[[relays]]`)
+    pub relays: Vec<RelayConfig>,
+    /// Send the stored relay token as `Authorization: Bearer` on connect
+    pub relay_auth: bool,
+    /// Extra headers for the WebSocket upgrade (`[headers]` table)
+    pub headers: BTreeMap<String, String>,
+    /// Shell command run on connection changes (`--on-event` overrides it)
+    pub on_event: Option<String>,
+    /// Tell DM partners when we have read their messages (`/receipts` toggles it)
+    pub read_receipts: bool,
+    /// When DM session keys are rotated (`[rekey]` table)
+    pub rekey: RekeyConfig,
+    /// Padding of encrypted messages and cover traffic (`[padding]` table)
+    pub padding: PaddingConfig,
+    /// Encrypt aliases, starred messages and other local files at rest
+    pub encrypt_storage: bool,
+    /// Keep chat messages across restarts in the data directory
+    pub save_history: bool,
+    /// How long and how much stored history is kept (`[retention]` table)
+    pub retention: RetentionConfig,
+    /// Color keywords, strings and comments in ``` code blocks
+    pub syntax_highlighting: bool,
+    /// Render *bold*, _italic_, `code` and > quotes; off shows the raw text
+    pub render_markdown: bool,
+    /// Whose join/leave notices to show (`/presence` changes it for the session)
+    pub presence: PresenceFilter,
+    /// Collect join/leave/rename/topic notices in the ~activity channel;
+    /// off shows them inline in the chats, as before
+    pub activity_channel: bool,
+    /// Messages kept in memory per channel; the oldest are dropped first
+    pub max_messages: usize,
+    /// Most users kept in the roster
+    pub max_users: usize,
+    /// Per-channel `max_messages` overrides by channel ID (`[channel_limits]` table)
+    pub channel_limits: BTreeMap<String, usize>,
+    /// Message times as clock times or as "5m ago" (`:set timestamps=` changes it)
+    pub timestamps: TimestampStyle,
+    /// Sidebar order below the favorites (`:set sidebar=` changes it)
+    pub sidebar_sort: SidebarSort,
+}
+
+/// How message times are shown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampStyle {
+    /// `14:03:27`
+    #[default]
+    Absolute,
+    /// `5m ago`
+    Relative,
+}
+
+/// How the channel sidebar is ordered below the favorites
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidebarSort {
+    /// Global and the virtual channels, then the rest as arranged with `H`/`L`
+    #[default]
+    Arranged,
+    /// Channels with unread mentions, then other unread channels, on top
+    Unread,
+}
+
+/// Whose join, leave and rename notices are shown. The roster is kept up to
+/// date either way; filtered users just come and go silently.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceFilter {
+    /// Everyone
+    #[default]
+    All,
+    /// Users with an alias or a verified identity key
+    Contacts,
+    /// Users we have a DM channel or an encrypted session history with
+    Dms,
+    /// Nobody
+    None,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            username: None,
+            show_sanitized_marker: true,
+            hide_system_messages: false,
+            ascii_only: false,
+            locale: None,
+            notifications: NotificationConfig::default(),
+            autoreply: AutoReplyConfig::default(),
+            servers: Vec::new(),
+            relays: Vec::new(),
+            relay_auth: false,
+            headers: BTreeMap::new(),
+            on_event: None,
+            read_receipts: true,
+            rekey: RekeyConfig::default(),
+            padding: PaddingConfig::default(),
+            encrypt_storage: true,
+            save_history: true,
+            retention: RetentionConfig::default(),
+            syntax_highlighting: true,
+            render_markdown: true,
+            activity_channel: true,
+            presence: PresenceFilter::All,
+            max_messages: DEFAULT_MAX_MESSAGES,
+            max_users: DEFAULT_MAX_USERS,
+            channel_limits: BTreeMap::new(),
+            timestamps: TimestampStyle::Absolute,
+            sidebar_sort: SidebarSort::Arranged,
+        }
+    }
+}
+
+impl Config {
+    /// Load the config from `path`, or the default location if `None`.
+    ///
+    /// A missing file yields the defaults; an unreadable or invalid file is
+    /// logged and also falls back to the defaults so the client still starts.
+    pub fn load(path: Option<&Path>) -> Self {
+        let Some(path) = path.map(Path::to_path_buf).or_else(default_config_path) else {
+            return Self::default();
+        };
+        if !path.exists() {
+            return Self::default();
+        }
+
+        match fs::read_to_string(&path).map(|text| toml::from_str::<Config>(&text)) {
+            Ok(Ok(config)) => {
+                info!("Loaded config from {}", path.display());
+                config
+            }
+            Ok(Err(e)) => {
+                warn!("Invalid config {}: {}", path.display(), e);
+                Self::default()
+            }
+            Err(e) => {
+                warn!("Failed to read config {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// How many messages a channel keeps: its override if it has one, else
+    /// `max_messages`. Never below one, so the newest message always shows.
+    pub fn history_limit(&self, channel_id: &str) -> usize {
+        self.channel_limits
+            .get(channel_id)
+            .copied()
+            .unwrap_or(self.max_messages)
+            .max(1)
+    }
+}
+
+/// `<config dir>/ghostwire/config.toml`
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("ghostwire").join(CONFIG_FILE))
+}
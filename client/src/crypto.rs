@@ -0,0 +1,146 @@
+// GhostWire Client - Passphrase Encryption
+// Argon2id key derivation + XChaCha20-Poly1305 for anything stored at rest,
+// and for the messages of passphrase-protected rooms
+
+use anyhow::{anyhow, Context};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use ghostwire_protocol::e2e::RoomEnvelope;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// Length of the random Argon2 salt in bytes
+const SALT_LEN: usize = 16;
+
+/// Symmetric key material, wiped from memory on drop
+pub type SecretKey = Zeroizing<[u8; 32]>;
+
+/// Derive a 256-bit key from a passphrase with Argon2id (default parameters)
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> anyhow::Result<SecretKey> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|e| anyhow!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+/// Build the AEAD cipher without copying the key out of its zeroizing buffer
+fn cipher(key: &SecretKey) -> XChaCha20Poly1305 {
+    XChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+}
+
+/// Ciphertext plus everything (except the passphrase) needed to decrypt it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedBox {
+    /// Format version, bumped if the KDF or cipher changes
+    pub version: u8,
+    /// Base64 Argon2 salt
+    pub salt: String,
+    /// Base64 XChaCha20 nonce
+    pub nonce: String,
+    /// Base64 ciphertext with Poly1305 tag
+    pub ciphertext: String,
+}
+
+impl SealedBox {
+    /// Encrypt `plaintext` under a key derived from `passphrase` with a fresh salt
+    pub fn seal(passphrase: &str, plaintext: &[u8]) -> anyhow::Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        chacha20poly1305::aead::rand_core::RngCore::fill_bytes(&mut OsRng, &mut salt);
+        let key = derive_key(passphrase, &salt)?;
+
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher(&key)
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| anyhow!("Encryption failed"))?;
+
+        Ok(Self {
+            version: 1,
+            salt: BASE64.encode(salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        })
+    }
+
+    /// Decrypt with `passphrase`; fails on a wrong passphrase or tampered data.
+    ///
+    /// The plaintext buffer is wiped when the returned value is dropped.
+    pub fn open(&self, passphrase: &str) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+        if self.version != 1 {
+            return Err(anyhow!("Unsupported sealed box version {}", self.version));
+        }
+
+        let salt = BASE64.decode(&self.salt).context("Invalid salt")?;
+        let nonce = BASE64.decode(&self.nonce).context("Invalid nonce")?;
+        let ciphertext = BASE64.decode(&self.ciphertext).context("Invalid ciphertext")?;
+        if nonce.len() != 24 {
+            return Err(anyhow!("Invalid nonce length"));
+        }
+
+        let key = derive_key(passphrase, &salt)?;
+        cipher(&key)
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+            .map(Zeroizing::new)
+            .map_err(|_| anyhow!("Wrong passphrase or corrupted data"))
+    }
+}
+
+/// Key of a passphrase room. Everyone who joins with the same passphrase
+/// derives the same key, so the salt is fixed per room (its wire channel ID).
+pub fn room_key(passphrase: &str, room_id: &str) -> anyhow::Result<SecretKey> {
+    derive_key(passphrase, format!("GhostWire {}", room_id).as_bytes())
+}
+
+/// Associated data binding a room message to its room, sender and send time
+fn room_aad(room_id: &str, sender: &str, sent: Option<i64>) -> Vec<u8> {
+    match sent {
+        Some(sent) => format!("{}\n{}\n{}", room_id, sender, sent).into_bytes(),
+        None => format!("{}\n{}", room_id, sender).into_bytes(),
+    }
+}
+
+/// Encrypt a message for a passphrase room, stamped with the Unix time `sent`
+pub fn seal_room_message(
+    key: &SecretKey,
+    room_id: &str,
+    sender: &str,
+    plaintext: &str,
+    sent: i64,
+) -> anyhow::Result<RoomEnvelope> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let aad = room_aad(room_id, sender, Some(sent));
+    let ciphertext = cipher(key)
+        .encrypt(&nonce, Payload { msg: plaintext.as_bytes(), aad: &aad })
+        .map_err(|_| anyhow!("Encryption failed"))?;
+    Ok(RoomEnvelope {
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+        sent: Some(sent),
+    })
+}
+
+/// Decrypt a passphrase room message; fails if it was sealed under another
+/// passphrase or by someone other than `sender`
+pub fn open_room_message(
+    key: &SecretKey,
+    room_id: &str,
+    sender: &str,
+    envelope: &RoomEnvelope,
+) -> anyhow::Result<Zeroizing<String>> {
+    let nonce = BASE64.decode(&envelope.nonce).context("Invalid nonce")?;
+    let ciphertext = BASE64.decode(&envelope.ciphertext).context("Invalid ciphertext")?;
+    if nonce.len() != 24 {
+        return Err(anyhow!("Invalid nonce length"));
+    }
+    let aad = room_aad(room_id, sender, envelope.sent);
+    let plaintext = cipher(key)
+        .decrypt(XNonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &aad })
+        .map(Zeroizing::new)
+        .map_err(|_| anyhow!("Wrong passphrase or corrupted message"))?;
+    let text = std::str::from_utf8(&plaintext).context("Message is not valid UTF-8")?;
+    Ok(Zeroizing::new(text.to_string()))
+}
@@ -0,0 +1,309 @@
+// GhostWire Client - Background Daemon
+// `ghostwire --daemon` runs only the network side: it stays connected to the
+// relays without a terminal and buffers what arrives while no client is
+// attached. `ghostwire attach` runs the TUI against it over a Unix socket, so
+// closing the terminal detaches instead of going offline. Encryption, storage and the UI stay in
+// the attached client; DM handshakes and receipts wait until one attaches.
+//
+// The socket speaks JSON lines: the daemon sends a `hello` with the username,
+// the events no client has seen yet and then live ones; the client sends
+// network commands. Each event goes to one client only: replaying a key
+// exchange or an encrypted message that a client already handled would
+// restart a finished DM handshake or look like a replay attack.
+
+use crate::network::{NetworkCommand, NetworkEvent};
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// File name of the socket inside the runtime (or data) directory
+const SOCKET_FILE: &str = "daemon.sock";
+
+/// Events kept for the next client that attaches while none is
+#[cfg(unix)]
+const REPLAY_EVENTS: usize = 5000;
+
+/// A line from the daemon to the attached client
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Frame {
+    /// First line after attaching: who the daemon is connected as
+    Hello { username: String },
+    /// A network event, buffered or live
+    Event { event: NetworkEvent },
+}
+
+/// A running daemon, standing in for the network task of a normal client
+pub struct Attached {
+    /// The daemon's current username; the TUI takes it instead of its own
+    pub username: String,
+    pub command_tx: mpsc::UnboundedSender<NetworkCommand>,
+    pub event_rx: mpsc::UnboundedReceiver<NetworkEvent>,
+    /// Bridge task; it ends on `NetworkCommand::Disconnect`, which detaches
+    /// without stopping the daemon
+    pub handle: JoinHandle<()>,
+}
+
+/// `<runtime dir>/ghostwire/daemon.sock`, or the data directory on systems
+/// without a runtime directory
+pub fn default_socket_path() -> anyhow::Result<PathBuf> {
+    match dirs::runtime_dir() {
+        Some(dir) => Ok(dir.join("ghostwire").join(SOCKET_FILE)),
+        None => storage::data_path(SOCKET_FILE),
+    }
+}
+
+/// Keep an event no client has seen for the next one that attaches;
+/// heartbeat measurements and who-is answers are only interesting live
+#[cfg(unix)]
+fn remember(history: &mut std::collections::VecDeque<NetworkEvent>, event: NetworkEvent) {
+    if matches!(
+        event,
+        NetworkEvent::Latency { .. } | NetworkEvent::ClockOffset { .. } | NetworkEvent::Whois { .. }
+    ) {
+        return;
+    }
+    history.push_back(event);
+    if history.len() > REPLAY_EVENTS {
+        history.pop_front();
+    }
+}
+
+/// Serve the network task's events on `socket_path` until SIGINT or SIGTERM.
+///
+/// One client is attached at a time; a new one takes over and the previous
+/// one sees the connection close. SIGHUP is ignored so the daemon outlives
+/// the terminal it was started from.
+#[cfg(unix)]
+pub async fn run(
+    socket_path: &Path,
+    mut username: String,
+    command_tx: mpsc::UnboundedSender<NetworkCommand>,
+    mut event_rx: mpsc::UnboundedReceiver<NetworkEvent>,
+) -> anyhow::Result<()> {
+    use std::collections::VecDeque;
+    use tokio::net::unix::OwnedWriteHalf;
+    use tokio::signal::unix::{signal, SignalKind};
+    use tracing::{debug, info, warn};
+
+    let listener = bind(socket_path).await?;
+    println!("GhostWire daemon for {} listening on {}", username, socket_path.display());
+    info!("Daemon listening on {}", socket_path.display());
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut history: VecDeque<NetworkEvent> = VecDeque::new();
+    let mut client: Option<OwnedWriteHalf> = None;
+    // Reads the attached client's commands; stopped when another takes over
+    let mut forwarder: Option<JoinHandle<()>> = None;
+    let (client_command_tx, mut client_command_rx) = mpsc::unbounded_channel();
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("Failed to accept client: {}", e);
+                        continue;
+                    }
+                };
+                let (reader, mut writer) = stream.into_split();
+                match replay(&mut writer, &username, &history).await {
+                    Ok(()) => {
+                        info!("Client attached ({} events replayed)", history.len());
+                        history.clear();
+                        client = Some(writer);
+                        if let Some(previous) = forwarder.take() {
+                            previous.abort();
+                        }
+                        forwarder = Some(tokio::spawn(forward_commands(reader, client_command_tx.clone())));
+                    }
+                    Err(e) => warn!("Failed to attach client: {}", e),
+                }
+            }
+            event = event_rx.recv() => {
+                let Some(event) = event else { break };
+                if let NetworkEvent::UserRenamed { old, new } = &event {
+                    if *old == username {
+                        username = new.clone();
+                    }
+                }
+                if let Some(writer) = &mut client {
+                    let frame = Frame::Event { event: event.clone() };
+                    if write_line(writer, &frame).await.is_ok() {
+                        continue;
+                    }
+                    info!("Client detached");
+                    client = None;
+                }
+                remember(&mut history, event);
+            }
+            Some(command) = client_command_rx.recv() => {
+                let _ = command_tx.send(command);
+            }
+            _ = hangup.recv() => debug!("Ignoring SIGHUP"),
+            _ = terminate.recv() => break,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    info!("Daemon stopping");
+    let _ = command_tx.send(NetworkCommand::Disconnect);
+    let _ = std::fs::remove_file(socket_path);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn run(
+    _socket_path: &Path,
+    _username: String,
+    _command_tx: mpsc::UnboundedSender<NetworkCommand>,
+    _event_rx: mpsc::UnboundedReceiver<NetworkEvent>,
+) -> anyhow::Result<()> {
+    anyhow::bail!("--daemon is only supported on Unix")
+}
+
+/// Listen on `path`, replacing a stale socket but not a live daemon's. The
+/// socket is only accessible to us: whoever can connect can chat as us. It
+/// is created with a umask that already leaves it 0600, since the directory
+/// may be shared (`--socket /tmp/...`) and a chmod after binding leaves a
+/// window where others can connect.
+#[cfg(unix)]
+async fn bind(path: &Path) -> anyhow::Result<tokio::net::UnixListener> {
+    use anyhow::Context;
+    use std::fs;
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    if tokio::net::UnixStream::connect(path).await.is_ok() {
+        anyhow::bail!("A daemon is already listening on {}", path.display());
+    }
+    if let Some(dir) = path.parent() {
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+    let _ = fs::remove_file(path);
+    // SAFETY: umask only swaps the process file mode mask; nothing else
+    // creates files while the daemon is starting
+    let previous = unsafe { libc::umask(0o177) };
+    let listener = tokio::net::UnixListener::bind(path);
+    unsafe { libc::umask(previous) };
+    let listener = listener.with_context(|| format!("Cannot listen on {}", path.display()))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Greet a new client and send it what it missed
+#[cfg(unix)]
+async fn replay(
+    writer: &mut tokio::net::unix::OwnedWriteHalf,
+    username: &str,
+    history: &std::collections::VecDeque<NetworkEvent>,
+) -> anyhow::Result<()> {
+    write_line(writer, &Frame::Hello { username: username.to_string() }).await?;
+    for event in history {
+        write_line(writer, &Frame::Event { event: event.clone() }).await?;
+    }
+    Ok(())
+}
+
+/// Pass an attached client's commands on to the daemon loop
+#[cfg(unix)]
+async fn forward_commands(
+    reader: tokio::net::unix::OwnedReadHalf,
+    command_tx: mpsc::UnboundedSender<NetworkCommand>,
+) {
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        match serde_json::from_str::<NetworkCommand>(&line) {
+            // Quitting the TUI detaches; only a signal stops the daemon
+            Ok(NetworkCommand::Disconnect) => break,
+            Ok(command) => {
+                if command_tx.send(command).is_err() {
+                    break;
+                }
+            }
+            Err(e) => tracing::warn!("Invalid command from attached client: {}", e),
+        }
+    }
+}
+
+/// Connect to the daemon on `socket_path` and bridge its socket to the
+/// channels the UI loop expects from a network task
+#[cfg(unix)]
+pub async fn attach(socket_path: &Path) -> anyhow::Result<Attached> {
+    use anyhow::Context;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    let stream = tokio::net::UnixStream::connect(socket_path).await.with_context(|| {
+        format!("No daemon listening on {} (start one with --daemon)", socket_path.display())
+    })?;
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let greeting = lines.next_line().await?.context("The daemon closed the connection")?;
+    let Frame::Hello { username } = serde_json::from_str(&greeting)? else {
+        anyhow::bail!("Unexpected greeting from the daemon");
+    };
+
+    let (event_tx, event_rx) = mpsc::unbounded_channel();
+    let (command_tx, mut command_rx) = mpsc::unbounded_channel();
+    let handle = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                line = lines.next_line() => match line {
+                    Ok(Some(line)) => match serde_json::from_str::<Frame>(&line) {
+                        Ok(Frame::Event { event }) => {
+                            let _ = event_tx.send(event);
+                        }
+                        Ok(Frame::Hello { .. }) => {}
+                        Err(e) => tracing::warn!("Invalid frame from the daemon: {}", e),
+                    },
+                    _ => {
+                        let _ = event_tx.send(NetworkEvent::Disconnected {
+                            reason: "Daemon connection closed".to_string(),
+                        });
+                        break;
+                    }
+                },
+                command = command_rx.recv() => match command {
+                    Some(NetworkCommand::Disconnect) | None => return,
+                    Some(command) => {
+                        if let Err(e) = write_line(&mut writer, &command).await {
+                            tracing::warn!("Failed to send command to the daemon: {}", e);
+                        }
+                    }
+                },
+            }
+        }
+        // The daemon is gone; wait for the UI to quit
+        while let Some(command) = command_rx.recv().await {
+            if matches!(command, NetworkCommand::Disconnect) {
+                break;
+            }
+        }
+    });
+
+    Ok(Attached { username, command_tx, event_rx, handle })
+}
+
+#[cfg(not(unix))]
+pub async fn attach(_socket_path: &Path) -> anyhow::Result<Attached> {
+    anyhow::bail!("attach is only supported on Unix")
+}
+
+/// Write one JSON line
+#[cfg(unix)]
+async fn write_line<T: Serialize>(
+    writer: &mut tokio::net::unix::OwnedWriteHalf,
+    value: &T,
+) -> anyhow::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    Ok(())
+}
@@ -0,0 +1,707 @@
+// GhostWire Client - DM Encryption
+// End-to-end encrypted DM sessions. A KEYX handshake (init, reply, confirm)
+// mixes both identity keys with fresh ephemeral keys, then each DM channel
+// runs its own double ratchet. Sessions are saved encrypted under a key
+// derived from the identity key, which lives in the secret store. A session
+// is replaced by a new handshake after `[rekey]` limits, or on `/rekey`. An
+// init we answer only replaces the working session once the initiator's
+// confirm decrypts, and a peer whose identity key changed gets no DMs until
+// the new key is accepted.
+
+use crate::ratchet::{self, Key, Ratchet};
+use crate::replay;
+use crate::secrets::SecretStore;
+use crate::storage;
+use crate::trust::{Seen, TrustStore};
+use anyhow::{anyhow, Context};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use ghostwire_protocol::e2e::{Envelope, KeyExchange};
+use hkdf::Hkdf;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+/// Secret store entry holding our identity private key (base64)
+pub const IDENTITY_SECRET: &str = "identity_key";
+
+/// Saved sessions, encrypted, in the data directory
+const SESSIONS_FILE: &str = "sessions.json";
+
+/// When DM sessions are replaced by a new handshake (`[rekey]` in config.toml)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RekeyConfig {
+    /// Hours a session is used before it is rotated; 0 turns it off
+    pub interval_hours: u64,
+    /// Messages, sent and received, before a session is rotated; 0 turns it off
+    pub after_messages: u64,
+}
+
+impl Default for RekeyConfig {
+    fn default() -> Self {
+        Self {
+            interval_hours: 24 * 7,
+            after_messages: 1000,
+        }
+    }
+}
+
+/// A frame the encryption layer wants sent
+#[derive(Debug, Clone)]
+pub enum Outgoing {
+    /// KEYX handshake step
+    KeyExchange { channel_id: String, exchange: KeyExchange },
+    /// Encrypted MSG; `id` is the ID the message was shown under locally
+    Message { channel_id: String, id: String, payload: String, action: bool },
+}
+
+/// A DM waiting for its session to be ready
+struct Queued {
+    id: String,
+    content: Zeroizing<String>,
+    action: bool,
+}
+
+/// Where a session's handshake stands
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+enum State {
+    /// We sent an init and wait for the reply
+    Initiated { ephemeral: Key },
+    /// We replied and wait for the initiator's confirm, with no working
+    /// session meanwhile
+    Replied(Reply),
+    /// Both sides can send
+    Established { ratchet: Ratchet },
+}
+
+/// Our answer to a peer's init
+#[derive(Clone, Serialize, Deserialize)]
+struct Reply {
+    /// Identity key the init presented, trusted once the confirm decrypts.
+    /// Sessions saved by earlier versions pinned it on receipt instead.
+    #[serde(default)]
+    identity: Option<Key>,
+    ratchet: Ratchet,
+}
+
+/// Encryption state of one DM channel
+#[derive(Serialize, Deserialize)]
+struct Session {
+    peer: String,
+    /// Peer's identity key, once a handshake has told us
+    peer_identity: Option<Key>,
+    state: State,
+    /// Ratchet of the session this one replaced, for messages still in flight
+    previous: Option<Ratchet>,
+    /// An init we answered while this session worked; it takes over once the
+    /// initiator's confirm decrypts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reply: Option<Reply>,
+    /// When the handshake of this session started
+    #[serde(default = "Utc::now")]
+    since: DateTime<Utc>,
+    /// Messages sent and received on this session
+    #[serde(default)]
+    messages: u64,
+    /// Messages typed before the session was ready (not saved)
+    #[serde(skip)]
+    queued: Vec<Queued>,
+}
+
+impl Session {
+    fn established(&self) -> Option<&Ratchet> {
+        match &self.state {
+            State::Established { ratchet } => Some(ratchet),
+            _ => None,
+        }
+    }
+
+    /// Our answer to the peer's init, if we wait for their confirm
+    fn reply_mut(&mut self) -> Option<&mut Reply> {
+        match &mut self.state {
+            State::Replied(reply) => Some(reply),
+            _ => self.reply.as_mut(),
+        }
+    }
+
+    /// Switch to the answered handshake once the initiator proved it. A
+    /// session it replaces stays around for messages in flight. Returns the
+    /// identity key the init presented.
+    fn adopt_reply(&mut self) -> Option<Key> {
+        let reply = match self.reply.take() {
+            Some(reply) => {
+                self.previous = self.established().cloned().or(self.previous.take());
+                self.since = Utc::now();
+                self.messages = 0;
+                reply
+            }
+            None => match &self.state {
+                State::Replied(reply) => reply.clone(),
+                _ => return None,
+            },
+        };
+        self.peer_identity = reply.identity.clone().or(self.peer_identity.take());
+        self.state = State::Established { ratchet: reply.ratchet };
+        reply.identity
+    }
+}
+
+/// What a handshake step changed, for notices
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HandshakeOutcome {
+    /// The session can now be used in both directions
+    pub established: bool,
+    /// The peer presented a different identity key than last time
+    pub identity_changed: bool,
+    /// The new session replaced an earlier one (a key rotation)
+    pub rotated: bool,
+}
+
+/// Saved sessions file: the session map, encrypted
+#[derive(Default, Serialize, Deserialize)]
+struct SealedSessions {
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Our identity and every DM session
+pub struct E2e {
+    identity: Key,
+    sessions: HashMap<String, Session>,
+    /// Identity keys of DM partners, pinned on first use
+    trust: TrustStore,
+    outbox: Vec<Outgoing>,
+    /// Save sessions to disk (off for throwaway identities)
+    persist: bool,
+    /// Limits after which a session is rotated
+    rekey: RekeyConfig,
+    /// DM channels whose session was just rotated on schedule, for notices
+    rotated: Vec<String>,
+}
+
+impl Default for E2e {
+    fn default() -> Self {
+        Self::ephemeral()
+    }
+}
+
+impl E2e {
+    /// A random identity that is never saved
+    pub fn ephemeral() -> Self {
+        Self {
+            identity: Key::random(),
+            sessions: HashMap::new(),
+            trust: TrustStore::default(),
+            outbox: Vec::new(),
+            persist: false,
+            rekey: RekeyConfig::default(),
+            rotated: Vec::new(),
+        }
+    }
+
+    /// Load our identity from the secret store, creating it on first run,
+    /// along with the saved sessions
+    pub fn load(store: &SecretStore) -> anyhow::Result<Self> {
+        let identity = match store.get(IDENTITY_SECRET)? {
+            Some(secret) => {
+                Key::from_base64(secret.expose_secret()).context("Stored identity key is invalid")?
+            }
+            None => {
+                let key = Key::random();
+                store.set(IDENTITY_SECRET, &key.to_base64())?;
+                info!("Created a new identity key");
+                key
+            }
+        };
+        let sessions = load_sessions(&identity);
+        Ok(Self {
+            identity,
+            sessions,
+            trust: TrustStore::load(),
+            outbox: Vec::new(),
+            persist: true,
+            rekey: RekeyConfig::default(),
+            rotated: Vec::new(),
+        })
+    }
+
+    /// Our identity key and the one we hold for a peer, for `/verify`
+    pub fn identities(&self, peer: &str) -> Option<(Key, Key)> {
+        let theirs = Key::from_base64(self.trust.current(peer)?)?;
+        Some((self.identity.public(), theirs))
+    }
+
+    /// Record the outcome of `/verify`; false if we hold no key for the peer.
+    /// A verified key is accepted, so DMs held for the peer go out.
+    pub fn set_verified(&mut self, peer: &str, verified: bool) -> bool {
+        if !self.trust.set_verified(peer, verified) {
+            return false;
+        }
+        self.save_trust();
+        self.flush_peer(peer);
+        true
+    }
+
+    /// Accept the peer's changed identity key (`/trust`) and send the DMs
+    /// held for them; false if no change was waiting
+    pub fn accept_identity(&mut self, peer: &str) -> bool {
+        if !self.trust.accept(peer) {
+            return false;
+        }
+        self.save_trust();
+        self.flush_peer(peer);
+        true
+    }
+
+    /// Whether the peer presented a changed identity key we have not
+    /// accepted; DMs to them are held until we do
+    pub fn identity_changed(&self, peer: &str) -> bool {
+        self.trust.is_changed(peer)
+    }
+
+    /// Whether a handshake ever showed us the peer's identity key
+    pub fn knows(&self, peer: &str) -> bool {
+        self.trust.get(peer).is_some()
+    }
+
+    /// Whether the peer's current identity key was verified
+    pub fn is_verified(&self, peer: &str) -> bool {
+        self.trust.is_verified(peer)
+    }
+
+    /// Set when sessions are rotated
+    pub fn set_rekey(&mut self, rekey: RekeyConfig) {
+        self.rekey = rekey;
+    }
+
+    /// DM channels whose session was rotated on schedule since the last call
+    pub fn take_rotated(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.rotated)
+    }
+
+    /// Whether the channel's session can send right away
+    pub fn is_established(&self, channel_id: &str) -> bool {
+        self.sessions.get(channel_id).is_some_and(|session| session.established().is_some())
+    }
+
+    /// Frames waiting to be sent, oldest first
+    pub fn take_outgoing(&mut self) -> Vec<Outgoing> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Encrypt a DM, or queue it until the session is ready (starting a
+    /// handshake if there is none) or the peer's changed identity key is
+    /// accepted. Returns true if it was queued. A session
+    /// past its `[rekey]` limits is rotated once the message is out.
+    pub fn send(&mut self, channel_id: &str, peer: &str, id: String, content: &str, action: bool) -> bool {
+        let held = self.trust.is_changed(peer);
+        if let Some(session) = self.sessions.get_mut(channel_id) {
+            let ratchet = match &mut session.state {
+                State::Established { ratchet } if !held => ratchet,
+                _ => return self.queue(channel_id, id, content, action),
+            };
+            match ratchet.encrypt(content.as_bytes()) {
+                Ok(envelope) => {
+                    session.messages += 1;
+                    self.outbox.push(Outgoing::Message {
+                        channel_id: channel_id.to_string(),
+                        id,
+                        payload: envelope.to_payload(),
+                        action,
+                    });
+                    if self.rotation_due(channel_id) {
+                        info!("Rotating the session with {}", peer);
+                        self.rotated.push(channel_id.to_string());
+                        self.start(channel_id, peer);
+                    } else {
+                        self.save();
+                    }
+                    return false;
+                }
+                Err(e) => warn!("Cannot encrypt for {}: {}", peer, e),
+            }
+        }
+        if !self.sessions.contains_key(channel_id) {
+            self.start(channel_id, peer);
+        }
+        self.queue(channel_id, id, content, action)
+    }
+
+    /// Hold a DM until the channel's session is ready; always true
+    fn queue(&mut self, channel_id: &str, id: String, content: &str, action: bool) -> bool {
+        if let Some(session) = self.sessions.get_mut(channel_id) {
+            session.queued.push(Queued {
+                id,
+                content: Zeroizing::new(content.to_string()),
+                action,
+            });
+        }
+        true
+    }
+
+    /// Whether the channel's session is past the `[rekey]` age or message limit
+    fn rotation_due(&self, channel_id: &str) -> bool {
+        let Some(session) = self.sessions.get(channel_id) else {
+            return false;
+        };
+        let hours = (Utc::now() - session.since).num_hours();
+        (self.rekey.after_messages > 0 && session.messages >= self.rekey.after_messages)
+            || (self.rekey.interval_hours > 0 && hours >= self.rekey.interval_hours as i64)
+    }
+
+    /// Start a fresh session, keeping the current ratchet for messages
+    /// already in flight (`/rekey`)
+    pub fn rekey(&mut self, channel_id: &str, peer: &str) {
+        self.start(channel_id, peer);
+    }
+
+    /// Resend pending handshake inits to a peer who just came online
+    pub fn peer_online(&mut self, peer: &str) {
+        let pending: Vec<(String, KeyExchange)> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| session.peer == peer)
+            .filter_map(|(channel_id, session)| match &session.state {
+                State::Initiated { ephemeral } => Some((channel_id.clone(), self.init(ephemeral))),
+                _ => None,
+            })
+            .collect();
+        for (channel_id, exchange) in pending {
+            self.outbox.push(Outgoing::KeyExchange { channel_id, exchange });
+        }
+    }
+
+    /// Handle a KEYX frame from the DM partner
+    pub fn handle_key_exchange(
+        &mut self,
+        channel_id: &str,
+        peer: &str,
+        exchange: KeyExchange,
+    ) -> anyhow::Result<HandshakeOutcome> {
+        let outcome = match exchange {
+            KeyExchange::Init { identity, ephemeral } => self.answer_init(channel_id, peer, &identity, &ephemeral)?,
+            KeyExchange::Reply { identity, ephemeral, init } => {
+                self.complete_init(channel_id, &identity, &ephemeral, &init)?
+            }
+            KeyExchange::Confirm { envelope } => self.confirm(channel_id, &envelope)?,
+        };
+        self.save();
+        Ok(outcome)
+    }
+
+    /// Decrypt a DM from the DM partner
+    pub fn decrypt(&mut self, channel_id: &str, envelope: &Envelope) -> anyhow::Result<Zeroizing<String>> {
+        let session = self.sessions.get_mut(channel_id).context("No encrypted session for this DM")?;
+        let result = match &mut session.state {
+            State::Established { ratchet } => ratchet.decrypt(envelope),
+            _ => Err(anyhow!("Session is still being set up")),
+        };
+        // A message from the initiator on the session we answered also
+        // proves that handshake
+        let mut adopted = None;
+        let result = match (result, session.reply_mut()) {
+            (Err(e), Some(reply)) if !replay::is_replay(&e) => {
+                let result = reply.ratchet.decrypt(envelope);
+                if result.is_ok() {
+                    adopted = Some(session.adopt_reply());
+                }
+                result
+            }
+            (result, _) => result,
+        };
+        let result = match (result, &mut session.previous) {
+            (Err(e), Some(previous)) if !replay::is_replay(&e) => previous.decrypt(envelope),
+            (result, _) => result,
+        };
+        let plaintext = result?;
+        session.messages += 1;
+        let peer = session.peer.clone();
+        if let Some(identity) = adopted.flatten() {
+            self.observe_identity(&peer, &identity.to_base64());
+        }
+        self.flush(channel_id);
+        self.save();
+        let text = std::str::from_utf8(&plaintext).context("Message is not valid UTF-8")?;
+        Ok(Zeroizing::new(text.to_string()))
+    }
+
+    fn init(&self, ephemeral: &Key) -> KeyExchange {
+        KeyExchange::Init {
+            identity: self.identity.public().to_base64(),
+            ephemeral: ephemeral.public().to_base64(),
+        }
+    }
+
+    /// Send a handshake init, replacing any session on this channel
+    fn start(&mut self, channel_id: &str, peer: &str) {
+        let ephemeral = Key::random();
+        let exchange = self.init(&ephemeral);
+        let old = self.sessions.remove(channel_id);
+        let session = Session {
+            peer: peer.to_string(),
+            peer_identity: old.as_ref().and_then(|s| s.peer_identity.clone()),
+            state: State::Initiated { ephemeral },
+            previous: old.as_ref().and_then(|s| s.established().or(s.previous.as_ref()).cloned()),
+            reply: None,
+            since: Utc::now(),
+            messages: 0,
+            queued: old.map(|s| s.queued).unwrap_or_default(),
+        };
+        self.sessions.insert(channel_id.to_string(), session);
+        self.outbox.push(Outgoing::KeyExchange {
+            channel_id: channel_id.to_string(),
+            exchange,
+        });
+        self.save();
+    }
+
+    /// Answer a peer's init with our identity and first ratchet key. Nothing
+    /// is trusted or replaced until the initiator's confirm decrypts: a
+    /// working session keeps running beside the answer.
+    fn answer_init(
+        &mut self,
+        channel_id: &str,
+        peer: &str,
+        identity: &str,
+        ephemeral: &str,
+    ) -> anyhow::Result<HandshakeOutcome> {
+        let their_identity = Key::from_base64(identity).context("Invalid identity key")?;
+        let their_ephemeral = Key::from_base64(ephemeral).context("Invalid ephemeral key")?;
+
+        // Both sides sent an init at once: the larger ephemeral key goes ahead
+        if let Some(State::Initiated { ephemeral: ours }) = self.sessions.get(channel_id).map(|s| &s.state) {
+            if ours.public().0 > their_ephemeral.0 {
+                return Ok(HandshakeOutcome::default());
+            }
+        }
+
+        let ratchet_key = Key::random();
+        let reply = KeyExchange::Reply {
+            identity: self.identity.public().to_base64(),
+            ephemeral: ratchet_key.public().to_base64(),
+            init: ephemeral.to_string(),
+        };
+        let secret = ratchet::responder_secret(&self.identity, &ratchet_key, &their_identity, &their_ephemeral);
+        let ratchet = Ratchet::responder(secret, ratchet_key, [their_identity.clone(), self.identity.public()]);
+        let answer = Reply {
+            identity: Some(their_identity),
+            ratchet,
+        };
+
+        match self.sessions.get_mut(channel_id) {
+            Some(session) if session.established().is_some() => session.reply = Some(answer),
+            _ => {
+                let old = self.sessions.remove(channel_id);
+                let session = Session {
+                    peer: peer.to_string(),
+                    peer_identity: old.as_ref().and_then(|s| s.peer_identity.clone()),
+                    state: State::Replied(answer),
+                    previous: old.as_ref().and_then(|s| s.previous.clone()),
+                    reply: None,
+                    since: Utc::now(),
+                    messages: 0,
+                    queued: old.map(|s| s.queued).unwrap_or_default(),
+                };
+                self.sessions.insert(channel_id.to_string(), session);
+            }
+        }
+        self.outbox.push(Outgoing::KeyExchange {
+            channel_id: channel_id.to_string(),
+            exchange: reply,
+        });
+        Ok(HandshakeOutcome::default())
+    }
+
+    /// Finish our init with the peer's reply and confirm it
+    fn complete_init(
+        &mut self,
+        channel_id: &str,
+        identity: &str,
+        ephemeral: &str,
+        init: &str,
+    ) -> anyhow::Result<HandshakeOutcome> {
+        let their_identity = Key::from_base64(identity).context("Invalid identity key")?;
+        let their_ephemeral = Key::from_base64(ephemeral).context("Invalid ephemeral key")?;
+        let Some(session) = self.sessions.get_mut(channel_id) else {
+            return Ok(HandshakeOutcome::default());
+        };
+        // Replies to an init we no longer wait for are stale
+        let State::Initiated { ephemeral: ours } = &session.state else {
+            return Ok(HandshakeOutcome::default());
+        };
+        if ours.public().to_base64() != init {
+            return Ok(HandshakeOutcome::default());
+        }
+
+        let secret = ratchet::initiator_secret(&self.identity, ours, &their_identity, &their_ephemeral);
+        let mut ratchet = Ratchet::initiator(secret, their_ephemeral, [self.identity.public(), their_identity.clone()]);
+        let confirm = KeyExchange::Confirm {
+            envelope: ratchet.encrypt(&[])?,
+        };
+        session.peer_identity = Some(their_identity);
+        session.state = State::Established { ratchet };
+        let rotated = session.previous.is_some();
+        let peer = session.peer.clone();
+        self.outbox.push(Outgoing::KeyExchange {
+            channel_id: channel_id.to_string(),
+            exchange: confirm,
+        });
+        // Before flushing, so a changed key holds the queued messages
+        let identity_changed = self.observe_identity(&peer, identity);
+        self.flush(channel_id);
+        Ok(HandshakeOutcome {
+            established: true,
+            identity_changed,
+            rotated,
+        })
+    }
+
+    /// Check the initiator's confirm; the answered session then replaces
+    /// the old one and is ready both ways
+    fn confirm(&mut self, channel_id: &str, envelope: &Envelope) -> anyhow::Result<HandshakeOutcome> {
+        let Some(session) = self.sessions.get_mut(channel_id) else {
+            return Ok(HandshakeOutcome::default());
+        };
+        let Some(reply) = session.reply_mut() else {
+            return Ok(HandshakeOutcome::default());
+        };
+        reply.ratchet.decrypt(envelope).context("Handshake confirmation failed")?;
+        let identity = session.adopt_reply();
+        let rotated = session.previous.is_some();
+        let peer = session.peer.clone();
+        let identity_changed = identity.is_some_and(|key| self.observe_identity(&peer, &key.to_base64()));
+        self.flush(channel_id);
+        Ok(HandshakeOutcome {
+            established: true,
+            identity_changed,
+            rotated,
+        })
+    }
+
+    /// Pin or check a peer's identity key; true if it changed
+    fn observe_identity(&mut self, peer: &str, identity: &str) -> bool {
+        let before = self.trust.get(peer).cloned();
+        let seen = self.trust.observe(peer, identity);
+        if self.trust.get(peer) != before.as_ref() {
+            self.save_trust();
+        }
+        seen == Seen::Changed
+    }
+
+    fn save_trust(&self) {
+        if self.persist {
+            if let Err(e) = self.trust.save() {
+                warn!("Failed to save trust store: {}", e);
+            }
+        }
+    }
+
+    /// Send the messages held on every session with the peer
+    fn flush_peer(&mut self, peer: &str) {
+        let channels: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| session.peer == peer)
+            .map(|(channel_id, _)| channel_id.clone())
+            .collect();
+        for channel_id in channels {
+            self.flush(&channel_id);
+        }
+        self.save();
+    }
+
+    /// Encrypt and send the messages queued on a session that can now send,
+    /// unless the peer's changed identity key is not accepted
+    fn flush(&mut self, channel_id: &str) {
+        let Some(session) = self.sessions.get_mut(channel_id) else {
+            return;
+        };
+        let State::Established { ratchet } = &mut session.state else {
+            return;
+        };
+        if self.trust.is_changed(&session.peer) {
+            return;
+        }
+        for queued in std::mem::take(&mut session.queued) {
+            match ratchet.encrypt(queued.content.as_bytes()) {
+                Ok(envelope) => self.outbox.push(Outgoing::Message {
+                    channel_id: channel_id.to_string(),
+                    id: queued.id,
+                    payload: envelope.to_payload(),
+                    action: queued.action,
+                }),
+                Err(e) => warn!("Cannot encrypt queued message for {}: {}", session.peer, e),
+            }
+        }
+    }
+
+    /// Write the sessions file; failures are logged, since losing sessions
+    /// only costs a new handshake
+    fn save(&self) {
+        if !self.persist {
+            return;
+        }
+        if let Err(e) = save_sessions(&self.identity, &self.sessions) {
+            warn!("Failed to save encrypted sessions: {}", e);
+        }
+    }
+}
+
+/// Key for the sessions file, derived from the identity key
+fn sessions_cipher(identity: &Key) -> XChaCha20Poly1305 {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(None, &identity.0)
+        .expand(b"GhostWire sessions", key.as_mut())
+        .expect("32 bytes is a valid HKDF output length");
+    XChaCha20Poly1305::new(chacha20poly1305::Key::from_slice(key.as_ref()))
+}
+
+fn save_sessions(identity: &Key, sessions: &HashMap<String, Session>) -> anyhow::Result<()> {
+    let plaintext = Zeroizing::new(serde_json::to_vec(sessions)?);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = sessions_cipher(identity)
+        .encrypt(&nonce, plaintext.as_slice())
+        .map_err(|_| anyhow!("Encryption failed"))?;
+    let sealed = SealedSessions {
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    storage::save_json(SESSIONS_FILE, &sealed)
+}
+
+/// Load saved sessions. Unreadable ones (e.g. from another identity) are
+/// logged and dropped; the affected DMs just run a new handshake.
+fn load_sessions(identity: &Key) -> HashMap<String, Session> {
+    let sealed: SealedSessions = storage::load_json(SESSIONS_FILE);
+    if sealed.ciphertext.is_empty() {
+        return HashMap::new();
+    }
+    let opened = (|| -> anyhow::Result<HashMap<String, Session>> {
+        let nonce = BASE64.decode(&sealed.nonce).context("Invalid nonce")?;
+        let ciphertext = BASE64.decode(&sealed.ciphertext).context("Invalid ciphertext")?;
+        if nonce.len() != 24 {
+            return Err(anyhow!("Invalid nonce length"));
+        }
+        let plaintext = sessions_cipher(identity)
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+            .map(Zeroizing::new)
+            .map_err(|_| anyhow!("Sessions were saved under a different identity"))?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    })();
+    match opened {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            warn!("Discarding saved sessions: {}", e);
+            HashMap::new()
+        }
+    }
+}
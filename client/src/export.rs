@@ -0,0 +1,167 @@
+// GhostWire Client - Transcript Export
+// Writes a channel's in-memory history to Markdown, plain text, or JSON
+
+use crate::app::{Channel, ChatMessage};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Output format for `/export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    Text,
+    Json,
+}
+
+impl ExportFormat {
+    /// File extension used for generated file names
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Text => "txt",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Ok(ExportFormat::Markdown),
+            "txt" | "text" => Ok(ExportFormat::Text),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// JSON transcript document
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Transcript {
+    /// Original channel ID (e.g. "global" or "dm:alice:bob")
+    pub channel: String,
+    pub exported_at: DateTime<Utc>,
+    pub messages: Vec<TranscriptMessage>,
+}
+
+/// A single message in a JSON transcript
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptMessage {
+    /// Wire message ID, if the sender provided one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub timestamp: DateTime<Utc>,
+    pub sender: Arc<str>,
+    pub content: Arc<str>,
+    #[serde(default)]
+    pub is_system: bool,
+    /// An action (`/roll` and friends), read as "* sender content"
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub action: bool,
+}
+
+/// Export a channel's history, returning the path written.
+///
+/// Only messages newer than `since` are included when given. If `out` is not
+/// provided a timestamped file name is generated in the current directory.
+pub fn export_channel(
+    channel: &Channel,
+    format: ExportFormat,
+    since: Option<Duration>,
+    out: Option<&Path>,
+) -> anyhow::Result<PathBuf> {
+    let cutoff = since.map(|since| Utc::now() - since);
+    let messages: Vec<&ChatMessage> = channel
+        .messages
+        .iter()
+        .filter(|msg| match cutoff {
+            Some(cutoff) => msg.timestamp >= cutoff,
+            None => true,
+        })
+        .collect();
+
+    let body = match format {
+        ExportFormat::Markdown => render_markdown(channel, &messages),
+        ExportFormat::Text => render_text(&messages),
+        ExportFormat::Json => render_json(channel, &messages)?,
+    };
+
+    let path = match out {
+        Some(path) => path.to_path_buf(),
+        None => PathBuf::from(default_file_name(&channel.id, format)),
+    };
+    fs::write(&path, body)?;
+    Ok(path)
+}
+
+/// Build a file name like `ghostwire-dm_alice_bob-20250101-120000.md`
+fn default_file_name(channel_id: &str, format: ExportFormat) -> String {
+    let safe_id: String = channel_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    format!(
+        "ghostwire-{}-{}.{}",
+        safe_id,
+        Utc::now().format("%Y%m%d-%H%M%S"),
+        format.extension()
+    )
+}
+
+fn render_markdown(channel: &Channel, messages: &[&ChatMessage]) -> String {
+    let mut out = format!(
+        "# {}\n\n_Exported from GhostWire at {}_\n\n",
+        channel.display_name(),
+        Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    for msg in messages {
+        let timestamp = msg.timestamp.format("%Y-%m-%d %H:%M:%S");
+        if msg.is_system {
+            out.push_str(&format!("- `{}` _{}_\n", timestamp, msg.content));
+        } else if msg.action {
+            out.push_str(&format!("- `{}` \\* **{}** {}\n", timestamp, msg.sender, msg.content));
+        } else {
+            out.push_str(&format!("- `{}` **{}**: {}\n", timestamp, msg.sender, msg.content));
+        }
+    }
+    out
+}
+
+fn render_text(messages: &[&ChatMessage]) -> String {
+    messages
+        .iter()
+        .map(|msg| {
+            let timestamp = msg.timestamp.format("%Y-%m-%d %H:%M:%S");
+            if msg.action {
+                format!("[{}] * {} {}\n", timestamp, msg.sender, msg.content)
+            } else {
+                format!("[{}] {}: {}\n", timestamp, msg.sender, msg.content)
+            }
+        })
+        .collect()
+}
+
+fn render_json(channel: &Channel, messages: &[&ChatMessage]) -> anyhow::Result<String> {
+    let transcript = Transcript {
+        channel: channel.id.clone(),
+        exported_at: Utc::now(),
+        messages: messages
+            .iter()
+            .map(|msg| TranscriptMessage {
+                id: msg.id.clone(),
+                timestamp: msg.timestamp,
+                sender: msg.sender.clone(),
+                content: msg.content.clone(),
+                is_system: msg.is_system,
+                action: msg.action,
+            })
+            .collect(),
+    };
+    Ok(serde_json::to_string_pretty(&transcript)?)
+}
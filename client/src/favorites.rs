@@ -0,0 +1,69 @@
+// GhostWire Client - Channel Favorites and Order
+// Favorite channels are listed above the rest of the sidebar, and channels
+// can be moved up and down by hand. Both are stored in
+// `<data dir>/ghostwire/channels.json`; channels never moved keep the
+// default order after the arranged ones.
+
+use crate::storage;
+use serde::{Deserialize, Serialize};
+
+/// File name of the channel order inside the data directory
+const CHANNELS_FILE: &str = "channels.json";
+
+/// Favorite channels and the hand-arranged order of the others, by channel ID
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChannelOrder {
+    /// Favorite channels, in sidebar order
+    #[serde(default)]
+    pub favorites: Vec<String>,
+    /// The other channels, in the order they were last arranged
+    #[serde(default)]
+    pub order: Vec<String>,
+}
+
+impl ChannelOrder {
+    /// Load the channel order from the data directory
+    pub fn load() -> Self {
+        storage::load_json(CHANNELS_FILE)
+    }
+
+    /// Write the channel order back to the data directory
+    pub fn save(&self) -> anyhow::Result<()> {
+        storage::save_json(CHANNELS_FILE, self)
+    }
+
+    /// Position of a favorite among the favorites
+    pub fn favorite(&self, channel_id: &str) -> Option<usize> {
+        self.favorites.iter().position(|id| id == channel_id)
+    }
+
+    /// Position of a channel among the arranged ones
+    pub fn position(&self, channel_id: &str) -> Option<usize> {
+        self.order.iter().position(|id| id == channel_id)
+    }
+
+    /// Add a channel to the end of the favorites, or remove it, returning
+    /// whether it is a favorite now
+    pub fn toggle_favorite(&mut self, channel_id: &str) -> bool {
+        match self.favorite(channel_id) {
+            Some(index) => {
+                self.favorites.remove(index);
+                false
+            }
+            None => {
+                self.favorites.push(channel_id.to_string());
+                true
+            }
+        }
+    }
+
+    /// Carry a channel's place over to its new ID (DM channels are re-keyed
+    /// when either side is renamed)
+    pub fn rename(&mut self, old: &str, new: &str) {
+        for id in self.favorites.iter_mut().chain(self.order.iter_mut()) {
+            if id == old {
+                *id = new.to_string();
+            }
+        }
+    }
+}
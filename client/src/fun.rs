@@ -0,0 +1,102 @@
+// GhostWire Client - Fun Commands
+// Dice rolls and coin flips for `/roll` and `/flip`. Results are drawn on the
+// sender's side and sent as ordinary action messages, so everyone in the
+// channel sees the same outcome.
+
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use std::fmt;
+
+/// Most dice in one roll
+pub const MAX_DICE: u32 = 100;
+
+/// Most sides on a die
+pub const MAX_SIDES: u32 = 1000;
+
+/// Largest modifier added to (or taken from) a roll
+pub const MAX_MODIFIER: i64 = 1000;
+
+/// The kaomoji `/shrug` sends
+pub const SHRUG: &str = "¯\\_(ツ)_/¯";
+
+/// A roll in dice notation: `2d6`, `d20`, `3d8+2`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dice {
+    pub count: u32,
+    pub sides: u32,
+    pub modifier: i64,
+}
+
+impl Dice {
+    /// Parse dice notation; the count defaults to one
+    pub fn parse(notation: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid dice: {} (expected e.g. 2d6 or d20+3)", notation);
+        let notation_lower = notation.to_ascii_lowercase();
+        let (count, rest) = notation_lower.split_once('d').ok_or_else(invalid)?;
+        let (sides, modifier) = match rest.find(['+', '-']) {
+            Some(at) => (&rest[..at], rest[at..].parse::<i64>().map_err(|_| invalid())?),
+            None => (rest, 0),
+        };
+        let count = if count.is_empty() { 1 } else { count.parse::<u32>().map_err(|_| invalid())? };
+        let sides = sides.parse::<u32>().map_err(|_| invalid())?;
+
+        if !(1..=MAX_DICE).contains(&count) {
+            return Err(format!("Roll between 1 and {} dice", MAX_DICE));
+        }
+        if !(2..=MAX_SIDES).contains(&sides) {
+            return Err(format!("Dice have between 2 and {} sides", MAX_SIDES));
+        }
+        if modifier.abs() > MAX_MODIFIER {
+            return Err(format!("Modifiers are at most {}", MAX_MODIFIER));
+        }
+        Ok(Self { count, sides, modifier })
+    }
+
+    /// Roll the dice: each die's result, then the total with the modifier
+    pub fn roll(&self) -> (Vec<u32>, i64) {
+        let rolls: Vec<u32> = (0..self.count).map(|_| uniform(self.sides) + 1).collect();
+        let total = rolls.iter().map(|&r| i64::from(r)).sum::<i64>() + self.modifier;
+        (rolls, total)
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d{}", self.count, self.sides)?;
+        match self.modifier {
+            0 => Ok(()),
+            m if m > 0 => write!(f, "+{}", m),
+            m => write!(f, "{}", m),
+        }
+    }
+}
+
+/// How a roll reads in chat: `3 + 5 = 8`, `3 + 5 + 2 = 10`, or just `17`
+pub fn describe_roll(dice: &Dice, rolls: &[u32], total: i64) -> String {
+    if rolls.len() == 1 && dice.modifier == 0 {
+        return total.to_string();
+    }
+    let mut sum = rolls.iter().map(u32::to_string).collect::<Vec<_>>().join(" + ");
+    match dice.modifier {
+        0 => {}
+        m if m > 0 => sum.push_str(&format!(" + {}", m)),
+        m => sum.push_str(&format!(" - {}", -m)),
+    }
+    format!("{} = {}", sum, total)
+}
+
+/// Flip a coin: true for heads
+pub fn flip() -> bool {
+    uniform(2) == 0
+}
+
+/// A uniformly random number below `bound`, rejecting the values that would
+/// make small results likelier
+fn uniform(bound: u32) -> u32 {
+    let limit = u32::MAX - u32::MAX % bound;
+    loop {
+        let value = OsRng.next_u32();
+        if value < limit {
+            return value % bound;
+        }
+    }
+}
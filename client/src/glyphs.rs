@@ -0,0 +1,138 @@
+// GhostWire Client - Glyph Sets
+// Status icons and decorations, with an ASCII fallback for terminals and fonts
+// that render symbols and emoji as tofu (`ascii_only` in config.toml).
+
+use ratatui::symbols::bar;
+
+/// Every non-ASCII symbol the UI draws outside of message text
+pub struct Glyphs {
+    pub online: &'static str,
+    pub idle: &'static str,
+    pub offline: &'static str,
+    pub warning: &'static str,
+    pub star: &'static str,
+    /// Marks the mentions channel in the channel list
+    pub mention: &'static str,
+    /// Marks the activity channel in the channel list
+    pub activity: &'static str,
+    /// Marks favorite channels in the channel list
+    pub favorite: &'static str,
+    pub pin: &'static str,
+    /// Marks our DMs the relay holds for an offline recipient
+    pub queued: &'static str,
+    /// Marks our DMs the relay could not deliver
+    pub undelivered: &'static str,
+    /// Mark our DMs the recipient got, and those they have read
+    pub delivered: &'static str,
+    pub read: &'static str,
+    /// Marks passphrase-encrypted rooms in the channel list, and encrypted
+    /// channels in the chat title
+    pub locked: &'static str,
+    /// Marks plaintext channels in the chat title
+    pub unlocked: &'static str,
+    /// Marks DMs from partners whose identity key was verified
+    pub verified: &'static str,
+    /// Stands in for each character of a passphrase being typed
+    pub mask: &'static str,
+    /// Shows a line break in the input box
+    pub newline: &'static str,
+    /// Frame drawn to the left of code blocks: opening corner, side, closing corner
+    pub code_top: &'static str,
+    pub code_side: &'static str,
+    pub code_bottom: &'static str,
+    /// Bar in front of "> " quote lines
+    pub quote: &'static str,
+    /// Fills each column of a hidden spoiler
+    pub spoiler: &'static str,
+    /// Roster section header, unfolded and folded
+    pub section_open: &'static str,
+    pub section_closed: &'static str,
+    /// Telemetry label prefixes; include their trailing space when non-empty
+    pub sent: &'static str,
+    pub received: &'static str,
+    pub bytes: &'static str,
+    pub channel: &'static str,
+    pub users: &'static str,
+    /// Bar chart fill levels
+    pub bars: bar::Set,
+}
+
+/// Default symbols and emoji
+pub const UNICODE: Glyphs = Glyphs {
+    online: "●",
+    idle: "◐",
+    offline: "○",
+    warning: "⚠",
+    star: "★",
+    mention: "🔔",
+    activity: "↔",
+    favorite: "♥",
+    pin: "📌",
+    queued: "…",
+    undelivered: "✗",
+    delivered: "✓",
+    read: "✓✓",
+    locked: "🔒",
+    unlocked: "🔓",
+    mask: "•",
+    newline: "↵",
+    code_top: "┌─",
+    code_side: "│ ",
+    code_bottom: "└─",
+    quote: "▎ ",
+    spoiler: "█",
+    section_open: "▾",
+    section_closed: "▸",
+    verified: "🛡",
+    sent: "↑ ",
+    received: "↓ ",
+    bytes: "📊 ",
+    channel: "📺 ",
+    users: "👥 ",
+    bars: bar::NINE_LEVELS,
+};
+
+/// Plain ASCII equivalents
+pub const ASCII: Glyphs = Glyphs {
+    online: "*",
+    idle: "~",
+    offline: "o",
+    warning: "!",
+    star: "*",
+    mention: "(@)",
+    activity: "<>",
+    favorite: "+",
+    pin: "PIN",
+    queued: "...",
+    undelivered: "x",
+    delivered: "+",
+    read: "++",
+    locked: "(e)",
+    unlocked: "(p)",
+    mask: "*",
+    newline: "\\n",
+    code_top: "+-",
+    code_side: "| ",
+    code_bottom: "+-",
+    quote: "| ",
+    spoiler: "#",
+    section_open: "v",
+    section_closed: ">",
+    verified: "[v]",
+    sent: "^ ",
+    received: "v ",
+    bytes: "",
+    channel: "",
+    users: "",
+    bars: bar::Set {
+        full: "#",
+        seven_eighths: "#",
+        three_quarters: "=",
+        five_eighths: "=",
+        half: "-",
+        three_eighths: "-",
+        one_quarter: ".",
+        one_eighth: ".",
+        empty: " ",
+    },
+};
@@ -0,0 +1,391 @@
+// GhostWire Client - Message History
+// Chat messages kept across restarts in `<data dir>/ghostwire/history/`, one
+// directory per channel holding pages of `PAGE_SIZE` messages in time order
+// (`0.json` is the oldest), encrypted at rest like the other data files. The
+// TUI loads only each channel's newest page at startup and reads older pages
+// when the user scrolls past the top, so large archives do not slow startup;
+// on exit it merges in what arrived. `ghostwire import <file>` merges an
+// exported transcript the same way. Single-file histories of older versions
+// are split into pages the first time they are read.
+//
+// The `[retention]` settings bound how much is kept: every channel is
+// compacted at startup, before its history is loaded, and then hourly by a
+// background task, deleting messages past the age and count limits and
+// rewriting the pages full. `/purge` deletes a channel's history on demand.
+
+use crate::export::{Transcript, TranscriptMessage};
+use crate::storage;
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use ghostwire_protocol::validate::validate_channel_id;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// Subdirectory of the data directory holding the history files
+const HISTORY_DIR: &str = "history";
+
+/// Messages per history page; every page but the newest is full
+pub const PAGE_SIZE: usize = 200;
+
+/// How often the background task applies the retention limits
+const COMPACT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Held while pages are read or written, so compaction in the background
+/// never shows the UI a half-rewritten history
+static PAGES: Mutex<()> = Mutex::new(());
+
+fn lock() -> std::sync::MutexGuard<'static, ()> {
+    PAGES.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// How much stored history is kept (`[retention]` table); both limits are
+/// off by default
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Days a stored message is kept; 0 keeps them forever
+    pub max_age_days: u64,
+    /// Stored messages kept per channel, the newest ones; 0 keeps them all
+    pub max_messages: usize,
+}
+
+impl RetentionConfig {
+    /// Whether either limit is set
+    pub fn enabled(&self) -> bool {
+        self.max_age_days > 0 || self.max_messages > 0
+    }
+
+    /// Messages older than this are deleted
+    pub fn cutoff(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let days = i64::try_from(self.max_age_days).ok().filter(|&days| days > 0)?;
+        Some(now - Duration::try_days(days)?)
+    }
+}
+
+/// A channel's name on disk. Channel IDs contain `:` and `/`, so everything
+/// but letters, digits, `-` and `_` is written as `%XX`.
+fn encode(channel_id: &str) -> String {
+    let mut name = String::new();
+    for byte in channel_id.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            name.push(byte as char);
+        } else {
+            name.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    name
+}
+
+/// Directory of a channel's pages, relative to the data directory
+fn channel_dir(channel_id: &str) -> String {
+    format!("{}/{}", HISTORY_DIR, encode(channel_id))
+}
+
+/// File of one page of a channel's history
+fn page_file(channel_id: &str, page: usize) -> String {
+    format!("{}/{}.json", channel_dir(channel_id), page)
+}
+
+/// Single history file of older versions
+fn legacy_file(channel_id: &str) -> String {
+    format!("{}.json", channel_dir(channel_id))
+}
+
+/// The channel ID a history name stands for
+fn channel_id(name: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = name.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Channels with stored history
+pub fn channels() -> Vec<String> {
+    let mut names = storage::list_dirs(HISTORY_DIR);
+    names.extend(storage::list_json(HISTORY_DIR));
+    names.sort();
+    names.dedup();
+    names.iter().filter_map(|name| channel_id(name)).collect()
+}
+
+/// Split a single-file history of an older version into pages
+fn migrate(channel_id: &str) -> anyhow::Result<()> {
+    let legacy = legacy_file(channel_id);
+    let messages: Vec<TranscriptMessage> = storage::load_json(&legacy);
+    if messages.is_empty() {
+        return Ok(());
+    }
+    save_pages(channel_id, 0, &messages)?;
+    storage::remove(&legacy)
+}
+
+/// Migrate, logging rather than failing: reads then see what is there
+fn ensure_migrated(channel_id: &str) {
+    if let Err(e) = migrate(channel_id) {
+        warn!("Failed to move the history of {} to pages: {}", channel_id, e);
+    }
+}
+
+/// Number of pages of a channel's history
+fn page_count(channel_id: &str) -> usize {
+    storage::list_json(&channel_dir(channel_id))
+        .iter()
+        .filter_map(|stem| stem.parse::<usize>().ok())
+        .max()
+        .map_or(0, |last| last + 1)
+}
+
+fn load_page(channel_id: &str, page: usize) -> Vec<TranscriptMessage> {
+    storage::load_json(&page_file(channel_id, page))
+}
+
+fn load_pages(channel_id: &str) -> Vec<TranscriptMessage> {
+    (0..page_count(channel_id)).flat_map(|page| load_page(channel_id, page)).collect()
+}
+
+/// Write messages as pages, the first of them page `first`
+fn save_pages(channel_id: &str, first: usize, messages: &[TranscriptMessage]) -> anyhow::Result<()> {
+    for (n, page) in messages.chunks(PAGE_SIZE).enumerate() {
+        storage::save_json(&page_file(channel_id, first + n), &page)?;
+    }
+    Ok(())
+}
+
+/// Replace a channel's whole history, deleting pages no longer needed
+fn rewrite(channel_id: &str, messages: &[TranscriptMessage]) -> anyhow::Result<()> {
+    let before = page_count(channel_id);
+    save_pages(channel_id, 0, messages)?;
+    for page in messages.len().div_ceil(PAGE_SIZE)..before {
+        storage::remove(&page_file(channel_id, page))?;
+    }
+    if messages.is_empty() {
+        storage::remove(&channel_dir(channel_id))?;
+    }
+    Ok(())
+}
+
+/// A channel's stored messages, oldest first
+pub fn load(channel_id: &str) -> Vec<TranscriptMessage> {
+    let _pages = lock();
+    ensure_migrated(channel_id);
+    load_pages(channel_id)
+}
+
+/// A channel's newest `count` stored messages, oldest first, reading only
+/// the pages they are on; also returns whether older ones are stored
+pub fn load_recent(channel_id: &str, count: usize) -> (Vec<TranscriptMessage>, bool) {
+    let _pages = lock();
+    ensure_migrated(channel_id);
+    let mut page = page_count(channel_id);
+    let mut messages = Vec::new();
+    while page > 0 && messages.len() < count {
+        page -= 1;
+        let mut older = load_page(channel_id, page);
+        older.append(&mut messages);
+        messages = older;
+    }
+    let skip = messages.len().saturating_sub(count);
+    (messages.split_off(skip), page > 0 || skip > 0)
+}
+
+/// Up to `count` stored messages from before `before`, oldest first, and
+/// whether there are more. Messages from the same second count as older
+/// unless `buffered` says they are already shown. Pages are in time order,
+/// so the one to start from is found by bisection.
+pub fn load_older(
+    channel_id: &str,
+    before: DateTime<Utc>,
+    count: usize,
+    buffered: impl Fn(&TranscriptMessage) -> bool,
+) -> (Vec<TranscriptMessage>, bool) {
+    let _pages = lock();
+    // Pages before this one start no later than `before`
+    let (mut low, mut high) = (0, page_count(channel_id));
+    while low < high {
+        let middle = (low + high) / 2;
+        if load_page(channel_id, middle).first().is_some_and(|msg| msg.timestamp <= before) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+
+    let mut page = low;
+    let mut messages = Vec::new();
+    while page > 0 && messages.len() <= count {
+        page -= 1;
+        let mut older: Vec<TranscriptMessage> = load_page(channel_id, page)
+            .into_iter()
+            .filter(|msg| msg.timestamp <= before && !buffered(msg))
+            .collect();
+        older.append(&mut messages);
+        messages = older;
+    }
+    let skip = messages.len().saturating_sub(count);
+    (messages.split_off(skip), page > 0 || skip > 0)
+}
+
+/// Add messages to a channel's stored history, skipping those already there
+/// (by ID, or by time, sender and text for messages without one) and keeping
+/// it in time order; returns how many were new. Only the pages from the one
+/// the oldest new message falls on are read and rewritten.
+pub fn merge(channel_id: &str, messages: Vec<TranscriptMessage>) -> anyhow::Result<usize> {
+    let _pages = lock();
+    migrate(channel_id)?;
+    let Some(earliest) = messages.iter().map(|msg| msg.timestamp).min() else {
+        return Ok(0);
+    };
+    // Pages before the first one starting earlier than the new messages are
+    // full and older than all of them, so they stay as they are
+    let mut first = page_count(channel_id);
+    let mut stored = Vec::new();
+    while first > 0 {
+        first -= 1;
+        let mut page = load_page(channel_id, first);
+        let starts_before = page.first().is_none_or(|msg| msg.timestamp < earliest);
+        page.append(&mut stored);
+        stored = page;
+        if starts_before {
+            break;
+        }
+    }
+
+    let mut ids: HashSet<String> = stored.iter().filter_map(|msg| msg.id.clone()).collect();
+    let before = stored.len();
+    for msg in messages {
+        let known = match &msg.id {
+            Some(id) => !ids.insert(id.clone()),
+            None => stored.iter().any(|old| {
+                old.timestamp == msg.timestamp && old.sender == msg.sender && old.content == msg.content
+            }),
+        };
+        if !known {
+            stored.push(msg);
+        }
+    }
+    let added = stored.len() - before;
+    if added > 0 {
+        // Stable, so messages sharing a second keep their order
+        stored.sort_by_key(|msg| msg.timestamp);
+        save_pages(channel_id, first, &stored)?;
+    }
+    Ok(added)
+}
+
+/// Number of stored messages of a channel, from the page count and the
+/// newest page
+fn stored_count(channel_id: &str) -> usize {
+    match page_count(channel_id) {
+        0 => 0,
+        pages => (pages - 1) * PAGE_SIZE + load_page(channel_id, pages - 1).len(),
+    }
+}
+
+/// Delete a channel's stored messages from before `cutoff` and beyond the
+/// newest `keep` (0 keeps any number); returns how many were deleted
+fn retain(channel_id: &str, cutoff: Option<DateTime<Utc>>, keep: usize) -> anyhow::Result<usize> {
+    migrate(channel_id)?;
+    // Most runs find nothing to do; that only takes the first and last page
+    let total = stored_count(channel_id);
+    if total == 0 {
+        return Ok(0);
+    }
+    let expired = cutoff.is_some_and(|cutoff| load_page(channel_id, 0).first().is_some_and(|msg| msg.timestamp < cutoff));
+    if !expired && (keep == 0 || total <= keep) {
+        return Ok(0);
+    }
+
+    let mut messages = load_pages(channel_id);
+    let before = messages.len();
+    if let Some(cutoff) = cutoff {
+        messages.retain(|msg| msg.timestamp >= cutoff);
+    }
+    if keep > 0 && messages.len() > keep {
+        messages.drain(..messages.len() - keep);
+    }
+    let deleted = before - messages.len();
+    if deleted > 0 {
+        rewrite(channel_id, &messages)?;
+    }
+    Ok(deleted)
+}
+
+/// Apply the retention limits to every channel; returns how many messages
+/// were deleted
+pub fn compact(retention: &RetentionConfig, now: DateTime<Utc>) -> anyhow::Result<usize> {
+    let cutoff = retention.cutoff(now);
+    let mut deleted = 0;
+    for channel_id in channels() {
+        let _pages = lock();
+        deleted += retain(&channel_id, cutoff, retention.max_messages)?;
+    }
+    Ok(deleted)
+}
+
+/// Compact in the background every `COMPACT_INTERVAL`
+pub fn spawn_compaction(retention: RetentionConfig) {
+    tokio::spawn(async move {
+        let first = tokio::time::Instant::now() + COMPACT_INTERVAL;
+        let mut interval = tokio::time::interval_at(first, COMPACT_INTERVAL);
+        loop {
+            interval.tick().await;
+            let retention = retention.clone();
+            match tokio::task::spawn_blocking(move || compact(&retention, Utc::now())).await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(deleted)) => info!("History compaction deleted {} message(s)", deleted),
+                Ok(Err(e)) => warn!("History compaction failed: {}", e),
+                Err(e) => warn!("History compaction stopped: {}", e),
+            }
+        }
+    });
+}
+
+/// `/purge`: delete a channel's stored messages from before `older_than`,
+/// or all of them; returns how many were deleted
+pub fn purge(channel_id: &str, older_than: Option<DateTime<Utc>>) -> anyhow::Result<usize> {
+    let _pages = lock();
+    if older_than.is_some() {
+        return retain(channel_id, older_than, 0);
+    }
+    migrate(channel_id)?;
+    let deleted = stored_count(channel_id);
+    rewrite(channel_id, &[])?;
+    Ok(deleted)
+}
+
+/// `ghostwire import <file>`: merge a JSON transcript written by
+/// `/export json` into the history of the channel it was exported from
+pub fn import(path: &Path) -> anyhow::Result<()> {
+    let text = fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let transcript: Transcript =
+        serde_json::from_str(&text).context("Not a GhostWire JSON transcript (/export json)")?;
+
+    // Channels of extra relays are exported as `relay/channel`
+    let (_, wire_id) = crate::relays::split_channel(&transcript.channel);
+    validate_channel_id(wire_id).map_err(|e| anyhow::anyhow!("Invalid channel in transcript: {}", e))?;
+
+    // System notices were local to whoever exported them
+    let messages: Vec<TranscriptMessage> = transcript.messages.into_iter().filter(|msg| !msg.is_system).collect();
+    let total = messages.len();
+    let added = merge(&transcript.channel, messages)?;
+    println!(
+        "Imported {} new message(s) into {} ({} already in the history)",
+        added,
+        transcript.channel,
+        total - added
+    );
+    Ok(())
+}
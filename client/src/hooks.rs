@@ -0,0 +1,159 @@
+// GhostWire Client - Connection Event Hooks
+// `--on-event <cmd>` (or `on_event` in config.toml) runs a shell command
+// whenever a relay connection comes up, comes back, is closed, drops or is
+// kicked, so scripts can re-announce presence, log incidents and the like.
+// The hook sits between the network tasks and whoever reads their events, so
+// it fires for the TUI and the daemon alike, and an attached TUI does not
+// fire it twice.
+
+use crate::network::NetworkEvent;
+use std::collections::{HashMap, HashSet};
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// A connection change a hook runs for
+#[derive(Debug)]
+enum HookEvent {
+    /// First connection to a relay this session
+    Connected,
+    /// Connected after failed attempts (retries or failover), or again
+    /// after an earlier connection
+    Reconnected,
+    /// The relay closed the connection and a reconnect follows
+    Closed { reason: String },
+    Disconnected { reason: String },
+    Kicked { reason: String },
+}
+
+impl HookEvent {
+    /// Name passed as `GHOSTWIRE_EVENT`
+    fn name(&self) -> &'static str {
+        match self {
+            HookEvent::Connected => "connected",
+            HookEvent::Reconnected => "reconnected",
+            HookEvent::Closed { .. } => "closed",
+            HookEvent::Disconnected { .. } => "disconnected",
+            HookEvent::Kicked { .. } => "kicked",
+        }
+    }
+}
+
+/// Runs the hook command, remembering which relays have connected before
+struct Hooks {
+    command: String,
+    username: String,
+    /// Last URL each relay connected to, by relay name ("" for the primary)
+    connected: HashMap<String, String>,
+    /// Relays that have had to retry since they were last connected
+    retrying: HashSet<String>,
+}
+
+impl Hooks {
+    /// Run the hook if `event` is a connection change
+    fn observe(&mut self, relay: &str, event: &NetworkEvent) {
+        let hook = match event {
+            NetworkEvent::FromRelay { relay, event } => return self.observe(relay, event),
+            NetworkEvent::UserRenamed { old, new } if relay.is_empty() && *old == self.username => {
+                self.username = new.clone();
+                return;
+            }
+            NetworkEvent::Reconnecting { .. } => {
+                self.retrying.insert(relay.to_string());
+                return;
+            }
+            NetworkEvent::ConnectedTo { url } => {
+                let before = self.connected.insert(relay.to_string(), url.clone());
+                if self.retrying.remove(relay) || before.is_some() {
+                    HookEvent::Reconnected
+                } else {
+                    HookEvent::Connected
+                }
+            }
+            NetworkEvent::Closed { reason } => HookEvent::Closed { reason: reason.clone() },
+            NetworkEvent::Disconnected { reason } => HookEvent::Disconnected { reason: reason.clone() },
+            NetworkEvent::Kicked { reason } => HookEvent::Kicked { reason: reason.clone() },
+            _ => return,
+        };
+        let url = self.connected.get(relay).map(String::as_str).unwrap_or_default();
+        self.run(relay, url, &hook);
+    }
+
+    /// Start the command without waiting for it; its exit status is logged
+    fn run(&self, relay: &str, url: &str, hook: &HookEvent) {
+        let reason = match hook {
+            HookEvent::Closed { reason } | HookEvent::Disconnected { reason } | HookEvent::Kicked { reason } => {
+                reason.as_str()
+            }
+            HookEvent::Connected | HookEvent::Reconnected => "",
+        };
+        let mut command = shell(&self.command);
+        command
+            .env("GHOSTWIRE_EVENT", hook.name())
+            .env("GHOSTWIRE_USERNAME", &self.username)
+            .env("GHOSTWIRE_SERVER", url)
+            .env("GHOSTWIRE_RELAY", relay)
+            .env("GHOSTWIRE_REASON", reason)
+            // The terminal belongs to the TUI
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        debug!("Running {} hook: {}", hook.name(), self.command);
+        match command.spawn() {
+            Ok(mut child) => {
+                let name = hook.name();
+                tokio::spawn(async move {
+                    match child.wait().await {
+                        Ok(status) if !status.success() => warn!("The {} hook exited with {}", name, status),
+                        Ok(_) => {}
+                        Err(e) => warn!("Failed to wait for the {} hook: {}", name, e),
+                    }
+                });
+            }
+            Err(e) => warn!("Failed to run the {} hook: {}", hook.name(), e),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+/// Pass `events` through, running `command` on each connection change.
+/// Without a command the receiver is returned as is.
+pub fn spawn(
+    command: Option<String>,
+    username: &str,
+    mut events: mpsc::UnboundedReceiver<NetworkEvent>,
+) -> mpsc::UnboundedReceiver<NetworkEvent> {
+    let Some(command) = command.filter(|command| !command.trim().is_empty()) else {
+        return events;
+    };
+    let mut hooks = Hooks {
+        command,
+        username: username.to_string(),
+        connected: HashMap::new(),
+        retrying: HashSet::new(),
+    };
+    let (event_tx, event_rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            hooks.observe("", &event);
+            if event_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    event_rx
+}
@@ -0,0 +1,701 @@
+// GhostWire Client - Localization
+// Per-locale tables of user-facing strings. Templates use `{}` placeholders,
+// filled in order by `fill`. The locale comes from `locale` in config.toml,
+// falling back to LC_ALL / LC_MESSAGES / LANG, then English.
+
+use std::fmt::Display;
+
+/// Every translatable string shown by the UI
+pub struct Strings {
+    // Chat notices
+    pub welcome: &'static str,
+    pub user_joined: &'static str,
+    pub user_left: &'static str,
+    pub user_renamed: &'static str,
+    pub connected: &'static str,
+    pub connected_to: &'static str,
+    pub relay_connected: &'static str,
+    pub relay_disconnected: &'static str,
+    pub kicked: &'static str,
+    pub relay_kicked: &'static str,
+    pub closed: &'static str,
+    pub relay_closed: &'static str,
+    pub receipts_on: &'static str,
+    pub receipts_off: &'static str,
+    pub presence_filter: &'static str,
+    pub presence_all: &'static str,
+    pub presence_contacts: &'static str,
+    pub presence_dms: &'static str,
+    pub presence_none: &'static str,
+    pub e2e_pending: &'static str,
+    pub e2e_established: &'static str,
+    pub e2e_identity_changed: &'static str,
+    pub e2e_held: &'static str,
+    pub e2e_failed: &'static str,
+    pub e2e_undecryptable: &'static str,
+    pub rekey_started: &'static str,
+    pub rekey_scheduled: &'static str,
+    pub e2e_rotated: &'static str,
+    pub rekey_not_dm: &'static str,
+    pub mode_passphrase: &'static str,
+    pub room_joined: &'static str,
+    pub room_joined_encrypted: &'static str,
+    pub room_passphrase_prompt: &'static str,
+    pub room_rejoin: &'static str,
+    pub room_passphrase_empty: &'static str,
+    pub room_wrong_passphrase: &'static str,
+    pub ephemeral_mode: &'static str,
+    pub verify_title: &'static str,
+    pub verify_instructions: &'static str,
+    pub verify_keys: &'static str,
+    pub verify_no_key: &'static str,
+    pub verify_matched: &'static str,
+    pub verify_mismatch: &'static str,
+    pub trust_accepted: &'static str,
+    pub trust_unchanged: &'static str,
+    pub security_plaintext: &'static str,
+    pub security_encrypted: &'static str,
+    pub security_verified: &'static str,
+    pub downgrade_title: &'static str,
+    pub downgrade_warning: &'static str,
+    pub downgrade_room_hint: &'static str,
+    pub downgrade_keys: &'static str,
+    pub disconnected: &'static str,
+    pub error: &'static str,
+    pub error_hint_dns: &'static str,
+    pub error_hint_tls: &'static str,
+    pub error_hint_handshake: &'static str,
+    pub error_hint_auth: &'static str,
+    pub error_hint_send_timeout: &'static str,
+    pub missed_messages: &'static str,
+    pub clock_skew: &'static str,
+    pub pinned_by: &'static str,
+    pub unpinned_by: &'static str,
+    pub pin_needs_id: &'static str,
+    pub starred_read_only: &'static str,
+    pub mentions_read_only: &'static str,
+    pub activity_read_only: &'static str,
+    pub export_done: &'static str,
+    pub export_failed: &'static str,
+    pub purge_done: &'static str,
+    pub purge_failed: &'static str,
+    pub purge_unknown_channel: &'static str,
+    pub purge_virtual: &'static str,
+    pub alias_set: &'static str,
+    pub alias_cleared: &'static str,
+    pub alias_save_failed: &'static str,
+    pub metrics_started: &'static str,
+    pub metrics_stopped: &'static str,
+    pub metrics_failed: &'static str,
+    pub dnd_on: &'static str,
+    pub dnd_on_until: &'static str,
+    pub dnd_off: &'static str,
+    pub reminder: &'static str,
+    pub reminder_set: &'static str,
+    pub reminder_save_failed: &'static str,
+    pub autoreply_on: &'static str,
+    pub autoreply_off: &'static str,
+    pub autoreply_none: &'static str,
+    pub autoreply_dnd_only: &'static str,
+    pub autoreply_from_config: &'static str,
+    pub autoreply_added: &'static str,
+    pub autoreply_removed: &'static str,
+    pub autoreply_failed: &'static str,
+    pub notify_mention: &'static str,
+    pub notify_watch_word: &'static str,
+    pub notify_dm: &'static str,
+
+    // Titles and status text
+    pub status_connected: &'static str,
+    pub status_connecting: &'static str,
+    pub status_reconnecting: &'static str,
+    pub status_retry_in: &'static str,
+    pub status_disconnected: &'static str,
+    pub status_kicked: &'static str,
+    pub channels_title: &'static str,
+    pub users_title: &'static str,
+    pub idle: &'static str,
+    pub roster_active: &'static str,
+    pub roster_idle: &'static str,
+    pub roster_offline: &'static str,
+    pub profile_status: &'static str,
+    pub profile_last_seen: &'static str,
+    pub profile_key: &'static str,
+    pub profile_no_key: &'static str,
+    pub profile_verified: &'static str,
+    pub profile_unverified: &'static str,
+    pub profile_blocked: &'static str,
+    pub profile_channels: &'static str,
+    pub profile_relay: &'static str,
+    pub profile_connected_since: &'static str,
+    pub profile_not_connected: &'static str,
+    pub profile_client: &'static str,
+    pub whois_unsupported: &'static str,
+    pub dm_self: &'static str,
+    pub favorite_added: &'static str,
+    pub favorite_removed: &'static str,
+    pub profile_no_channels: &'static str,
+    pub profile_actions: &'static str,
+    pub profile_actions_blocked: &'static str,
+    pub palette_title: &'static str,
+    pub palette_no_matches: &'static str,
+    pub palette_keys: &'static str,
+    pub search_title: &'static str,
+    pub search_no_hits: &'static str,
+    pub search_keys: &'static str,
+    pub user_blocked: &'static str,
+    pub user_unblocked: &'static str,
+    pub block_save_failed: &'static str,
+    pub unknown: &'static str,
+    pub starred: &'static str,
+    pub mentions: &'static str,
+    pub activity: &'static str,
+    pub system_hidden: &'static str,
+    pub history_loading: &'static str,
+    pub pinned_more: &'static str,
+    pub sanitized: &'static str,
+    pub mode_normal: &'static str,
+    pub mode_edit: &'static str,
+    pub mode_command: &'static str,
+    pub option_unknown: &'static str,
+    pub option_invalid: &'static str,
+    pub time_now: &'static str,
+    pub time_ago: &'static str,
+    pub debug_log_title: &'static str,
+    pub uptime_title: &'static str,
+    pub latency_title: &'static str,
+    pub stats_title: &'static str,
+    pub stat_sent: &'static str,
+    pub stat_received: &'static str,
+    pub stat_bytes: &'static str,
+    pub stat_channel: &'static str,
+    pub stat_users: &'static str,
+    pub stat_channels: &'static str,
+    pub stat_replays: &'static str,
+    pub stat_most_active: &'static str,
+    pub stat_no_activity: &'static str,
+    pub activity_title: &'static str,
+    pub latency_history_title: &'static str,
+    pub latency_summary: &'static str,
+    pub server_time_title: &'static str,
+    pub clock_offset: &'static str,
+    pub relay_unknown: &'static str,
+    pub inspector_title: &'static str,
+    pub no_message_selected: &'static str,
+    pub code_copied: &'static str,
+    pub code_none: &'static str,
+    pub send_failed: &'static str,
+    pub retry_none: &'static str,
+    pub code_copy_failed: &'static str,
+    pub roll_action: &'static str,
+    pub flip_action: &'static str,
+    pub coin_heads: &'static str,
+    pub coin_tails: &'static str,
+    pub shrug_action: &'static str,
+    pub pins_title: &'static str,
+    pub no_pins: &'static str,
+    pub motd_title: &'static str,
+    pub compat_title: &'static str,
+    pub compat_client_too_old: &'static str,
+    pub compat_newer_protocol: &'static str,
+    pub dnd_badge: &'static str,
+    pub dnd_badge_until: &'static str,
+
+    // Groups
+    pub members_title: &'static str,
+    pub roster_unscoped: &'static str,
+    pub slow_badge: &'static str,
+    pub announce_badge: &'static str,
+    pub group_created: &'static str,
+    pub group_joined: &'static str,
+    pub group_invited: &'static str,
+    pub group_kicked: &'static str,
+    pub group_removed_you: &'static str,
+    pub group_topic: &'static str,
+    pub group_topic_cleared: &'static str,
+    pub group_slow_on: &'static str,
+    pub group_slow_off: &'static str,
+    pub group_announce_on: &'static str,
+    pub group_announce_off: &'static str,
+    pub group_announcer_added: &'static str,
+    pub group_announcer_removed: &'static str,
+    pub group_rejected: &'static str,
+    pub group_not_active: &'static str,
+    pub slow_mode_wait: &'static str,
+    pub announce_read_only: &'static str,
+    pub mode_read_only: &'static str,
+}
+
+/// English (default)
+pub const EN: Strings = Strings {
+    welcome: "Welcome to GhostWire, {}!",
+    user_joined: "{} joined the chat",
+    user_left: "{} left the chat",
+    user_renamed: "{} is now known as {}",
+    connected: "Connected",
+    connected_to: "Connected to {} v{} (features: {})",
+    relay_connected: "Connected to relay {} ({})",
+    relay_disconnected: "Disconnected from relay {}: {}",
+    kicked: "Kicked by the relay: {} (not reconnecting)",
+    relay_kicked: "Kicked by relay {}: {}",
+    closed: "{}; reconnecting",
+    relay_closed: "Relay {}: {}; reconnecting",
+    receipts_on: "Read receipts on: DM partners see when you have read their messages",
+    receipts_off: "Read receipts off: DM partners only see that messages arrived",
+    presence_filter: "Join/leave notices: {}",
+    presence_all: "everyone",
+    presence_contacts: "contacts (aliased or verified users) only",
+    presence_dms: "users you have DMs with only",
+    presence_none: "off",
+    e2e_pending: "Setting up an encrypted session with {}; the message is sent once it is ready",
+    e2e_established: "Encrypted session with {} established",
+    e2e_identity_changed: "Warning: {}'s identity key has changed. DMs to them are held until you compare keys with /verify or accept the new one with /trust",
+    e2e_held: "{}'s changed identity key is not accepted yet; the message is sent once you /verify or /trust it",
+    e2e_failed: "Key exchange with {} failed; try /rekey",
+    e2e_undecryptable: "[message could not be decrypted]",
+    rekey_started: "Starting a new encrypted session with {}",
+    rekey_scheduled: "Session keys with {} are due for rotation; starting a new encrypted session",
+    e2e_rotated: "Session keys with {} rotated; new messages use the new session",
+    rekey_not_dm: "/rekey only works in a DM",
+    mode_passphrase: "[PASSPHRASE]",
+    room_joined: "Joined #{}",
+    room_joined_encrypted: "Joined #{}; messages are encrypted with your passphrase",
+    room_passphrase_prompt: "Enter the passphrase for #{} (Esc to cancel)",
+    room_rejoin: "Room keys are not saved: /join #{} --passphrase to read and post here again",
+    room_passphrase_empty: "Empty passphrase; not joining",
+    room_wrong_passphrase: "[encrypted: wrong passphrase]",
+    ephemeral_mode: "Ephemeral mode: throwaway identity, nothing is saved, and memory is wiped on exit",
+    verify_title: "Verify {}",
+    verify_instructions: "Ask {} to run /verify too, then compare these symbols in person or on a call:",
+    verify_keys: "[y] They match   [n] They differ   [Esc] Decide later",
+    verify_no_key: "No identity key for {} yet; exchange a DM first",
+    verify_matched: "{} is verified; their DMs are marked as such",
+    verify_mismatch: "Codes for {} did not match; someone may be intercepting your DMs. Their key is marked unverified",
+    trust_accepted: "Accepted {}'s new identity key; held DMs are on their way",
+    trust_unchanged: "{}'s identity key has not changed; nothing to accept",
+    security_plaintext: "PLAINTEXT",
+    security_encrypted: "ENCRYPTED",
+    security_verified: "ENCRYPTED + VERIFIED",
+    downgrade_title: "Send in plaintext?",
+    downgrade_warning: "Messages in {} were encrypted before, but this one would be sent in plaintext: the relay can read it.",
+    downgrade_room_hint: "Cancel and /join #{} --passphrase to encrypt it again.",
+    downgrade_keys: "[y] Send in plaintext   [n] Cancel",
+    disconnected: "Disconnected: {}",
+    error: "Error: {}",
+    error_hint_dns: "check the relay address and your network connection",
+    error_hint_tls: "the relay's certificate is not trusted; check the wss:// address, or use ws:// if the relay has no TLS",
+    error_hint_handshake: "this may not be a GhostWire relay; its WebSocket endpoint is usually /ws",
+    error_hint_auth: "the relay requires a token: run ghostwire --save-token, then set relay_auth = true in config.toml",
+    error_hint_send_timeout: "the relay stopped reading; it may be overloaded or the network is down",
+    missed_messages: "Possible missed messages: {} frame(s) from {} never arrived",
+    clock_skew: "{}'s clock is off by {}; showing receive time for their messages",
+    pinned_by: "{} pinned a message",
+    unpinned_by: "{} unpinned a message",
+    pin_needs_id: "Only chat messages with an ID can be pinned",
+    starred_read_only: "Starred messages are read-only; switch channels to reply",
+    mentions_read_only: "Mentions are read-only; switch channels to reply",
+    activity_read_only: "Activity is read-only: joins, leaves, renames and topic changes from every channel",
+    export_done: "Exported {} to {}",
+    export_failed: "Export failed: {}",
+    purge_done: "Deleted {} stored message(s) of {}",
+    purge_failed: "Purge failed: {}",
+    purge_unknown_channel: "No channel {} (use its ID, e.g. room:lobby, or #room / @user)",
+    purge_virtual: "{} is not stored, so there is nothing to purge",
+    alias_set: "{} will be shown as {}",
+    alias_cleared: "Cleared alias for {}",
+    alias_save_failed: "Failed to save alias: {}",
+    metrics_started: "Appending telemetry to {} every {}s",
+    metrics_stopped: "Stopped telemetry export to {}",
+    metrics_failed: "Telemetry export failed: {}",
+    dnd_on: "Do not disturb is on until you turn it off with /dnd",
+    dnd_on_until: "Do not disturb is on until {} UTC",
+    dnd_off: "Do not disturb is off",
+    reminder: "Reminder: {}",
+    reminder_set: "Reminder set for {} UTC",
+    reminder_save_failed: "Failed to save reminder: {}",
+    autoreply_on: "Auto-replies are on",
+    autoreply_off: "Auto-replies are off",
+    autoreply_none: "No auto-reply rules (add one with /autoreply add <regex> <reply>)",
+    autoreply_dnd_only: " (only during do-not-disturb)",
+    autoreply_from_config: " (config.toml)",
+    autoreply_added: "Added auto-reply rule for {}",
+    autoreply_removed: "Removed auto-reply rule for {}",
+    autoreply_failed: "Auto-reply rules not changed: {}",
+    notify_mention: "GhostWire: {} mentioned you",
+    notify_watch_word: "GhostWire: {} mentioned {}",
+    notify_dm: "GhostWire: message from {}",
+
+    status_connected: "CONNECTED",
+    status_connecting: "CONNECTING",
+    status_reconnecting: "RECONNECTING (attempt {})",
+    status_retry_in: "retry in {}s",
+    status_disconnected: "DISCONNECTED: {}",
+    status_kicked: "KICKED: {}",
+    channels_title: "Channels ({})",
+    users_title: "Users ({}) [J/K to select, d for DM]",
+    idle: "idle {}m",
+    roster_active: "Active",
+    roster_idle: "Idle",
+    roster_offline: "Offline",
+    profile_status: "Status:    ",
+    profile_last_seen: "Last seen: ",
+    profile_key: "Key:       ",
+    profile_no_key: "none yet (no encrypted DM)",
+    profile_verified: "verified",
+    profile_unverified: "not verified",
+    profile_blocked: "Blocked: their messages are hidden",
+    profile_channels: "Channels:  ",
+    profile_relay: "Relay:     ",
+    profile_connected_since: "connected since {} ({})",
+    profile_not_connected: "not connected",
+    profile_client: "Client:    ",
+    whois_unsupported: "This relay does not answer /whois; the profile shows what this client knows",
+    dm_self: "You cannot DM yourself",
+    favorite_added: "{} added to favorites",
+    favorite_removed: "{} removed from favorites",
+    profile_no_channels: "none",
+    profile_actions: "[d] DM  [b] Block  [a] Alias  [v] Verify  [Esc] Close",
+    profile_actions_blocked: "[d] DM  [b] Unblock  [a] Alias  [v] Verify  [Esc] Close",
+    palette_title: "Command Palette",
+    palette_no_matches: "No matching actions or commands",
+    palette_keys: "[↑↓] Select  [Enter] Run  [Esc] Close",
+    search_title: "Search \"{}\" ({} found)",
+    search_no_hits: "No messages contain that text",
+    search_keys: "[↑↓] Select  [Enter] Jump to message  [Esc] Close",
+    user_blocked: "Blocked {}: their messages and DM requests are dropped",
+    user_unblocked: "Unblocked {}",
+    block_save_failed: "Failed to save the block list: {}",
+    unknown: "Unknown",
+    starred: "starred",
+    mentions: "mentions",
+    activity: "activity",
+    system_hidden: "{} system events hidden",
+    history_loading: "loading older messages…",
+    pinned_more: "+{} more, /pins",
+    sanitized: "[sanitized]",
+    mode_normal: "[NORMAL]",
+    mode_edit: "[EDIT]",
+    mode_command: "[COMMAND]",
+    option_unknown: "Unknown option: {} (options: {})",
+    option_invalid: "Invalid value for {}: {} (expected {})",
+    time_now: "now",
+    time_ago: "{} ago",
+    debug_log_title: "Debug Log [F12]",
+    uptime_title: "Uptime",
+    latency_title: "Latency: {}ms",
+    stats_title: "Statistics",
+    stat_sent: "Sent",
+    stat_received: "Recv",
+    stat_bytes: "Bytes",
+    stat_channel: "Channel",
+    stat_users: "Users",
+    stat_channels: "Channels",
+    stat_replays: "Replays dropped",
+    stat_most_active: "Most active (5m)",
+    stat_no_activity: "no messages",
+    activity_title: "Activity (max: {}/s)",
+    latency_history_title: "Latency History",
+    latency_summary: "{}/{}/{}ms min/avg/max",
+    server_time_title: "Server Time",
+    clock_offset: "offset {}ms",
+    relay_unknown: "relay version unknown",
+    inspector_title: "Message Inspector [Esc to close]",
+    no_message_selected: "No message selected",
+    code_copied: "Copied {} lines of code to the clipboard",
+    code_none: "The selected message has no code block",
+    send_failed: "Message not sent: {} (select it and press r to retry)",
+    retry_none: "Select one of your messages marked as not sent to retry it",
+    code_copy_failed: "Could not copy to the clipboard: {}",
+    roll_action: "rolled {}: {}",
+    flip_action: "flipped a coin: {}",
+    coin_heads: "heads",
+    coin_tails: "tails",
+    shrug_action: "shrugs {}",
+    pins_title: "Pinned Messages ({}) [Esc to close]",
+    no_pins: "No pinned messages",
+    motd_title: "Message of the day",
+    compat_title: "Update needed",
+    compat_client_too_old: "{} requires GhostWire {} or newer; this client is {}. Some features may not work until you update.",
+    compat_newer_protocol: "{} speaks protocol version {}; this client only knows version {}. Messages may be lost until you update.",
+    dnd_badge: "DND",
+    dnd_badge_until: "DND until {}",
+
+    members_title: "Members ({}) [@ = operator, u for all users]",
+    roster_unscoped: "The Users pane already lists everyone here; channel members are only known in groups and DMs",
+    slow_badge: "SLOW {}s",
+    announce_badge: "ANNOUNCEMENTS",
+    group_created: "Created group {}",
+    group_joined: "{} added you to group {}",
+    group_invited: "{} added {} to the group",
+    group_kicked: "{} removed {} from the group",
+    group_removed_you: "{} removed you from group {}",
+    group_topic: "{} set the topic: {}",
+    group_topic_cleared: "{} cleared the topic",
+    group_slow_on: "{} turned on slow mode ({}s between messages)",
+    group_slow_off: "{} turned off slow mode",
+    group_announce_on: "{} turned on announcement mode: only operators and announcers can post",
+    group_announce_off: "{} turned off announcement mode",
+    group_announcer_added: "{} let {} post announcements",
+    group_announcer_removed: "{} stopped {} from posting announcements",
+    group_rejected: "Group change rejected: {}",
+    group_not_active: "Switch to a group channel first",
+    slow_mode_wait: "Slow mode: wait {}s before posting again",
+    announce_read_only: "Announcement channel: only operators and announcers can post here",
+    mode_read_only: "[READ-ONLY]",
+};
+
+/// Spanish
+pub const ES: Strings = Strings {
+    welcome: "¡Bienvenido a GhostWire, {}!",
+    user_joined: "{} se unió al chat",
+    user_left: "{} salió del chat",
+    user_renamed: "{} ahora se llama {}",
+    connected: "Conectado",
+    connected_to: "Conectado a {} v{} (funciones: {})",
+    relay_connected: "Conectado al relay {} ({})",
+    relay_disconnected: "Desconectado del relay {}: {}",
+    kicked: "Expulsado por el relay: {} (sin reconexión)",
+    relay_kicked: "Expulsado por el relay {}: {}",
+    closed: "{}; reconectando",
+    relay_closed: "Relay {}: {}; reconectando",
+    receipts_on: "Confirmaciones de lectura activadas: tus contactos ven cuándo has leído sus mensajes",
+    receipts_off: "Confirmaciones de lectura desactivadas: tus contactos solo ven que los mensajes llegaron",
+    presence_filter: "Avisos de entrada/salida: {}",
+    presence_all: "de todos",
+    presence_contacts: "solo de contactos (con alias o verificados)",
+    presence_dms: "solo de usuarios con los que tienes mensajes directos",
+    presence_none: "desactivados",
+    e2e_pending: "Preparando una sesión cifrada con {}; el mensaje se enviará cuando esté lista",
+    e2e_established: "Sesión cifrada con {} establecida",
+    e2e_identity_changed: "Aviso: la clave de identidad de {} ha cambiado. Los mensajes directos quedan retenidos hasta que compares claves con /verify o aceptes la nueva con /trust",
+    e2e_held: "La nueva clave de identidad de {} aún no está aceptada; el mensaje se enviará cuando la verifiques con /verify o la aceptes con /trust",
+    e2e_failed: "Falló el intercambio de claves con {}; prueba /rekey",
+    e2e_undecryptable: "[no se pudo descifrar el mensaje]",
+    rekey_started: "Iniciando una nueva sesión cifrada con {}",
+    rekey_scheduled: "Toca rotar las claves de sesión con {}; iniciando una nueva sesión cifrada",
+    e2e_rotated: "Claves de sesión con {} rotadas; los mensajes nuevos usan la nueva sesión",
+    rekey_not_dm: "/rekey solo funciona en un mensaje directo",
+    mode_passphrase: "[CONTRASEÑA]",
+    room_joined: "Te uniste a #{}",
+    room_joined_encrypted: "Te uniste a #{}; los mensajes se cifran con tu contraseña",
+    room_passphrase_prompt: "Escribe la contraseña de #{} (Esc para cancelar)",
+    room_rejoin: "Las claves de sala no se guardan: /join #{} --passphrase para leer y escribir aquí de nuevo",
+    room_passphrase_empty: "Contraseña vacía; no te uniste",
+    room_wrong_passphrase: "[cifrado: contraseña incorrecta]",
+    ephemeral_mode: "Modo efímero: identidad desechable, no se guarda nada y la memoria se borra al salir",
+    verify_title: "Verificar a {}",
+    verify_instructions: "Pide a {} que ejecute /verify también y comparad estos símbolos en persona o por llamada:",
+    verify_keys: "[y] Coinciden   [n] Son distintos   [Esc] Decidir luego",
+    verify_no_key: "Aún no hay clave de identidad de {}; intercambiad un mensaje directo primero",
+    verify_matched: "{} está verificado; sus mensajes directos se marcan como tales",
+    verify_mismatch: "Los códigos de {} no coinciden; alguien podría estar interceptando tus mensajes directos. Su clave queda sin verificar",
+    trust_accepted: "Aceptada la nueva clave de identidad de {}; se envían los mensajes directos retenidos",
+    trust_unchanged: "La clave de identidad de {} no ha cambiado; no hay nada que aceptar",
+    security_plaintext: "SIN CIFRAR",
+    security_encrypted: "CIFRADO",
+    security_verified: "CIFRADO + VERIFICADO",
+    downgrade_title: "¿Enviar sin cifrar?",
+    downgrade_warning: "Los mensajes de {} estaban cifrados, pero este se enviaría sin cifrar: el relay puede leerlo.",
+    downgrade_room_hint: "Cancela y usa /join #{} --passphrase para volver a cifrarlo.",
+    downgrade_keys: "[y] Enviar sin cifrar   [n] Cancelar",
+    disconnected: "Desconectado: {}",
+    error: "Error: {}",
+    error_hint_dns: "revisa la dirección del relay y tu conexión de red",
+    error_hint_tls: "el certificado del relay no es de confianza; revisa la dirección wss://, o usa ws:// si el relay no tiene TLS",
+    error_hint_handshake: "puede que no sea un relay de GhostWire; su endpoint WebSocket suele ser /ws",
+    error_hint_auth: "el relay requiere un token: ejecuta ghostwire --save-token y luego pon relay_auth = true en config.toml",
+    error_hint_send_timeout: "el relay dejó de leer; puede estar sobrecargado o la red caída",
+    missed_messages: "Posibles mensajes perdidos: {} trama(s) de {} nunca llegaron",
+    clock_skew: "El reloj de {} está desfasado {}; se muestra la hora de recepción de sus mensajes",
+    pinned_by: "{} fijó un mensaje",
+    unpinned_by: "{} desfijó un mensaje",
+    pin_needs_id: "Solo se pueden fijar mensajes de chat con ID",
+    starred_read_only: "Los mensajes destacados son de solo lectura; cambia de canal para responder",
+    mentions_read_only: "Las menciones son de solo lectura; cambia de canal para responder",
+    activity_read_only: "La actividad es de solo lectura: entradas, salidas, cambios de nombre y de tema de todos los canales",
+    export_done: "{} exportado a {}",
+    export_failed: "La exportación falló: {}",
+    purge_done: "Se borraron {} mensaje(s) guardados de {}",
+    purge_failed: "El borrado falló: {}",
+    purge_unknown_channel: "No existe el canal {} (usa su ID, p. ej. room:lobby, o #sala / @usuario)",
+    purge_virtual: "{} no se guarda, así que no hay nada que borrar",
+    alias_set: "{} se mostrará como {}",
+    alias_cleared: "Alias de {} eliminado",
+    alias_save_failed: "No se pudo guardar el alias: {}",
+    metrics_started: "Añadiendo telemetría a {} cada {}s",
+    metrics_stopped: "Exportación de telemetría a {} detenida",
+    metrics_failed: "La exportación de telemetría falló: {}",
+    dnd_on: "No molestar activado hasta que lo desactives con /dnd",
+    dnd_on_until: "No molestar activado hasta las {} UTC",
+    dnd_off: "No molestar desactivado",
+    reminder: "Recordatorio: {}",
+    reminder_set: "Recordatorio programado para el {} UTC",
+    reminder_save_failed: "No se pudo guardar el recordatorio: {}",
+    autoreply_on: "Respuestas automáticas activadas",
+    autoreply_off: "Respuestas automáticas desactivadas",
+    autoreply_none: "No hay reglas de respuesta automática (añade una con /autoreply add <regex> <respuesta>)",
+    autoreply_dnd_only: " (solo con no molestar)",
+    autoreply_from_config: " (config.toml)",
+    autoreply_added: "Regla de respuesta automática añadida para {}",
+    autoreply_removed: "Regla de respuesta automática eliminada para {}",
+    autoreply_failed: "No se cambiaron las reglas de respuesta automática: {}",
+    notify_mention: "GhostWire: {} te mencionó",
+    notify_watch_word: "GhostWire: {} mencionó {}",
+    notify_dm: "GhostWire: mensaje de {}",
+
+    status_connected: "CONECTADO",
+    status_connecting: "CONECTANDO",
+    status_reconnecting: "RECONECTANDO (intento {})",
+    status_retry_in: "reintento en {}s",
+    status_disconnected: "DESCONECTADO: {}",
+    status_kicked: "EXPULSADO: {}",
+    channels_title: "Canales ({})",
+    users_title: "Usuarios ({}) [J/K para elegir, d para MD]",
+    idle: "inactivo {}m",
+    roster_active: "Activos",
+    roster_idle: "Inactivos",
+    roster_offline: "Desconectados",
+    profile_status: "Estado:      ",
+    profile_last_seen: "Visto:       ",
+    profile_key: "Clave:       ",
+    profile_no_key: "ninguna aún (sin MD cifrado)",
+    profile_verified: "verificada",
+    profile_unverified: "sin verificar",
+    profile_blocked: "Bloqueado: sus mensajes se ocultan",
+    profile_channels: "Canales:     ",
+    profile_relay: "Relay:       ",
+    profile_connected_since: "conectado desde {} ({})",
+    profile_not_connected: "no conectado",
+    profile_client: "Cliente:     ",
+    whois_unsupported: "Este relay no responde a /whois; el perfil muestra lo que sabe este cliente",
+    dm_self: "No puedes enviarte un MD a ti mismo",
+    favorite_added: "{} añadido a favoritos",
+    favorite_removed: "{} quitado de favoritos",
+    profile_no_channels: "ninguno",
+    profile_actions: "[d] MD  [b] Bloquear  [a] Alias  [v] Verificar  [Esc] Cerrar",
+    profile_actions_blocked: "[d] MD  [b] Desbloquear  [a] Alias  [v] Verificar  [Esc] Cerrar",
+    palette_title: "Paleta de comandos",
+    palette_no_matches: "Ninguna acción ni comando coincide",
+    palette_keys: "[↑↓] Elegir  [Enter] Ejecutar  [Esc] Cerrar",
+    search_title: "Buscar \"{}\" ({} encontrados)",
+    search_no_hits: "Ningún mensaje contiene ese texto",
+    search_keys: "[↑↓] Elegir  [Enter] Ir al mensaje  [Esc] Cerrar",
+    user_blocked: "{} bloqueado: se descartan sus mensajes y solicitudes de MD",
+    user_unblocked: "{} desbloqueado",
+    block_save_failed: "No se pudo guardar la lista de bloqueos: {}",
+    unknown: "Desconocido",
+    starred: "destacados",
+    mentions: "menciones",
+    activity: "actividad",
+    system_hidden: "{} eventos del sistema ocultos",
+    history_loading: "cargando mensajes anteriores…",
+    pinned_more: "+{} más, /pins",
+    sanitized: "[saneado]",
+    mode_normal: "[NORMAL]",
+    mode_edit: "[EDICIÓN]",
+    mode_command: "[COMANDO]",
+    option_unknown: "Opción desconocida: {} (opciones: {})",
+    option_invalid: "Valor no válido para {}: {} (se esperaba {})",
+    time_now: "ahora",
+    time_ago: "hace {}",
+    debug_log_title: "Registro de depuración [F12]",
+    uptime_title: "Tiempo conectado",
+    latency_title: "Latencia: {}ms",
+    stats_title: "Estadísticas",
+    stat_sent: "Enviados",
+    stat_received: "Recibidos",
+    stat_bytes: "Bytes",
+    stat_channel: "Canal",
+    stat_users: "Usuarios",
+    stat_channels: "Canales",
+    stat_replays: "Repeticiones descartadas",
+    stat_most_active: "Más activos (5m)",
+    stat_no_activity: "sin mensajes",
+    activity_title: "Actividad (máx: {}/s)",
+    latency_history_title: "Historial de latencia",
+    latency_summary: "{}/{}/{}ms mín/med/máx",
+    server_time_title: "Hora del servidor",
+    clock_offset: "desfase {}ms",
+    relay_unknown: "versión del relay desconocida",
+    inspector_title: "Inspector de mensajes [Esc para cerrar]",
+    no_message_selected: "Ningún mensaje seleccionado",
+    code_copied: "{} líneas de código copiadas al portapapeles",
+    code_none: "El mensaje seleccionado no tiene bloques de código",
+    send_failed: "Mensaje no enviado: {} (selecciónalo y pulsa r para reintentar)",
+    retry_none: "Selecciona uno de tus mensajes marcados como no enviados para reintentarlo",
+    code_copy_failed: "No se pudo copiar al portapapeles: {}",
+    roll_action: "tiró {}: {}",
+    flip_action: "lanzó una moneda: {}",
+    coin_heads: "cara",
+    coin_tails: "cruz",
+    shrug_action: "se encoge de hombros {}",
+    pins_title: "Mensajes fijados ({}) [Esc para cerrar]",
+    no_pins: "No hay mensajes fijados",
+    motd_title: "Mensaje del día",
+    compat_title: "Actualización necesaria",
+    compat_client_too_old: "{} requiere GhostWire {} o posterior; este cliente es {}. Algunas funciones pueden fallar hasta que actualices.",
+    compat_newer_protocol: "{} usa la versión {} del protocolo; este cliente solo conoce la versión {}. Pueden perderse mensajes hasta que actualices.",
+    dnd_badge: "NO MOLESTAR",
+    dnd_badge_until: "NO MOLESTAR hasta {}",
+
+    members_title: "Miembros ({}) [@ = operador, u para todos]",
+    roster_unscoped: "El panel de usuarios ya muestra a todos aquí; los miembros solo se conocen en grupos y MD",
+    slow_badge: "LENTO {}s",
+    announce_badge: "ANUNCIOS",
+    group_created: "Grupo {} creado",
+    group_joined: "{} te añadió al grupo {}",
+    group_invited: "{} añadió a {} al grupo",
+    group_kicked: "{} expulsó a {} del grupo",
+    group_removed_you: "{} te expulsó del grupo {}",
+    group_topic: "{} cambió el tema: {}",
+    group_topic_cleared: "{} borró el tema",
+    group_slow_on: "{} activó el modo lento ({}s entre mensajes)",
+    group_slow_off: "{} desactivó el modo lento",
+    group_announce_on: "{} activó el modo anuncios: solo operadores y anunciantes pueden escribir",
+    group_announce_off: "{} desactivó el modo anuncios",
+    group_announcer_added: "{} permitió a {} publicar anuncios",
+    group_announcer_removed: "{} ya no permite a {} publicar anuncios",
+    group_rejected: "Cambio de grupo rechazado: {}",
+    group_not_active: "Cambia primero a un canal de grupo",
+    slow_mode_wait: "Modo lento: espera {}s antes de volver a escribir",
+    announce_read_only: "Canal de anuncios: solo operadores y anunciantes pueden escribir aquí",
+    mode_read_only: "[SOLO LECTURA]",
+};
+
+/// Pick the string table for a configured locale, or the environment's
+pub fn select(configured: Option<&str>) -> &'static Strings {
+    let locale = configured.map(str::to_string).or_else(|| {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+    });
+
+    // "es_ES.UTF-8" -> "es"
+    let language = locale
+        .as_deref()
+        .and_then(|locale| locale.split(['_', '.', '-']).next())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match language.as_str() {
+        "es" => &ES,
+        _ => &EN,
+    }
+}
+
+/// Fill `{}` placeholders in a template, in order
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    if let Some(first) = parts.next() {
+        out.push_str(first);
+    }
+    for part in parts {
+        if let Some(arg) = args.next() {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
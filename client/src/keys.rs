@@ -0,0 +1,94 @@
+// GhostWire Client - Key Backup
+// `ghostwire keys export|import <file>`: the identity key and the trust store
+// in one passphrase-encrypted bundle, for moving to another machine without
+// losing the identity DM partners know us by.
+
+use crate::crypto::SealedBox;
+use crate::e2e::IDENTITY_SECRET;
+use crate::ratchet::Key;
+use crate::secrets::SecretStore;
+use crate::trust::TrustStore;
+use anyhow::{anyhow, Context};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Decrypted contents of a backup file
+#[derive(Serialize, Deserialize)]
+struct KeyBundle {
+    /// Base64 identity private key
+    identity: String,
+    trust: TrustStore,
+}
+
+impl Drop for KeyBundle {
+    fn drop(&mut self) {
+        self.identity.zeroize();
+    }
+}
+
+/// Write the identity key and trust store to `path`, encrypted with a
+/// passphrase asked for twice. Existing files are not overwritten.
+pub fn export(store: &SecretStore, path: &Path) -> anyhow::Result<()> {
+    let identity = store
+        .get(IDENTITY_SECRET)?
+        .context("No identity key yet; start GhostWire once to create one")?;
+    let bundle = KeyBundle {
+        identity: identity.expose_secret().to_string(),
+        trust: TrustStore::load(),
+    };
+
+    let passphrase = Zeroizing::new(rpassword::prompt_password("Backup passphrase: ")?);
+    let confirm = Zeroizing::new(rpassword::prompt_password("Repeat passphrase: ")?);
+    if passphrase.is_empty() {
+        return Err(anyhow!("The backup passphrase must not be empty"));
+    }
+    if passphrase != confirm {
+        return Err(anyhow!("Passphrases do not match"));
+    }
+
+    let json = Zeroizing::new(serde_json::to_vec(&bundle)?);
+    let sealed = SealedBox::seal(&passphrase, &json)?;
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .with_context(|| format!("Cannot create {}", path.display()))?;
+    file.write_all(serde_json::to_string_pretty(&sealed)?.as_bytes())?;
+    println!(
+        "Exported the identity key and {} trusted key(s) to {}",
+        bundle.trust.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Restore a backup made by `export`. Replacing a different identity key
+/// needs `force`, since DM partners will see it change.
+pub fn import(store: &SecretStore, path: &Path, force: bool) -> anyhow::Result<()> {
+    let text = fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let sealed: SealedBox = serde_json::from_str(&text).context("Not a GhostWire key backup")?;
+    let passphrase = Zeroizing::new(rpassword::prompt_password("Backup passphrase: ")?);
+    let json = sealed.open(&passphrase)?;
+    let mut bundle: KeyBundle = serde_json::from_slice(&json).context("Not a GhostWire key backup")?;
+    Key::from_base64(&bundle.identity).context("Backup holds an invalid identity key")?;
+
+    if let Some(current) = store.get(IDENTITY_SECRET)? {
+        if current.expose_secret() != bundle.identity && !force {
+            return Err(anyhow!(
+                "This machine already has a different identity key; use --force to replace it"
+            ));
+        }
+    }
+    store.set(IDENTITY_SECRET, &bundle.identity)?;
+
+    let mut trust = TrustStore::load();
+    let count = bundle.trust.len();
+    trust.merge(std::mem::take(&mut bundle.trust));
+    trust.save()?;
+    println!("Imported the identity key and {} trusted key(s)", count);
+    Ok(())
+}
@@ -0,0 +1,122 @@
+// GhostWire Client - In-App Logging
+// Ratatui owns stdout, so tracing output is captured into a ring buffer
+// that the debug panel (F12) renders, and optionally mirrored to a file.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Maximum number of log lines kept in memory
+const MAX_LOG_LINES: usize = 500;
+
+/// Environment variable used to override the log filter
+const LOG_ENV: &str = "GHOSTWIRE_LOG";
+
+/// Shared ring buffer of formatted log lines
+#[derive(Debug, Clone, Default)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl LogBuffer {
+    /// Append a line, dropping the oldest once the buffer is full
+    fn push(&self, line: String) {
+        if let Ok(mut lines) = self.lines.lock() {
+            lines.push_back(line);
+            if lines.len() > MAX_LOG_LINES {
+                lines.pop_front();
+            }
+        }
+    }
+
+    /// Get the most recent `count` lines, oldest first
+    pub fn tail(&self, count: usize) -> Vec<String> {
+        match self.lines.lock() {
+            Ok(lines) => lines
+                .iter()
+                .skip(lines.len().saturating_sub(count))
+                .cloned()
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// Tracing layer that formats events into a `LogBuffer`
+struct RingBufferLayer {
+    buffer: LogBuffer,
+}
+
+impl<S: Subscriber> Layer<S> for RingBufferLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = LineVisitor::default();
+        event.record(&mut visitor);
+
+        let meta = event.metadata();
+        self.buffer.push(format!(
+            "{} {:>5} {}: {}{}",
+            chrono::Local::now().format("%H:%M:%S"),
+            meta.level(),
+            meta.target(),
+            visitor.message,
+            visitor.fields,
+        ));
+    }
+}
+
+/// Collects the message and any extra fields of an event into strings
+#[derive(Default)]
+struct LineVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for LineVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Install the global tracing subscriber.
+///
+/// Events always go to the returned in-memory buffer; if `log_file` is given
+/// they are also appended to that file.
+pub fn init(log_file: Option<&Path>) -> anyhow::Result<LogBuffer> {
+    let buffer = LogBuffer::default();
+
+    let filter = EnvFilter::try_from_env(LOG_ENV)
+        .unwrap_or_else(|_| EnvFilter::new("ghostwire=debug"));
+
+    let file_layer = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(Mutex::new(file)),
+            )
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(RingBufferLayer {
+            buffer: buffer.clone(),
+        })
+        .with(file_layer)
+        .try_init()?;
+
+    Ok(buffer)
+}
@@ -5,82 +5,210 @@
 // - Communication: mpsc unbounded channels
 
 mod app;
+mod aliases;
+mod autoreply;
+mod blocked;
+mod cli;
+mod cmdline;
+mod codeblock;
+mod commands;
+mod config;
+mod crypto;
+mod daemon;
+mod e2e;
+mod export;
+mod favorites;
+mod fun;
+mod glyphs;
+mod history;
+mod hooks;
+mod i18n;
+mod keys;
+mod logging;
+mod markdown;
+mod metrics;
 mod network;
+mod relays;
+mod reminders;
+mod replay;
+mod notify;
+mod padding;
+mod palette;
+mod ratchet;
+mod sanitize;
+mod search;
+mod session;
+mod secrets;
+mod starred;
+mod storage;
+mod terminal;
+mod trust;
+mod uri;
+mod verify;
 mod ui;
 
-use app::{App, ChatMessage, InputMode, User};
-use chrono::Utc;
-use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
-use network::{NetworkCommand, NetworkEvent};
-use ratatui::{
-    backend::CrosstermBackend,
-    Terminal,
-};
-use std::io;
+use aliases::Aliases;
+use autoreply::AutoAction;
+use blocked::Blocked;
+use starred::Starred;
+use app::{App, ChatMessage, ConnectionState, Delivery, InputMode, User};
+use clap::Parser;
+use cli::{Cli, CliCommand, KeysAction};
+use commands::{AutoReplyRequest, Command, DndRequest, GroupCommand};
+use ghostwire_protocol::group::GroupEvent;
+use config::{Config, PresenceFilter, SidebarSort, TimestampStyle};
+use i18n::fill;
+use ghostwire_protocol::validate::validate_username;
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use network::{NetworkCommand, NetworkError, NetworkEvent};
+use notify::DoNotDisturb;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
+use terminal::TerminalGuard;
+use tracing::{debug, info, warn};
+use zeroize::Zeroizing;
 
-/// Default server URL (can be overridden via CLI args)
-const DEFAULT_SERVER_URL: &str = "wss://ghost.jcyrus.com/ws";
+/// Sender clocks further than this from the relay's are considered wrong
+const MAX_CLOCK_SKEW_SECS: i64 = 120;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // Parse command line arguments
-    let args: Vec<String> = std::env::args().collect();
-    
-    let username = if args.len() > 1 {
-        args[1].clone()
-    } else {
-        // Generate a random username if none provided
-        format!("ghost_{}", &uuid::Uuid::new_v4().to_string()[..8])
+    let cli = Cli::parse();
+
+    // Capture tracing output before anything else starts logging
+    let logs = logging::init(cli.log_file.as_deref())?;
+
+    // An attached client takes the daemon's username and network side
+    let attached = match &cli.command {
+        Some(CliCommand::Attach) if cli.ephemeral || cli.daemon => {
+            anyhow::bail!("attach cannot be combined with --ephemeral or --daemon")
+        }
+        Some(CliCommand::Attach) => Some(daemon::attach(&cli.socket_path()?).await?),
+        _ => None,
     };
-    
-    let server_url = if args.len() > 2 {
-        args[2].clone()
+    let config = Config::load(cli.config.as_deref());
+    let username = match &attached {
+        Some(attached) => attached.username.clone(),
+        None => cli.username(config.username.as_deref()),
+    };
+    validate_username(&username)
+        .map_err(|e| anyhow::anyhow!("Invalid username {:?}: {}", username, e))?;
+    let link = cli.link()?;
+    let servers = cli.server_urls(link.as_ref(), &config.servers);
+    info!("Starting GhostWire client as {} ({})", username, servers.join(", "));
+
+    if let Some(CliCommand::Keys { action }) = &cli.command {
+        let store = secrets::SecretStore::open()?;
+        storage::unlock(&store, config.encrypt_storage)?;
+        return match action {
+            KeysAction::Export { file } => keys::export(&store, file),
+            KeysAction::Import { file, force } => keys::import(&store, file, *force),
+        };
+    }
+
+    if let Some(CliCommand::Import { file }) = &cli.command {
+        let store = secrets::SecretStore::open()?;
+        storage::unlock(&store, config.encrypt_storage)?;
+        return history::import(file);
+    }
+
+    if cli.save_token {
+        let token = rpassword::prompt_password("Relay token: ")?;
+        secrets::SecretStore::open()?.set(secrets::RELAY_TOKEN, &token)?;
+        println!("Relay token saved; set relay_auth = true in config.toml to send it");
+        return Ok(());
+    }
+
+    if cli.daemon {
+        let store = secrets::SecretStore::open()?;
+        let handshake = network::Handshake {
+            headers: config.headers.clone(),
+            auth_token: secrets::relay_token(config.relay_auth.then_some(&store))?,
+        };
+        let (command_tx, event_rx, network_handle) = start_network(&config, &cli, servers, &username, handshake);
+        daemon::run(&cli.socket_path()?, username, command_tx, event_rx).await?;
+        let _ = network_handle.await;
+        return Ok(());
+    }
+
+    // The relay token, identity key and storage key are read before the TUI starts
+    // because the encrypted store may prompt for a passphrase. Ephemeral
+    // sessions never touch the store or the data directory.
+    let (auth_token, e2e) = if cli.ephemeral {
+        storage::disable();
+        (secrets::relay_token(None)?, e2e::E2e::ephemeral())
     } else {
-        DEFAULT_SERVER_URL.to_string()
+        let store = secrets::SecretStore::open()?;
+        let auth_token = secrets::relay_token(config.relay_auth.then_some(&store))?;
+        storage::unlock(&store, config.encrypt_storage)?;
+        (auth_token, e2e::E2e::load(&store)?)
+    };
+    let handshake = network::Handshake {
+        headers: config.headers.clone(),
+        auth_token,
     };
 
     // Create the application state
-    let mut app = App::new(username.clone());
-
-    // Create channels for communication between UI and network task
-    // event_rx: UI receives events from network
-    // command_tx: UI sends commands to network
-    let (event_tx, mut event_rx) = mpsc::unbounded_channel::<NetworkEvent>();
-    let (command_tx, command_rx) = mpsc::unbounded_channel::<NetworkCommand>();
+    let mut app = App::new(username.clone(), config);
+    app.logs = logs;
+    app.e2e = e2e;
+    app.e2e.set_rekey(app.config.rekey.clone());
+    app.aliases = Aliases::load();
+    app.blocked = Blocked::load();
+    app.channel_order = favorites::ChannelOrder::load();
+    app.starred = Starred::load();
+    app.refresh_starred_channel();
+    app.restore_session(session::SessionState::load());
+    if app.config.save_history {
+        // Compact first, so nothing past the limits is loaded and saved again
+        if app.config.retention.enabled() && !cli.ephemeral {
+            if let Err(e) = history::compact(&app.config.retention, chrono::Utc::now()) {
+                warn!("History compaction failed: {}", e);
+            }
+            history::spawn_compaction(app.config.retention.clone());
+        }
+        app.load_history();
+    }
+    if let Some(target) = link.and_then(|link| link.target) {
+        app.open_link(target);
+    }
+    app.reminders = reminders::Reminders::load();
+    app.autoreply = autoreply::AutoReplies::new(&app.config.autoreply);
+    if cli.ephemeral {
+        app.add_message(ChatMessage::system(app.strings.ephemeral_mode.to_string()));
+    }
+    if let Some(path) = &cli.metrics_file {
+        let writer = metrics::MetricsWriter::open(path)
+            .map_err(|e| anyhow::anyhow!("Cannot open metrics file {}: {}", path.display(), e))?;
+        app.metrics = Some(writer);
+    }
 
-    // Spawn the network task in a separate async runtime
-    // This is the CRITICAL async/sync split!
-    let network_handle = tokio::spawn(network::network_task(
-        server_url,
-        username.clone(),
-        event_tx,
-        command_rx,
-    ));
+    let (command_tx, mut event_rx, network_handle) = match attached {
+        Some(attached) => (attached.command_tx, attached.event_rx, attached.handle),
+        None => start_network(&app.config, &cli, servers, &username, handshake),
+    };
 
-    // Setup terminal for TUI
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    // Setup terminal for TUI. The guard restores it on drop and the panic
+    // hook restores it before a panic message is printed.
+    terminal::install_panic_hook();
+    let mut terminal = TerminalGuard::new()?;
 
     // Main UI loop (synchronous, runs on main thread)
     let result = run_ui_loop(&mut terminal, &mut app, &mut event_rx, &command_tx);
 
     // Cleanup: Restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    drop(terminal);
+    if cli.ephemeral {
+        app.wipe();
+    } else {
+        if let Err(e) = app.session_state().save() {
+            warn!("Failed to save session state: {}", e);
+        }
+        if app.config.save_history {
+            app.save_history();
+        }
+    }
 
     // Shutdown network task
     let _ = command_tx.send(NetworkCommand::Disconnect);
@@ -94,32 +222,138 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Spawn the network task for the primary relay plus the extra relays'
+/// tasks, with the `--on-event` hook watching their events; returns the
+/// command sender, the event receiver and the primary task
+fn start_network(
+    config: &Config,
+    cli: &Cli,
+    servers: Vec<String>,
+    username: &str,
+    handshake: network::Handshake,
+) -> (
+    mpsc::UnboundedSender<NetworkCommand>,
+    mpsc::UnboundedReceiver<NetworkEvent>,
+    tokio::task::JoinHandle<()>,
+) {
+    // Create channels for communication between UI and network task
+    // event_rx: UI receives events from network
+    // command_tx: UI sends commands to network
+    let (event_tx, event_rx) = mpsc::unbounded_channel::<NetworkEvent>();
+    let (primary_tx, command_rx) = mpsc::unbounded_channel::<NetworkCommand>();
+
+    // Extra relays get their own tasks; the UI sends every command through
+    // the router, which picks the relay from the channel namespace
+    let command_tx = relays::spawn(&config.relays, username, primary_tx, event_tx.clone());
+
+    // Spawn the network task in a separate async runtime
+    // This is the CRITICAL async/sync split!
+    let network_handle = tokio::spawn(network::network_task(
+        servers,
+        username.to_string(),
+        handshake,
+        event_tx,
+        command_rx,
+    ));
+    let on_event = cli.on_event.clone().or_else(|| config.on_event.clone());
+    let event_rx = hooks::spawn(on_event, username, event_rx);
+    (command_tx, event_rx, network_handle)
+}
+
 /// Main UI event loop - runs synchronously on the main thread
 fn run_ui_loop(
-    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    terminal: &mut TerminalGuard,
     app: &mut App,
     event_rx: &mut mpsc::UnboundedReceiver<NetworkEvent>,
     command_tx: &mpsc::UnboundedSender<NetworkCommand>,
 ) -> anyhow::Result<()> {
     // Track uptime
     let mut last_uptime_update = Instant::now();
+    let mut last_metrics_write = Instant::now();
     
     loop {
         // Render the UI
         terminal.draw(|f| ui::render(f, app))?;
 
+        // Older history asked for by scrolling past the top, now that
+        // "loading" is on screen
+        app.load_pending_history();
+
         // Check for network events (non-blocking)
         while let Ok(event) = event_rx.try_recv() {
             handle_network_event(app, event);
         }
+        
+        // Handshake steps and encrypted DMs the encryption layer produced
+        for outgoing in app.e2e.take_outgoing() {
+            let command = match outgoing {
+                e2e::Outgoing::KeyExchange { channel_id, exchange } => {
+                    NetworkCommand::KeyExchange { channel_id, exchange }
+                }
+                e2e::Outgoing::Message { channel_id, id, payload, action } => NetworkCommand::SendMessage {
+                    id,
+                    content: payload,
+                    channel_id,
+                    encrypted: true,
+                    action,
+                },
+            };
+            let _ = command_tx.send(command);
+        }
+        
+        // Acknowledge DMs: DELIVERED on arrival, READ once seen
+        app.queue_read();
+        for receipt in app.pending_receipts.drain(..) {
+            let _ = command_tx.send(NetworkCommand::SendReceipt {
+                channel_id: receipt.channel_id,
+                message_id: receipt.message_id,
+                read: receipt.read,
+            });
+        }
+        
+        // Timed do-not-disturb ends on its own
+        if app.dnd.expired(chrono::Utc::now()) {
+            app.dnd = DoNotDisturb::Off;
+            app.add_message(ChatMessage::system(app.strings.dnd_off.to_string()));
+        }
+
+        // Auto-replies and commands for DMs that matched a rule
+        for (channel_id, action) in std::mem::take(&mut app.pending_autoreplies) {
+            match action {
+                AutoAction::Reply(text) => send_to_channel(app, &channel_id, text, false, command_tx),
+                AutoAction::Command(line) => match commands::parse(&line) {
+                    Some(Ok(command)) => handle_command(app, command, command_tx),
+                    Some(Err(e)) => warn!("Auto-reply command {:?} is invalid: {}", line, e),
+                    None => {}
+                },
+            }
+        }
+
+        // Reminders come due, including any that did while we were closed
+        app.post_due_reminders(chrono::Utc::now());
+
+        // Cover traffic hides when we really write
+        if app.next_cover.is_some_and(|due| Instant::now() >= due) {
+            send_cover(app, command_tx);
+            app.next_cover = app.config.padding.next_cover().map(|wait| Instant::now() + wait);
+        }
 
         // Check for terminal events (blocking with timeout)
         if event::poll(std::time::Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                handle_key_event(app, key.code, key.modifiers, command_tx)?;
+            match event::read()? {
+                // Ctrl+Z arrives as a key in raw mode; suspending needs the terminal
+                Event::Key(key) if key.code == KeyCode::Char('z') && key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    terminal.suspend()?;
+                }
+                Event::Key(key) => handle_key_event(app, key.code, key.modifiers, command_tx)?,
+                Event::Paste(text) if app.input_mode == InputMode::Editing => app.input_paste(&text),
+                _ => {}
             }
         }
 
+        // Suspend on an external SIGTSTP (e.g. `kill -TSTP`)
+        terminal.handle_pending_suspend()?;
+
         // Update uptime every second
         if last_uptime_update.elapsed() >= Duration::from_secs(1) {
             app.increment_uptime(1);
@@ -127,6 +361,17 @@ fn run_ui_loop(
             last_uptime_update = Instant::now();
         }
         
+        // Append a telemetry snapshot if exporting; stop on write errors
+        if last_metrics_write.elapsed() >= metrics::METRICS_INTERVAL {
+            if let Some(writer) = &mut app.metrics {
+                if let Err(e) = writer.append(&app.telemetry) {
+                    app.metrics = None;
+                    app.add_message(ChatMessage::system(fill(app.strings.metrics_failed, &[&e])));
+                }
+            }
+            last_metrics_write = Instant::now();
+        }
+        
         // Check if we should quit
         if app.should_quit {
             break;
@@ -140,85 +385,191 @@ fn run_ui_loop(
 fn handle_key_event(
     app: &mut App,
     key: KeyCode,
-    _modifiers: KeyModifiers,
+    modifiers: KeyModifiers,
     command_tx: &mpsc::UnboundedSender<NetworkCommand>,
 ) -> anyhow::Result<()> {
+    // The debug log panel can be toggled from any mode
+    if key == KeyCode::F(12) {
+        app.toggle_logs();
+        return Ok(());
+    }
+
+    // The command palette opens from any mode
+    if key == KeyCode::Char('p') && modifiers.contains(KeyModifiers::CONTROL) && app.popup.is_none() {
+        app.open_palette();
+        return Ok(());
+    }
+
+    // Alt+Tab and Alt+1..9 switch channels from any mode, as in other chat clients
+    if modifiers.contains(KeyModifiers::ALT) && app.popup.is_none() {
+        match key {
+            KeyCode::Tab => {
+                app.switch_to_previous_channel();
+                return Ok(());
+            }
+            KeyCode::Char(digit @ '1'..='9') => {
+                app.switch_to_nth_channel(digit as usize - '0' as usize);
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+
+    // The palette takes typing as its search and runs the chosen row
+    if let Some(app::Popup::Palette { query, selected }) = &mut app.popup {
+        let matches = palette::search(query);
+        match key {
+            KeyCode::Esc => app.close_popup(),
+            KeyCode::Up => *selected = selected.saturating_sub(1),
+            KeyCode::Down => *selected = (*selected + 1).min(matches.len().saturating_sub(1)),
+            KeyCode::Backspace => {
+                query.pop();
+                *selected = 0;
+            }
+            KeyCode::Char(c) if !modifiers.contains(KeyModifiers::CONTROL) => {
+                query.push(c);
+                *selected = 0;
+            }
+            KeyCode::Enter => {
+                let chosen = matches.get(*selected).map(|entry| entry.target);
+                app.close_popup();
+                match chosen {
+                    Some(palette::Target::Action(action)) => run_action(app, action, command_tx),
+                    Some(palette::Target::Command(spec)) => run_palette_command(app, spec, command_tx),
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    // Search results: pick a hit and jump to it
+    if let Some(app::Popup::Search { hits, selected, .. }) = &mut app.popup {
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => *selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => *selected = (*selected + 1).min(hits.len().saturating_sub(1)),
+            KeyCode::Enter => {
+                let hit = hits.get(*selected).cloned();
+                app.close_popup();
+                if let Some(hit) = hit {
+                    app.jump_to_hit(&hit);
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => app.close_popup(),
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    // The verification popup waits for a verdict (Esc decides later)
+    if matches!(app.popup, Some(app::Popup::Verify { .. })) {
+        match key {
+            KeyCode::Char('y') => app.finish_verification(true),
+            KeyCode::Char('n') => app.finish_verification(false),
+            KeyCode::Esc => app.close_popup(),
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    // Plaintext in a channel that was encrypted is only sent on `y`
+    if matches!(app.popup, Some(app::Popup::Downgrade { .. })) {
+        match key {
+            KeyCode::Char('y') => {
+                if let Some((channel_id, content, action)) = app.finish_downgrade(true) {
+                    send_to_channel(app, &channel_id, content, action, command_tx);
+                }
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                app.finish_downgrade(false);
+            }
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    // The profile card offers quick actions on its user
+    if let Some(app::Popup::Profile { user }) = &app.popup {
+        let user = user.clone();
+        match key {
+            KeyCode::Char('d') => {
+                app.close_popup();
+                app.open_dm(user);
+            }
+            KeyCode::Char('b') => app.toggle_block(&user),
+            KeyCode::Char('a') => {
+                app.close_popup();
+                app.input = format!("/alias {} ", user);
+                app.enter_edit_mode();
+            }
+            KeyCode::Char('v') => {
+                app.close_popup();
+                if let Err(notice) = app.start_verification(&user) {
+                    app.add_message(ChatMessage::system(notice));
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Enter => app.close_popup(),
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    // An open popup captures all keys until it is dismissed
+    if app.popup.is_some() {
+        if matches!(key, KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('v')) {
+            app.close_popup();
+        }
+        return Ok(());
+    }
+
     match app.input_mode {
         InputMode::Normal => {
+            // Enter acts on the roster only right after moving around in it
+            let roster_focused = std::mem::take(&mut app.roster_focused);
             match key {
-                // Quit
-                KeyCode::Char('q') | KeyCode::Esc => {
-                    app.quit();
-                }
-                // Enter edit mode
-                KeyCode::Char('i') | KeyCode::Enter => {
-                    app.enter_edit_mode();
-                }
-                // Scroll chat
-                KeyCode::Char('j') | KeyCode::Down => {
-                    app.scroll_down();
-                }
-                KeyCode::Char('k') | KeyCode::Up => {
-                    app.scroll_up();
-                }
-                // Scroll to bottom
-                KeyCode::Char('G') => {
-                    app.scroll_to_bottom();
-                }
-                
-                // Channel navigation
-                KeyCode::Char('h') | KeyCode::Left => app.select_previous_channel(),
-                KeyCode::Char('l') | KeyCode::Right => app.select_next_channel(),
-                KeyCode::Tab => app.activate_selected_channel(),
-                KeyCode::Char('#') => app.switch_channel("global".to_string()),
-                
-                // Create DM
-                KeyCode::Char('d') => {
-                    // Prompt for username (simple implementation)
-                    if !app.users.is_empty() {
-                        // Use selected user
-                        if let Some(user) = app.users.get(app.selected_user) {
-                            app.open_dm(user.username.clone());
-                        }
+                // Profile card of the selected user (or fold the selected section)
+                KeyCode::Enter if roster_focused => {
+                    app.activate_roster_row();
+                    app.roster_focused = true;
+                }
+                _ => {
+                    if let Some(action) = palette::Action::for_key(key) {
+                        run_action(app, action, command_tx);
                     }
                 }
-                
-                // User selection (for DM creation)
-                KeyCode::Char('J') => app.select_next_user(),
-                KeyCode::Char('K') => app.select_previous_user(),
-                
-                _ => {}
             }
         }
         InputMode::Editing => {
             match key {
-                // Exit edit mode
+                // Exit edit mode, abandoning a passphrase prompt
                 KeyCode::Esc => {
+                    if app.passphrase_prompt.take().is_some() {
+                        drop(Zeroizing::new(app.take_input()));
+                    }
                     app.exit_edit_mode();
                 }
+                // Alt+Enter starts a new line (passphrases are one line)
+                KeyCode::Enter if modifiers.contains(KeyModifiers::ALT) && app.passphrase_prompt.is_none() => {
+                    app.input_char('\n');
+                }
                 // Send message
                 KeyCode::Enter => {
                     let input = app.take_input();
-                    if !input.is_empty() {
-                        let channel_id = app.active_channel.clone();
-                        
-                        // Send to network task
-                        let _ = command_tx.send(NetworkCommand::SendMessage {
-                            content: input.clone(),
-                            channel_id: channel_id.clone(),
-                        });
-                        
-                        // Add to local chat immediately (optimistic update)
-                        app.add_message(ChatMessage::new(
-                            app.username.clone(),
-                            input,
-                            false,
-                        ));
-                        
-                        // Update telemetry
-                        app.telemetry.messages_sent += 1;
+                    if let Some(channel_id) = app.passphrase_prompt.take() {
+                        join_with_passphrase(app, channel_id, Zeroizing::new(input));
+                    } else if let Some(parsed) = commands::parse(&input) {
+                        match parsed {
+                            Ok(command) => handle_command(app, command, command_tx),
+                            Err(e) => app.add_message(ChatMessage::system(e)),
+                        }
+                    } else if !input.is_empty() {
+                        send_message(app, input, false, command_tx);
+                    }
+                    // Stay in the input box if a command asked for a passphrase
+                    if app.passphrase_prompt.is_none() {
+                        app.exit_edit_mode();
                     }
-                    app.exit_edit_mode();
                 }
                 // Character input
                 KeyCode::Char(c) => {
@@ -238,52 +589,1150 @@ fn handle_key_event(
                 _ => {}
             }
         }
+        InputMode::Command => {
+            match key {
+                KeyCode::Esc => app.exit_edit_mode(),
+                KeyCode::Enter => {
+                    let line = app.command_line.submit();
+                    app.exit_edit_mode();
+                    run_command_line(app, &line, command_tx);
+                }
+                // Backspace on an empty line closes it, as in vim
+                KeyCode::Backspace if !app.command_line.backspace() => app.exit_edit_mode(),
+                KeyCode::Tab => app.command_line.complete(),
+                KeyCode::Up => app.command_line.history_previous(),
+                KeyCode::Down => app.command_line.history_next(),
+                KeyCode::Left => app.command_line.move_left(),
+                KeyCode::Right => app.command_line.move_right(),
+                KeyCode::Char(c) => app.command_line.insert(c),
+                _ => {}
+            }
+        }
     }
 
     Ok(())
 }
 
+/// Run a `:` command line: quit, `:set`, or a slash command without its slash
+fn run_command_line(app: &mut App, line: &str, command_tx: &mpsc::UnboundedSender<NetworkCommand>) {
+    if line.trim().is_empty() {
+        return;
+    }
+    match cmdline::parse(line) {
+        cmdline::ExCommand::Quit => app.quit(),
+        cmdline::ExCommand::Set(None) => {
+            for (name, _) in cmdline::OPTIONS {
+                let value = option_value(app, name).unwrap_or_default();
+                app.add_message(ChatMessage::system(format!("{}={}", name, value)));
+            }
+        }
+        cmdline::ExCommand::Set(Some(arg)) => set_option(app, arg),
+        cmdline::ExCommand::Slash(input) => match commands::parse(&input) {
+            Some(Ok(command)) => handle_command(app, command, command_tx),
+            Some(Err(e)) => app.add_message(ChatMessage::system(e)),
+            None => {}
+        },
+    }
+    // `:join room --passphrase` asks for the passphrase in the input box
+    if app.passphrase_prompt.is_some() {
+        app.enter_edit_mode();
+    }
+}
+
+/// Current value of a `:set` option, or `None` if there is no such option
+fn option_value(app: &App, name: &str) -> Option<String> {
+    let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+    let value = match name {
+        "timestamps" => match app.config.timestamps {
+            TimestampStyle::Absolute => "absolute",
+            TimestampStyle::Relative => "relative",
+        },
+        "sidebar" => match app.config.sidebar_sort {
+            SidebarSort::Arranged => "arranged",
+            SidebarSort::Unread => "unread",
+        },
+        "presence" => match app.presence {
+            PresenceFilter::All => "all",
+            PresenceFilter::Contacts => "contacts",
+            PresenceFilter::Dms => "dms",
+            PresenceFilter::None => "none",
+        },
+        "markdown" => on_off(app.config.render_markdown),
+        "highlight" => on_off(app.config.syntax_highlighting),
+        "ascii" => on_off(app.config.ascii_only),
+        "receipts" => on_off(app.read_receipts),
+        "activity" => on_off(app.config.activity_channel),
+        _ => return None,
+    };
+    Some(value.to_string())
+}
+
+/// Change a setting for this session (config.toml is left alone) and show
+/// its value
+fn set_option(app: &mut App, arg: cmdline::SetArg) {
+    use cmdline::SetValue;
+
+    let name = arg.name.as_str();
+    if option_value(app, name).is_none() {
+        let names: Vec<&str> = cmdline::OPTIONS.iter().map(|(name, _)| *name).collect();
+        let notice = fill(app.strings.option_unknown, &[&name, &names.join(", ")]);
+        app.add_message(ChatMessage::system(notice));
+        return;
+    }
+
+    let value = match &arg.value {
+        SetValue::Show => None,
+        SetValue::On => Some("on"),
+        SetValue::Off => Some("off"),
+        SetValue::To(value) => Some(value.as_str()),
+    };
+    if let Some(value) = value {
+        if !apply_option(app, name, value) {
+            let expected = cmdline::OPTIONS
+                .iter()
+                .find(|(option, _)| *option == name)
+                .map(|(_, values)| if values.is_empty() { "on|off".to_string() } else { values.join("|") })
+                .unwrap_or_default();
+            let notice = fill(app.strings.option_invalid, &[&name, &value, &expected]);
+            app.add_message(ChatMessage::system(notice));
+            return;
+        }
+    }
+
+    let current = option_value(app, name).unwrap_or_default();
+    app.add_message(ChatMessage::system(format!("{}={}", name, current)));
+}
+
+/// Set a known option from its `:set` value; false if the value is invalid
+fn apply_option(app: &mut App, name: &str, value: &str) -> bool {
+    let toggle = match value {
+        "on" | "true" | "yes" => Some(true),
+        "off" | "false" | "no" => Some(false),
+        _ => None,
+    };
+    match (name, toggle) {
+        ("timestamps", _) => match value {
+            "absolute" => app.config.timestamps = TimestampStyle::Absolute,
+            "relative" => app.config.timestamps = TimestampStyle::Relative,
+            _ => return false,
+        },
+        ("sidebar", _) => match value {
+            "arranged" => app.config.sidebar_sort = SidebarSort::Arranged,
+            "unread" => app.config.sidebar_sort = SidebarSort::Unread,
+            _ => return false,
+        },
+        ("presence", _) => match value {
+            "all" => app.presence = PresenceFilter::All,
+            "contacts" => app.presence = PresenceFilter::Contacts,
+            "dms" => app.presence = PresenceFilter::Dms,
+            "none" => app.presence = PresenceFilter::None,
+            _ => return false,
+        },
+        ("markdown", Some(enabled)) => app.config.render_markdown = enabled,
+        ("highlight", Some(enabled)) => app.config.syntax_highlighting = enabled,
+        ("ascii", Some(enabled)) => app.config.ascii_only = enabled,
+        ("receipts", Some(enabled)) => app.read_receipts = enabled,
+        ("activity", Some(enabled)) => app.set_activity_channel(enabled),
+        _ => return false,
+    }
+    true
+}
+
+/// Do what a normal-mode key (or its palette row) does
+fn run_action(
+    app: &mut App,
+    action: palette::Action,
+    command_tx: &mpsc::UnboundedSender<NetworkCommand>,
+) {
+    use palette::Action;
+    match action {
+        Action::Quit => app.quit(),
+        Action::EditMode => app.enter_edit_mode(),
+        Action::CommandLine => app.open_command_line(),
+        // Scroll chat
+        Action::ScrollDown => app.scroll_down(),
+        Action::ScrollUp => app.scroll_up(),
+        Action::ScrollToBottom => app.scroll_to_bottom(),
+        Action::ToggleStar => app.toggle_star_selected(),
+        // Channel navigation
+        Action::PreviousChannel => app.select_previous_channel(),
+        Action::NextChannel => app.select_next_channel(),
+        Action::OpenChannel => app.activate_selected_channel(),
+        Action::GlobalChannel => app.switch_channel("global".to_string()),
+        Action::LastChannel => app.switch_to_previous_channel(),
+        Action::ToggleFavorite => app.toggle_favorite_selected(),
+        Action::MoveChannelUp => app.move_selected_channel(true),
+        Action::MoveChannelDown => app.move_selected_channel(false),
+        // Create DM with the selected user, or type the name of anyone else
+        Action::OpenDm => match app.selected_roster_user() {
+            Some(user) => app.open_dm(user.username.clone()),
+            None => {
+                app.input = "/dm ".to_string();
+                app.enter_edit_mode();
+            }
+        },
+        // Inspect the raw wire frame of the selected message
+        Action::Inspect => app.open_inspector(),
+        // Copy the code blocks of the selected message
+        Action::CopyCode => copy_selected_code(app),
+        // Reveal or hide spoilers in the selected message
+        Action::ToggleSpoilers => app.toggle_spoilers_selected(),
+        // Send the selected message again if it did not get through
+        Action::Retry => match app.take_failed_selected() {
+            Some((content, action)) => {
+                send_message(app, content, action, command_tx);
+                app.scroll_to_bottom();
+            }
+            None => app.add_message(ChatMessage::system(app.strings.retry_none.to_string())),
+        },
+        // User selection (for DM creation)
+        Action::NextUser => app.select_next_user(),
+        Action::PreviousUser => app.select_previous_user(),
+        Action::ToggleSection => app.toggle_roster_section(),
+        Action::ToggleRoster => app.toggle_global_roster(),
+        Action::ToggleLogs => app.toggle_logs(),
+    }
+}
+
+/// Run a slash command chosen in the palette; commands that take arguments
+/// are typed into the input box instead, ready to be completed
+fn run_palette_command(
+    app: &mut App,
+    spec: &commands::CommandSpec,
+    command_tx: &mpsc::UnboundedSender<NetworkCommand>,
+) {
+    let bare = format!("/{}", spec.name);
+    if spec.usage != bare {
+        app.input = format!("{} ", bare);
+        app.enter_edit_mode();
+        return;
+    }
+    match commands::parse(&bare) {
+        Some(Ok(command)) => handle_command(app, command, command_tx),
+        Some(Err(e)) => app.add_message(ChatMessage::system(e)),
+        None => {}
+    }
+}
+
+/// Copy the code blocks of the selected message to the clipboard
+fn copy_selected_code(app: &mut App) {
+    let Some(code) = app.selected_message().and_then(|msg| codeblock::code_of(&msg.content)) else {
+        app.add_message(ChatMessage::system(app.strings.code_none.to_string()));
+        return;
+    };
+    let notice = match terminal::copy_to_clipboard(&code) {
+        Ok(()) => fill(app.strings.code_copied, &[&code.lines().count()]),
+        Err(e) => fill(app.strings.code_copy_failed, &[&e]),
+    };
+    app.add_message(ChatMessage::system(notice));
+}
+
+/// Send a chat message (or an action) we typed to the active channel
+fn send_message(
+    app: &mut App,
+    content: String,
+    action: bool,
+    command_tx: &mpsc::UnboundedSender<NetworkCommand>,
+) {
+    let channel_id = app.active_channel.clone();
+    app.autoreply.wrote(&channel_id);
+    send_to_channel(app, &channel_id, content, action, command_tx);
+}
+
+/// Send a chat message (or an action) to a channel and show it right away
+fn send_to_channel(
+    app: &mut App,
+    channel_id: &str,
+    content: String,
+    action: bool,
+    command_tx: &mpsc::UnboundedSender<NetworkCommand>,
+) {
+    if channel_id == app::STARRED_CHANNEL {
+        app.add_message_to_channel(channel_id, ChatMessage::system(app.strings.starred_read_only.to_string()));
+        return;
+    }
+    if channel_id == app::MENTIONS_CHANNEL {
+        app.add_message_to_channel(channel_id, ChatMessage::system(app.strings.mentions_read_only.to_string()));
+        return;
+    }
+    if channel_id == app::ACTIVITY_CHANNEL {
+        app.add_message_to_channel(channel_id, ChatMessage::system(app.strings.activity_read_only.to_string()));
+        return;
+    }
+    if !app.may_post(channel_id, &app.username) {
+        app.add_message_to_channel(channel_id, ChatMessage::system(app.strings.announce_read_only.to_string()));
+        return;
+    }
+    if let Some(wait) = app.slow_mode_wait(channel_id) {
+        app.add_message_to_channel(channel_id, ChatMessage::system(fill(app.strings.slow_mode_wait, &[&wait])));
+        return;
+    }
+    if app.is_downgraded(channel_id) {
+        app.confirm_downgrade(channel_id, content, action);
+        return;
+    }
+
+    let channel_id = channel_id.to_string();
+    let id = uuid::Uuid::new_v4().to_string();
+    app.last_sent.insert(channel_id.clone(), Instant::now());
+
+    // DMs go through the encryption layer, which may hold them until the
+    // session is set up; passphrase rooms are sealed with the room key.
+    // Either way the sealed text is padded if `[padding]` is on
+    let padded = app.config.padding.pad(&content);
+    let mut queued = None;
+    let mut rotating = None;
+    let mut failed = None;
+    if let Some(peer) = app.dm_peer(&channel_id).map(str::to_string) {
+        if app.e2e.send(&channel_id, &peer, id.clone(), &padded, action) {
+            queued = Some(peer.clone());
+        }
+        // Sending may have taken the session past its [rekey] limits
+        if !app.e2e.take_rotated().is_empty() {
+            rotating = Some(peer);
+        }
+    } else {
+        let payload = match app.seal_for_room(&channel_id, &padded) {
+            Some(Ok(payload)) => Some((payload, true)),
+            Some(Err(e)) => {
+                warn!("Cannot encrypt for {}: {}", channel_id, e);
+                failed = Some(e.to_string());
+                None
+            }
+            None => Some((content.clone(), false)),
+        };
+        if let Some((payload, encrypted)) = payload {
+            let _ = command_tx.send(NetworkCommand::SendMessage {
+                id: id.clone(),
+                content: payload,
+                channel_id: channel_id.clone(),
+                encrypted,
+                action,
+            });
+        }
+    }
+
+    // Add to local chat immediately (optimistic update)
+    let mut msg = ChatMessage::new(app.username.clone(), content, false);
+    msg.id = Some(id);
+    msg.action = action;
+    if failed.is_some() {
+        msg.delivery = Delivery::Failed;
+    }
+    app.add_message_to_channel(&channel_id, msg);
+    if let Some(peer) = queued {
+        let notice = if app.e2e.identity_changed(&peer) {
+            fill(app.strings.e2e_held, &[&peer])
+        } else {
+            fill(app.strings.e2e_pending, &[&peer])
+        };
+        app.add_message_to_channel(&channel_id, ChatMessage::system(notice));
+    }
+    if let Some(peer) = rotating {
+        app.add_message_to_channel(&channel_id, ChatMessage::system(fill(app.strings.rekey_scheduled, &[&peer])));
+    }
+    if let Some(reason) = failed {
+        app.add_message_to_channel(&channel_id, ChatMessage::system(fill(app.strings.send_failed, &[&reason])));
+    }
+
+    // Update telemetry
+    app.telemetry.record_sent(&channel_id);
+}
+
+/// Send a message of padding only to a random encrypted channel; receivers
+/// drop it, and the relay cannot tell it from a real one
+fn send_cover(app: &mut App, command_tx: &mpsc::UnboundedSender<NetworkCommand>) {
+    let Some(channel_id) = padding::pick(app.cover_channels()) else {
+        return;
+    };
+    let id = uuid::Uuid::new_v4().to_string();
+    let padding = app.config.padding.pad("");
+    if let Some(peer) = app.dm_peer(&channel_id).map(str::to_string) {
+        app.e2e.send(&channel_id, &peer, id, &padding, false);
+    } else if let Some(Ok(payload)) = app.seal_for_room(&channel_id, &padding) {
+        let _ = command_tx.send(NetworkCommand::SendMessage {
+            id,
+            content: payload,
+            channel_id,
+            encrypted: true,
+            action: false,
+        });
+    }
+}
+
+/// Execute a parsed slash command
+fn handle_command(
+    app: &mut App,
+    command: Command,
+    command_tx: &mpsc::UnboundedSender<NetworkCommand>,
+) {
+    match command {
+        Command::Help => {
+            for spec in commands::COMMANDS {
+                app.add_message(ChatMessage::system(format!(
+                    "{} - {}",
+                    spec.usage, spec.description
+                )));
+            }
+        }
+        Command::Export { format, since, out } => {
+            let Some(channel) = app.channels.get(&app.active_channel) else {
+                return;
+            };
+            let status = match export::export_channel(channel, format, since, out.as_deref()) {
+                Ok(path) => fill(app.strings.export_done, &[&app.channel_name(channel), &path.display()]),
+                Err(e) => fill(app.strings.export_failed, &[&e]),
+            };
+            app.add_message(ChatMessage::system(status));
+        }
+        Command::Purge { channel, older_than } => {
+            let channel_id = match &channel {
+                Some(spec) => app.find_channel(spec),
+                None => Some(app.active_channel.clone()),
+            };
+            match channel_id {
+                Some(channel_id) => app.purge_history(&channel_id, older_than),
+                None => {
+                    let notice = fill(app.strings.purge_unknown_channel, &[&channel.unwrap_or_default()]);
+                    app.add_message(ChatMessage::system(notice));
+                }
+            }
+        }
+        Command::Nick(new_username) => {
+            let _ = command_tx.send(NetworkCommand::Rename { new_username });
+        }
+        Command::Alias { username, alias } => {
+            let status = match alias {
+                Some(alias) => {
+                    let status = fill(app.strings.alias_set, &[&username, &alias]);
+                    app.aliases.set(&username, alias);
+                    status
+                }
+                None => {
+                    app.aliases.remove(&username);
+                    fill(app.strings.alias_cleared, &[&username])
+                }
+            };
+            match app.aliases.save() {
+                Ok(()) => app.add_message(ChatMessage::system(status)),
+                Err(e) => app.add_message(ChatMessage::system(fill(app.strings.alias_save_failed, &[&e]))),
+            }
+        }
+        Command::Pin { pinned } => {
+            let Some(msg) = app.selected_message() else {
+                return;
+            };
+            let Some(message_id) = msg.id.clone() else {
+                app.add_message(ChatMessage::system(app.strings.pin_needs_id.to_string()));
+                return;
+            };
+            let channel_id = app.active_channel.clone();
+            let changed = app
+                .channels
+                .get_mut(&channel_id)
+                .is_some_and(|channel| channel.set_pinned(&message_id, pinned));
+            if changed {
+                let _ = command_tx.send(NetworkCommand::SetPin { channel_id, message_id, pinned });
+            }
+        }
+        Command::Pins => app.open_pins(),
+        Command::Search(text) => app.open_search(text),
+        Command::System => app.toggle_system_messages(),
+        Command::Who => {
+            let _ = command_tx.send(NetworkCommand::RequestRoster);
+        }
+        Command::Telemetry(Some(path)) => {
+            let notice = match metrics::MetricsWriter::open(&path) {
+                Ok(writer) => {
+                    app.metrics = Some(writer);
+                    fill(
+                        app.strings.metrics_started,
+                        &[&path.display(), &metrics::METRICS_INTERVAL.as_secs()],
+                    )
+                }
+                Err(e) => fill(app.strings.metrics_failed, &[&e]),
+            };
+            app.add_message(ChatMessage::system(notice));
+        }
+        Command::Telemetry(None) => {
+            if let Some(writer) = app.metrics.take() {
+                let notice = fill(app.strings.metrics_stopped, &[&writer.path().display()]);
+                app.add_message(ChatMessage::system(notice));
+            }
+        }
+        Command::Dnd(request) => {
+            app.dnd = match request {
+                DndRequest::Toggle if app.dnd.is_on() => DoNotDisturb::Off,
+                DndRequest::Toggle => DoNotDisturb::On { until: None },
+                DndRequest::For(duration) => DoNotDisturb::On {
+                    until: Some(chrono::Utc::now() + duration),
+                },
+                DndRequest::Off => DoNotDisturb::Off,
+            };
+            let notice = match app.dnd {
+                DoNotDisturb::Off => app.strings.dnd_off.to_string(),
+                DoNotDisturb::On { until: None } => app.strings.dnd_on.to_string(),
+                DoNotDisturb::On { until: Some(until) } => {
+                    fill(app.strings.dnd_on_until, &[&until.format("%H:%M")])
+                }
+            };
+            app.add_message(ChatMessage::system(notice));
+        }
+        Command::Remind { after, text } => {
+            let due = chrono::Utc::now() + after;
+            app.reminders.add(reminders::Reminder {
+                channel: app.active_channel.clone(),
+                text,
+                due,
+            });
+            let notice = match app.reminders.save() {
+                Ok(()) => fill(app.strings.reminder_set, &[&due.format("%Y-%m-%d %H:%M")]),
+                Err(e) => fill(app.strings.reminder_save_failed, &[&e]),
+            };
+            app.add_message(ChatMessage::system(notice));
+        }
+        Command::AutoReply(AutoReplyRequest::List) => {
+            let state = if app.autoreply.enabled {
+                app.strings.autoreply_on
+            } else {
+                app.strings.autoreply_off
+            };
+            app.add_message(ChatMessage::system(state.to_string()));
+            let rules: Vec<String> = app
+                .autoreply
+                .rules()
+                .enumerate()
+                .map(|(i, (rule, added))| {
+                    let mut line = format!("{}. {} -> {}", i + 1, rule.pattern, rule.reply);
+                    if rule.dnd_only {
+                        line.push_str(app.strings.autoreply_dnd_only);
+                    }
+                    if !added {
+                        line.push_str(app.strings.autoreply_from_config);
+                    }
+                    line
+                })
+                .collect();
+            if rules.is_empty() {
+                app.add_message(ChatMessage::system(app.strings.autoreply_none.to_string()));
+            }
+            for line in rules {
+                app.add_message(ChatMessage::system(line));
+            }
+        }
+        Command::AutoReply(AutoReplyRequest::Enable(enabled)) => {
+            app.autoreply.enabled = enabled;
+            let notice = if enabled {
+                app.strings.autoreply_on
+            } else {
+                app.strings.autoreply_off
+            };
+            app.add_message(ChatMessage::system(notice.to_string()));
+        }
+        Command::AutoReply(AutoReplyRequest::Add(rule)) => {
+            let pattern = rule.pattern.clone();
+            let notice = match app.autoreply.add(rule) {
+                Ok(()) => fill(app.strings.autoreply_added, &[&pattern]),
+                Err(e) => fill(app.strings.autoreply_failed, &[&e]),
+            };
+            app.add_message(ChatMessage::system(notice));
+        }
+        Command::AutoReply(AutoReplyRequest::Remove(index)) => {
+            let notice = match app.autoreply.remove(index) {
+                Ok(rule) => fill(app.strings.autoreply_removed, &[&rule.pattern]),
+                Err(e) => fill(app.strings.autoreply_failed, &[&e]),
+            };
+            app.add_message(ChatMessage::system(notice));
+        }
+        Command::Receipts(enabled) => {
+            app.read_receipts = enabled.unwrap_or(!app.read_receipts);
+            let notice = if app.read_receipts {
+                app.strings.receipts_on
+            } else {
+                app.strings.receipts_off
+            };
+            app.add_message(ChatMessage::system(notice.to_string()));
+        }
+        Command::Presence(filter) => {
+            if let Some(filter) = filter {
+                app.presence = filter;
+            }
+            let shown = match app.presence {
+                PresenceFilter::All => app.strings.presence_all,
+                PresenceFilter::Contacts => app.strings.presence_contacts,
+                PresenceFilter::Dms => app.strings.presence_dms,
+                PresenceFilter::None => app.strings.presence_none,
+            };
+            app.add_message(ChatMessage::system(fill(app.strings.presence_filter, &[&shown])));
+        }
+        Command::Rekey => {
+            let channel_id = app.active_channel.clone();
+            let notice = match app.dm_peer(&channel_id).map(str::to_string) {
+                Some(peer) => {
+                    app.e2e.rekey(&channel_id, &peer);
+                    fill(app.strings.rekey_started, &[&peer])
+                }
+                None => app.strings.rekey_not_dm.to_string(),
+            };
+            app.add_message(ChatMessage::system(notice));
+        }
+        Command::Verify(user) => {
+            if let Err(notice) = app.start_verification(&user) {
+                app.add_message(ChatMessage::system(notice));
+            }
+        }
+        Command::Trust(user) => {
+            let notice = if app.e2e.accept_identity(&user) {
+                fill(app.strings.trust_accepted, &[&user])
+            } else {
+                fill(app.strings.trust_unchanged, &[&user])
+            };
+            app.add_message(ChatMessage::system(notice));
+        }
+        Command::Dm(user) if user == app.username => {
+            app.add_message(ChatMessage::system(app.strings.dm_self.to_string()));
+        }
+        Command::Dm(user) => app.open_dm(user),
+        Command::Whois(user) => {
+            app.open_profile(user.clone());
+            if app.server_info.as_ref().is_some_and(|info| info.supports("whois")) {
+                let _ = command_tx.send(NetworkCommand::Whois { username: user });
+            } else {
+                app.add_message(ChatMessage::system(app.strings.whois_unsupported.to_string()));
+            }
+        }
+        Command::Join { name, passphrase } => {
+            // Rooms live on the relay of the active channel
+            let channel_id = match relays::split_channel(&app.active_channel) {
+                (Some(relay), _) => format!("{}/room:{}", relay, name),
+                (None, _) => format!("room:{}", name),
+            };
+            if passphrase {
+                app.passphrase_prompt = Some(channel_id);
+                app.add_message(ChatMessage::system(fill(app.strings.room_passphrase_prompt, &[&name])));
+            } else {
+                app.join_room(channel_id, name.clone(), None);
+                app.add_message(ChatMessage::system(fill(app.strings.room_joined, &[&name])));
+            }
+        }
+        Command::CreateGroup { name, members } => {
+            // Groups live on the relay of the active channel
+            let channel_id = match relays::split_channel(&app.active_channel) {
+                (Some(relay), _) => format!("{}/group:{}", relay, name),
+                (None, _) => format!("group:{}", name),
+            };
+            match app.create_group(channel_id, name.clone(), members) {
+                Ok((channel_id, event)) => {
+                    let _ = command_tx.send(NetworkCommand::Group { channel_id, event });
+                    app.add_message(ChatMessage::system(fill(app.strings.group_created, &[&name])));
+                }
+                Err(e) => app.add_message(ChatMessage::system(fill(app.strings.group_rejected, &[&e]))),
+            }
+        }
+        Command::Moderate(action) => {
+            let Some(info) = app.active_group() else {
+                app.add_message(ChatMessage::system(app.strings.group_not_active.to_string()));
+                return;
+            };
+            let event = match action {
+                GroupCommand::Invite(user) => {
+                    // The invitee needs the group as it will be after the invite
+                    let mut group = info.clone();
+                    group.members.insert(user.clone());
+                    GroupEvent::Invite { user, group }
+                }
+                GroupCommand::Kick(user) => GroupEvent::Kick { user },
+                GroupCommand::Topic(topic) => GroupEvent::Topic { topic },
+                GroupCommand::SlowMode(secs) => GroupEvent::SlowMode { secs },
+                GroupCommand::Announce(on) => GroupEvent::Announce { on },
+                GroupCommand::Announcer { user, allowed } => GroupEvent::Announcer { user, allowed },
+            };
+            // Apply locally first: the relay does not echo our frames back
+            let channel_id = app.active_channel.clone();
+            let username = app.username.clone();
+            match app.apply_group_event(&channel_id, &username, &event) {
+                Ok(notice) => {
+                    let _ = command_tx.send(NetworkCommand::Group { channel_id, event });
+                    if let Some(notice) = notice {
+                        app.add_message(ChatMessage::system(notice));
+                    }
+                }
+                Err(e) => app.add_message(ChatMessage::system(fill(app.strings.group_rejected, &[&e]))),
+            }
+        }
+        Command::Roll(dice) => {
+            let (rolls, total) = dice.roll();
+            let result = fun::describe_roll(&dice, &rolls, total);
+            send_message(app, fill(app.strings.roll_action, &[&dice, &result]), true, command_tx);
+        }
+        Command::Flip => {
+            let side = if fun::flip() { app.strings.coin_heads } else { app.strings.coin_tails };
+            send_message(app, fill(app.strings.flip_action, &[&side]), true, command_tx);
+        }
+        Command::Shrug => {
+            send_message(app, fill(app.strings.shrug_action, &[&fun::SHRUG]), true, command_tx);
+        }
+    }
+}
+
+/// Derive a room key from the passphrase typed at the prompt and join the room
+fn join_with_passphrase(app: &mut App, channel_id: String, passphrase: Zeroizing<String>) {
+    let room_id = relays::split_channel(&channel_id).1;
+    let name = room_id.trim_start_matches("room:").to_string();
+    if passphrase.is_empty() {
+        app.add_message(ChatMessage::system(app.strings.room_passphrase_empty.to_string()));
+        return;
+    }
+    match crypto::room_key(&passphrase, room_id) {
+        Ok(key) => {
+            app.join_room(channel_id, name.clone(), Some(key));
+            app.add_message(ChatMessage::system(fill(app.strings.room_joined_encrypted, &[&name])));
+        }
+        Err(e) => app.add_message(ChatMessage::system(fill(app.strings.error, &[&e]))),
+    }
+}
+
+/// Format a clock skew as a signed, human-readable duration (e.g. "+2h 5m")
+fn format_skew(skew: chrono::Duration) -> String {
+    let sign = if skew < chrono::Duration::zero() { '-' } else { '+' };
+    let secs = skew.num_seconds().unsigned_abs();
+    match secs {
+        0..=59 => format!("{}{}s", sign, secs),
+        60..=3599 => format!("{}{}m {}s", sign, secs / 60, secs % 60),
+        _ => format!("{}{}h {}m", sign, secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Banner text when this client and the relay that sent `info` may not work
+/// together
+fn compat_warning(app: &App, info: &ghostwire_protocol::hello::ServerInfo) -> Option<String> {
+    use ghostwire_protocol::hello::{Incompatibility, PROTOCOL_VERSION};
+
+    let version = env!("CARGO_PKG_VERSION");
+    Some(match info.compatibility(version)? {
+        Incompatibility::ClientTooOld { minimum } => {
+            fill(app.strings.compat_client_too_old, &[&info.name, &minimum, &version])
+        }
+        Incompatibility::NewerProtocol { relay } => fill(
+            app.strings.compat_newer_protocol,
+            &[&info.name, &relay.to_string(), &PROTOCOL_VERSION.to_string()],
+        ),
+    })
+}
+
+/// Notice for a network error, followed by what to do about it when its
+/// class has a usual fix
+fn error_notice(app: &App, error: &NetworkError) -> String {
+    let hint = match error {
+        NetworkError::Dns { .. } => app.strings.error_hint_dns,
+        NetworkError::Tls { .. } => app.strings.error_hint_tls,
+        NetworkError::Handshake { .. } => app.strings.error_hint_handshake,
+        NetworkError::AuthRejected { .. } => app.strings.error_hint_auth,
+        NetworkError::SendTimeout { .. } => app.strings.error_hint_send_timeout,
+        NetworkError::Connect { .. } | NetworkError::Protocol { .. } | NetworkError::Send { .. } => {
+            return fill(app.strings.error, &[error]);
+        }
+    };
+    format!("{} — {}", fill(app.strings.error, &[error]), hint)
+}
+
 /// Handle network events from the async task
 fn handle_network_event(app: &mut App, event: NetworkEvent) {
     match event {
-        NetworkEvent::Connected => {
-            app.set_connected(true);
+        NetworkEvent::ConnectedTo { url } => {
+            app.server_url = Some(url);
+            app.set_connection(ConnectionState::Connected);
+        }
+        NetworkEvent::Reconnecting { attempt, retry_in_ms } => {
+            let next_retry = chrono::Utc::now() + chrono::Duration::milliseconds(retry_in_ms as i64);
+            app.set_connection(ConnectionState::Reconnecting { attempt, next_retry });
         }
-        NetworkEvent::Disconnected => {
-            app.set_connected(false);
+        NetworkEvent::Disconnected { reason } => {
+            app.set_connection(ConnectionState::Disconnected { reason });
         }
-        NetworkEvent::Message { sender, content, timestamp, channel_id } => {
+        NetworkEvent::Closed { reason } => {
+            app.add_message_to_channel("global", ChatMessage::system(fill(app.strings.closed, &[&reason])));
+        }
+        NetworkEvent::Kicked { reason } => {
+            app.add_message_to_channel("global", ChatMessage::system(fill(app.strings.kicked, &[&reason])));
+            if app.active_channel != "global" {
+                app.add_message(ChatMessage::system(fill(app.strings.kicked, &[&reason])));
+            }
+            app.set_connection(ConnectionState::Kicked { reason });
+        }
+        NetworkEvent::Message { id, seq, sender, content, timestamp, channel_id, raw, sanitized, delayed, encrypted, action } => {
+            // A jump in the sender's sequence in this channel means frames
+            // were lost in between. Recorded before anything below drops the
+            // message, since a dropped frame still arrived. Messages the relay
+            // held for us arrive out of order, so they only move the sequence on.
+            if let Some(seq) = seq {
+                let blocked = app.blocked.contains(&sender);
+                let missed = app.record_seq(&sender, &channel_id, seq).filter(|_| !delayed && !blocked);
+                if let Some(missed) = missed {
+                    let notice = fill(app.strings.missed_messages, &[&missed, &sender]);
+                    app.add_message_to_channel(&channel_id, ChatMessage::system(notice));
+                }
+            }
+            
+            // Reconnects and replays can deliver the same message twice
+            if let Some(id) = &id {
+                if app.is_duplicate(&channel_id, id) {
+                    debug!("Dropping duplicate message {} in {}", id, channel_id);
+                    return;
+                }
+            }
+            
+            if app.blocked.contains(&sender) {
+                debug!("Dropping message from blocked user {}", sender);
+                return;
+            }
+            
+            // Groups only take messages from their members (and in
+            // announcement mode only from operators and announcers). The
+            // relay binds the sender to the connection that sent the frame,
+            // so nobody can post under an operator's name.
+            if !app.may_post(&channel_id, &sender) {
+                debug!("Dropping message from {} who may not post in {}", sender, channel_id);
+                return;
+            }
+            
+            // Encrypted DMs only count from the DM partner; the plaintext was
+            // never seen by the network layer, so it is sanitized here
+            let room = relays::split_channel(&channel_id).1.starts_with("room:");
+            // Decryption failures are shown as plain notices, never as actions
+            let mut readable = true;
+            let (content, sanitized, action) = if encrypted && room {
+                match app.open_room_message(&channel_id, &sender, &content) {
+                    Ok(plaintext) => {
+                        let clean = sanitize::sanitize_multiline(padding::unpad(&plaintext));
+                        (clean.text, sanitized || clean.modified, action)
+                    }
+                    Err(e) if replay::is_replay(&e) => {
+                        warn!("Dropping room message from {} in {}: {}", sender, channel_id, e);
+                        app.telemetry.replays_dropped += 1;
+                        return;
+                    }
+                    Err(e) => {
+                        debug!("Cannot decrypt room message from {}: {}", sender, e);
+                        readable = false;
+                        (app.strings.room_wrong_passphrase.to_string(), sanitized, false)
+                    }
+                }
+            } else if encrypted {
+                if app.dm_peer(&channel_id) != Some(sender.as_str()) {
+                    debug!("Dropping encrypted message from {} in {}", sender, channel_id);
+                    return;
+                }
+                // The message may complete a handshake that shows a new key
+                let changed_before = app.e2e.identity_changed(&sender);
+                match app.open_envelope(&channel_id, &content) {
+                    Ok(plaintext) => {
+                        if !changed_before && app.e2e.identity_changed(&sender) {
+                            let notice = fill(app.strings.e2e_identity_changed, &[&sender]);
+                            app.add_message_to_channel(&channel_id, ChatMessage::system(notice));
+                        }
+                        let clean = sanitize::sanitize_multiline(padding::unpad(&plaintext));
+                        (clean.text, sanitized || clean.modified, action)
+                    }
+                    Err(e) if replay::is_replay(&e) => {
+                        warn!("Dropping DM from {}: {}", sender, e);
+                        app.telemetry.replays_dropped += 1;
+                        return;
+                    }
+                    Err(e) => {
+                        warn!("Cannot decrypt message from {}: {}", sender, e);
+                        readable = false;
+                        (app.strings.e2e_undecryptable.to_string(), sanitized, false)
+                    }
+                }
+            } else {
+                (content, sanitized, action)
+            };
+            
+            // Cover traffic: padding only. DMs still get their DELIVERED
+            // receipt, so the relay sees the same frames as for a real one
+            if encrypted && readable && content.is_empty() {
+                debug!("Dropping cover message from {} in {}", sender, channel_id);
+                if let Some(id) = &id {
+                    app.queue_delivered(&channel_id, &sender, id);
+                }
+                return;
+            }
+            
             // Convert Unix timestamp to DateTime
+            let server_now = app.server_now();
             let datetime = chrono::DateTime::from_timestamp(timestamp, 0)
-                .unwrap_or_else(Utc::now);
+                .unwrap_or(server_now);
             
-            // Create message with actual timestamp
-            let mut msg = ChatMessage::new(sender.clone(), content, false);
-            msg.timestamp = datetime;
+            // A sender clock far from the relay's would misplace the message,
+            // so fall back to receive time and warn once per sender. Held
+            // messages are old by design and keep their timestamp.
+            let skew = datetime - server_now;
+            let clock_skewed = !delayed && skew.num_seconds().abs() > MAX_CLOCK_SKEW_SECS;
+            if clock_skewed && app.skew_warned.insert(sender.clone()) {
+                let notice = fill(app.strings.clock_skew, &[&sender, &format_skew(skew)]);
+                app.add_message_to_channel(&channel_id, ChatMessage::system(notice));
+            }
             
-            // Add user to roster if not already there (for user discovery)
-            if !app.users.iter().any(|u| u.username == sender) && sender != app.username {
-                app.add_user(User::new(sender.clone()));
+            // Mentions, watch words and DMs from others get a bell or sound,
+            // unless DND is on
+            let trigger = notify::trigger(&app.config.notifications, &app.username, &channel_id, &content);
+            if let Some(trigger) = trigger.filter(|_| sender != app.username && !app.dnd.is_on()) {
+                let from = app.display_name(&sender);
+                let title = match trigger {
+                    notify::Trigger::Mention => fill(app.strings.notify_mention, &[&from]),
+                    notify::Trigger::DirectMessage => fill(app.strings.notify_dm, &[&from]),
+                    notify::Trigger::WatchWord(word) => fill(app.strings.notify_watch_word, &[&from, &word]),
+                };
+                notify::alert(&app.config.notifications, &title, &content);
+            }
+            
+            // DMs from our partner may match an auto-reply rule
+            if readable && app.dm_peer(&channel_id) == Some(sender.as_str()) {
+                if let Some(action) = app.autoreply.check(&channel_id, &content, app.dnd.is_on(), Instant::now()) {
+                    app.pending_autoreplies.push((channel_id.clone(), action));
+                }
             }
             
-            // Route to the correct channel
+            // Create message with actual timestamp
+            let mut msg = ChatMessage::new(sender.clone(), content, false);
+            msg.id = id;
+            msg.timestamp = if clock_skewed { server_now } else { datetime };
+            // Our own messages replayed by a daemon have no wire frame
+            msg.raw = (!raw.is_empty()).then_some(raw);
+            msg.sanitized = sanitized;
+            msg.clock_skewed = clock_skewed;
+            msg.starred = app.starred.contains(&msg);
+            msg.verified = encrypted && !room && app.e2e.is_verified(&sender);
+            msg.action = action;
+            msg.highlighted = readable
+                && sender != app.username
+                && notify::highlights(&app.config.notifications, &app.username, &msg.content);
+            
+            // Route to the correct channel (creating DM channels on first use),
+            // with a copy of mentions in the mentions channel
+            let receipt_id = msg.id.clone();
+            let mention = msg.highlighted.then(|| msg.clone());
             app.add_message_to_channel(&channel_id, msg);
-            app.telemetry.messages_received += 1;
+            if let Some(mention) = mention {
+                app.collect_mention(&channel_id, &mention);
+            }
+            // Remembered so we do not answer in plaintext without asking
+            if encrypted && app.channels.contains_key(&channel_id) {
+                app.encrypted_before.insert(channel_id.clone());
+            }
+            if let Some(id) = &receipt_id {
+                app.queue_delivered(&channel_id, &sender, id);
+            }
+            app.telemetry.record_received(&channel_id);
             
             // Update user activity
             app.update_user_activity(&sender);
         }
         NetworkEvent::UserJoined { username } => {
+            app.e2e.peer_online(&username);
             app.add_user(User::new(username));
         }
+        NetworkEvent::KeyExchange { channel_id, sender, exchange } => {
+            if app.blocked.contains(&sender) {
+                debug!("Dropping key exchange from blocked user {}", sender);
+                return;
+            }
+            if app.dm_peer(&channel_id) != Some(sender.as_str()) {
+                debug!("Dropping key exchange from {} in {}", sender, channel_id);
+                return;
+            }
+            match app.e2e.handle_key_exchange(&channel_id, &sender, exchange) {
+                Ok(outcome) => {
+                    if outcome.identity_changed {
+                        let notice = fill(app.strings.e2e_identity_changed, &[&sender]);
+                        app.add_message_to_channel(&channel_id, ChatMessage::system(notice));
+                    }
+                    if outcome.established {
+                        let notice = if outcome.rotated {
+                            fill(app.strings.e2e_rotated, &[&sender])
+                        } else {
+                            fill(app.strings.e2e_established, &[&sender])
+                        };
+                        app.add_message_to_channel(&channel_id, ChatMessage::system(notice));
+                    }
+                }
+                Err(e) => {
+                    warn!("Key exchange with {} failed: {}", sender, e);
+                    let notice = fill(app.strings.e2e_failed, &[&sender]);
+                    app.add_message_to_channel(&channel_id, ChatMessage::system(notice));
+                }
+            }
+        }
         NetworkEvent::UserLeft { username } => {
             app.remove_user(&username);
         }
         NetworkEvent::SystemMessage { content } => {
             app.add_message(ChatMessage::system(content));
         }
-        NetworkEvent::Error { message } => {
-            app.add_message(ChatMessage::system(format!("Error: {}", message)));
+        NetworkEvent::ServerHello { info } => {
+            let features = if info.features.is_empty() {
+                "-".to_string()
+            } else {
+                info.features.join(", ")
+            };
+            let notice = fill(app.strings.connected_to, &[&info.name, &info.version, &features]);
+            app.add_message(ChatMessage::system(notice));
+            if let Some(warning) = compat_warning(app, &info) {
+                app.add_message(ChatMessage::compat(warning));
+            }
+            app.server_info = Some(info);
+        }
+        NetworkEvent::Motd { lines } => {
+            app.add_message_to_channel("global", ChatMessage::motd(lines));
+        }
+        NetworkEvent::Whois { info } => {
+            app.whois = Some(info);
         }
+        NetworkEvent::DeliveryNotice { channel_id, message_id, queued, notice } => {
+            if let Some(id) = &message_id {
+                let delivery = if queued { Delivery::Queued } else { Delivery::Undelivered };
+                app.mark_delivery(&channel_id, id, delivery);
+            }
+            app.add_message_to_channel(&channel_id, ChatMessage::system(notice));
+        }
+        NetworkEvent::UserRenamed { old, new } => {
+            app.rename_user(&old, &new);
+        }
+        NetworkEvent::Receipt { channel_id, sender, message_id, read } => {
+            app.apply_receipt(&channel_id, &sender, &message_id, read);
+        }
+        NetworkEvent::Group { channel_id, sender, event } => {
+            match app.apply_group_event(&channel_id, &sender, &event) {
+                Ok(Some(notice)) => app.add_message_to_channel(&channel_id, ChatMessage::system(notice)),
+                Ok(None) => {}
+                Err(e) => warn!("Dropping group event in {} from {}: {}", channel_id, sender, e),
+            }
+        }
+        NetworkEvent::PinChanged { channel_id, message_id, by, pinned } => {
+            let changed = app
+                .channels
+                .get_mut(&channel_id)
+                .is_some_and(|channel| channel.set_pinned(&message_id, pinned));
+            if changed {
+                let template = if pinned { app.strings.pinned_by } else { app.strings.unpinned_by };
+                let notice = fill(template, &[&app.display_name(&by)]);
+                app.add_message_to_channel(&channel_id, ChatMessage::system(notice));
+            }
+        }
+        NetworkEvent::Roster { usernames } => {
+            app.set_roster(usernames);
+        }
+        NetworkEvent::Latency { ms } => {
+            app.update_latency(ms);
+        }
+        NetworkEvent::ClockOffset { ms } => {
+            app.clock_offset_ms = ms;
+        }
+        NetworkEvent::SendFailed { channel_id, message_id, reason } => {
+            app.mark_delivery(&channel_id, &message_id, Delivery::Failed);
+            let notice = fill(app.strings.send_failed, &[&reason]);
+            app.add_message_to_channel(&channel_id, ChatMessage::system(notice));
+        }
+        NetworkEvent::Error { error } => {
+            let notice = error_notice(app, &error);
+            app.add_message(ChatMessage::system(notice));
+        }
+        NetworkEvent::FromRelay { relay, event } => handle_relay_event(app, &relay, *event),
     }
 }
+
+/// Handle an event from an extra relay. Chat traffic lands in the relay's
+/// namespaced channels and status goes to its global channel; roster,
+/// latency and clock data keep tracking the primary relay only.
+fn handle_relay_event(app: &mut App, relay: &str, event: NetworkEvent) {
+    let global = format!("{}/global", relay);
+    let notice = match event {
+        NetworkEvent::Message { id, sender, content, timestamp, channel_id, raw, sanitized, delayed, encrypted, action, .. } => {
+            // Sequence numbers are tracked per sender name, which relays do not share
+            handle_network_event(app, NetworkEvent::Message {
+                id,
+                seq: None,
+                sender,
+                content,
+                timestamp,
+                channel_id: format!("{}/{}", relay, channel_id),
+                raw,
+                sanitized,
+                delayed,
+                encrypted,
+                action,
+            });
+            return;
+        }
+        NetworkEvent::KeyExchange { channel_id, sender, exchange } => {
+            handle_network_event(app, NetworkEvent::KeyExchange {
+                channel_id: format!("{}/{}", relay, channel_id),
+                sender,
+                exchange,
+            });
+            return;
+        }
+        NetworkEvent::DeliveryNotice { channel_id, message_id, queued, notice } => {
+            handle_network_event(app, NetworkEvent::DeliveryNotice {
+                channel_id: format!("{}/{}", relay, channel_id),
+                message_id,
+                queued,
+                notice,
+            });
+            return;
+        }
+        NetworkEvent::SendFailed { channel_id, message_id, reason } => {
+            handle_network_event(app, NetworkEvent::SendFailed {
+                channel_id: format!("{}/{}", relay, channel_id),
+                message_id,
+                reason,
+            });
+            return;
+        }
+        NetworkEvent::Receipt { channel_id, sender, message_id, read } => {
+            handle_network_event(app, NetworkEvent::Receipt {
+                channel_id: format!("{}/{}", relay, channel_id),
+                sender,
+                message_id,
+                read,
+            });
+            return;
+        }
+        NetworkEvent::Group { channel_id, sender, event } => {
+            handle_network_event(app, NetworkEvent::Group {
+                channel_id: format!("{}/{}", relay, channel_id),
+                sender,
+                event,
+            });
+            return;
+        }
+        NetworkEvent::PinChanged { channel_id, message_id, by, pinned } => {
+            handle_network_event(app, NetworkEvent::PinChanged {
+                channel_id: format!("{}/{}", relay, channel_id),
+                message_id,
+                by,
+                pinned,
+            });
+            return;
+        }
+        NetworkEvent::Motd { lines } => {
+            app.add_message_to_channel(&global, ChatMessage::motd(lines));
+            return;
+        }
+        NetworkEvent::ConnectedTo { url } => fill(app.strings.relay_connected, &[&relay, &url]),
+        NetworkEvent::Disconnected { reason } => fill(app.strings.relay_disconnected, &[&relay, &reason]),
+        NetworkEvent::Kicked { reason } => fill(app.strings.relay_kicked, &[&relay, &reason]),
+        NetworkEvent::Closed { reason } => fill(app.strings.relay_closed, &[&relay, &reason]),
+        NetworkEvent::ServerHello { info } => {
+            if let Some(warning) = compat_warning(app, &info) {
+                app.add_message_to_channel(&global, ChatMessage::compat(warning));
+            }
+            fill(app.strings.connected_to, &[&info.name, &info.version, &info.features.join(", ")])
+        }
+        NetworkEvent::SystemMessage { content } => content,
+        NetworkEvent::Error { error } => error_notice(app, &error),
+        _ => return,
+    };
+    app.add_message_to_channel(&global, ChatMessage::system(notice));
+}
@@ -0,0 +1,99 @@
+// GhostWire Client - Inline Markdown
+// The small subset of Markdown people type in chat: *bold* (or **bold**),
+// _italic_, `code`, ||spoilers|| and "> " quote lines. Markers are only
+// recognized where they open and close a span, so snake_case names and lone
+// asterisks stay as typed.
+
+/// How a stretch of text is emphasized
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Emphasis {
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+    /// Hidden until the reader reveals the message
+    pub spoiler: bool,
+}
+
+/// A line with its quote marker, if any, split off
+pub fn quote(line: &str) -> Option<&str> {
+    line.strip_prefix("> ").or_else(|| (line == ">").then_some(""))
+}
+
+/// Split a line into emphasized spans, dropping the markers. Without
+/// `formatting` only spoilers are recognized, since showing raw text must not
+/// give them away.
+pub fn inline(line: &str, formatting: bool) -> Vec<(Emphasis, &str)> {
+    let mut spans = Vec::new();
+    parse(line, Emphasis::default(), formatting, &mut spans);
+    spans
+}
+
+fn parse<'a>(text: &'a str, emphasis: Emphasis, formatting: bool, spans: &mut Vec<(Emphasis, &'a str)>) {
+    let mut plain_start = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let span = if rest.starts_with("||") && !emphasis.spoiler {
+            closing(text, i, "||", false).map(|end| (end, "||", Emphasis { spoiler: true, ..emphasis }))
+        } else if !formatting {
+            None
+        } else if rest.starts_with('`') {
+            closing(text, i, "`", false).map(|end| (end, "`", Emphasis { code: true, ..emphasis }))
+        } else if rest.starts_with("**") && !emphasis.bold {
+            closing(text, i, "**", false).map(|end| (end, "**", Emphasis { bold: true, ..emphasis }))
+        } else if rest.starts_with('*') && !emphasis.bold {
+            closing(text, i, "*", false).map(|end| (end, "*", Emphasis { bold: true, ..emphasis }))
+        } else if rest.starts_with('_') && !emphasis.italic && !after_word(text, i) {
+            closing(text, i, "_", true).map(|end| (end, "_", Emphasis { italic: true, ..emphasis }))
+        } else {
+            None
+        };
+
+        let Some((end, marker, inner)) = span else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+        if i > plain_start {
+            spans.push((emphasis, &text[plain_start..i]));
+        }
+        let body = &text[i + marker.len()..end];
+        if inner.code {
+            spans.push((inner, body));
+        } else {
+            parse(body, inner, formatting, spans);
+        }
+        i = end + marker.len();
+        plain_start = i;
+    }
+    if plain_start < text.len() {
+        spans.push((emphasis, &text[plain_start..]));
+    }
+}
+
+/// Byte offset of the marker closing the one opened at `open`. The body must
+/// not start or end with a space; `word_boundary` markers must also not be
+/// followed by a letter or digit (so `snake_case_name` is left alone).
+fn closing(text: &str, open: usize, marker: &str, word_boundary: bool) -> Option<usize> {
+    let start = open + marker.len();
+    let body = &text[start..];
+    if body.starts_with(char::is_whitespace) || body.is_empty() {
+        return None;
+    }
+    let mut from = 0;
+    while let Some(found) = body[from..].find(marker) {
+        let end = from + found;
+        let valid = end > 0
+            && !body[..end].ends_with(char::is_whitespace)
+            && !(word_boundary && body[end + marker.len()..].starts_with(|c: char| c.is_alphanumeric()));
+        if valid {
+            return Some(start + end);
+        }
+        from = end + marker.len();
+    }
+    None
+}
+
+/// Whether the character before `i` is part of a word
+fn after_word(text: &str, i: usize) -> bool {
+    text[..i].ends_with(|c: char| c.is_alphanumeric())
+}
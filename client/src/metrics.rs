@@ -0,0 +1,131 @@
+// GhostWire Client - Telemetry Export
+// Periodically appends telemetry snapshots to a CSV or JSON-lines file
+// (`/telemetry export <path>` or `--metrics-file <path>`).
+
+use crate::app::Telemetry;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often a snapshot is appended
+pub const METRICS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// File format, chosen from the extension: `.csv` is CSV, anything else JSON lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetricsFormat {
+    Csv,
+    JsonLines,
+}
+
+/// One row of exported telemetry
+#[derive(Debug, Serialize)]
+struct Snapshot {
+    timestamp: DateTime<Utc>,
+    uptime_secs: u64,
+    messages_sent: u64,
+    messages_received: u64,
+    /// Messages sent + received per second since the previous snapshot
+    messages_per_sec: f64,
+    bytes_sent: u64,
+    bytes_received: u64,
+    latency_ms: u64,
+    reconnects: u64,
+    /// Encrypted messages dropped as replays so far
+    replays_dropped: u64,
+}
+
+impl Snapshot {
+    const CSV_HEADER: &'static str = "timestamp,uptime_secs,messages_sent,messages_received,\
+        messages_per_sec,bytes_sent,bytes_received,latency_ms,reconnects,replays_dropped";
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{:.2},{},{},{},{},{}",
+            self.timestamp.to_rfc3339(),
+            self.uptime_secs,
+            self.messages_sent,
+            self.messages_received,
+            self.messages_per_sec,
+            self.bytes_sent,
+            self.bytes_received,
+            self.latency_ms,
+            self.reconnects,
+            self.replays_dropped,
+        )
+    }
+}
+
+/// Appends snapshots to a metrics file
+#[derive(Debug)]
+pub struct MetricsWriter {
+    path: PathBuf,
+    file: File,
+    format: MetricsFormat,
+    /// Message total and time of the previous snapshot, for the rate
+    last: Option<(u64, Instant)>,
+}
+
+impl MetricsWriter {
+    /// Open `path` for appending, writing a CSV header if the file is new
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let format = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => MetricsFormat::Csv,
+            _ => MetricsFormat::JsonLines,
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if format == MetricsFormat::Csv && file.metadata()?.len() == 0 {
+            writeln!(file, "{}", Snapshot::CSV_HEADER)?;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            format,
+            last: None,
+        })
+    }
+
+    /// File being written
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a snapshot of the current telemetry
+    pub fn append(&mut self, telemetry: &Telemetry) -> anyhow::Result<()> {
+        let now = Instant::now();
+        let total = telemetry.messages_sent + telemetry.messages_received;
+        let messages_per_sec = match self.last {
+            Some((last_total, at)) => {
+                let secs = now.duration_since(at).as_secs_f64();
+                if secs > 0.0 {
+                    total.saturating_sub(last_total) as f64 / secs
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
+        };
+        self.last = Some((total, now));
+
+        let snapshot = Snapshot {
+            timestamp: Utc::now(),
+            uptime_secs: telemetry.connection_uptime,
+            messages_sent: telemetry.messages_sent,
+            messages_received: telemetry.messages_received,
+            messages_per_sec,
+            bytes_sent: telemetry.bytes_sent,
+            bytes_received: telemetry.bytes_received,
+            latency_ms: telemetry.latency_ms,
+            reconnects: telemetry.reconnects(),
+            replays_dropped: telemetry.replays_dropped,
+        };
+        let line = match self.format {
+            MetricsFormat::Csv => snapshot.to_csv(),
+            MetricsFormat::JsonLines => serde_json::to_string(&snapshot)?,
+        };
+        writeln!(self.file, "{}", line)?;
+        Ok(())
+    }
+}
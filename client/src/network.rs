@@ -1,25 +1,80 @@
 // GhostWire Client - Network Layer
 // This module handles WebSocket communication in a separate async task
 
-use crate::app::{MessageMeta, MessageType, WireMessage};
+use crate::sanitize::{sanitize, sanitize_multiline};
+use ghostwire_protocol::chunk::{self, Chunk, Reassembler, MAX_FRAME_LEN};
+use ghostwire_protocol::clock;
+use ghostwire_protocol::decode::decode_frame;
+use ghostwire_protocol::e2e::KeyExchange;
+use ghostwire_protocol::group::GroupEvent;
+use ghostwire_protocol::hello::ServerInfo;
+use ghostwire_protocol::validate::{validate_channel_id, validate_username};
+use ghostwire_protocol::whois::WhoisInfo;
+use ghostwire_protocol::{
+    decode_roster, MessageMeta, MessageType, WireMessage, CLIENT_VERSION_HEADER, MOTD_SENDER,
+    QUEUED_SENDER, UNDELIVERED_SENDER,
+};
+use futures_util::stream::SplitSink;
 use futures_util::{SinkExt, StreamExt};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use tokio::sync::mpsc;
-use tokio::time::{interval, Duration};
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-#[derive(Debug, Clone)]
+use tokio::time::{interval, Duration, Instant};
+use anyhow::Context;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{client_async_tls, MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+mod close;
+mod error;
+use close::RelayClose;
+pub use error::NetworkError;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NetworkEvent {
-    /// Successfully connected to server
-    Connected,
+    /// Successfully connected to the relay at `url`
+    ConnectedTo { url: String },
+    
+    /// A connection attempt failed; attempt number `attempt` follows in
+    /// `retry_in_ms`
+    Reconnecting { attempt: u32, retry_in_ms: u64 },
+    
+    /// Disconnected from server, or no relay could be reached
+    Disconnected { reason: String },
     
-    /// Disconnected from server
-    Disconnected,
+    /// The relay closed the connection but is expected back; `Reconnecting`
+    /// follows
+    Closed { reason: String },
+    
+    /// The relay kicked us; the network task has stopped for good
+    Kicked { reason: String },
     
     /// Received a chat message
     Message {
+        /// Sender-assigned message ID, if the sender provided one
+        id: Option<String>,
+        /// Sender's sequence number, if the sender provided one
+        seq: Option<u64>,
         sender: String,
         content: String,
         timestamp: i64,
         channel_id: String,
+        /// Raw JSON frame as received, for the message inspector
+        raw: String,
+        /// Control sequences were stripped from the sender or content
+        sanitized: bool,
+        /// Held by the relay while we were offline; the timestamp is from when it was sent
+        delayed: bool,
+        /// The content is an `Envelope` still to be decrypted
+        encrypted: bool,
+        /// An action (`/roll` and friends) rather than speech
+        action: bool,
     },
     
     /// User joined
@@ -30,49 +85,380 @@ pub enum NetworkEvent {
     
     /// System message
     SystemMessage { content: String },
+    /// Relay identity and capabilities, sent on connect
+    ServerHello { info: ServerInfo },
+    /// Relay message of the day, one entry per line
+    Motd { lines: Vec<String> },
+    /// The recipient of our DM is offline: the relay either dropped the
+    /// message or, if `queued`, holds it until they reconnect
+    DeliveryNotice {
+        channel_id: String,
+        message_id: Option<String>,
+        queued: bool,
+        notice: String,
+    },
+    
+    /// Event from one of the extra `[[relays]]`, whose channels are namespaced
+    FromRelay { relay: String, event: Box<NetworkEvent> },
+    
+    /// A user's nickname change was accepted by the relay
+    UserRenamed { old: String, new: String },
+    
+    /// A DM partner got (or, if `read`, has seen up to) one of our messages
+    Receipt {
+        channel_id: String,
+        sender: String,
+        message_id: String,
+        read: bool,
+    },
+    
+    /// A DM session handshake step, not yet checked against the session
+    KeyExchange {
+        channel_id: String,
+        sender: String,
+        exchange: KeyExchange,
+    },
+    
+    /// A group membership or settings change, not yet checked against the group
+    Group {
+        channel_id: String,
+        sender: String,
+        event: GroupEvent,
+    },
+    
+    /// A message was pinned or unpinned by a peer
+    PinChanged {
+        channel_id: String,
+        message_id: String,
+        by: String,
+        pinned: bool,
+    },
     
-    /// Error occurred
-    Error { message: String },
+    /// Full roster of authenticated users, from the relay's WHO reply
+    Roster { usernames: Vec<String> },
+    
+    /// The relay's answer to a WHOIS request
+    Whois { info: WhoisInfo },
+    
+    /// Round-trip time measured from a heartbeat ping/pong
+    Latency { ms: u64 },
+    
+    /// Estimated offset of the relay clock from ours (relay minus local)
+    ClockOffset { ms: i64 },
+    
+    /// One of our chat messages could not be written to the relay
+    SendFailed { channel_id: String, message_id: String, reason: String },
+    
+    /// Something went wrong; see `NetworkError` for the classes
+    Error { error: NetworkError },
 }
 
 /// Messages sent from the UI to the network task
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NetworkCommand {
-    /// Send a chat message to a specific channel
-    SendMessage { content: String, channel_id: String },
+    /// Send a chat message to a specific channel; `encrypted` if the content
+    /// is an encrypted `Envelope`, `action` if it is an action
+    SendMessage { id: String, content: String, channel_id: String, encrypted: bool, action: bool },
     
     /// Authenticate with username (for reconnection scenarios)
     #[allow(dead_code)]
     Authenticate { username: String },
     
+    /// Ask the relay for the current roster
+    RequestRoster,
+    
+    /// Ask the relay what it shares about a user's connection
+    Whois { username: String },
+    
+    /// Pin or unpin a message for everyone in the channel
+    SetPin { channel_id: String, message_id: String, pinned: bool },
+    
+    /// Ask the relay to change our username
+    Rename { new_username: String },
+    
+    /// Announce a group membership or settings change
+    Group { channel_id: String, event: GroupEvent },
+    
+    /// Send a DM session handshake step
+    KeyExchange { channel_id: String, exchange: KeyExchange },
+    
+    /// Tell a DM partner we got (or, if `read`, have seen up to) their message
+    SendReceipt { channel_id: String, message_id: String, read: bool },
+    
     /// Disconnect from server
     Disconnect,
 }
 
+/// Connection attempts per relay before failing over to the next one
+const ATTEMPTS_PER_SERVER: u32 = 2;
+
+/// Pause between failed connection attempts
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// How long writing one frame may take before the send is given up
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to keep reading after a write fails, for a KICK or close frame
+/// the relay sent before it dropped the connection
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A session that lasted this long resets the count of quick reconnects
+const STABLE_SESSION: Duration = Duration::from_secs(60);
+
+/// Reconnects in a row after sessions shorter than `STABLE_SESSION` before
+/// a relay that keeps closing on us is given up
+const MAX_QUICK_RECONNECTS: u32 = 5;
+
+/// Extra headers for the WebSocket upgrade request
+#[derive(Default)]
+pub struct Handshake {
+    /// Configured headers, sent as-is
+    pub headers: BTreeMap<String, String>,
+    /// Relay token, sent as `Authorization: Bearer <token>`
+    pub auth_token: Option<SecretString>,
+}
+
+impl Handshake {
+    /// Build the upgrade request for `server_url` with our headers attached
+    fn request(&self, server_url: &str) -> anyhow::Result<Request> {
+        let mut request = server_url.into_client_request()?;
+        let headers = request.headers_mut();
+        headers.insert(
+            CLIENT_VERSION_HEADER,
+            HeaderValue::from_static(concat!("ghostwire/", env!("CARGO_PKG_VERSION"))),
+        );
+        for (name, value) in &self.headers {
+            let name = HeaderName::try_from(name.as_str())
+                .map_err(|_| anyhow::anyhow!("Invalid header name {:?}", name))?;
+            let value = HeaderValue::try_from(value.as_str())
+                .map_err(|_| anyhow::anyhow!("Invalid value for header {}", name))?;
+            headers.insert(name, value);
+        }
+        if let Some(token) = &self.auth_token {
+            let mut value = HeaderValue::try_from(format!("Bearer {}", token.expose_secret()))
+                .map_err(|_| anyhow::anyhow!("Relay token is not a valid header value"))?;
+            // Keeps the token out of Debug output
+            value.set_sensitive(true);
+            headers.insert("authorization", value);
+        }
+        Ok(request)
+    }
+}
+
+/// Byte stream a WebSocket runs over: TCP, or a Unix domain socket
+trait Transport: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Transport for T {}
+
+type WsStream = WebSocketStream<MaybeTlsStream<Box<dyn Transport>>>;
+
+/// Write half of a relay connection
+type WsSink = SplitSink<WsStream, Message>;
+
+/// Default HTTP path of the WebSocket endpoint behind a `unix://` URL
+const UNIX_WS_PATH: &str = "/ws";
+
+/// Open a WebSocket to `server_url`.
+///
+/// `ws://` and `wss://` connect over TCP (TLS for wss). `unix:///path/to.sock`
+/// connects to a Unix domain socket, upgrading on `/ws` unless the URL ends
+/// in `?path=/other`.
+async fn connect(server_url: &str, handshake: &Handshake) -> Result<WsStream, NetworkError> {
+    let invalid = |e: anyhow::Error| NetworkError::Handshake {
+        server: server_url.to_string(),
+        detail: format!("{:#}", e),
+    };
+    if let Some(rest) = server_url.strip_prefix("unix://") {
+        let (socket_path, ws_path) = match rest.split_once("?path=") {
+            Some((socket_path, ws_path)) => (socket_path, ws_path),
+            None => (rest, UNIX_WS_PATH),
+        };
+        let request = handshake.request(&format!("ws://localhost{}", ws_path)).map_err(invalid)?;
+        let stream = connect_unix(socket_path).await.map_err(|e| NetworkError::Connect {
+            server: server_url.to_string(),
+            detail: format!("{:#}", e),
+        })?;
+        let (ws, _) = client_async_tls(request, stream)
+            .await
+            .map_err(|e| NetworkError::from_handshake(server_url, e))?;
+        return Ok(ws);
+    }
+
+    let request = handshake.request(server_url).map_err(invalid)?;
+    let uri = request.uri();
+    let host = uri.host().context("URL has no host").map_err(invalid)?;
+    // IPv6 literals keep their brackets in the URI
+    let host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+    let port = uri
+        .port_u16()
+        .unwrap_or(if uri.scheme_str() == Some("wss") { 443 } else { 80 });
+
+    // Resolve first so a bad host name is told apart from a refused connection
+    let dns = |detail: String| NetworkError::Dns {
+        server: server_url.to_string(),
+        detail,
+    };
+    let addrs: Vec<_> = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(|e| dns(e.to_string()))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(dns(format!("no addresses for {}", host)));
+    }
+    let connect_error = |e: std::io::Error| NetworkError::Connect {
+        server: server_url.to_string(),
+        detail: e.to_string(),
+    };
+    let tcp = TcpStream::connect(addrs.as_slice()).await.map_err(connect_error)?;
+    tcp.set_nodelay(true).map_err(connect_error)?;
+    let (ws, _) = client_async_tls(request, Box::new(tcp) as Box<dyn Transport>)
+        .await
+        .map_err(|e| NetworkError::from_handshake(server_url, e))?;
+    Ok(ws)
+}
+
+/// Write one frame, giving up after `SEND_TIMEOUT`. `action` says what the
+/// frame was for, e.g. "request roster".
+async fn send_frame(write: &mut WsSink, frame: Message, action: &str) -> Result<(), NetworkError> {
+    match tokio::time::timeout(SEND_TIMEOUT, write.send(frame)).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(NetworkError::Send {
+            action: action.to_string(),
+            detail: e.to_string(),
+        }),
+        Err(_) => Err(NetworkError::SendTimeout { action: action.to_string() }),
+    }
+}
+
+#[cfg(unix)]
+async fn connect_unix(path: &str) -> anyhow::Result<Box<dyn Transport>> {
+    let stream = tokio::net::UnixStream::connect(path)
+        .await
+        .with_context(|| format!("Cannot connect to socket {}", path))?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_path: &str) -> anyhow::Result<Box<dyn Transport>> {
+    anyhow::bail!("unix:// URLs are only supported on Unix")
+}
+
+/// Try each relay in order, `ATTEMPTS_PER_SERVER` times each, returning the
+/// first connection and its URL. Every failure is reported to the UI; a
+/// failure that would only repeat (see `NetworkError::is_retryable`) moves
+/// straight on to the next relay.
+async fn connect_failover(
+    servers: &[String],
+    handshake: &Handshake,
+    event_tx: &mpsc::UnboundedSender<NetworkEvent>,
+) -> Option<(WsStream, String)> {
+    let mut attempts = 0;
+    for (index, server_url) in servers.iter().enumerate() {
+        for attempt in 1..=ATTEMPTS_PER_SERVER {
+            attempts += 1;
+            info!("Connecting to {} (attempt {})", server_url, attempt);
+            match connect(server_url, handshake).await {
+                Ok(stream) => return Some((stream, server_url.clone())),
+                Err(error) => {
+                    warn!("{}", error);
+                    let retry = error.is_retryable() && attempt < ATTEMPTS_PER_SERVER;
+                    let _ = event_tx.send(NetworkEvent::Error { error });
+                    let last_server = index + 1 == servers.len();
+                    if retry || !last_server {
+                        let _ = event_tx.send(NetworkEvent::Reconnecting {
+                            attempt: attempts + 1,
+                            retry_in_ms: RETRY_DELAY.as_millis() as u64,
+                        });
+                        tokio::time::sleep(RETRY_DELAY).await;
+                    }
+                    if !retry {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
 /// Network task that runs in a separate tokio runtime
 /// This is the CRITICAL async/sync split - this task is async, UI is sync
 pub async fn network_task(
-    server_url: String,
-    username: String,
+    servers: Vec<String>,
+    mut username: String,
+    handshake: Handshake,
     event_tx: mpsc::UnboundedSender<NetworkEvent>,
     mut command_rx: mpsc::UnboundedReceiver<NetworkCommand>,
 ) {
-    // Attempt to connect to the server
-    let ws_stream = match connect_async(&server_url).await {
-        Ok((stream, _)) => {
-            let _ = event_tx.send(NetworkEvent::Connected);
-            stream
-        }
-        Err(e) => {
-            let _ = event_tx.send(NetworkEvent::Error {
-                message: format!("Failed to connect: {}", e),
+    // Sequence number of the last chat message we sent in each channel; it
+    // carries on across reconnects so peers see no reset
+    let mut seq: BTreeMap<String, u64> = BTreeMap::new();
+
+    // Reconnects after sessions shorter than `STABLE_SESSION`, in a row
+    let mut quick_reconnects = 0;
+
+    loop {
+        let Some((ws_stream, server_url)) = connect_failover(&servers, &handshake, &event_tx).await else {
+            let _ = event_tx.send(NetworkEvent::Disconnected {
+                reason: "No relay could be reached".to_string(),
             });
             return;
+        };
+        info!("Connected to {}", server_url);
+        let _ = event_tx.send(NetworkEvent::ConnectedTo { url: server_url });
+
+        let started = Instant::now();
+        let close = match session(ws_stream, &mut username, &mut seq, &event_tx, &mut command_rx).await {
+            SessionEnd::Stopped => return,
+            SessionEnd::Closed(close) => close,
+        };
+        if started.elapsed() >= STABLE_SESSION {
+            quick_reconnects = 0;
         }
-    };
+        let delay = match close.reconnect_after {
+            Some(delay) if quick_reconnects < MAX_QUICK_RECONNECTS => delay,
+            _ => {
+                let _ = event_tx.send(NetworkEvent::Disconnected { reason: close.explanation });
+                return;
+            }
+        };
+        quick_reconnects += 1;
+        info!("{}; reconnecting in {:?}", close.explanation, delay);
+        let _ = event_tx.send(NetworkEvent::Closed { reason: close.explanation });
+        let _ = event_tx.send(NetworkEvent::Reconnecting {
+            attempt: quick_reconnects,
+            retry_in_ms: delay.as_millis() as u64,
+        });
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// How a session on an open connection ended
+enum SessionEnd {
+    /// For good, and the UI has been told why (a kick, or failing to
+    /// authenticate)
+    Stopped,
+    /// The connection is gone; the close says whether to reconnect
+    Closed(RelayClose),
+}
 
+/// Authenticate on an open connection, then relay frames and commands until
+/// it ends
+async fn session(
+    ws_stream: WsStream,
+    username: &mut String,
+    seq: &mut BTreeMap<String, u64>,
+    event_tx: &mpsc::UnboundedSender<NetworkEvent>,
+    command_rx: &mut mpsc::UnboundedReceiver<NetworkCommand>,
+) -> SessionEnd {
     let (mut write, mut read) = ws_stream.split();
 
+    // Clock sync state: relay clock minus ours, and the last measured round trip
+    let mut clock_offset_ms: i64 = 0;
+    let mut rtt_ms: i64 = 0;
+
+    // Partly received chunked messages
+    let mut reassembler = Reassembler::new();
+
     // Send authentication message
     let auth_msg = WireMessage {
         msg_type: MessageType::Auth,
@@ -80,33 +466,55 @@ pub async fn network_task(
         channel: "global".to_string(),
         meta: MessageMeta {
             sender: username.clone(),
-            timestamp: chrono::Utc::now().timestamp(),
+            timestamp: wire_timestamp(clock_offset_ms),
+            id: Some(uuid::Uuid::new_v4().to_string()),
+            seq: None,
+            delayed: false,
+            encrypted: false,
+            action: false,
         },
     };
 
     if let Ok(json) = serde_json::to_string(&auth_msg) {
-        if let Err(e) = write.send(Message::Text(json)).await {
-            let _ = event_tx.send(NetworkEvent::Error {
-                message: format!("Failed to authenticate: {}", e),
-            });
-            return;
+        if let Err(error) = send_frame(&mut write, Message::Text(json), "authenticate").await {
+            let _ = event_tx.send(NetworkEvent::Error { error });
+            return SessionEnd::Stopped;
         }
     }
 
-    // Heartbeat interval - send ping every 30 seconds to keep connection alive
+    // Learn who is already here instead of waiting for them to speak
+    if let Err(e) = send_frame(&mut write, Message::Text(who_frame(username, clock_offset_ms)), "request roster").await {
+        warn!("{}", e);
+    }
+
+    // Heartbeat interval - send ping every 30 seconds to keep connection alive.
+    // The first tick completes immediately, giving a latency sample on connect.
     let mut heartbeat = interval(Duration::from_secs(30));
-    heartbeat.tick().await; // First tick completes immediately
+
+    // A failed ping or pong ends the session once the frames already received
+    // are read: a kick, for one, closes the socket before we answer its ping
+    let mut write_failed: Option<RelayClose> = None;
+    let drain = tokio::time::sleep(DRAIN_TIMEOUT);
+    tokio::pin!(drain);
 
     // Main network loop
-    loop {
+    let close = loop {
         tokio::select! {
             // Heartbeat - send ping to keep connection alive
-            _ = heartbeat.tick() => {
-                if let Err(e) = write.send(Message::Ping(vec![])).await {
-                    let _ = event_tx.send(NetworkEvent::Error {
-                        message: format!("Failed to send heartbeat: {}", e),
-                    });
-                    break;
+            _ = heartbeat.tick(), if write_failed.is_none() => {
+                debug!("Sending heartbeat ping");
+                // Stamp the ping so the echoed pong gives us the round trip
+                let sent_at = clock::encode_millis(clock::unix_millis());
+                if let Err(e) = send_frame(&mut write, Message::Ping(sent_at), "send heartbeat").await {
+                    drain.as_mut().reset(Instant::now() + DRAIN_TIMEOUT);
+                    write_failed = Some(RelayClose::dropped(e.to_string()));
+                }
+            }
+
+            // Nothing explained the failed write
+            _ = &mut drain, if write_failed.is_some() => {
+                if let Some(close) = write_failed.take() {
+                    break close;
                 }
             }
 
@@ -114,37 +522,76 @@ pub async fn network_task(
             Some(msg_result) = read.next() => {
                 match msg_result {
                     Ok(Message::Text(text)) => {
-                        // Parse the wire message
-                        if let Ok(wire_msg) = serde_json::from_str::<WireMessage>(&text) {
-                            handle_wire_message(wire_msg, &event_tx);
-                        } else {
-                            let _ = event_tx.send(NetworkEvent::Error {
-                                message: "Failed to parse message".to_string(),
-                            });
+                        debug!("Received frame: {} bytes", text.len());
+                        // Parse the wire message, saying why if it is rejected
+                        let wire_msg = match decode_frame(&text) {
+                            Ok(wire_msg) => wire_msg,
+                            Err(e) => {
+                                warn!("Rejected frame ({}): {}", e, text);
+                                let _ = event_tx.send(NetworkEvent::Error {
+                                    error: NetworkError::Protocol { detail: e.to_string() },
+                                });
+                                continue;
+                            }
+                        };
+                        // Long messages come in slices; wait for the last one
+                        let (wire_msg, text) = match wire_msg.msg_type {
+                            MessageType::Chunk => match reassemble(&mut reassembler, wire_msg) {
+                                Some(whole) => whole,
+                                None => continue,
+                            },
+                            _ => (wire_msg, text),
+                        };
+                        // A kick ends the session; reconnecting would defeat it
+                        if matches!(wire_msg.msg_type, MessageType::Kick)
+                            && wire_msg.meta.sender == "SYSTEM"
+                        {
+                            let reason = sanitize(&wire_msg.payload).text;
+                            info!("Kicked by relay: {}", reason);
+                            let _ = send_frame(&mut write, Message::Close(None), "close the connection").await;
+                            let _ = event_tx.send(NetworkEvent::Kicked { reason });
+                            return SessionEnd::Stopped;
+                        }
+                        // The relay echoes our own RENAME back once it is accepted
+                        if matches!(wire_msg.msg_type, MessageType::Rename)
+                            && wire_msg.meta.sender == *username
+                            && validate_username(&wire_msg.payload).is_ok()
+                        {
+                            info!("Now known as {}", wire_msg.payload);
+                            *username = wire_msg.payload.clone();
                         }
+                        handle_wire_message(wire_msg, text, event_tx);
                     }
                     Ok(Message::Ping(data)) => {
+                        // Relay pings carry its clock; assume the ping took half a round trip
+                        if let Some(relay_ms) = clock::decode_millis(&data) {
+                            clock_offset_ms = relay_ms + rtt_ms / 2 - clock::unix_millis();
+                            debug!("Clock offset from relay: {}ms", clock_offset_ms);
+                            let _ = event_tx.send(NetworkEvent::ClockOffset { ms: clock_offset_ms });
+                        }
+
                         // Respond to server ping with pong
-                        if let Err(e) = write.send(Message::Pong(data)).await {
-                            let _ = event_tx.send(NetworkEvent::Error {
-                                message: format!("Failed to send pong: {}", e),
-                            });
-                            break;
+                        if write_failed.is_none() {
+                            if let Err(e) = send_frame(&mut write, Message::Pong(data), "send pong").await {
+                                drain.as_mut().reset(Instant::now() + DRAIN_TIMEOUT);
+                                write_failed = Some(RelayClose::dropped(e.to_string()));
+                            }
                         }
                     }
-                    Ok(Message::Pong(_)) => {
+                    Ok(Message::Pong(data)) => {
                         // Server responded to our ping - connection is alive
-                        // No action needed, just continue
+                        if let Some(sent_ms) = clock::decode_millis(&data) {
+                            rtt_ms = (clock::unix_millis() - sent_ms).max(0);
+                            let _ = event_tx.send(NetworkEvent::Latency { ms: rtt_ms as u64 });
+                        }
                     }
-                    Ok(Message::Close(_)) => {
-                        let _ = event_tx.send(NetworkEvent::Disconnected);
-                        break;
+                    Ok(Message::Close(frame)) => {
+                        info!("Server closed connection: {:?}", frame);
+                        break RelayClose::from_frame(frame.as_ref());
                     }
                     Err(e) => {
-                        let _ = event_tx.send(NetworkEvent::Error {
-                            message: format!("WebSocket error: {}", e),
-                        });
-                        break;
+                        warn!("WebSocket error: {}", e);
+                        break RelayClose::dropped(format!("WebSocket error: {}", e));
                     }
                     _ => {}
                 }
@@ -153,23 +600,56 @@ pub async fn network_task(
             // Handle commands from UI
             Some(command) = command_rx.recv() => {
                 match command {
-                    NetworkCommand::SendMessage { content, channel_id } => {
+                    NetworkCommand::SendMessage { id, content, channel_id, encrypted, action } => {
+                        // Failures are reported against the message, in its channel
+                        let failed = |reason: String| NetworkEvent::SendFailed {
+                            channel_id: channel_id.clone(),
+                            message_id: id.clone(),
+                            reason,
+                        };
                         let msg = WireMessage {
                             msg_type: MessageType::Message,
                             payload: content,
-                            channel: channel_id,
+                            channel: channel_id.clone(),
                             meta: MessageMeta {
                                 sender: username.clone(),
-                                timestamp: chrono::Utc::now().timestamp(),
+                                timestamp: wire_timestamp(clock_offset_ms),
+                                id: Some(id.clone()),
+                                // Encrypted frames go unnumbered: cover and padded
+                                // messages look like real ones, and numbering only
+                                // the real ones would tell the relay which they are
+                                seq: (!encrypted).then(|| next_seq(seq, &channel_id)),
+                                delayed: false,
+                                encrypted,
+                                action,
                             },
                         };
 
-                        if let Ok(json) = serde_json::to_string(&msg) {
+                        let json = match serde_json::to_string(&msg) {
+                            Ok(json) => json,
+                            Err(e) => {
+                                let _ = event_tx.send(failed(e.to_string()));
+                                continue;
+                            }
+                        };
+                        debug!("Sending frame: {} bytes", json.len());
+                        let frames = match chunk_frames(&msg, json, clock_offset_ms) {
+                            Ok(frames) => frames,
+                            Err(e) => {
+                                let _ = event_tx.send(failed(e));
+                                continue;
+                            }
+                        };
+                        for frame in frames {
                             // Use if let to handle errors gracefully (no .unwrap())
-                            if let Err(e) = write.send(Message::Text(json)).await {
-                                let _ = event_tx.send(NetworkEvent::Error {
-                                    message: format!("Failed to send message: {}", e),
-                                });
+                            if let Err(e) = send_frame(&mut write, Message::Text(frame), "send message").await {
+                                warn!("{}", e);
+                                let reason = match e {
+                                    NetworkError::Send { detail, .. } => detail,
+                                    e => e.to_string(),
+                                };
+                                let _ = event_tx.send(failed(reason));
+                                break;
                             }
                         }
                     }
@@ -180,67 +660,473 @@ pub async fn network_task(
                             channel: "global".to_string(),
                             meta: MessageMeta {
                                 sender: new_username,
-                                timestamp: chrono::Utc::now().timestamp(),
+                                timestamp: wire_timestamp(clock_offset_ms),
+                                id: Some(uuid::Uuid::new_v4().to_string()),
+                                seq: None,
+                                delayed: false,
+                                encrypted: false,
+                                action: false,
                             },
                         };
 
                         if let Ok(json) = serde_json::to_string(&msg) {
-                            if let Err(e) = write.send(Message::Text(json)).await {
-                                let _ = event_tx.send(NetworkEvent::Error {
-                                    message: format!("Failed to authenticate: {}", e),
-                                });
+                            if let Err(error) = send_frame(&mut write, Message::Text(json), "authenticate").await {
+                                let _ = event_tx.send(NetworkEvent::Error { error });
+                            }
+                        }
+                    }
+                    NetworkCommand::RequestRoster => {
+                        let frame = who_frame(username, clock_offset_ms);
+                        if let Err(error) = send_frame(&mut write, Message::Text(frame), "request roster").await {
+                            let _ = event_tx.send(NetworkEvent::Error { error });
+                        }
+                    }
+                    NetworkCommand::Whois { username: target } => {
+                        let frame = whois_frame(username, &target, clock_offset_ms);
+                        let action = format!("look up {}", target);
+                        if let Err(error) = send_frame(&mut write, Message::Text(frame), &action).await {
+                            let _ = event_tx.send(NetworkEvent::Error { error });
+                        }
+                    }
+                    NetworkCommand::SetPin { channel_id, message_id, pinned } => {
+                        let msg = WireMessage {
+                            msg_type: if pinned { MessageType::Pin } else { MessageType::Unpin },
+                            payload: message_id,
+                            channel: channel_id,
+                            meta: MessageMeta {
+                                sender: username.clone(),
+                                timestamp: wire_timestamp(clock_offset_ms),
+                                id: Some(uuid::Uuid::new_v4().to_string()),
+                                seq: None,
+                                delayed: false,
+                                encrypted: false,
+                                action: false,
+                            },
+                        };
+
+                        if let Ok(json) = serde_json::to_string(&msg) {
+                            if let Err(error) = send_frame(&mut write, Message::Text(json), "update pin").await {
+                                let _ = event_tx.send(NetworkEvent::Error { error });
+                            }
+                        }
+                    }
+                    NetworkCommand::Group { channel_id, event } => {
+                        let msg = WireMessage {
+                            msg_type: MessageType::Group,
+                            payload: event.to_payload(),
+                            channel: channel_id,
+                            meta: MessageMeta {
+                                sender: username.clone(),
+                                timestamp: wire_timestamp(clock_offset_ms),
+                                id: Some(uuid::Uuid::new_v4().to_string()),
+                                seq: None,
+                                delayed: false,
+                                encrypted: false,
+                                action: false,
+                            },
+                        };
+
+                        if let Ok(json) = serde_json::to_string(&msg) {
+                            if let Err(error) = send_frame(&mut write, Message::Text(json), "update group").await {
+                                let _ = event_tx.send(NetworkEvent::Error { error });
+                            }
+                        }
+                    }
+                    NetworkCommand::KeyExchange { channel_id, exchange } => {
+                        let msg = WireMessage {
+                            msg_type: MessageType::KeyExchange,
+                            payload: exchange.to_payload(),
+                            channel: channel_id,
+                            meta: MessageMeta {
+                                sender: username.clone(),
+                                timestamp: wire_timestamp(clock_offset_ms),
+                                id: None,
+                                seq: None,
+                                delayed: false,
+                                encrypted: false,
+                                action: false,
+                            },
+                        };
+
+                        if let Ok(json) = serde_json::to_string(&msg) {
+                            if let Err(error) = send_frame(&mut write, Message::Text(json), "send key exchange").await {
+                                let _ = event_tx.send(NetworkEvent::Error { error });
+                            }
+                        }
+                    }
+                    NetworkCommand::SendReceipt { channel_id, message_id, read } => {
+                        let msg = WireMessage {
+                            msg_type: if read { MessageType::Read } else { MessageType::Delivered },
+                            payload: message_id,
+                            channel: channel_id,
+                            meta: MessageMeta {
+                                sender: username.clone(),
+                                timestamp: wire_timestamp(clock_offset_ms),
+                                id: None,
+                                seq: None,
+                                delayed: false,
+                                encrypted: false,
+                                action: false,
+                            },
+                        };
+
+                        if let Ok(json) = serde_json::to_string(&msg) {
+                            if let Err(e) = send_frame(&mut write, Message::Text(json), "send receipt").await {
+                                warn!("{}", e);
+                            }
+                        }
+                    }
+                    NetworkCommand::Rename { new_username } => {
+                        let msg = WireMessage {
+                            msg_type: MessageType::Rename,
+                            payload: new_username,
+                            channel: "global".to_string(),
+                            meta: MessageMeta {
+                                sender: username.clone(),
+                                timestamp: wire_timestamp(clock_offset_ms),
+                                id: Some(uuid::Uuid::new_v4().to_string()),
+                                seq: None,
+                                delayed: false,
+                                encrypted: false,
+                                action: false,
+                            },
+                        };
+
+                        if let Ok(json) = serde_json::to_string(&msg) {
+                            if let Err(error) = send_frame(&mut write, Message::Text(json), "change nickname").await {
+                                let _ = event_tx.send(NetworkEvent::Error { error });
                             }
                         }
                     }
                     NetworkCommand::Disconnect => {
-                        let _ = write.send(Message::Close(None)).await;
-                        break;
+                        info!("Disconnecting");
+                        let _ = send_frame(&mut write, Message::Close(None), "close the connection").await;
+                        break RelayClose::disconnected("Disconnected".to_string());
                     }
                 }
             }
 
             // If both channels are closed, exit
-            else => break,
+            else => break RelayClose::disconnected("Connection closed".to_string()),
         }
+    };
+
+    SessionEnd::Closed(close)
+}
+
+/// Current time in Unix seconds, corrected to the relay's clock
+fn wire_timestamp(clock_offset_ms: i64) -> i64 {
+    (clock::unix_millis() + clock_offset_ms) / 1000
+}
+
+/// Build a WHO roster request
+fn who_frame(username: &str, clock_offset_ms: i64) -> String {
+    let msg = WireMessage {
+        msg_type: MessageType::Who,
+        payload: String::new(),
+        channel: "global".to_string(),
+        meta: MessageMeta {
+            sender: username.to_string(),
+            timestamp: wire_timestamp(clock_offset_ms),
+            id: None,
+            seq: None,
+            delayed: false,
+            encrypted: false,
+            action: false,
+        },
+    };
+    serde_json::to_string(&msg).unwrap_or_default()
+}
+
+/// Build a WHOIS request for `target`
+fn whois_frame(username: &str, target: &str, clock_offset_ms: i64) -> String {
+    let msg = WireMessage {
+        msg_type: MessageType::Whois,
+        payload: target.to_string(),
+        channel: "global".to_string(),
+        meta: MessageMeta {
+            sender: username.to_string(),
+            timestamp: wire_timestamp(clock_offset_ms),
+            id: None,
+            seq: None,
+            delayed: false,
+            encrypted: false,
+            action: false,
+        },
+    };
+    serde_json::to_string(&msg).unwrap_or_default()
+}
+
+/// Frames for an outgoing MSG: the frame itself, or CHUNK frames carrying
+/// it in slices if it is longer than the relay accepts
+fn chunk_frames(msg: &WireMessage, json: String, clock_offset_ms: i64) -> Result<Vec<String>, String> {
+    if json.len() <= MAX_FRAME_LEN {
+        return Ok(vec![json]);
+    }
+    let id = msg.meta.id.clone().unwrap_or_default();
+    let chunks = chunk::split(&id, &json).ok_or_else(|| {
+        format!(
+            "Message too long to send ({} KiB max)",
+            chunk::MAX_CHUNKS as usize * chunk::CHUNK_DATA_LEN / 1024
+        )
+    })?;
+    debug!("Sending frame in {} chunks", chunks.len());
+    Ok(chunks
+        .iter()
+        .map(|chunk| {
+            let frame = WireMessage {
+                msg_type: MessageType::Chunk,
+                payload: chunk.to_payload(),
+                channel: msg.channel.clone(),
+                meta: MessageMeta {
+                    sender: msg.meta.sender.clone(),
+                    timestamp: wire_timestamp(clock_offset_ms),
+                    id: Some(id.clone()),
+                    seq: None,
+                    delayed: false,
+                    encrypted: false,
+                    action: false,
+                },
+            };
+            serde_json::to_string(&frame).unwrap_or_default()
+        })
+        .collect())
+}
+
+/// Add a CHUNK frame to the reassembler, returning the MSG frame it
+/// completes and its raw JSON. The whole frame must be a MSG from the same
+/// sender in the same channel as its chunks.
+fn reassemble(reassembler: &mut Reassembler, msg: WireMessage) -> Option<(WireMessage, String)> {
+    let Some(chunk) = Chunk::from_payload(&msg.payload) else {
+        warn!("Ignoring malformed CHUNK payload from {:?}", msg.meta.sender);
+        return None;
+    };
+    let raw = reassembler.add(&msg.meta.sender, chunk)?;
+    let mut whole = match decode_frame(&raw) {
+        Ok(whole) => whole,
+        Err(e) => {
+            warn!("Ignoring chunked frame from {:?}: {}", msg.meta.sender, e);
+            return None;
+        }
+    };
+    if !matches!(whole.msg_type, MessageType::Message)
+        || whole.meta.sender != msg.meta.sender
+        || whole.channel != msg.channel
+    {
+        warn!("Ignoring chunked frame from {:?} that does not match its chunks", msg.meta.sender);
+        return None;
     }
+    // The relay marks the chunks it held, not the frame inside them
+    whole.meta.delayed |= msg.meta.delayed;
+    Some((whole, raw))
+}
 
-    let _ = event_tx.send(NetworkEvent::Disconnected);
+/// Advance and return the outgoing sequence number in `channel_id`
+fn next_seq(seq: &mut BTreeMap<String, u64>, channel_id: &str) -> u64 {
+    let seq = seq.entry(channel_id.to_string()).or_insert(0);
+    *seq += 1;
+    *seq
 }
 
 /// Handle a wire message and convert it to a NetworkEvent
+///
+/// Sender names and payloads come from untrusted peers, so they are
+/// sanitized here before anything else in the client sees them.
 fn handle_wire_message(
     msg: WireMessage,
+    raw: String,
     event_tx: &mpsc::UnboundedSender<NetworkEvent>,
 ) {
+    // Malformed channel IDs would break DM routing; drop the frame
+    if let Err(e) = validate_channel_id(&msg.channel) {
+        warn!("Dropping frame from {:?}: {}", msg.meta.sender, e);
+        return;
+    }
+
+    let sender = sanitize(&msg.meta.sender);
+    // Chat messages keep their line breaks (code blocks, pasted logs)
+    let payload = match msg.msg_type {
+        MessageType::Message => sanitize_multiline(&msg.payload),
+        _ => sanitize(&msg.payload),
+    };
+    if sender.modified || payload.modified {
+        warn!("Stripped control sequences from frame sent as {:?}", msg.meta.sender);
+    }
+
     match msg.msg_type {
         MessageType::Message => {
             let _ = event_tx.send(NetworkEvent::Message {
-                sender: msg.meta.sender,
-                content: msg.payload,
+                id: msg.meta.id,
+                seq: msg.meta.seq,
+                sender: sender.text,
+                content: payload.text,
                 timestamp: msg.meta.timestamp,
                 channel_id: msg.channel,
+                raw,
+                sanitized: sender.modified || payload.modified,
+                delayed: msg.meta.delayed,
+                encrypted: msg.meta.encrypted,
+                action: msg.meta.action,
             });
         }
         MessageType::System => {
             // Parse system messages for user join/leave
-            if msg.payload.contains("joined") {
+            if msg.meta.sender == MOTD_SENDER {
+                // Keep the operator's line breaks, sanitizing each line
+                let lines = msg.payload.lines().map(|line| sanitize(line).text).collect();
+                let _ = event_tx.send(NetworkEvent::Motd { lines });
+            } else if msg.meta.sender == UNDELIVERED_SENDER || msg.meta.sender == QUEUED_SENDER {
+                let _ = event_tx.send(NetworkEvent::DeliveryNotice {
+                    channel_id: msg.channel,
+                    message_id: msg.meta.id,
+                    queued: msg.meta.sender == QUEUED_SENDER,
+                    notice: payload.text,
+                });
+            } else if payload.text.contains("joined") {
                 let _ = event_tx.send(NetworkEvent::UserJoined {
-                    username: msg.meta.sender,
+                    username: sender.text,
                 });
-            } else if msg.payload.contains("left") {
+            } else if payload.text.contains("left") {
                 let _ = event_tx.send(NetworkEvent::UserLeft {
-                    username: msg.meta.sender,
+                    username: sender.text,
                 });
             } else {
                 let _ = event_tx.send(NetworkEvent::SystemMessage {
-                    content: msg.payload,
+                    content: payload.text,
                 });
             }
         }
         MessageType::Auth => {
             // User authenticated - add them to roster
-            let username = msg.meta.sender.clone();
-            let _ = event_tx.send(NetworkEvent::UserJoined { username });
+            let _ = event_tx.send(NetworkEvent::UserJoined {
+                username: sender.text,
+            });
+        }
+        MessageType::Rename => {
+            // Names end up in DM channel IDs, so only accept valid ones
+            if validate_username(&payload.text).is_err() {
+                warn!("Ignoring rename of {:?} to invalid name", msg.meta.sender);
+                return;
+            }
+            let _ = event_tx.send(NetworkEvent::UserRenamed {
+                old: sender.text,
+                new: payload.text,
+            });
+        }
+        MessageType::Pin | MessageType::Unpin => {
+            let _ = event_tx.send(NetworkEvent::PinChanged {
+                channel_id: msg.channel,
+                message_id: payload.text,
+                by: sender.text,
+                pinned: matches!(msg.msg_type, MessageType::Pin),
+            });
+        }
+        MessageType::Who => {
+            // Only the relay answers WHO; a peer's request relayed by an
+            // older server is not a roster
+            if msg.meta.sender != "SYSTEM" {
+                return;
+            }
+            let usernames = decode_roster(&payload.text)
+                .into_iter()
+                .filter(|name| validate_username(name).is_ok())
+                .collect();
+            let _ = event_tx.send(NetworkEvent::Roster { usernames });
+        }
+        MessageType::Whois => {
+            if msg.meta.sender != "SYSTEM" {
+                return;
+            }
+            let Some(info) = WhoisInfo::from_payload(&msg.payload) else {
+                warn!("Ignoring malformed WHOIS payload");
+                return;
+            };
+            if validate_username(&info.username).is_err() {
+                return;
+            }
+            let info = WhoisInfo {
+                client_version: info.client_version.as_deref().map(|v| sanitize(v).text),
+                ..info
+            };
+            let _ = event_tx.send(NetworkEvent::Whois { info });
+        }
+        MessageType::Group => {
+            if !msg.channel.starts_with("group:") {
+                return;
+            }
+            // Decode the raw payload (JSON); names are validated when the
+            // event is applied, and the topic is shown so sanitize it
+            let Some(mut event) = GroupEvent::from_payload(&msg.payload) else {
+                warn!("Ignoring malformed GROUP payload from {:?}", msg.meta.sender);
+                return;
+            };
+            match &mut event {
+                GroupEvent::Topic { topic } => *topic = sanitize(topic).text,
+                GroupEvent::Invite { group, .. } => {
+                    group.topic = group.topic.as_deref().map(|topic| sanitize(topic).text);
+                }
+                _ => {}
+            }
+            let _ = event_tx.send(NetworkEvent::Group {
+                channel_id: msg.channel,
+                sender: sender.text,
+                event,
+            });
+        }
+        MessageType::KeyExchange => {
+            if !msg.channel.starts_with("dm:") {
+                return;
+            }
+            // Keys are checked when the step is applied
+            let Some(exchange) = KeyExchange::from_payload(&msg.payload) else {
+                warn!("Ignoring malformed KEYX payload from {:?}", msg.meta.sender);
+                return;
+            };
+            let _ = event_tx.send(NetworkEvent::KeyExchange {
+                channel_id: msg.channel,
+                sender: sender.text,
+                exchange,
+            });
+        }
+        MessageType::Delivered | MessageType::Read => {
+            if !msg.channel.starts_with("dm:") {
+                return;
+            }
+            let _ = event_tx.send(NetworkEvent::Receipt {
+                channel_id: msg.channel,
+                sender: sender.text,
+                message_id: payload.text,
+                read: matches!(msg.msg_type, MessageType::Read),
+            });
+        }
+        MessageType::Chunk => {
+            // Reassembled by the network loop before we get here
+            warn!("Ignoring stray CHUNK from {:?}", msg.meta.sender);
+        }
+        MessageType::Kick => {
+            // Relay kicks are handled by the network loop; peers cannot kick
+            warn!("Ignoring KICK sent as {:?}", msg.meta.sender);
+        }
+        MessageType::Hello => {
+            if msg.meta.sender != "SYSTEM" {
+                return;
+            }
+            // Decode the raw payload (JSON), then sanitize the fields we display
+            let Some(info) = ServerInfo::from_payload(&msg.payload) else {
+                warn!("Ignoring malformed HELLO payload");
+                return;
+            };
+            let info = ServerInfo {
+                name: sanitize(&info.name).text,
+                version: sanitize(&info.version).text,
+                features: info.features.iter().map(|f| sanitize(f).text).collect(),
+                protocol: info.protocol,
+                min_client_version: info.min_client_version.as_deref().map(|v| sanitize(v).text),
+            };
+            let _ = event_tx.send(NetworkEvent::ServerHello { info });
         }
     }
 }
+
+#[cfg(test)]
+mod tests;
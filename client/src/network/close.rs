@@ -0,0 +1,81 @@
+// GhostWire Client - Relay Close Codes
+// The relay says why it closes a connection with a WebSocket close code
+// (RFC 6455, section 7.4) and an optional reason. Each code gets an
+// explanation for the user, and the codes that mean the relay is coming back
+// (shutdown, restart, overload, internal error) make the network task
+// reconnect after a pause instead of stopping. So does a connection that
+// drops without a close frame: the relay may still be there.
+
+use crate::sanitize::sanitize;
+use ghostwire_protocol::CLOSE_IDLE_TIMEOUT;
+use tokio::time::Duration;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+
+/// Pause before reconnecting to a relay that is restarting or hit an error
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+
+/// Pause before reconnecting to a relay that asked us to try again later
+const OVERLOAD_DELAY: Duration = Duration::from_secs(30);
+
+/// Pause before reconnecting after the connection dropped unexplained
+const DROPPED_DELAY: Duration = Duration::from_secs(2);
+
+/// What a close from the relay means for the user and the connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayClose {
+    /// Explanation for the user, followed by the relay's reason if it gave one
+    pub explanation: String,
+    /// How long to wait before reconnecting; `None` stays disconnected
+    pub reconnect_after: Option<Duration>,
+}
+
+impl RelayClose {
+    /// A connection that ended for good, e.g. on the user's request
+    pub fn disconnected(explanation: String) -> Self {
+        Self {
+            explanation,
+            reconnect_after: None,
+        }
+    }
+
+    /// A connection that dropped without the relay saying why, e.g. on a
+    /// reset or a failed write; worth reconnecting to
+    pub fn dropped(explanation: String) -> Self {
+        Self {
+            explanation,
+            reconnect_after: Some(DROPPED_DELAY),
+        }
+    }
+
+    /// Explain a close frame, or its absence
+    pub fn from_frame(frame: Option<&CloseFrame>) -> Self {
+        let Some(frame) = frame else {
+            return Self::dropped("The relay closed the connection".to_string());
+        };
+        let (explanation, reconnect_after) = match frame.code {
+            CloseCode::Normal => ("The relay closed the connection".to_string(), None),
+            CloseCode::Away => ("The relay is shutting down".to_string(), Some(RESTART_DELAY)),
+            CloseCode::Restart => ("The relay is restarting".to_string(), Some(RESTART_DELAY)),
+            CloseCode::Again => ("The relay is overloaded".to_string(), Some(OVERLOAD_DELAY)),
+            CloseCode::Error => ("The relay hit an internal error".to_string(), Some(RESTART_DELAY)),
+            CloseCode::Policy => ("The relay closed the connection for a policy violation".to_string(), None),
+            CloseCode::Size => ("The relay closed the connection: a frame was too large".to_string(), None),
+            CloseCode::Protocol | CloseCode::Unsupported | CloseCode::Invalid => {
+                ("The relay closed the connection: protocol error".to_string(), None)
+            }
+            CloseCode::Library(code) if code == CLOSE_IDLE_TIMEOUT => {
+                ("The relay closed the connection after it was idle too long".to_string(), None)
+            }
+            code => (format!("The relay closed the connection (code {})", u16::from(code)), None),
+        };
+        // The reason is the relay's own text, shown as-is
+        let reason = sanitize(&frame.reason).text;
+        let explanation = if reason.trim().is_empty() {
+            explanation
+        } else {
+            format!("{}: {}", explanation, reason.trim())
+        };
+        Self { explanation, reconnect_after }
+    }
+}
@@ -0,0 +1,83 @@
+// GhostWire Client - Network Errors
+// What went wrong talking to a relay, by class, so the UI can say what to do
+// about it and the connect loop can tell a relay worth retrying from one that
+// will fail the same way again.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tokio_tungstenite::tungstenite;
+use tokio_tungstenite::tungstenite::http::StatusCode;
+
+/// A failure reported by the network task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NetworkError {
+    /// The relay's host name could not be resolved
+    Dns { server: String, detail: String },
+
+    /// The TCP (or Unix socket) connection could not be opened
+    Connect { server: String, detail: String },
+
+    /// The TLS handshake failed, e.g. an untrusted or expired certificate
+    Tls { server: String, detail: String },
+
+    /// The WebSocket upgrade was refused or malformed
+    Handshake { server: String, detail: String },
+
+    /// The relay, or a proxy in front of it, refused the upgrade with 401 or
+    /// 403: a token is missing or wrong, or the origin is not allowed
+    AuthRejected { server: String, status: u16 },
+
+    /// The relay sent a frame that breaks the protocol
+    Protocol { detail: String },
+
+    /// A frame could not be written within `SEND_TIMEOUT`
+    SendTimeout { action: String },
+
+    /// A frame could not be written to the connection
+    Send { action: String, detail: String },
+}
+
+impl NetworkError {
+    /// Classify a failed WebSocket upgrade on an open connection
+    pub fn from_handshake(server: &str, error: tungstenite::Error) -> Self {
+        let server = server.to_string();
+        match error {
+            tungstenite::Error::Tls(e) => Self::Tls { server, detail: e.to_string() },
+            tungstenite::Error::Http(response)
+                if matches!(response.status(), StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) =>
+            {
+                Self::AuthRejected { server, status: response.status().as_u16() }
+            }
+            tungstenite::Error::Http(response) => Self::Handshake {
+                server,
+                detail: format!("HTTP {}", response.status()),
+            },
+            tungstenite::Error::Io(e) => Self::Connect { server, detail: e.to_string() },
+            e => Self::Handshake { server, detail: e.to_string() },
+        }
+    }
+
+    /// Whether connecting to the same relay again could succeed. DNS, TLS
+    /// and auth failures repeat until something is reconfigured, so the
+    /// connect loop moves on to the next relay instead.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, Self::Dns { .. } | Self::Tls { .. } | Self::AuthRejected { .. })
+    }
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dns { server, detail } => write!(f, "Cannot resolve {}: {}", server, detail),
+            Self::Connect { server, detail } => write!(f, "Failed to connect to {}: {}", server, detail),
+            Self::Tls { server, detail } => write!(f, "TLS handshake with {} failed: {}", server, detail),
+            Self::Handshake { server, detail } => {
+                write!(f, "WebSocket handshake with {} failed: {}", server, detail)
+            }
+            Self::AuthRejected { server, status } => write!(f, "{} refused the connection (HTTP {})", server, status),
+            Self::Protocol { detail } => write!(f, "Rejected a frame from the relay: {}", detail),
+            Self::SendTimeout { action } => write!(f, "Timed out trying to {}", action),
+            Self::Send { action, detail } => write!(f, "Failed to {}: {}", action, detail),
+        }
+    }
+}
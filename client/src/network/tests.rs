@@ -0,0 +1,399 @@
+// GhostWire Client - Network Task Tests
+// A scripted relay listens on an ephemeral port; each test accepts the
+// client's connection, reads the frames `network_task` writes and answers
+// with its own, so protocol changes can be checked end to end without
+// running the server crate.
+
+use super::*;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tokio_tungstenite::accept_async;
+
+/// How long a test waits for a connection, frame or event
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Listening side of the scripted relay
+struct MockRelay {
+    listener: TcpListener,
+    url: String,
+}
+
+impl MockRelay {
+    async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let url = format!("ws://{}/ws", listener.local_addr().expect("local addr"));
+        Self { listener, url }
+    }
+
+    /// Accept the client's connection and complete the upgrade
+    async fn accept(&self) -> RelayConnection {
+        let (tcp, _) = timeout(TIMEOUT, self.listener.accept())
+            .await
+            .expect("client never connected")
+            .expect("accept");
+        let ws = accept_async(tcp).await.expect("upgrade");
+        RelayConnection { ws }
+    }
+
+    /// Accept the client's connection and answer its upgrade request with
+    /// `status`, like a proxy checking tokens in front of the relay
+    async fn refuse(&self, status: &str) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (mut tcp, _) = timeout(TIMEOUT, self.listener.accept())
+            .await
+            .expect("client never connected")
+            .expect("accept");
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let n = tcp.read(&mut buf).await.expect("read request");
+            assert!(n > 0, "client closed before finishing its request");
+            request.extend_from_slice(&buf[..n]);
+        }
+        let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status);
+        tcp.write_all(response.as_bytes()).await.expect("write response");
+    }
+}
+
+/// One accepted client, seen from the relay
+struct RelayConnection {
+    ws: WebSocketStream<TcpStream>,
+}
+
+impl RelayConnection {
+    /// Next frame the client sent, or `None` once it closed. Heartbeats are
+    /// skipped; tungstenite answers the pings itself.
+    async fn recv(&mut self) -> Option<WireMessage> {
+        loop {
+            let message = timeout(TIMEOUT, self.ws.next()).await.expect("no frame from client");
+            match message? {
+                Ok(Message::Text(text)) => {
+                    return Some(serde_json::from_str(&text).expect("client sent invalid JSON"))
+                }
+                Ok(Message::Close(_)) | Err(_) => return None,
+                Ok(_) => {}
+            }
+        }
+    }
+
+    /// Read past the AUTH and WHO frames every connection starts with
+    async fn handshake(&mut self, username: &str) {
+        let auth = self.recv().await.expect("AUTH frame");
+        assert!(matches!(auth.msg_type, MessageType::Auth));
+        assert_eq!(auth.payload, username);
+        let who = self.recv().await.expect("WHO frame");
+        assert!(matches!(who.msg_type, MessageType::Who));
+    }
+
+    async fn send(&mut self, msg: &WireMessage) {
+        let json = serde_json::to_string(msg).expect("serialize");
+        self.ws.send(Message::Text(json)).await.expect("send to client");
+    }
+}
+
+/// `network_task` running against the mock, with both ends of its channels
+struct TestClient {
+    events: mpsc::UnboundedReceiver<NetworkEvent>,
+    commands: mpsc::UnboundedSender<NetworkCommand>,
+    task: JoinHandle<()>,
+}
+
+impl TestClient {
+    fn spawn(servers: Vec<String>, username: &str) -> Self {
+        let (event_tx, events) = mpsc::unbounded_channel();
+        let (commands, command_rx) = mpsc::unbounded_channel();
+        let task = tokio::spawn(network_task(
+            servers,
+            username.to_string(),
+            Handshake::default(),
+            event_tx,
+            command_rx,
+        ));
+        Self { events, commands, task }
+    }
+
+    /// Next event for the UI, skipping latency and clock samples
+    async fn event(&mut self) -> NetworkEvent {
+        loop {
+            let event = timeout(TIMEOUT, self.events.recv())
+                .await
+                .expect("no event from network task")
+                .expect("network task dropped its events");
+            if !matches!(event, NetworkEvent::Latency { .. } | NetworkEvent::ClockOffset { .. }) {
+                return event;
+            }
+        }
+    }
+
+    /// Wait for the network task to end
+    async fn finished(self) {
+        timeout(TIMEOUT, self.task).await.expect("network task kept running").expect("task panicked");
+    }
+}
+
+/// A chat frame as another client would send it through the relay
+fn chat_frame(sender: &str, channel: &str, payload: &str) -> WireMessage {
+    WireMessage {
+        msg_type: MessageType::Message,
+        payload: payload.to_string(),
+        channel: channel.to_string(),
+        meta: MessageMeta {
+            sender: sender.to_string(),
+            timestamp: chrono::Utc::now().timestamp(),
+            id: Some(uuid::Uuid::new_v4().to_string()),
+            seq: Some(1),
+            delayed: false,
+            encrypted: false,
+            action: false,
+        },
+    }
+}
+
+/// Connect the client and get past the handshake
+async fn connected(username: &str) -> (MockRelay, RelayConnection, TestClient) {
+    let relay = MockRelay::start().await;
+    let mut client = TestClient::spawn(vec![relay.url.clone()], username);
+    let mut connection = relay.accept().await;
+    connection.handshake(username).await;
+    match client.event().await {
+        NetworkEvent::ConnectedTo { url } => assert_eq!(url, relay.url),
+        other => panic!("expected ConnectedTo, got {:?}", other),
+    }
+    (relay, connection, client)
+}
+
+#[tokio::test]
+async fn connects_and_authenticates() {
+    let relay = MockRelay::start().await;
+    let client = TestClient::spawn(vec![relay.url.clone()], "alice");
+    let mut connection = relay.accept().await;
+
+    let auth = connection.recv().await.expect("AUTH frame");
+    assert!(matches!(auth.msg_type, MessageType::Auth));
+    assert_eq!(auth.payload, "alice");
+    assert_eq!(auth.meta.sender, "alice");
+    assert_eq!(auth.channel, "global");
+    let who = connection.recv().await.expect("WHO frame");
+    assert!(matches!(who.msg_type, MessageType::Who));
+    client.task.abort();
+}
+
+#[tokio::test]
+async fn sends_chat_messages_with_ids_and_sequence_numbers() {
+    let (_relay, mut connection, client) = connected("alice").await;
+
+    // Numbered per channel; encrypted frames are not numbered at all
+    let sends = [
+        ("hello", "global", false, Some(1)),
+        ("again", "global", false, Some(2)),
+        ("team", "group:ops", false, Some(1)),
+        ("sealed", "dm:alice:bob", true, None),
+        ("more", "global", false, Some(3)),
+    ];
+    for (n, (text, channel, encrypted, seq)) in sends.into_iter().enumerate() {
+        client
+            .commands
+            .send(NetworkCommand::SendMessage {
+                id: format!("id-{}", n),
+                content: text.to_string(),
+                channel_id: channel.to_string(),
+                encrypted,
+                action: false,
+            })
+            .expect("send command");
+        let frame = connection.recv().await.expect("MSG frame");
+        assert!(matches!(frame.msg_type, MessageType::Message));
+        assert_eq!(frame.payload, text);
+        assert_eq!(frame.channel, channel);
+        assert_eq!(frame.meta.sender, "alice");
+        assert_eq!(frame.meta.id.as_deref(), Some(format!("id-{}", n).as_str()));
+        assert_eq!(frame.meta.seq, seq);
+    }
+    client.task.abort();
+}
+
+#[tokio::test]
+async fn delivers_broadcast_messages() {
+    let (_relay, mut connection, mut client) = connected("alice").await;
+
+    connection.send(&chat_frame("bob", "global", "hi alice")).await;
+    match client.event().await {
+        NetworkEvent::Message { sender, content, channel_id, seq, .. } => {
+            assert_eq!(sender, "bob");
+            assert_eq!(content, "hi alice");
+            assert_eq!(channel_id, "global");
+            assert_eq!(seq, Some(1));
+        }
+        other => panic!("expected Message, got {:?}", other),
+    }
+    client.task.abort();
+}
+
+#[tokio::test]
+async fn drops_frames_with_invalid_channels() {
+    let (_relay, mut connection, mut client) = connected("alice").await;
+
+    connection.send(&chat_frame("bob", "dm:bob", "routed nowhere")).await;
+    connection.send(&chat_frame("bob", "global", "still here")).await;
+    match client.event().await {
+        NetworkEvent::Message { content, .. } => assert_eq!(content, "still here"),
+        other => panic!("expected Message, got {:?}", other),
+    }
+    client.task.abort();
+}
+
+#[tokio::test]
+async fn reports_why_a_frame_was_rejected() {
+    let (_relay, mut connection, mut client) = connected("alice").await;
+
+    let frame = r#"{"type":"SHOUT","payload":"hi","meta":{"sender":"bob","timestamp":1}}"#;
+    connection.ws.send(Message::Text(frame.to_string())).await.expect("send to client");
+    match client.event().await {
+        NetworkEvent::Error { error: NetworkError::Protocol { detail } } => {
+            assert!(detail.contains("unknown frame type \"SHOUT\""), "{}", detail)
+        }
+        other => panic!("expected Error, got {:?}", other),
+    }
+    connection.send(&chat_frame("bob", "global", "still here")).await;
+    assert!(matches!(client.event().await, NetworkEvent::Message { .. }));
+    client.task.abort();
+}
+
+#[tokio::test]
+async fn disconnect_command_closes_the_connection() {
+    let (_relay, mut connection, mut client) = connected("alice").await;
+
+    client.commands.send(NetworkCommand::Disconnect).expect("send command");
+    assert!(connection.recv().await.is_none(), "relay should see the close");
+    match client.event().await {
+        NetworkEvent::Disconnected { reason } => assert_eq!(reason, "Disconnected"),
+        other => panic!("expected Disconnected, got {:?}", other),
+    }
+    client.finished().await;
+}
+
+#[tokio::test]
+async fn reconnects_when_the_relay_closes_without_a_frame() {
+    let (relay, mut connection, mut client) = connected("alice").await;
+
+    connection.ws.close(None).await.expect("close");
+    match client.event().await {
+        NetworkEvent::Closed { reason } => assert_eq!(reason, "The relay closed the connection"),
+        other => panic!("expected Closed, got {:?}", other),
+    }
+    assert!(matches!(client.event().await, NetworkEvent::Reconnecting { attempt: 1, .. }));
+    let mut connection = relay.accept().await;
+    connection.handshake("alice").await;
+    assert!(matches!(client.event().await, NetworkEvent::ConnectedTo { .. }));
+
+    // A quick second drop counts as the next attempt
+    connection.ws.close(None).await.expect("close");
+    assert!(matches!(client.event().await, NetworkEvent::Closed { .. }));
+    assert!(matches!(client.event().await, NetworkEvent::Reconnecting { attempt: 2, .. }));
+    client.task.abort();
+}
+
+#[tokio::test]
+async fn explains_a_policy_close_and_stays_disconnected() {
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+    use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+
+    let (_relay, mut connection, mut client) = connected("alice").await;
+
+    let frame = CloseFrame { code: CloseCode::Policy, reason: "flooding".into() };
+    connection.ws.close(Some(frame)).await.expect("close");
+    match client.event().await {
+        NetworkEvent::Disconnected { reason } => {
+            assert_eq!(reason, "The relay closed the connection for a policy violation: flooding")
+        }
+        other => panic!("expected Disconnected, got {:?}", other),
+    }
+    client.finished().await;
+}
+
+#[tokio::test]
+async fn stops_for_good_when_kicked() {
+    let (_relay, mut connection, mut client) = connected("alice").await;
+
+    let mut kick = chat_frame("SYSTEM", "global", "spamming");
+    kick.msg_type = MessageType::Kick;
+    connection.send(&kick).await;
+    match client.event().await {
+        NetworkEvent::Kicked { reason } => assert_eq!(reason, "spamming"),
+        other => panic!("expected Kicked, got {:?}", other),
+    }
+    client.finished().await;
+}
+
+#[tokio::test]
+async fn retries_then_gives_up_on_an_unreachable_relay() {
+    // Bind and drop a listener so the port is known to be closed
+    let url = MockRelay::start().await.url;
+    let mut client = TestClient::spawn(vec![url], "alice");
+
+    assert!(matches!(
+        client.event().await,
+        NetworkEvent::Error { error: NetworkError::Connect { .. } }
+    ));
+    match client.event().await {
+        NetworkEvent::Reconnecting { attempt, retry_in_ms } => {
+            assert_eq!(attempt, 2);
+            assert_eq!(retry_in_ms, RETRY_DELAY.as_millis() as u64);
+        }
+        other => panic!("expected Reconnecting, got {:?}", other),
+    }
+    assert!(matches!(client.event().await, NetworkEvent::Error { .. }));
+    match client.event().await {
+        NetworkEvent::Disconnected { reason } => assert_eq!(reason, "No relay could be reached"),
+        other => panic!("expected Disconnected, got {:?}", other),
+    }
+    client.finished().await;
+}
+
+#[tokio::test]
+async fn fails_over_to_the_next_relay() {
+    let down = MockRelay::start().await.url;
+    let relay = MockRelay::start().await;
+    let mut client = TestClient::spawn(vec![down, relay.url.clone()], "alice");
+
+    let mut connection = relay.accept().await;
+    connection.handshake("alice").await;
+    loop {
+        match client.event().await {
+            NetworkEvent::ConnectedTo { url } => {
+                assert_eq!(url, relay.url);
+                break;
+            }
+            NetworkEvent::Error { .. } | NetworkEvent::Reconnecting { .. } => {}
+            other => panic!("expected ConnectedTo, got {:?}", other),
+        }
+    }
+    client.task.abort();
+}
+
+#[tokio::test]
+async fn fails_over_without_retrying_a_refused_token() {
+    let refusing = MockRelay::start().await;
+    let relay = MockRelay::start().await;
+    let mut client = TestClient::spawn(vec![refusing.url.clone(), relay.url.clone()], "alice");
+
+    refusing.refuse("401 Unauthorized").await;
+    match client.event().await {
+        NetworkEvent::Error { error } => assert_eq!(
+            error,
+            NetworkError::AuthRejected { server: refusing.url.clone(), status: 401 }
+        ),
+        other => panic!("expected Error, got {:?}", other),
+    }
+    assert!(matches!(client.event().await, NetworkEvent::Reconnecting { attempt: 2, .. }));
+    // A second attempt would hang on the refusing relay, which no longer answers
+    let mut connection = relay.accept().await;
+    connection.handshake("alice").await;
+    match client.event().await {
+        NetworkEvent::ConnectedTo { url } => assert_eq!(url, relay.url),
+        other => panic!("expected ConnectedTo, got {:?}", other),
+    }
+    client.task.abort();
+}
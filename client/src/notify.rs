@@ -0,0 +1,223 @@
+// GhostWire Client - Notifications
+// Decides which incoming messages deserve attention (mentions, watch words,
+// DMs) and alerts the user with the terminal bell or a sound file, plus an
+// OSC 9 / OSC 777 escape sequence that terminals like kitty and WezTerm turn
+// into a desktop notification, even from inside tmux or over SSH.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tracing::warn;
+
+/// Notification settings (`[notifications]` in config.toml)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    /// Notify when a message mentions our username
+    pub on_mention: bool,
+    /// Notify on direct messages
+    pub on_dm: bool,
+    /// Words that count like our username: highlighted, collected in the
+    /// mentions channel and, with `on_watch_word`, notified
+    pub watch_words: Vec<String>,
+    /// Notify when a message contains a watch word
+    pub on_watch_word: bool,
+    /// Ring the terminal bell
+    pub bell: bool,
+    /// Play this sound file instead of the bell, where a player is available
+    pub sound_file: Option<PathBuf>,
+    /// Ask the terminal for a desktop notification with an escape sequence
+    pub osc: OscNotify,
+}
+
+/// Which notification escape sequence to send, if any
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OscNotify {
+    /// OSC 9 inside tmux or over SSH, where nothing else reaches the desktop
+    #[default]
+    Auto,
+    /// Never
+    Off,
+    /// `ESC ] 9 ; text BEL` (iTerm2, WezTerm, kitty, Windows Terminal)
+    Osc9,
+    /// `ESC ] 777 ; notify ; title ; text BEL` (urxvt, foot, WezTerm, kitty)
+    Osc777,
+}
+
+/// Longest message excerpt put in a notification, in characters
+const MAX_OSC_TEXT: usize = 200;
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            on_mention: true,
+            on_dm: true,
+            watch_words: Vec::new(),
+            on_watch_word: true,
+            bell: true,
+            sound_file: None,
+            osc: OscNotify::Auto,
+        }
+    }
+}
+
+/// Why a message triggered a notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trigger {
+    /// The message mentions us by name
+    Mention,
+    /// The message arrived in a DM channel
+    DirectMessage,
+    /// The message contains this watch word
+    WatchWord(String),
+}
+
+/// Do-not-disturb state: while on, alerts and unread badges are suppressed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DoNotDisturb {
+    #[default]
+    Off,
+    /// On until the given time, or until toggled off if `None`
+    On { until: Option<DateTime<Utc>> },
+}
+
+impl DoNotDisturb {
+    /// Whether alerts are currently suppressed
+    pub fn is_on(&self) -> bool {
+        matches!(self, Self::On { .. })
+    }
+
+    /// Whether a timed DND period has run out at `now`
+    pub fn expired(&self, now: DateTime<Utc>) -> bool {
+        matches!(self, Self::On { until: Some(until) } if *until <= now)
+    }
+}
+
+/// Check an incoming message against the notification rules
+pub fn trigger(
+    config: &NotificationConfig,
+    username: &str,
+    channel_id: &str,
+    content: &str,
+) -> Option<Trigger> {
+    // Channels of extra relays are namespaced `relay/channel`
+    let (_, channel_id) = crate::relays::split_channel(channel_id);
+    if config.on_dm && channel_id.starts_with("dm:") {
+        return Some(Trigger::DirectMessage);
+    }
+    if config.on_mention && mentions(content, username) {
+        return Some(Trigger::Mention);
+    }
+    if config.on_watch_word {
+        return watch_word(config, content).map(|word| Trigger::WatchWord(word.to_string()));
+    }
+    None
+}
+
+/// Whether a message mentions us or contains a watch word, so it is
+/// highlighted and collected in the mentions channel
+pub fn highlights(config: &NotificationConfig, username: &str, content: &str) -> bool {
+    mentions(content, username) || watch_word(config, content).is_some()
+}
+
+/// The first watch word `content` contains, matched like a mention
+fn watch_word<'a>(config: &'a NotificationConfig, content: &str) -> Option<&'a str> {
+    config
+        .watch_words
+        .iter()
+        .map(|word| word.trim())
+        .find(|word| !word.is_empty() && mentions(content, word))
+}
+
+/// Whether `content` mentions `username` (or a watch word) as a whole word,
+/// optionally `@`-prefixed
+fn mentions(content: &str, username: &str) -> bool {
+    content
+        .split(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')))
+        .map(|word| word.trim_end_matches('.'))
+        .any(|word| word.eq_ignore_ascii_case(username))
+}
+
+/// Alert the user: send the notification escape sequence if enabled, then
+/// play the sound file if configured, otherwise ring the bell
+pub fn alert(config: &NotificationConfig, title: &str, text: &str) {
+    let mut stdout = std::io::stdout();
+    if let Some(sequence) = osc_sequence(config.osc, title, text) {
+        let _ = stdout.write_all(sequence.as_bytes());
+        let _ = stdout.flush();
+    }
+    if let Some(path) = &config.sound_file {
+        if play_sound(path) {
+            return;
+        }
+    }
+    if config.bell {
+        // BEL is harmless in raw mode on the alternate screen
+        let _ = stdout.write_all(b"\x07");
+        let _ = stdout.flush();
+    }
+}
+
+/// Build the notification escape sequence for `mode`, wrapped for tmux
+/// (which only passes it on with `set -g allow-passthrough on`)
+fn osc_sequence(mode: OscNotify, title: &str, text: &str) -> Option<String> {
+    let tmux = std::env::var_os("TMUX").is_some();
+    let ssh = std::env::var_os("SSH_CONNECTION").is_some() || std::env::var_os("SSH_TTY").is_some();
+    let mode = match mode {
+        OscNotify::Auto if tmux || ssh => OscNotify::Osc9,
+        OscNotify::Auto | OscNotify::Off => return None,
+        mode => mode,
+    };
+
+    // Message text is untrusted: no control characters may end the sequence
+    // early, and the title must not add OSC 777 fields
+    let title: String = title
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| if c == ';' { ',' } else { c })
+        .collect();
+    let text: String = text
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .take(MAX_OSC_TEXT)
+        .collect();
+    let sequence = match mode {
+        OscNotify::Osc777 => format!("\x1b]777;notify;{};{}\x07", title, text),
+        _ => format!("\x1b]9;{}: {}\x07", title, text),
+    };
+
+    Some(if tmux {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    })
+}
+
+/// Play a sound file with the platform's command-line player, without waiting.
+/// Returns false if no player could be started.
+fn play_sound(path: &Path) -> bool {
+    let player = if cfg!(target_os = "macos") {
+        "afplay"
+    } else if cfg!(target_os = "linux") {
+        "paplay"
+    } else {
+        return false;
+    };
+
+    match Command::new(player)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("Failed to play {} with {}: {}", path.display(), player, e);
+            false
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// GhostWire Client - Padding and Cover Traffic
+// An optional mode against a relay or network observer that studies message
+// sizes and timing. Encrypted messages are padded with NUL characters up to
+// a fixed bucket size before sealing, and cover messages (padding only) go
+// to a random encrypted channel at a jittered cadence. Receivers strip the
+// padding whatever their own settings, and drop messages that were nothing
+// but padding.
+
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Padding settings (`[padding]` in config.toml)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaddingConfig {
+    /// Pad encrypted messages and send cover traffic
+    pub enabled: bool,
+    /// Sizes, in bytes, messages are padded up to; longer messages are
+    /// padded to a multiple of the largest
+    pub buckets: Vec<usize>,
+    /// Average seconds between cover messages; 0 sends none
+    pub cover_secs: u64,
+}
+
+impl Default for PaddingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            buckets: vec![256, 1024, 4096],
+            cover_secs: 30,
+        }
+    }
+}
+
+impl PaddingConfig {
+    /// Size a message of `len` bytes is padded up to
+    fn bucket(&self, len: usize) -> usize {
+        if let Some(bucket) = self.buckets.iter().copied().filter(|&bucket| bucket >= len).min() {
+            return bucket;
+        }
+        match self.buckets.iter().copied().max().filter(|&largest| largest > 0) {
+            Some(largest) => len.div_ceil(largest) * largest,
+            None => len,
+        }
+    }
+
+    /// The text to seal for `text`: padded when the mode is on
+    pub fn pad(&self, text: &str) -> Zeroizing<String> {
+        let mut padded = Zeroizing::new(text.to_string());
+        if self.enabled {
+            let target = self.bucket(text.len());
+            padded.extend(std::iter::repeat_n('\0', target - text.len()));
+        }
+        padded
+    }
+
+    /// Time until the next cover message, between half and one and a half
+    /// times `cover_secs` so the cadence itself is not a signal; `None` when
+    /// no cover traffic is sent
+    pub fn next_cover(&self) -> Option<Duration> {
+        if !self.enabled || self.cover_secs == 0 {
+            return None;
+        }
+        let millis = self.cover_secs * 1000;
+        Some(Duration::from_millis(millis / 2 + OsRng.next_u64() % millis))
+    }
+}
+
+/// A random channel out of those a cover message could go to
+pub fn pick(mut channels: Vec<String>) -> Option<String> {
+    if channels.is_empty() {
+        return None;
+    }
+    let index = (OsRng.next_u64() % channels.len() as u64) as usize;
+    Some(channels.swap_remove(index))
+}
+
+/// A message as its sender typed it, without padding
+pub fn unpad(text: &str) -> &str {
+    text.trim_end_matches('\0')
+}
@@ -0,0 +1,190 @@
+// GhostWire Client - Command Palette
+// Ctrl+P lists every normal-mode action with its key and every slash command
+// with its usage. Typing filters the list with a fuzzy match: the query's
+// characters must appear in order, and runs and word starts rank higher.
+
+use crate::commands::{CommandSpec, COMMANDS};
+use crossterm::event::KeyCode;
+
+/// Something a normal-mode key does, runnable from the palette as well
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    EditMode,
+    CommandLine,
+    ScrollDown,
+    ScrollUp,
+    ScrollToBottom,
+    ToggleStar,
+    PreviousChannel,
+    NextChannel,
+    OpenChannel,
+    GlobalChannel,
+    LastChannel,
+    ToggleFavorite,
+    MoveChannelUp,
+    MoveChannelDown,
+    OpenDm,
+    Inspect,
+    CopyCode,
+    ToggleSpoilers,
+    Retry,
+    NextUser,
+    PreviousUser,
+    ToggleSection,
+    ToggleRoster,
+    ToggleLogs,
+}
+
+/// Description of an action, used for the palette
+pub struct ActionSpec {
+    pub action: Action,
+    /// Key(s) bound to it, as shown to the user
+    pub key: &'static str,
+    pub description: &'static str,
+}
+
+/// Registry of all key actions
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec { action: Action::EditMode, key: "i / Enter", description: "Write a message" },
+    ActionSpec { action: Action::CommandLine, key: ":", description: "Open the command line (:q, :set, :join ...)" },
+    ActionSpec { action: Action::ScrollUp, key: "k / ↑", description: "Select the previous message" },
+    ActionSpec { action: Action::ScrollDown, key: "j / ↓", description: "Select the next message" },
+    ActionSpec { action: Action::ScrollToBottom, key: "G", description: "Jump to the newest message" },
+    ActionSpec { action: Action::ToggleStar, key: "s", description: "Star or unstar the selected message" },
+    ActionSpec { action: Action::Inspect, key: "v", description: "Inspect the selected message's wire frame" },
+    ActionSpec { action: Action::CopyCode, key: "c", description: "Copy the selected message's code blocks" },
+    ActionSpec { action: Action::ToggleSpoilers, key: "x", description: "Reveal or hide spoilers in the selected message" },
+    ActionSpec { action: Action::Retry, key: "r", description: "Resend the selected message if it was not sent" },
+    ActionSpec { action: Action::PreviousChannel, key: "h / ←", description: "Highlight the previous channel" },
+    ActionSpec { action: Action::NextChannel, key: "l / →", description: "Highlight the next channel" },
+    ActionSpec { action: Action::OpenChannel, key: "Tab", description: "Switch to the highlighted channel" },
+    ActionSpec { action: Action::GlobalChannel, key: "#", description: "Switch to # global" },
+    ActionSpec { action: Action::ToggleFavorite, key: "f", description: "Add the highlighted channel to favorites, or remove it" },
+    ActionSpec { action: Action::MoveChannelUp, key: "H", description: "Move the highlighted channel up the sidebar" },
+    ActionSpec { action: Action::MoveChannelDown, key: "L", description: "Move the highlighted channel down the sidebar" },
+    ActionSpec { action: Action::LastChannel, key: "` / Alt+Tab", description: "Switch back to the previous channel" },
+    ActionSpec { action: Action::NextUser, key: "J", description: "Select the next user in the roster" },
+    ActionSpec { action: Action::PreviousUser, key: "K", description: "Select the previous user in the roster" },
+    ActionSpec { action: Action::ToggleSection, key: "z", description: "Fold or unfold the selected roster section" },
+    ActionSpec { action: Action::ToggleRoster, key: "u", description: "Show the channel's members or every user" },
+    ActionSpec { action: Action::OpenDm, key: "d", description: "Open a DM with the selected user, or type a name" },
+    ActionSpec { action: Action::ToggleLogs, key: "F12", description: "Show or hide the debug log panel" },
+    ActionSpec { action: Action::Quit, key: "q / Esc", description: "Quit" },
+];
+
+impl Action {
+    /// The action a key runs in normal mode (F12 works in every mode and is
+    /// handled before this)
+    pub fn for_key(key: KeyCode) -> Option<Self> {
+        let action = match key {
+            KeyCode::Char('q') | KeyCode::Esc => Self::Quit,
+            KeyCode::Char('i') | KeyCode::Enter => Self::EditMode,
+            KeyCode::Char(':') => Self::CommandLine,
+            KeyCode::Char('j') | KeyCode::Down => Self::ScrollDown,
+            KeyCode::Char('k') | KeyCode::Up => Self::ScrollUp,
+            KeyCode::Char('G') => Self::ScrollToBottom,
+            KeyCode::Char('s') => Self::ToggleStar,
+            KeyCode::Char('h') | KeyCode::Left => Self::PreviousChannel,
+            KeyCode::Char('l') | KeyCode::Right => Self::NextChannel,
+            KeyCode::Tab => Self::OpenChannel,
+            KeyCode::Char('#') => Self::GlobalChannel,
+            KeyCode::Char('`') => Self::LastChannel,
+            KeyCode::Char('f') => Self::ToggleFavorite,
+            KeyCode::Char('H') => Self::MoveChannelUp,
+            KeyCode::Char('L') => Self::MoveChannelDown,
+            KeyCode::Char('d') => Self::OpenDm,
+            KeyCode::Char('v') => Self::Inspect,
+            KeyCode::Char('c') => Self::CopyCode,
+            KeyCode::Char('x') => Self::ToggleSpoilers,
+            KeyCode::Char('r') => Self::Retry,
+            KeyCode::Char('J') => Self::NextUser,
+            KeyCode::Char('K') => Self::PreviousUser,
+            KeyCode::Char('z') => Self::ToggleSection,
+            KeyCode::Char('u') => Self::ToggleRoster,
+            _ => return None,
+        };
+        Some(action)
+    }
+}
+
+/// What choosing a palette row does
+#[derive(Clone, Copy)]
+pub enum Target {
+    Action(Action),
+    Command(&'static CommandSpec),
+}
+
+/// A palette row: what it does, and the key or usage shown next to it
+#[derive(Clone, Copy)]
+pub struct Entry {
+    pub target: Target,
+    pub description: &'static str,
+    pub hint: &'static str,
+}
+
+/// Every action and command matching `query`, best match first. An empty
+/// query lists everything in registry order.
+pub fn search(query: &str) -> Vec<Entry> {
+    let actions = ACTIONS.iter().map(|spec| Entry {
+        target: Target::Action(spec.action),
+        description: spec.description,
+        hint: spec.key,
+    });
+    let commands = COMMANDS.iter().map(|spec| Entry {
+        target: Target::Command(spec),
+        description: spec.description,
+        hint: spec.usage,
+    });
+
+    let mut scored: Vec<(i32, Entry)> = actions
+        .chain(commands)
+        .filter_map(|entry| {
+            let text = format!("{} {}", entry.description, entry.hint);
+            fuzzy_score(query, &text).map(|score| (score, entry))
+        })
+        .collect();
+    // Stable, so equal scores keep registry order
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Score `text` against `query` (case-insensitive, spaces ignored), or
+/// `None` if the query's characters do not all appear in order. Each place
+/// the first character occurs is tried as a start, keeping the best.
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let query: Vec<char> = query.to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+    let Some(&first) = query.first() else {
+        return Some(0);
+    };
+    (0..text.len())
+        .filter(|&start| text[start] == first)
+        .filter_map(|start| score_from(&query, &text, start))
+        .max()
+}
+
+/// Score a match that takes the query's first character at `start` and each
+/// later one at its next occurrence
+fn score_from(query: &[char], text: &[char], start: usize) -> Option<i32> {
+    let mut score = 0;
+    let mut from = start;
+    let mut previous: Option<usize> = None;
+    for &wanted in query {
+        let found = from + text[from..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        // Skipped characters cost a little, up to a point
+        if previous.is_some() {
+            score -= (found - from).min(3) as i32;
+        }
+        previous = Some(found);
+        from = found + 1;
+    }
+    Some(score)
+}
@@ -0,0 +1,350 @@
+// GhostWire Client - Double Ratchet
+// Signal-style double ratchet for DM sessions. Every message gets its own key
+// from a KDF chain, and the chains are reseeded with a fresh X25519 exchange
+// whenever the conversation changes direction, so nothing in the current
+// state decrypts earlier messages.
+
+use anyhow::{anyhow, Context};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use crate::replay::Replay;
+use ghostwire_protocol::e2e::Envelope;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::Sha256;
+use std::collections::VecDeque;
+use std::fmt;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Most message keys skipped in one chain, so a bogus counter cannot make
+/// us derive millions of keys
+const MAX_SKIP: u32 = 1000;
+
+/// Most skipped message keys kept for late messages; the oldest go first
+const MAX_SKIPPED_KEYS: usize = 2000;
+
+/// Peer ratchet keys remembered after their chain ended, to recognize replays
+const MAX_PAST_KEYS: usize = 16;
+
+/// 32 bytes of key material: an X25519 key or a chain key. Base64 when
+/// serialized, wiped from memory on drop.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key(pub [u8; 32]);
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_base64())
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = Zeroizing::new(String::deserialize(deserializer)?);
+        Key::from_base64(&text).ok_or_else(|| D::Error::custom("invalid key"))
+    }
+}
+
+impl Key {
+    /// A new random X25519 private key
+    pub fn random() -> Self {
+        Self(StaticSecret::random_from_rng(OsRng).to_bytes())
+    }
+
+    /// Decode a base64 key, as found in frames and saved sessions
+    pub fn from_base64(text: &str) -> Option<Self> {
+        let bytes = Zeroizing::new(BASE64.decode(text).ok()?);
+        let bytes: [u8; 32] = bytes.as_slice().try_into().ok()?;
+        Some(Self(bytes))
+    }
+
+    pub fn to_base64(&self) -> String {
+        BASE64.encode(self.0)
+    }
+
+    /// Public half of this private key
+    pub fn public(&self) -> Key {
+        Key(PublicKey::from(&StaticSecret::from(self.0)).to_bytes())
+    }
+
+    /// X25519 shared secret between this private key and a public key
+    fn dh(&self, public: &Key) -> Key {
+        let shared = StaticSecret::from(self.0).diffie_hellman(&PublicKey::from(public.0));
+        Key(shared.to_bytes())
+    }
+}
+
+/// Session secret for the side that sent the handshake init (3DH): both
+/// identity keys mixed with both ephemeral keys
+pub fn initiator_secret(identity: &Key, ephemeral: &Key, their_identity: &Key, their_ephemeral: &Key) -> Key {
+    derive_secret([
+        identity.dh(their_ephemeral),
+        ephemeral.dh(their_identity),
+        ephemeral.dh(their_ephemeral),
+    ])
+}
+
+/// Session secret for the side that answered the handshake init; equal to
+/// the initiator's
+pub fn responder_secret(identity: &Key, ephemeral: &Key, their_identity: &Key, their_ephemeral: &Key) -> Key {
+    derive_secret([
+        ephemeral.dh(their_identity),
+        identity.dh(their_ephemeral),
+        ephemeral.dh(their_ephemeral),
+    ])
+}
+
+fn derive_secret(shared: [Key; 3]) -> Key {
+    let mut ikm = Zeroizing::new(vec![0xFF; 32]);
+    for key in &shared {
+        ikm.extend_from_slice(&key.0);
+    }
+    let mut secret = Key([0; 32]);
+    Hkdf::<Sha256>::new(Some(&[0; 32]), &ikm)
+        .expand(b"GhostWire handshake", &mut secret.0)
+        .expect("32 bytes is a valid HKDF output length");
+    secret
+}
+
+/// Root KDF: new root key and chain key from a DH output
+fn kdf_rk(root: &Key, dh_out: &Key) -> (Key, Key) {
+    let mut okm = Zeroizing::new([0u8; 64]);
+    Hkdf::<Sha256>::new(Some(&root.0), &dh_out.0)
+        .expand(b"GhostWire ratchet", okm.as_mut())
+        .expect("64 bytes is a valid HKDF output length");
+    let mut root = Key([0; 32]);
+    let mut chain = Key([0; 32]);
+    root.0.copy_from_slice(&okm[..32]);
+    chain.0.copy_from_slice(&okm[32..]);
+    (root, chain)
+}
+
+/// Chain KDF: next chain key and this message's key
+fn kdf_ck(chain: &Key) -> (Key, Key) {
+    let derive = |byte: u8| {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&chain.0).expect("HMAC takes any key length");
+        mac.update(&[byte]);
+        Key(mac.finalize().into_bytes().into())
+    };
+    (derive(0x02), derive(0x01))
+}
+
+fn cipher(message_key: &Key) -> XChaCha20Poly1305 {
+    XChaCha20Poly1305::new(chacha20poly1305::Key::from_slice(&message_key.0))
+}
+
+/// A message key kept for a message that has not arrived yet
+#[derive(Clone, Serialize, Deserialize)]
+struct SkippedKey {
+    dh: Key,
+    n: u32,
+    key: Key,
+}
+
+/// Double ratchet state for one DM session
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Ratchet {
+    /// Our current ratchet private key
+    dhs: Key,
+    /// Peer's current ratchet public key
+    dhr: Option<Key>,
+    root: Key,
+    sending: Option<Key>,
+    receiving: Option<Key>,
+    /// Messages sent and received in the current chains
+    ns: u32,
+    nr: u32,
+    /// Length of our previous sending chain
+    pn: u32,
+    /// Both identity public keys, initiator first, authenticated with every message
+    identities: [Key; 2],
+    skipped: VecDeque<SkippedKey>,
+    /// Peer ratchet keys of earlier receiving chains, newest last
+    #[serde(default)]
+    past: VecDeque<Key>,
+}
+
+impl Ratchet {
+    /// State of the handshake initiator, who knows the responder's first
+    /// ratchet key and can send right away
+    pub fn initiator(secret: Key, their_ratchet: Key, identities: [Key; 2]) -> Self {
+        let dhs = Key::random();
+        let (root, sending) = kdf_rk(&secret, &dhs.dh(&their_ratchet));
+        Self {
+            dhs,
+            dhr: Some(their_ratchet),
+            root,
+            sending: Some(sending),
+            receiving: None,
+            ns: 0,
+            nr: 0,
+            pn: 0,
+            identities,
+            skipped: VecDeque::new(),
+            past: VecDeque::new(),
+        }
+    }
+
+    /// State of the handshake responder, who can send once the initiator's
+    /// first message has arrived
+    pub fn responder(secret: Key, ratchet_key: Key, identities: [Key; 2]) -> Self {
+        Self {
+            dhs: ratchet_key,
+            dhr: None,
+            root: secret,
+            sending: None,
+            receiving: None,
+            ns: 0,
+            nr: 0,
+            pn: 0,
+            identities,
+            skipped: VecDeque::new(),
+            past: VecDeque::new(),
+        }
+    }
+
+    /// Encrypt the next message in our sending chain
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> anyhow::Result<Envelope> {
+        let chain = self.sending.as_ref().context("Session cannot send yet")?;
+        let (next, message_key) = kdf_ck(chain);
+        let mut envelope = Envelope {
+            dh: self.dhs.public().to_base64(),
+            pn: self.pn,
+            n: self.ns,
+            nonce: String::new(),
+            ciphertext: String::new(),
+        };
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = self.aad(&envelope);
+        let ciphertext = cipher(&message_key)
+            .encrypt(&nonce, Payload { msg: plaintext, aad: &aad })
+            .map_err(|_| anyhow!("Encryption failed"))?;
+        envelope.nonce = BASE64.encode(nonce);
+        envelope.ciphertext = BASE64.encode(ciphertext);
+
+        self.sending = Some(next);
+        self.ns += 1;
+        Ok(envelope)
+    }
+
+    /// Decrypt a message. The state only advances if it authenticates, so
+    /// forged or corrupted messages cannot desynchronize the session.
+    pub fn decrypt(&mut self, envelope: &Envelope) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+        let mut next = self.clone();
+        let plaintext = next.advance(envelope)?;
+        *self = next;
+        Ok(plaintext)
+    }
+
+    fn advance(&mut self, envelope: &Envelope) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+        let dh = Key::from_base64(&envelope.dh).context("Invalid ratchet key")?;
+
+        // A message we skipped over earlier
+        if let Some(i) = self.skipped.iter().position(|k| k.dh == dh && k.n == envelope.n) {
+            let skipped = self.skipped.remove(i).expect("index from position");
+            return self.open(&skipped.key, envelope);
+        }
+
+        // Keys of messages already received are gone; a relay resending one
+        // must not look like a new chain either
+        let replayed = match &self.dhr {
+            Some(current) if *current == dh => envelope.n < self.nr,
+            _ => self.past.contains(&dh),
+        };
+        if replayed {
+            return Err(Replay::Duplicate.into());
+        }
+
+        // The peer has a new ratchet key: finish the old chain, then step
+        if self.dhr.as_ref() != Some(&dh) {
+            self.skip_to(envelope.pn)?;
+            self.dh_step(dh);
+        }
+        self.skip_to(envelope.n)?;
+
+        let chain = self.receiving.as_ref().context("No receiving chain")?;
+        let (next, message_key) = kdf_ck(chain);
+        self.receiving = Some(next);
+        self.nr += 1;
+        self.open(&message_key, envelope)
+    }
+
+    /// Derive and keep the keys of messages before `until` in the receiving chain
+    fn skip_to(&mut self, until: u32) -> anyhow::Result<()> {
+        let (Some(mut chain), Some(dh)) = (self.receiving.clone(), self.dhr.clone()) else {
+            return Ok(());
+        };
+        if until > self.nr.saturating_add(MAX_SKIP) {
+            return Err(anyhow!("Too many skipped messages"));
+        }
+        while self.nr < until {
+            let (next, key) = kdf_ck(&chain);
+            self.skipped.push_back(SkippedKey { dh: dh.clone(), n: self.nr, key });
+            if self.skipped.len() > MAX_SKIPPED_KEYS {
+                self.skipped.pop_front();
+            }
+            chain = next;
+            self.nr += 1;
+        }
+        self.receiving = Some(chain);
+        Ok(())
+    }
+
+    /// DH ratchet step: new receiving chain from the peer's key, then a new
+    /// key pair of ours and a new sending chain
+    fn dh_step(&mut self, dh: Key) {
+        if let Some(old) = self.dhr.take() {
+            self.past.push_back(old);
+            if self.past.len() > MAX_PAST_KEYS {
+                self.past.pop_front();
+            }
+        }
+        self.pn = self.ns;
+        self.ns = 0;
+        self.nr = 0;
+        let (root, receiving) = kdf_rk(&self.root, &self.dhs.dh(&dh));
+        self.dhs = Key::random();
+        let (root, sending) = kdf_rk(&root, &self.dhs.dh(&dh));
+        self.root = root;
+        self.receiving = Some(receiving);
+        self.sending = Some(sending);
+        self.dhr = Some(dh);
+    }
+
+    fn open(&self, message_key: &Key, envelope: &Envelope) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+        let nonce = BASE64.decode(&envelope.nonce).context("Invalid nonce")?;
+        let ciphertext = BASE64.decode(&envelope.ciphertext).context("Invalid ciphertext")?;
+        if nonce.len() != 24 {
+            return Err(anyhow!("Invalid nonce length"));
+        }
+        let aad = self.aad(envelope);
+        cipher(message_key)
+            .decrypt(XNonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &aad })
+            .map(Zeroizing::new)
+            .map_err(|_| anyhow!("Message failed authentication"))
+    }
+
+    fn aad(&self, envelope: &Envelope) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(64 + envelope.dh.len() + 8);
+        aad.extend_from_slice(&self.identities[0].0);
+        aad.extend_from_slice(&self.identities[1].0);
+        aad.extend_from_slice(&envelope.header_bytes());
+        aad
+    }
+}
@@ -0,0 +1,170 @@
+// GhostWire Client - Multiple Relays
+// Extra relays from `[[relays]]` in config.toml run their own network task.
+// Their channels are namespaced `name/channel` in the app; the router below
+// strips the namespace from outgoing commands and tags incoming events.
+
+use crate::network::{self, Handshake, NetworkCommand, NetworkEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// An additional relay to stay connected to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayConfig {
+    /// Namespace for this relay's channels (letters, digits, `-` and `_`)
+    pub name: String,
+    /// Relay WebSocket URL
+    pub url: String,
+}
+
+impl RelayConfig {
+    /// Names end up in channel IDs, so keep them to a safe character set
+    fn is_valid(&self) -> bool {
+        !self.name.is_empty()
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+    }
+}
+
+/// Split a channel ID into its relay namespace (`None` for the primary relay)
+/// and the channel ID on that relay's wire
+pub fn split_channel(channel_id: &str) -> (Option<&str>, &str) {
+    match channel_id.split_once('/') {
+        Some((relay, channel)) => (Some(relay), channel),
+        None => (None, channel_id),
+    }
+}
+
+/// Start a network task per extra relay plus a router in front of them all.
+///
+/// Returns the sender the UI uses for every command; commands for `name/...`
+/// channels go to that relay, everything else to the primary. Extra relays
+/// only get the client version header: tokens and custom headers are meant
+/// for the primary relay.
+pub fn spawn(
+    relays: &[RelayConfig],
+    username: &str,
+    primary_tx: mpsc::UnboundedSender<NetworkCommand>,
+    event_tx: mpsc::UnboundedSender<NetworkEvent>,
+) -> mpsc::UnboundedSender<NetworkCommand> {
+    let mut extra: HashMap<String, mpsc::UnboundedSender<NetworkCommand>> = HashMap::new();
+    for relay in relays {
+        if !relay.is_valid() || extra.contains_key(&relay.name) {
+            warn!("Skipping relay with invalid or duplicate name {:?}", relay.name);
+            continue;
+        }
+
+        let (relay_event_tx, mut relay_event_rx) = mpsc::unbounded_channel();
+        let (relay_command_tx, relay_command_rx) = mpsc::unbounded_channel();
+        tokio::spawn(network::network_task(
+            vec![relay.url.clone()],
+            username.to_string(),
+            Handshake::default(),
+            relay_event_tx,
+            relay_command_rx,
+        ));
+
+        // Tag this relay's events so the UI can namespace them
+        let name = relay.name.clone();
+        let event_tx = event_tx.clone();
+        tokio::spawn(async move {
+            while let Some(event) = relay_event_rx.recv().await {
+                let tagged = NetworkEvent::FromRelay {
+                    relay: name.clone(),
+                    event: Box::new(event),
+                };
+                if event_tx.send(tagged).is_err() {
+                    break;
+                }
+            }
+        });
+
+        extra.insert(relay.name.clone(), relay_command_tx);
+    }
+
+    let (command_tx, mut command_rx) = mpsc::unbounded_channel::<NetworkCommand>();
+    tokio::spawn(async move {
+        while let Some(command) = command_rx.recv().await {
+            route(command, &primary_tx, &extra);
+        }
+    });
+    command_tx
+}
+
+/// Send a command to the relay owning its channel
+fn route(
+    command: NetworkCommand,
+    primary: &mpsc::UnboundedSender<NetworkCommand>,
+    extra: &HashMap<String, mpsc::UnboundedSender<NetworkCommand>>,
+) {
+    let (target, command) = match command {
+        NetworkCommand::SendMessage { id, content, channel_id, encrypted, action } => {
+            let (relay, channel) = split_channel(&channel_id);
+            let command = NetworkCommand::SendMessage {
+                id,
+                content,
+                channel_id: channel.to_string(),
+                encrypted,
+                action,
+            };
+            (relay.map(str::to_string), command)
+        }
+        NetworkCommand::SetPin { channel_id, message_id, pinned } => {
+            let (relay, channel) = split_channel(&channel_id);
+            let command = NetworkCommand::SetPin {
+                channel_id: channel.to_string(),
+                message_id,
+                pinned,
+            };
+            (relay.map(str::to_string), command)
+        }
+        NetworkCommand::Group { channel_id, event } => {
+            let (relay, channel) = split_channel(&channel_id);
+            let command = NetworkCommand::Group {
+                channel_id: channel.to_string(),
+                event,
+            };
+            (relay.map(str::to_string), command)
+        }
+        NetworkCommand::KeyExchange { channel_id, exchange } => {
+            let (relay, channel) = split_channel(&channel_id);
+            let command = NetworkCommand::KeyExchange {
+                channel_id: channel.to_string(),
+                exchange,
+            };
+            (relay.map(str::to_string), command)
+        }
+        NetworkCommand::SendReceipt { channel_id, message_id, read } => {
+            let (relay, channel) = split_channel(&channel_id);
+            let command = NetworkCommand::SendReceipt {
+                channel_id: channel.to_string(),
+                message_id,
+                read,
+            };
+            (relay.map(str::to_string), command)
+        }
+        NetworkCommand::Disconnect => {
+            for tx in extra.values() {
+                let _ = tx.send(NetworkCommand::Disconnect);
+            }
+            (None, NetworkCommand::Disconnect)
+        }
+        // Roster and renames concern the primary relay
+        other => (None, other),
+    };
+
+    let tx = match &target {
+        Some(relay) => match extra.get(relay) {
+            Some(tx) => tx,
+            None => {
+                warn!("No relay named {:?}", relay);
+                return;
+            }
+        },
+        None => primary,
+    };
+    let _ = tx.send(command);
+}
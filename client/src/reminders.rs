@@ -0,0 +1,55 @@
+// GhostWire Client - Reminders
+// `/remind 30m check the oven` posts the text back into the channel it was set
+// in once the interval is up. Reminders never leave this machine; they are
+// kept in `<data dir>/ghostwire/reminders.json`, so one that falls due while
+// the client is closed is posted at the next start.
+
+use crate::storage;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// File name of the reminder store inside the data directory
+const REMINDERS_FILE: &str = "reminders.json";
+
+/// Maximum length of a reminder's text, in characters
+pub const MAX_REMINDER_LEN: usize = 500;
+
+/// A reminder waiting to be posted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reminder {
+    /// Channel the reminder was set in, and is posted back into
+    pub channel: String,
+    pub text: String,
+    pub due: DateTime<Utc>,
+}
+
+/// All pending reminders, soonest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Reminders(pub Vec<Reminder>);
+
+impl Reminders {
+    /// Load pending reminders from the data directory
+    pub fn load() -> Self {
+        let mut reminders: Self = storage::load_json(REMINDERS_FILE);
+        reminders.0.sort_by_key(|reminder| reminder.due);
+        reminders
+    }
+
+    /// Write pending reminders back to the data directory
+    pub fn save(&self) -> anyhow::Result<()> {
+        storage::save_json(REMINDERS_FILE, self)
+    }
+
+    /// Add a reminder, keeping the list ordered by due time
+    pub fn add(&mut self, reminder: Reminder) {
+        let at = self.0.partition_point(|pending| pending.due <= reminder.due);
+        self.0.insert(at, reminder);
+    }
+
+    /// Remove and return the reminders due at `now`
+    pub fn take_due(&mut self, now: DateTime<Utc>) -> Vec<Reminder> {
+        let due = self.0.partition_point(|pending| pending.due <= now);
+        self.0.drain(..due).collect()
+    }
+}
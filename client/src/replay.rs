@@ -0,0 +1,74 @@
+// GhostWire Client - Replay Protection
+// A relay can resend ciphertext it forwarded earlier, under a new message ID
+// so ID deduplication does not catch it. DM ratchets refuse message keys they
+// already used; passphrase rooms have no such state, so each room keeps the
+// nonces it has opened within a time window and refuses messages sealed
+// outside of it. Refused messages are dropped and counted in telemetry.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+/// Seconds a room message's send time may be away from our clock
+pub const ROOM_WINDOW_SECS: i64 = 10 * 60;
+
+/// Most nonces remembered per room; the oldest are forgotten first
+const MAX_NONCES: usize = 4096;
+
+/// Why an encrypted message was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replay {
+    /// It was opened before
+    Duplicate,
+    /// It was sealed outside the replay window
+    Stale,
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Replay::Duplicate => write!(f, "Message was already received (replayed)"),
+            Replay::Stale => write!(f, "Message was sealed outside the replay window"),
+        }
+    }
+}
+
+impl std::error::Error for Replay {}
+
+/// Whether an error from opening a message means it was a replay
+pub fn is_replay(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<Replay>().is_some()
+}
+
+/// Nonces of the messages a room opened recently
+#[derive(Debug, Default)]
+pub struct ReplayWindow {
+    seen: HashSet<String>,
+    /// Nonces with the time they were opened, oldest first
+    order: VecDeque<(i64, String)>,
+}
+
+impl ReplayWindow {
+    /// Accept a message that authenticated, sealed at `sent` (None for
+    /// clients that do not send it) with `nonce`, or refuse it as a replay
+    pub fn check(&mut self, sent: Option<i64>, nonce: &str, now: i64) -> Result<(), Replay> {
+        if sent.is_some_and(|sent| (now - sent).abs() > ROOM_WINDOW_SECS) {
+            return Err(Replay::Stale);
+        }
+        if self.seen.contains(nonce) {
+            return Err(Replay::Duplicate);
+        }
+
+        // Anything opened two windows ago is refused as stale anyway
+        while let Some((opened, _)) = self.order.front() {
+            if now - opened <= 2 * ROOM_WINDOW_SECS && self.order.len() < MAX_NONCES {
+                break;
+            }
+            if let Some((_, old)) = self.order.pop_front() {
+                self.seen.remove(&old);
+            }
+        }
+        self.seen.insert(nonce.to_string());
+        self.order.push_back((now, nonce.to_string()));
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+// GhostWire Client - Untrusted Text Sanitization
+// Peers control message payloads and sender names. Escape sequences in them
+// could recolor, move the cursor, retitle, or otherwise spoof the terminal,
+// so they are stripped before anything is stored or rendered.
+
+/// ASCII escape, the start of every ANSI/OSC sequence
+const ESC: char = '\u{1b}';
+
+/// Result of sanitizing a piece of untrusted text
+pub struct Sanitized {
+    /// Text with all control sequences removed
+    pub text: String,
+    /// Whether anything other than plain whitespace was stripped
+    pub modified: bool,
+}
+
+/// Strip ANSI/OSC escape sequences, control characters, and bidi overrides.
+///
+/// Newlines and tabs are turned into spaces without counting as a modification.
+pub fn sanitize(input: &str) -> Sanitized {
+    strip(input, false)
+}
+
+/// Like `sanitize`, but keeps line breaks (`\r\n` and `\r` become `\n`) and expands
+/// tabs to four spaces, for message bodies that may hold code
+pub fn sanitize_multiline(input: &str) -> Sanitized {
+    strip(input, true)
+}
+
+fn strip(input: &str, multiline: bool) -> Sanitized {
+    let mut text = String::with_capacity(input.len());
+    let mut modified = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ESC => {
+                modified = true;
+                match chars.next() {
+                    // CSI: ESC [ params... final byte in 0x40..=0x7E
+                    Some('[') => {
+                        for c in chars.by_ref() {
+                            if ('\u{40}'..='\u{7e}').contains(&c) {
+                                break;
+                            }
+                        }
+                    }
+                    // OSC/DCS/APC/PM/SOS: terminated by BEL or ST (ESC \)
+                    Some(']' | 'P' | '_' | '^' | 'X') => {
+                        while let Some(c) = chars.next() {
+                            if c == '\u{07}' {
+                                break;
+                            }
+                            if c == ESC && chars.peek() == Some(&'\\') {
+                                chars.next();
+                                break;
+                            }
+                        }
+                    }
+                    // Two-character escape (or a trailing lone ESC)
+                    _ => {}
+                }
+            }
+            '\n' if multiline => text.push('\n'),
+            '\r' if multiline && chars.peek() == Some(&'\n') => {}
+            '\r' if multiline => text.push('\n'),
+            '\t' if multiline => text.push_str("    "),
+            '\n' | '\r' | '\t' => text.push(' '),
+            c if c.is_control() || is_bidi_control(c) => modified = true,
+            c => text.push(c),
+        }
+    }
+
+    Sanitized { text, modified }
+}
+
+/// Unicode bidi embedding/override/isolate controls, usable to visually
+/// reorder text (e.g. disguise a sender name or link)
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
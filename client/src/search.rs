@@ -0,0 +1,74 @@
+// GhostWire Client - Global Search
+// `/search <text>` looks for the text, ignoring case, in the messages of
+// every open channel: those in the buffer and the stored history that no
+// longer fits in it. Hits are listed newest first in a popup; Enter switches
+// to the hit's channel and selects the message, reading older pages of the
+// stored history first if it is not in the buffer.
+
+use crate::app::ChatMessage;
+use crate::export::TranscriptMessage;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+/// Most hits listed; older ones are left out
+pub const MAX_HITS: usize = 200;
+
+/// A message that contains the search text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    /// Channel the message is in
+    pub channel_id: String,
+    /// Wire message ID, if the sender provided one
+    pub id: Option<String>,
+    pub sender: Arc<str>,
+    pub content: Arc<str>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl SearchHit {
+    /// Hit for a buffered message
+    pub fn buffered(channel_id: &str, msg: &ChatMessage) -> Self {
+        Self {
+            channel_id: channel_id.to_string(),
+            id: msg.id.clone(),
+            sender: msg.sender.clone(),
+            content: msg.content.clone(),
+            timestamp: msg.timestamp,
+        }
+    }
+
+    /// Hit for a message in the stored history
+    pub fn stored(channel_id: &str, msg: &TranscriptMessage) -> Self {
+        Self {
+            channel_id: channel_id.to_string(),
+            id: msg.id.clone(),
+            sender: msg.sender.clone(),
+            content: msg.content.clone(),
+            timestamp: msg.timestamp,
+        }
+    }
+
+    /// Whether a message is the one this hit found: by ID, or by time,
+    /// sender and text for messages without one
+    pub fn is(&self, id: Option<&str>, timestamp: DateTime<Utc>, sender: &str, content: &str) -> bool {
+        match (self.id.as_deref(), id) {
+            (Some(ours), Some(theirs)) => ours == theirs,
+            _ => self.timestamp == timestamp && *self.sender == *sender && *self.content == *content,
+        }
+    }
+
+    /// Whether a buffered message is the one this hit found
+    pub fn is_message(&self, msg: &ChatMessage) -> bool {
+        self.is(msg.id.as_deref(), msg.timestamp, &msg.sender, &msg.content)
+    }
+
+    /// Whether a stored message is the one this hit found
+    pub fn is_stored(&self, msg: &TranscriptMessage) -> bool {
+        self.is(msg.id.as_deref(), msg.timestamp, &msg.sender, &msg.content)
+    }
+}
+
+/// Whether `text` contains `needle`, which must already be lowercase
+pub fn matches(needle: &str, text: &str) -> bool {
+    text.to_lowercase().contains(needle)
+}
@@ -0,0 +1,157 @@
+// GhostWire Client - Secret Storage
+// Identity keys and relay auth tokens live in the OS keyring. Headless systems
+// without a keyring fall back to a passphrase-encrypted file in the data dir.
+
+use crate::crypto::SealedBox;
+use anyhow::Context;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Service name under which keyring entries are stored
+const KEYRING_SERVICE: &str = "ghostwire";
+
+/// File name of the encrypted fallback store
+const SECRETS_FILE: &str = "secrets.json";
+
+/// Environment variable that supplies the fallback passphrase non-interactively
+const PASSPHRASE_ENV: &str = "GHOSTWIRE_PASSPHRASE";
+
+/// Secret name of the relay auth token
+pub const RELAY_TOKEN: &str = "relay_token";
+
+/// Environment variable that overrides the stored relay token
+const TOKEN_ENV: &str = "GHOSTWIRE_TOKEN";
+
+/// Decrypted contents of the fallback file; values are wiped on drop
+#[derive(Default, Serialize, Deserialize)]
+#[serde(transparent)]
+struct SecretMap(BTreeMap<String, String>);
+
+impl Drop for SecretMap {
+    fn drop(&mut self) {
+        self.0.values_mut().for_each(Zeroize::zeroize);
+    }
+}
+
+/// Where secrets are kept
+pub enum SecretStore {
+    /// OS keyring (Keychain, Credential Manager, kernel keyutils)
+    Keyring,
+    /// Argon2-passphrase-encrypted JSON file
+    EncryptedFile { path: PathBuf, passphrase: SecretString },
+}
+
+impl SecretStore {
+    /// Open the best available store.
+    ///
+    /// Uses the OS keyring when it works, otherwise the encrypted file with a
+    /// passphrase from `GHOSTWIRE_PASSPHRASE` or an interactive prompt. Must be
+    /// called before the TUI takes over the terminal.
+    pub fn open() -> anyhow::Result<Self> {
+        if keyring_available() {
+            return Ok(SecretStore::Keyring);
+        }
+
+        // SecretString takes ownership, so no plaintext copy of the passphrase lingers
+        let passphrase: SecretString = match std::env::var(PASSPHRASE_ENV) {
+            Ok(passphrase) => passphrase.into(),
+            Err(_) => rpassword::prompt_password("GhostWire secret store passphrase: ")?.into(),
+        };
+        Ok(SecretStore::EncryptedFile {
+            path: default_secrets_path()?,
+            passphrase,
+        })
+    }
+
+    /// Read a secret, returning `None` if it has never been stored
+    pub fn get(&self, name: &str) -> anyhow::Result<Option<SecretString>> {
+        match self {
+            SecretStore::Keyring => match keyring::Entry::new(KEYRING_SERVICE, name)?.get_password() {
+                Ok(secret) => Ok(Some(secret.into())),
+                Err(keyring::Error::NoEntry) => Ok(None),
+                Err(e) => Err(e.into()),
+            },
+            SecretStore::EncryptedFile { path, passphrase } => {
+                let secrets = load_file(path, passphrase)?;
+                Ok(secrets.0.get(name).map(|secret| secret.as_str().into()))
+            }
+        }
+    }
+
+    /// Store (or replace) a secret
+    pub fn set(&self, name: &str, secret: &str) -> anyhow::Result<()> {
+        match self {
+            SecretStore::Keyring => {
+                keyring::Entry::new(KEYRING_SERVICE, name)?.set_password(secret)?;
+                Ok(())
+            }
+            SecretStore::EncryptedFile { path, passphrase } => {
+                let mut secrets = load_file(path, passphrase)?;
+                if let Some(mut old) = secrets.0.insert(name.to_string(), secret.to_string()) {
+                    old.zeroize();
+                }
+                save_file(path, passphrase, &secrets)
+            }
+        }
+    }
+}
+
+/// Relay token for the WebSocket upgrade: `GHOSTWIRE_TOKEN` if set, otherwise
+/// the stored `relay_token` when a store is given (`relay_auth` in config.toml)
+pub fn relay_token(store: Option<&SecretStore>) -> anyhow::Result<Option<SecretString>> {
+    if let Ok(token) = std::env::var(TOKEN_ENV) {
+        return Ok(Some(token.into()));
+    }
+    let Some(store) = store else {
+        return Ok(None);
+    };
+    let token = store.get(RELAY_TOKEN)?;
+    if token.is_none() {
+        tracing::warn!("relay_auth is on but no relay token is stored (use --save-token)");
+    }
+    Ok(token)
+}
+
+/// Probe the keyring with a lookup; a missing entry still means it works
+fn keyring_available() -> bool {
+    matches!(
+        keyring::Entry::new(KEYRING_SERVICE, "probe").and_then(|entry| entry.get_password()),
+        Ok(_) | Err(keyring::Error::NoEntry)
+    )
+}
+
+/// `<data dir>/ghostwire/secrets.json`
+fn default_secrets_path() -> anyhow::Result<PathBuf> {
+    let dir = dirs::data_dir().context("No data directory on this platform")?;
+    Ok(dir.join("ghostwire").join(SECRETS_FILE))
+}
+
+fn load_file(path: &PathBuf, passphrase: &SecretString) -> anyhow::Result<SecretMap> {
+    if !path.exists() {
+        return Ok(SecretMap::default());
+    }
+    let sealed: SealedBox = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let plaintext = sealed.open(passphrase.expose_secret())?;
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+fn save_file(path: &PathBuf, passphrase: &SecretString, secrets: &SecretMap) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let plaintext = Zeroizing::new(serde_json::to_vec(secrets)?);
+    let sealed = SealedBox::seal(passphrase.expose_secret(), &plaintext)?;
+    fs::write(path, serde_json::to_string_pretty(&sealed)?)?;
+
+    // Ciphertext only, but keep it private anyway
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,52 @@
+// GhostWire Client - Session State
+// Open channels, the active channel, read markers and scroll anchors, saved
+// to `<data dir>/ghostwire/session.json` on exit and restored at startup so
+// the client picks up where it left off. Messages are kept by `history.rs`.
+
+use crate::app::ChannelType;
+use crate::storage;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// File name of the session state inside the data directory
+const SESSION_FILE: &str = "session.json";
+
+/// What is restored at the next launch
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionState {
+    /// Who the state belongs to; DM channel IDs embed the username, so
+    /// state saved under another name is not restored
+    pub username: String,
+    pub active_channel: String,
+    pub channels: Vec<SavedChannel>,
+}
+
+/// A channel as it was left
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedChannel {
+    pub id: String,
+    pub channel_type: ChannelType,
+    /// Timestamp of the newest message that had been seen
+    #[serde(default)]
+    pub read_marker: Option<DateTime<Utc>>,
+    /// ID of the selected message, if the channel was scrolled up
+    #[serde(default)]
+    pub scroll_anchor: Option<String>,
+    #[serde(default)]
+    pub hide_system: Option<bool>,
+    /// Messages here were encrypted at some point, so plaintext is confirmed
+    #[serde(default)]
+    pub encrypted_before: bool,
+}
+
+impl SessionState {
+    /// Load the saved session from the data directory
+    pub fn load() -> Self {
+        storage::load_json(SESSION_FILE)
+    }
+
+    /// Write the session to the data directory
+    pub fn save(&self) -> anyhow::Result<()> {
+        storage::save_json(SESSION_FILE, self)
+    }
+}
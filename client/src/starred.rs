@@ -0,0 +1,72 @@
+// GhostWire Client - Starred Messages
+// Local-only bookmarks, copied out of their channel so they survive the
+// message buffer and restarts. Stored in `<data dir>/ghostwire/starred.json`.
+
+use crate::app::ChatMessage;
+use crate::storage;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// File name of the starred store inside the data directory
+const STARRED_FILE: &str = "starred.json";
+
+/// A starred copy of a chat message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StarredMessage {
+    /// Channel the message was posted in
+    pub channel: String,
+    /// Wire message ID, if the sender provided one
+    pub id: Option<String>,
+    pub sender: Arc<str>,
+    /// Shares the text of the message it was copied from
+    pub content: Arc<str>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl StarredMessage {
+    /// Copy a message posted in `channel`
+    pub fn from_message(channel: &str, msg: &ChatMessage) -> Self {
+        Self {
+            channel: channel.to_string(),
+            id: msg.id.clone(),
+            sender: msg.sender.clone(),
+            content: msg.content.clone(),
+            timestamp: msg.timestamp,
+        }
+    }
+
+    /// Whether this is a copy of `msg`
+    pub fn matches(&self, msg: &ChatMessage) -> bool {
+        match (&self.id, &msg.id) {
+            (Some(a), Some(b)) => a == b,
+            _ => {
+                self.sender == msg.sender
+                    && self.timestamp == msg.timestamp
+                    && self.content == msg.content
+            }
+        }
+    }
+}
+
+/// All starred messages, oldest star first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Starred(pub Vec<StarredMessage>);
+
+impl Starred {
+    /// Load starred messages from the data directory
+    pub fn load() -> Self {
+        storage::load_json(STARRED_FILE)
+    }
+
+    /// Write starred messages back to the data directory
+    pub fn save(&self) -> anyhow::Result<()> {
+        storage::save_json(STARRED_FILE, self)
+    }
+
+    /// Whether a copy of `msg` is starred
+    pub fn contains(&self, msg: &ChatMessage) -> bool {
+        self.0.iter().any(|starred| starred.matches(msg))
+    }
+}
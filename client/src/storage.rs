@@ -0,0 +1,224 @@
+// GhostWire Client - Local Storage
+// Small JSON files in `<data dir>/ghostwire/` for state that outlives a session
+// (aliases, starred messages, message history). Nothing here is ever sent to the relay. Once
+// unlocked, files are encrypted at rest with a key kept in the secret store;
+// in ephemeral mode nothing is read or written at all.
+
+use crate::secrets::SecretStore;
+use anyhow::{anyhow, Context};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use secrecy::ExposeSecret;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+/// Secret store entry holding the data file key (base64)
+const STORAGE_SECRET: &str = "storage_key";
+
+/// Data file key, set once at startup by `unlock`
+struct StorageKey {
+    key: Zeroizing<[u8; 32]>,
+    /// Encrypt files when saving (`encrypt_storage`); sealed files are
+    /// always readable, so turning it off moves files back to plaintext
+    seal: bool,
+}
+
+static STORAGE_KEY: OnceLock<StorageKey> = OnceLock::new();
+
+/// Set by `disable` for `--ephemeral`
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Stop all reads and writes of data files for the rest of the process
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// On-disk form of an encrypted data file
+#[derive(Serialize, Deserialize)]
+struct SealedFile {
+    /// Format version, bumped if the cipher changes
+    sealed: u8,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Load the data file key from the secret store, creating it on first run.
+/// With `seal`, files are encrypted from now on; plaintext files written
+/// before are still read and are encrypted the next time they are saved.
+pub fn unlock(store: &SecretStore, seal: bool) -> anyhow::Result<()> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    match store.get(STORAGE_SECRET)? {
+        Some(secret) => {
+            let bytes = Zeroizing::new(BASE64.decode(secret.expose_secret()).context("Stored storage key is invalid")?);
+            if bytes.len() != key.len() {
+                return Err(anyhow!("Stored storage key has the wrong length"));
+            }
+            key.copy_from_slice(&bytes);
+        }
+        None => {
+            chacha20poly1305::aead::rand_core::RngCore::fill_bytes(&mut OsRng, key.as_mut());
+            store.set(STORAGE_SECRET, &BASE64.encode(key.as_ref()))?;
+            info!("Created a new storage key");
+        }
+    }
+    let _ = STORAGE_KEY.set(StorageKey { key, seal });
+    Ok(())
+}
+
+fn cipher(key: &Zeroizing<[u8; 32]>) -> XChaCha20Poly1305 {
+    XChaCha20Poly1305::new(chacha20poly1305::Key::from_slice(key.as_ref()))
+}
+
+/// Decrypt a sealed file's JSON, or pass plaintext JSON through
+fn open(text: String) -> anyhow::Result<Zeroizing<String>> {
+    let Ok(sealed) = serde_json::from_str::<SealedFile>(&text) else {
+        return Ok(Zeroizing::new(text));
+    };
+    let key = &STORAGE_KEY.get().context("File is encrypted but storage is locked")?.key;
+    if sealed.sealed != 1 {
+        return Err(anyhow!("Unsupported encrypted file version {}", sealed.sealed));
+    }
+    let nonce = BASE64.decode(&sealed.nonce).context("Invalid nonce")?;
+    let ciphertext = BASE64.decode(&sealed.ciphertext).context("Invalid ciphertext")?;
+    if nonce.len() != 24 {
+        return Err(anyhow!("Invalid nonce length"));
+    }
+    let plaintext = cipher(key)
+        .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+        .map(Zeroizing::new)
+        .map_err(|_| anyhow!("File was encrypted with a different storage key"))?;
+    Ok(Zeroizing::new(String::from_utf8(plaintext.to_vec()).context("File is not valid UTF-8")?))
+}
+
+/// Encrypt JSON for writing if storage is unlocked
+fn seal(json: Zeroizing<String>) -> anyhow::Result<String> {
+    let Some(StorageKey { key, seal: true }) = STORAGE_KEY.get() else {
+        return Ok(json.to_string());
+    };
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher(key)
+        .encrypt(&nonce, json.as_bytes())
+        .map_err(|_| anyhow!("Encryption failed"))?;
+    let sealed = SealedFile {
+        sealed: 1,
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    Ok(serde_json::to_string_pretty(&sealed)?)
+}
+
+/// `<data dir>/ghostwire/<file>`
+pub fn data_path(file: &str) -> anyhow::Result<PathBuf> {
+    let dir = dirs::data_dir().context("No data directory on this platform")?;
+    Ok(dir.join("ghostwire").join(file))
+}
+
+/// Load a JSON file from the data directory.
+///
+/// A missing file yields the default; an unreadable, undecryptable or invalid
+/// file is logged and also falls back to the default so the client still starts.
+pub fn load_json<T: DeserializeOwned + Default>(file: &str) -> T {
+    if DISABLED.load(Ordering::Relaxed) {
+        return T::default();
+    }
+    let Ok(path) = data_path(file) else {
+        return T::default();
+    };
+    if !path.exists() {
+        return T::default();
+    }
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Failed to read {}: {}", path.display(), e);
+            return T::default();
+        }
+    };
+
+    // Keep files we cannot decrypt out of the way rather than overwrite them
+    let json = match open(text) {
+        Ok(json) => json,
+        Err(e) => {
+            let aside = path.with_extension("json.locked");
+            warn!("Cannot open {} ({}); moving it to {}", path.display(), e, aside.display());
+            let _ = fs::rename(&path, &aside);
+            return T::default();
+        }
+    };
+
+    serde_json::from_str(&json).unwrap_or_else(|e| {
+        warn!("Invalid {}: {}", path.display(), e);
+        T::default()
+    })
+}
+
+/// Names of the JSON files in a subdirectory of the data directory, without
+/// their extension; empty once storage is disabled
+pub fn list_json(dir: &str) -> Vec<String> {
+    if DISABLED.load(Ordering::Relaxed) {
+        return Vec::new();
+    }
+    let Ok(entries) = data_path(dir).and_then(|path| Ok(fs::read_dir(path)?)) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".json").map(str::to_string))
+        .collect()
+}
+
+/// Names of the subdirectories of a subdirectory of the data directory;
+/// empty once storage is disabled
+pub fn list_dirs(dir: &str) -> Vec<String> {
+    if DISABLED.load(Ordering::Relaxed) {
+        return Vec::new();
+    }
+    let Ok(entries) = data_path(dir).and_then(|path| Ok(fs::read_dir(path)?)) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+        .collect()
+}
+
+/// Delete a file or an empty directory from the data directory if it
+/// exists; a no-op once storage is disabled
+pub fn remove(file: &str) -> anyhow::Result<()> {
+    if DISABLED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let path = data_path(file)?;
+    let removed = if path.is_dir() { fs::remove_dir(&path) } else { fs::remove_file(&path) };
+    match removed {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Write a JSON file to the data directory, creating it if needed; a no-op
+/// once storage is disabled
+pub fn save_json<T: Serialize>(file: &str, value: &T) -> anyhow::Result<()> {
+    if DISABLED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let path = data_path(file)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = Zeroizing::new(serde_json::to_string_pretty(value)?);
+    fs::write(&path, seal(json)?).with_context(|| format!("Failed to write {}", path.display()))
+}
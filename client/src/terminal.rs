@@ -0,0 +1,150 @@
+// GhostWire Client - Terminal Lifecycle
+// Owns raw mode / alternate screen setup so the terminal is always restored,
+// even when the UI loop returns early or panics mid-draw.
+
+use crossterm::{
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ratatui::{backend::CrosstermBackend, Terminal};
+use std::io::{self, Stdout, Write};
+use std::ops::{Deref, DerefMut};
+#[cfg(unix)]
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Switch the terminal into TUI mode (raw mode, alternate screen, mouse
+/// capture, bracketed paste so pasted line breaks are not Enter presses)
+fn enter() -> io::Result<()> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
+    Ok(())
+}
+
+/// Return the terminal to its normal state.
+///
+/// Every step is attempted even if an earlier one fails, and calling this
+/// more than once is harmless.
+fn restore() -> io::Result<()> {
+    let raw = disable_raw_mode();
+    let screen = execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste,
+        crossterm::cursor::Show
+    );
+    raw.and(screen)
+}
+
+/// Put text on the system clipboard with an OSC 52 sequence. Most terminal
+/// emulators honor it, including over SSH; tmux needs `set-clipboard on`.
+pub fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", BASE64.encode(text))?;
+    stdout.flush()
+}
+
+/// Install a panic hook that restores the terminal before the panic message is printed
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore();
+        tracing::error!("Panic: {}", info);
+        default_hook(info);
+    }));
+}
+
+/// RAII handle to the TUI terminal; restores the terminal when dropped
+pub struct TerminalGuard {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+    /// Set when a SIGTSTP arrives from outside (e.g. `kill -TSTP`)
+    #[cfg(unix)]
+    suspend_requested: Arc<AtomicBool>,
+}
+
+impl TerminalGuard {
+    /// Enter TUI mode and create the Ratatui terminal
+    pub fn new() -> io::Result<Self> {
+        // Catch SIGTSTP ourselves so the terminal can be restored before stopping
+        #[cfg(unix)]
+        let suspend_requested = {
+            let flag = Arc::new(AtomicBool::new(false));
+            signal_hook::flag::register(signal_hook::consts::SIGTSTP, Arc::clone(&flag))?;
+            flag
+        };
+
+        enter()?;
+        // If the terminal cannot be created, undo the mode switch before bailing
+        match Terminal::new(CrosstermBackend::new(io::stdout())) {
+            Ok(terminal) => Ok(Self {
+                terminal,
+                #[cfg(unix)]
+                suspend_requested,
+            }),
+            Err(e) => {
+                let _ = restore();
+                Err(e)
+            }
+        }
+    }
+
+    /// Suspend the process like a shell job (Ctrl+Z).
+    ///
+    /// The terminal is restored before stopping and TUI mode is re-entered
+    /// once the shell resumes us with SIGCONT.
+    #[cfg(unix)]
+    pub fn suspend(&mut self) -> io::Result<()> {
+        restore()?;
+        // Blocks until SIGCONT
+        signal_hook::low_level::emulate_default_handler(signal_hook::consts::SIGTSTP)?;
+        enter()?;
+        // The screen contents are gone; force a full redraw
+        self.terminal.clear()
+    }
+
+    /// Job control is not available on this platform
+    #[cfg(not(unix))]
+    pub fn suspend(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Suspend if a SIGTSTP was delivered since the last check
+    #[cfg(unix)]
+    pub fn handle_pending_suspend(&mut self) -> io::Result<()> {
+        if self.suspend_requested.swap(false, Ordering::Relaxed) {
+            self.suspend()?;
+        }
+        Ok(())
+    }
+
+    /// Job control is not available on this platform
+    #[cfg(not(unix))]
+    pub fn handle_pending_suspend(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Deref for TerminalGuard {
+    type Target = Terminal<CrosstermBackend<Stdout>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.terminal
+    }
+}
+
+impl DerefMut for TerminalGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.terminal
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = restore();
+    }
+}
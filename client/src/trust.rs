@@ -0,0 +1,145 @@
+// GhostWire Client - Trust Store
+// Identity keys of DM partners, stored in `<data dir>/ghostwire/trust.json`.
+// A key is pinned the first time a handshake shows it (trust on first use);
+// a different key later is held as a change, and DMs to the user wait until
+// it is accepted with `/trust` or compared with `/verify`. `/verify` marks
+// keys that were compared out of band.
+
+use crate::storage;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// File name of the trust store inside the data directory
+const TRUST_FILE: &str = "trust.json";
+
+/// The identity key we hold for a user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedKey {
+    /// Base64 X25519 identity public key
+    pub identity: String,
+    /// When this key was first seen
+    pub since: DateTime<Utc>,
+    /// The user confirmed this key with `/verify`
+    #[serde(default)]
+    pub verified: bool,
+    /// A different key a handshake showed since, not accepted yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed: Option<String>,
+}
+
+/// How a handshake's identity key compares to the stored one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Seen {
+    /// First key seen for this user; now pinned
+    New,
+    /// Same key as before
+    Known,
+    /// A different key than before; it is held until accepted
+    Changed,
+}
+
+/// Username -> identity key map
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TrustStore(BTreeMap<String, TrustedKey>);
+
+impl TrustStore {
+    /// Load the trust store from the data directory
+    pub fn load() -> Self {
+        storage::load_json(TRUST_FILE)
+    }
+
+    /// Write the trust store back to the data directory
+    pub fn save(&self) -> anyhow::Result<()> {
+        storage::save_json(TRUST_FILE, self)
+    }
+
+    /// Record the identity key a user presented. A key other than the pinned
+    /// one does not replace it; it is kept as `changed` until accepted.
+    pub fn observe(&mut self, username: &str, identity: &str) -> Seen {
+        let Some(known) = self.0.get_mut(username) else {
+            self.0.insert(
+                username.to_string(),
+                TrustedKey {
+                    identity: identity.to_string(),
+                    since: Utc::now(),
+                    verified: false,
+                    changed: None,
+                },
+            );
+            return Seen::New;
+        };
+        if known.identity == identity {
+            // Back on the pinned key, so nothing is left to accept
+            known.changed = None;
+            Seen::Known
+        } else {
+            known.changed = Some(identity.to_string());
+            Seen::Changed
+        }
+    }
+
+    /// Pin a user's changed key in place of the old one, unverified; false
+    /// if no change is waiting
+    pub fn accept(&mut self, username: &str) -> bool {
+        let Some(key) = self.0.get_mut(username) else {
+            return false;
+        };
+        let Some(identity) = key.changed.take() else {
+            return false;
+        };
+        key.identity = identity;
+        key.since = Utc::now();
+        key.verified = false;
+        true
+    }
+
+    /// Whether a changed key for the user waits to be accepted
+    pub fn is_changed(&self, username: &str) -> bool {
+        self.0.get(username).is_some_and(|key| key.changed.is_some())
+    }
+
+    /// The key the user's sessions run on: a changed one if there is one,
+    /// else the pinned one
+    pub fn current(&self, username: &str) -> Option<&str> {
+        let key = self.0.get(username)?;
+        Some(key.changed.as_deref().unwrap_or(&key.identity))
+    }
+
+    /// Identity key we hold for a user
+    pub fn get(&self, username: &str) -> Option<&TrustedKey> {
+        self.0.get(username)
+    }
+
+    /// Mark a user's current key as verified or not; false if we hold no key.
+    /// Verifying a changed key also accepts it.
+    pub fn set_verified(&mut self, username: &str, verified: bool) -> bool {
+        if verified {
+            self.accept(username);
+        }
+        match self.0.get_mut(username) {
+            Some(key) => {
+                key.verified = verified;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the user's current key was verified; a changed key never is
+    pub fn is_verified(&self, username: &str) -> bool {
+        self.0.get(username).is_some_and(|key| key.verified && key.changed.is_none())
+    }
+
+    /// Add entries from another store (a key backup); entries already here win
+    pub fn merge(&mut self, other: TrustStore) {
+        for (username, key) in other.0 {
+            self.0.entry(username).or_insert(key);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
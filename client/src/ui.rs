@@ -1,17 +1,36 @@
 // GhostWire Client - UI Components
 // This module handles all Ratatui rendering logic
 
-use crate::app::{App, InputMode};
+use crate::app::{
+    App, ChannelType, ChatMessage, ConnectionState, Delivery, InputMode, Popup, RosterRow, Security, User, UserStatus,
+    STARRED_CHANNEL,
+};
+use crate::codeblock::{self, Segment, Token};
+use crate::config::TimestampStyle;
+use crate::i18n::fill;
+use crate::markdown::{self, Emphasis};
+use crate::notify::DoNotDisturb;
+use crate::palette;
+use crate::search::SearchHit;
+use crate::verify;
+use unicode_width::UnicodeWidthStr;
+use ghostwire_protocol::decode::decode_frame;
+use ghostwire_protocol::group::GroupInfo;
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{
-        Block, BorderType, Borders, Gauge, List, ListItem, Paragraph,
+        block::{Position, Title},
+        Block, BorderType, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph, Sparkline,
+        Wrap,
     },
     Frame,
 };
 
+/// Consecutive messages from one sender this close together render as one block
+const GROUP_WINDOW_SECS: i64 = 120;
+
 /// Main UI render function
 pub fn render(f: &mut Frame, app: &App) {
     // Create the main layout: Left sidebar | Middle chat | Right sidebar
@@ -28,6 +47,19 @@ pub fn render(f: &mut Frame, app: &App) {
     render_channel_list(f, app, chunks[0]);
     render_chat_area(f, app, chunks[1]);
     render_telemetry(f, app, chunks[2]);
+
+    // Popups are drawn last so they sit on top of everything else
+    if let Some(popup) = &app.popup {
+        match popup {
+            Popup::Inspector => render_inspector(f, app),
+            Popup::Pins => render_pins(f, app),
+            Popup::Verify { user, code } => render_verify(f, app, user, code),
+            Popup::Profile { user } => render_profile(f, app, user),
+            Popup::Palette { query, selected } => render_palette(f, app, query, *selected),
+            Popup::Search { query, hits, selected } => render_search(f, app, query, hits, *selected),
+            Popup::Downgrade { channel_id, .. } => render_downgrade(f, app, channel_id),
+        }
+    }
 }
 
 /// Render the channel list (left sidebar)
@@ -58,10 +90,14 @@ fn render_channels(f: &mut Frame, app: &App, area: Rect) {
         .iter()
         .map(|channel_id| {
             if let Some(channel) = app.channels.get(channel_id) {
-                let display_name = channel.display_name();
+                let display_name = match app.channel_order.favorite(channel_id) {
+                    Some(_) => format!("{} {}", app.glyphs().favorite, app.channel_name(channel)),
+                    None => app.channel_name(channel),
+                };
                 
-                // Add unread count if any
-                let content = if channel.unread_count > 0 {
+                // Add unread count if any (hidden while do-not-disturb is on)
+                let unread = channel.unread_count > 0 && !app.dnd.is_on();
+                let content = if unread {
                     format!("{} ({})", display_name, channel.unread_count)
                 } else {
                     display_name
@@ -73,7 +109,7 @@ fn render_channels(f: &mut Frame, app: &App, area: Rect) {
                         .fg(Color::Black)
                         .bg(Color::Green)
                         .add_modifier(Modifier::BOLD)
-                } else if channel.unread_count > 0 {
+                } else if unread {
                     Style::default()
                         .fg(Color::Yellow)
                         .add_modifier(Modifier::BOLD)
@@ -88,7 +124,7 @@ fn render_channels(f: &mut Frame, app: &App, area: Rect) {
         })
         .collect();
 
-    let title = format!(" Channels ({}) ", app.channels.len());
+    let title = format!(" {} ", fill(app.strings.channels_title, &[&app.channels.len()]));
     let channel_list = List::new(channels)
         .block(
             Block::default()
@@ -104,51 +140,51 @@ fn render_channels(f: &mut Frame, app: &App, area: Rect) {
 
 /// Render users section
 fn render_users(f: &mut Frame, app: &App, area: Rect) {
-    use chrono::Utc;
+    if !app.global_roster {
+        if let Some(members) = app.channel_members() {
+            render_members(f, app, members, app.active_group(), area);
+            return;
+        }
+    }
     
-    // Create user list items
-    let users: Vec<ListItem> = app
-        .users
+    // Sections of active, idle and offline users, each sorted by name
+    let rows = app.roster();
+    let selected = app.selected_roster_row(&rows);
+    let users: Vec<ListItem> = rows
         .iter()
         .enumerate()
-        .map(|(i, user)| {
-            // Determine user status: online, idle, or offline
-            let (status_icon, status_color) = if !user.is_online {
-                ("○", Color::DarkGray) // Offline
-            } else if user.is_idle() {
-                ("◐", Color::Yellow) // Idle (half-circle)
-            } else {
-                ("●", Color::Green) // Online and active
+        .map(|(i, row)| {
+            let glyphs = app.glyphs();
+            let user = match *row {
+                RosterRow::Section { status, count, collapsed } => {
+                    let fold = if collapsed { glyphs.section_closed } else { glyphs.section_open };
+                    let name = match status {
+                        UserStatus::Active => app.strings.roster_active,
+                        UserStatus::Idle => app.strings.roster_idle,
+                        UserStatus::Offline => app.strings.roster_offline,
+                    };
+                    let style = if Some(i) == selected {
+                        Style::default().fg(Color::Black).bg(Color::Cyan).add_modifier(Modifier::BOLD)
+                    } else {
+                        Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD)
+                    };
+                    return ListItem::new(format!("{} {} ({})", fold, name, count)).style(style);
+                }
+                RosterRow::User(user) => user,
             };
             
-            // Calculate time since last seen for offline/idle users
-            let last_seen_text = if !user.is_online {
-                let duration = Utc::now().signed_duration_since(user.last_seen);
-                let mins = duration.num_minutes();
-                let hours = duration.num_hours();
-                let days = duration.num_days();
-                
-                if days > 0 {
-                    format!(" ({}d)", days)
-                } else if hours > 0 {
-                    format!(" ({}h)", hours)
-                } else if mins > 0 {
-                    format!(" ({}m)", mins)
-                } else {
-                    "".to_string()
-                }
-            } else if user.is_idle() {
-                // Show idle time for idle users
-                let duration = Utc::now().signed_duration_since(user.last_seen);
-                let mins = duration.num_minutes();
-                format!(" (idle {}m)", mins)
-            } else {
-                String::new()
+            // Determine user status: online, idle, or offline
+            let (status_icon, status_color) = match user.status() {
+                UserStatus::Offline => (glyphs.offline, Color::DarkGray),
+                UserStatus::Idle => (glyphs.idle, Color::Yellow), // Half-circle
+                UserStatus::Active => (glyphs.online, Color::Green),
             };
             
-            let content = format!("{} {}{}", status_icon, user.username, last_seen_text);
+            let last_seen_text = last_seen_suffix(app, user);
             
-            let style = if i == app.selected_user {
+            let content = format!("  {} {}{}", status_icon, app.display_name(&user.username), last_seen_text);
+            
+            let mut style = if Some(i) == selected {
                 Style::default()
                     .fg(Color::Black)
                     .bg(Color::Cyan)
@@ -156,12 +192,15 @@ fn render_users(f: &mut Frame, app: &App, area: Rect) {
             } else {
                 Style::default().fg(status_color)
             };
+            if app.blocked.contains(&user.username) {
+                style = style.add_modifier(Modifier::CROSSED_OUT);
+            }
             
             ListItem::new(content).style(style)
         })
         .collect();
 
-    let title = format!(" Users ({}) [J/K to select, d for DM] ", app.users.len());
+    let title = format!(" {} ", fill(app.strings.users_title, &[&app.users.len()]));
     let users_list = List::new(users)
         .block(
             Block::default()
@@ -172,50 +211,210 @@ fn render_users(f: &mut Frame, app: &App, area: Rect) {
         )
         .style(Style::default().fg(Color::Green));
 
-    f.render_widget(users_list, area);
+    // The list state keeps the selection scrolled into view
+    let mut state = ListState::default();
+    state.select(selected);
+
+    f.render_stateful_widget(users_list, area, &mut state);
+}
+
+/// How long ago an offline user was seen, or how long an online one has been idle
+fn last_seen_suffix(app: &App, user: &User) -> String {
+    let duration = chrono::Utc::now().signed_duration_since(user.last_seen);
+    match user.status() {
+        UserStatus::Offline => {
+            if duration.num_days() > 0 {
+                format!(" ({}d)", duration.num_days())
+            } else if duration.num_hours() > 0 {
+                format!(" ({}h)", duration.num_hours())
+            } else if duration.num_minutes() > 0 {
+                format!(" ({}m)", duration.num_minutes())
+            } else {
+                String::new()
+            }
+        }
+        UserStatus::Idle => format!(" ({})", fill(app.strings.idle, &[&duration.num_minutes()])),
+        UserStatus::Active => String::new(),
+    }
+}
+
+/// Render the active channel's members; in a group, operators come first and
+/// are marked with `@`
+fn render_members(f: &mut Frame, app: &App, mut members: Vec<&str>, group: Option<&GroupInfo>, area: Rect) {
+    let glyphs = app.glyphs();
+    let is_operator = |member: &str| group.is_some_and(|info| info.is_operator(member));
+    members.sort_by_key(|member| !is_operator(member));
+    let count = members.len();
+    
+    let items: Vec<ListItem> = members
+        .into_iter()
+        .map(|member| {
+            let online = member == app.username
+                || app.users.iter().any(|u| u.username == member && u.is_online);
+            let (status_icon, status_color) = if online {
+                (glyphs.online, Color::Green)
+            } else {
+                (glyphs.offline, Color::DarkGray)
+            };
+            let mut spans = vec![Span::styled(format!("{} ", status_icon), Style::default().fg(status_color))];
+            if is_operator(member) {
+                spans.push(Span::styled("@", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
+            }
+            spans.push(Span::styled(app.display_name(member).to_string(), Style::default().fg(status_color)));
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    
+    let title = format!(" {} ", fill(app.strings.members_title, &[&count]));
+    let members_list = List::new(items).block(
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Cyan)),
+    );
+    
+    f.render_widget(members_list, area);
 }
 
 /// Render the chat area (middle section)
 fn render_chat_area(f: &mut Frame, app: &App, area: Rect) {
-    // Split chat area into messages and input
+    // Split chat area into pinned bar, messages, optional log panel, and input
+    let pinned = app
+        .channels
+        .get(&app.active_channel)
+        .map(|channel| channel.pinned_messages())
+        .unwrap_or_default();
+    let pinned_height = if pinned.is_empty() { 0 } else { 1 };
+    let log_height = if app.show_logs { 12 } else { 0 };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Min(3),      // Chat messages
-            Constraint::Length(3),   // Input box
+            Constraint::Length(pinned_height), // Pinned messages bar
+            Constraint::Min(3),               // Chat messages
+            Constraint::Length(log_height),   // Debug log panel
+            Constraint::Length(3),            // Input box
         ])
         .split(area);
 
-    render_messages(f, app, chunks[0]);
-    render_input(f, app, chunks[1]);
+    if let Some(latest) = pinned.last() {
+        render_pinned_bar(f, app, latest, pinned.len(), chunks[0]);
+    }
+    render_messages(f, app, chunks[1]);
+    if app.show_logs {
+        render_logs(f, app, chunks[2]);
+    }
+    render_input(f, app, chunks[3]);
+}
+
+/// Render the one-line bar showing the most recent pin
+fn render_pinned_bar(f: &mut Frame, app: &App, latest: &ChatMessage, count: usize, area: Rect) {
+    let more = if count > 1 {
+        format!(" ({})", fill(app.strings.pinned_more, &[&(count - 1)]))
+    } else {
+        String::new()
+    };
+    let bar = Paragraph::new(Line::from(vec![
+        Span::styled(format!(" {} ", app.glyphs().pin), Style::default().fg(Color::Yellow)),
+        Span::styled(
+            format!("{}: ", app.display_name(&latest.sender)),
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(&*latest.content, Style::default().fg(Color::White)),
+        Span::styled(more, Style::default().fg(Color::DarkGray)),
+    ]))
+    .style(Style::default().bg(Color::Rgb(30, 30, 30)));
+
+    f.render_widget(bar, area);
+}
+
+/// Render the debug log panel (toggled with F12)
+fn render_logs(f: &mut Frame, app: &App, area: Rect) {
+    // Only fetch as many lines as fit inside the borders
+    let visible = area.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = app
+        .logs
+        .tail(visible)
+        .into_iter()
+        .map(|line| {
+            let color = if line.contains("ERROR") {
+                Color::Red
+            } else if line.contains(" WARN ") {
+                Color::Yellow
+            } else {
+                Color::DarkGray
+            };
+            Line::styled(line, Style::default().fg(color))
+        })
+        .collect();
+
+    let logs = Paragraph::new(lines).block(
+        Block::default()
+            .title(format!(" {} ", app.strings.debug_log_title))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Magenta)),
+    );
+
+    f.render_widget(logs, area);
 }
 
 /// Render chat messages
 fn render_messages(f: &mut Frame, app: &App, area: Rect) {
-    // Get messages from active channel
-    let messages: Vec<ListItem> = if let Some(channel) = app.channels.get(&app.active_channel) {
-        channel.messages
+    // Get messages from active channel, minus hidden system notices
+    let active = app.channels.get(&app.active_channel);
+    let visible = app.visible_indices();
+    let hidden = active.map_or(0, |channel| channel.messages.len() - visible.len());
+    // Only the messages that can be on screen are laid out, so long histories
+    // cost nothing per frame. Past the first page the list ends at the
+    // selected message, and every item is at least one line high, so nothing
+    // more than a page before it can show.
+    let page = area.height.saturating_sub(2).max(1) as usize;
+    let first = app.scroll_position.saturating_sub(page - 1);
+    // Width of the time column of the message heading the current group, so
+    // grouped lines line up under it whatever its marker or relative time
+    let mut head_time_width = 0;
+    let messages: Vec<ListItem> = if let Some(channel) = active {
+        let shown: Vec<&ChatMessage> = visible
             .iter()
-            .map(|msg| {
-                let timestamp = msg.timestamp.format("%H:%M:%S");
+            .filter_map(|&i| channel.messages.get(i))
+            .collect();
+        shown
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(page)
+            .map(|(n, &msg)| {
+                let timestamp = message_time(app, msg);
+                let sender_name = app.display_name(&msg.sender);
+                // The first message on screen always shows its heading, even
+                // if it continues a group that started above the window
+                let grouped = n > first && continues_group(shown[n - 1], msg);
+                
+                if msg.motd {
+                    return ListItem::new(banner(app, msg, app.strings.motd_title, Color::Magenta));
+                }
+                if msg.compat {
+                    return ListItem::new(banner(app, msg, app.strings.compat_title, Color::Red));
+                }
                 
                 let content = if msg.is_system {
                     // System messages in red
-                    Line::from(vec![
+                    vec![Line::from(vec![
                         Span::styled(
                             format!("[{}] ", timestamp),
                             Style::default().fg(Color::DarkGray),
                         ),
                         Span::styled(
-                            format!("⚠ {}", msg.content),
+                            format!("{} {}", app.glyphs().warning, msg.content),
                             Style::default()
                                 .fg(Color::Red)
                                 .add_modifier(Modifier::BOLD),
                         ),
-                    ])
+                    ])]
                 } else {
                     // Regular messages
-                    let sender_style = if msg.sender == app.username {
+                    let sender_style = if *msg.sender == *app.username {
                         Style::default()
                             .fg(Color::Cyan)
                             .add_modifier(Modifier::BOLD)
@@ -225,16 +424,90 @@ fn render_messages(f: &mut Frame, app: &App, area: Rect) {
                             .add_modifier(Modifier::BOLD)
                     };
                     
-                    Line::from(vec![
-                        Span::styled(
-                            format!("[{}] ", timestamp),
+                    // Actions read "* alice rolled 2d6: 3 + 5 = 8"
+                    let sender_label = if msg.action {
+                        format!("* {} ", sender_name)
+                    } else {
+                        format!("{}: ", sender_name)
+                    };
+                    let sender_style = if msg.action {
+                        Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)
+                    } else {
+                        sender_style
+                    };
+                    
+                    // "~" marks a receive time substituted for a skewed sender clock
+                    let skew_marker = if msg.clock_skewed { "~" } else { "" };
+                    let time = format!("[{}{}] ", skew_marker, timestamp);
+                    if !grouped {
+                        head_time_width = time.width();
+                    }
+                    // Continuation lines are indented under the first line's content
+                    let indent = " ".repeat(head_time_width + sender_label.width());
+                    let mut spans = if grouped {
+                        vec![Span::raw(indent.clone())]
+                    } else {
+                        vec![
+                            Span::styled(time, Style::default().fg(Color::DarkGray)),
+                            Span::styled(sender_label, sender_style),
+                        ]
+                    };
+                    if msg.verified && !grouped {
+                        spans.push(Span::styled(
+                            format!("{} ", app.glyphs().verified),
+                            Style::default().fg(Color::Green),
+                        ));
+                    }
+                    if msg.starred && app.active_channel != STARRED_CHANNEL {
+                        spans.push(Span::styled(
+                            format!("{} ", app.glyphs().star),
+                            Style::default().fg(Color::Yellow),
+                        ));
+                    }
+                    // Multi-line bodies and code blocks continue on indented lines
+                    let mut body = message_body(app, msg).into_iter();
+                    spans.extend(body.next().unwrap_or_default());
+                    let mut lines = vec![Line::from(spans)];
+                    lines.extend(body.map(|line| {
+                        let mut spans = vec![Span::raw(indent.clone())];
+                        spans.extend(line);
+                        Line::from(spans)
+                    }));
+                    let spans = &mut lines.last_mut().expect("at least the first line").spans;
+                    match msg.delivery {
+                        Delivery::Sent => {}
+                        Delivery::Queued => spans.push(Span::styled(
+                            format!(" {}", app.glyphs().queued),
                             Style::default().fg(Color::DarkGray),
-                        ),
-                        Span::styled(format!("{}: ", msg.sender), sender_style),
-                        Span::styled(&msg.content, Style::default().fg(Color::White)),
-                    ])
+                        )),
+                        Delivery::Delivered => spans.push(Span::styled(
+                            format!(" {}", app.glyphs().delivered),
+                            Style::default().fg(Color::DarkGray),
+                        )),
+                        Delivery::Read => spans.push(Span::styled(
+                            format!(" {}", app.glyphs().read),
+                            Style::default().fg(Color::Cyan),
+                        )),
+                        Delivery::Undelivered | Delivery::Failed => spans.push(Span::styled(
+                            format!(" {}", app.glyphs().undelivered),
+                            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                        )),
+                    }
+                    if msg.sanitized && app.config.show_sanitized_marker {
+                        spans.push(Span::styled(
+                            format!(" {}", app.strings.sanitized),
+                            Style::default()
+                                .fg(Color::DarkGray)
+                                .add_modifier(Modifier::ITALIC),
+                        ));
+                    }
+                    lines
                 };
                 
+                // Mentions of us and watch words stand out from the rest
+                if msg.highlighted {
+                    return ListItem::new(content).style(Style::default().bg(Color::Indexed(236)));
+                }
                 ListItem::new(content)
             })
             .collect()
@@ -242,23 +515,117 @@ fn render_messages(f: &mut Frame, app: &App, area: Rect) {
         Vec::new()
     };
 
-    let connection_status = if app.is_connected {
-        Span::styled(" ● CONNECTED ", Style::default().fg(Color::Green))
-    } else {
-        Span::styled(" ○ DISCONNECTED ", Style::default().fg(Color::Red))
+    let connection_status = match &app.connection {
+        ConnectionState::Connecting => Span::styled(
+            format!(" {} {} ", app.glyphs().idle, app.strings.status_connecting),
+            Style::default().fg(Color::Yellow),
+        ),
+        ConnectionState::Connected => Span::styled(
+            format!(" {} {} ", app.glyphs().online, app.strings.status_connected),
+            Style::default().fg(Color::Green),
+        ),
+        ConnectionState::Reconnecting { attempt, next_retry } => {
+            let mut status = fill(app.strings.status_reconnecting, &[attempt]);
+            // Rounded up, so a pending retry never reads "0s"
+            let wait_ms = (*next_retry - chrono::Utc::now()).num_milliseconds();
+            let wait = (wait_ms + 999) / 1000;
+            if wait > 0 {
+                status = format!("{}, {}", status, fill(app.strings.status_retry_in, &[&wait]));
+            }
+            Span::styled(
+                format!(" {} {} ", app.glyphs().idle, status),
+                Style::default().fg(Color::Yellow),
+            )
+        }
+        ConnectionState::Disconnected { reason } => Span::styled(
+            format!(" {} {} ", app.glyphs().offline, fill(app.strings.status_disconnected, &[reason])),
+            Style::default().fg(Color::Red),
+        ),
+        // Stays up until the client restarts, since we will not reconnect
+        ConnectionState::Kicked { reason } => Span::styled(
+            format!(" {} {} ", app.glyphs().offline, fill(app.strings.status_kicked, &[reason])),
+            Style::default().fg(Color::White).bg(Color::Red).add_modifier(Modifier::BOLD),
+        ),
     };
     
     // Get active channel display name
     let channel_name = app.channels.get(&app.active_channel)
-        .map(|ch| ch.display_name())
-        .unwrap_or_else(|| "Unknown".to_string());
+        .map(|ch| app.channel_name(ch))
+        .unwrap_or_else(|| app.strings.unknown.to_string());
 
-    let title = Line::from(vec![
+    let mut title = Line::from(vec![
         Span::raw(" "),
         Span::styled(channel_name, Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
         Span::raw(" "),
         connection_status,
     ]);
+    // How our messages here are protected; red once plaintext replaces encryption
+    if let Some(security) = app.security(&app.active_channel) {
+        let glyphs = app.glyphs();
+        let (glyph, label, color) = match security {
+            Security::Plaintext if app.is_downgraded(&app.active_channel) => {
+                (glyphs.unlocked, app.strings.security_plaintext, Color::Red)
+            }
+            Security::Plaintext => (glyphs.unlocked, app.strings.security_plaintext, Color::DarkGray),
+            Security::Encrypted => (glyphs.locked, app.strings.security_encrypted, Color::Green),
+            Security::Verified => (glyphs.verified, app.strings.security_verified, Color::Cyan),
+        };
+        title.spans.push(Span::styled(
+            format!("{} {} ", glyph, label),
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        ));
+    }
+    // Group topic and slow mode
+    if let Some(info) = app.active_group() {
+        if let Some(topic) = &info.topic {
+            title.spans.push(Span::styled(format!("{} ", topic), Style::default().fg(Color::Yellow)));
+        }
+        if info.slow_mode_secs > 0 {
+            title.spans.push(Span::styled(
+                format!("{} ", fill(app.strings.slow_badge, &[&info.slow_mode_secs])),
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            ));
+        }
+        if info.announce {
+            title.spans.push(Span::styled(
+                format!("{} ", app.strings.announce_badge),
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            ));
+        }
+    }
+    // Which relay of the failover list is in use
+    if let Some(url) = &app.server_url {
+        title.spans.push(Span::styled(format!("{} ", url), Style::default().fg(Color::DarkGray)));
+    }
+    if let DoNotDisturb::On { until } = app.dnd {
+        let badge = match until {
+            Some(until) => fill(app.strings.dnd_badge_until, &[&until.format("%H:%M")]),
+            None => app.strings.dnd_badge.to_string(),
+        };
+        title.spans.push(Span::styled(
+            format!(" {} ", badge),
+            Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if hidden > 0 {
+        title.spans.push(Span::styled(
+            format!(" ({}) ", fill(app.strings.system_hidden, &[&hidden])),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    if app.pending_history.as_ref() == Some(&app.active_channel) {
+        title.spans.push(Span::styled(
+            format!(" {} ", app.strings.history_loading),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+
+    // Highlight the selection cursor only while navigating
+    let highlight_style = if app.input_mode == InputMode::Normal {
+        Style::default().bg(Color::DarkGray)
+    } else {
+        Style::default()
+    };
 
     let messages_list = List::new(messages)
         .block(
@@ -268,28 +635,208 @@ fn render_messages(f: &mut Frame, app: &App, area: Rect) {
                 .border_type(BorderType::Rounded)
                 .border_style(Style::default().fg(Color::Green)),
         )
-        .style(Style::default().fg(Color::Green));
+        .style(Style::default().fg(Color::Green))
+        .highlight_style(highlight_style);
+
+    // The list state keeps the selected message scrolled into view
+    let mut state = ListState::default();
+    state.select(Some(app.scroll_position - first));
+
+    f.render_stateful_widget(messages_list, area, &mut state);
+}
+
+/// Lines of a message body: prose line by line, and fenced code blocks in a
+/// frame with each line highlighted
+fn message_body<'a>(app: &App, msg: &'a ChatMessage) -> Vec<Vec<Span<'a>>> {
+    // Actions are generated by commands, so they are shown as they are
+    if msg.action {
+        let style = Style::default().fg(Color::Magenta).add_modifier(Modifier::ITALIC);
+        return msg.content.split('\n').map(|line| vec![Span::styled(line, style)]).collect();
+    }
+    let frame_style = Style::default().fg(Color::DarkGray);
+    let mut lines = Vec::new();
+    for segment in codeblock::segments(&msg.content) {
+        match segment {
+            Segment::Text(text) => {
+                lines.extend(text.split('\n').map(|line| text_line(app, line, msg.revealed)));
+            }
+            Segment::Code { lang, code } => {
+                let lang = lang.filter(|_| app.config.syntax_highlighting);
+                lines.push(vec![Span::styled(
+                    format!("{} {}", app.glyphs().code_top, lang.unwrap_or_default()),
+                    frame_style,
+                )]);
+                for line in code.split('\n') {
+                    let mut spans = vec![Span::styled(app.glyphs().code_side, frame_style)];
+                    spans.extend(
+                        codeblock::highlight(lang, line)
+                            .into_iter()
+                            .map(|(token, text)| Span::styled(text, token_style(token))),
+                    );
+                    lines.push(spans);
+                }
+                lines.push(vec![Span::styled(app.glyphs().code_bottom, frame_style)]);
+            }
+        }
+    }
+    lines
+}
+
+/// Spans of a line of prose, with Markdown emphasis and quotes unless
+/// `render_markdown` is off. Spoilers are blacked out either way unless
+/// the message is `revealed`.
+fn text_line<'a>(app: &App, line: &'a str, revealed: bool) -> Vec<Span<'a>> {
+    let formatting = app.config.render_markdown;
+    let (mut spans, line, base) = match markdown::quote(line).filter(|_| formatting) {
+        Some(quoted) => (
+            vec![Span::styled(app.glyphs().quote, Style::default().fg(Color::DarkGray))],
+            quoted,
+            Style::default().fg(Color::Gray).add_modifier(Modifier::ITALIC),
+        ),
+        None => (Vec::new(), line, Style::default().fg(Color::White)),
+    };
+    spans.extend(markdown::inline(line, formatting).into_iter().map(|(emphasis, text)| {
+        if emphasis.spoiler && !revealed {
+            Span::styled(app.glyphs().spoiler.repeat(text.width()), Style::default().fg(Color::DarkGray))
+        } else {
+            Span::styled(text, emphasis_style(base, emphasis))
+        }
+    }));
+    spans
+}
+
+/// Style of emphasized text on top of the line's base style
+fn emphasis_style(base: Style, emphasis: Emphasis) -> Style {
+    let mut style = base;
+    if emphasis.bold {
+        style = style.add_modifier(Modifier::BOLD);
+    }
+    if emphasis.italic {
+        style = style.add_modifier(Modifier::ITALIC);
+    }
+    if emphasis.code {
+        style = style.fg(Color::LightYellow);
+    }
+    // Revealed spoilers stay marked
+    if emphasis.spoiler {
+        style = style.add_modifier(Modifier::UNDERLINED);
+    }
+    style
+}
+
+/// Color of a code token
+fn token_style(token: Token) -> Style {
+    match token {
+        Token::Plain => Style::default().fg(Color::Gray),
+        Token::Keyword => Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+        Token::String => Style::default().fg(Color::Green),
+        Token::Comment => Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+        Token::Number => Style::default().fg(Color::Cyan),
+    }
+}
+
+/// A message's time as set by `timestamps`: the clock time, or how long ago
+/// it was padded to the same width so continuation lines stay aligned
+fn message_time(app: &App, msg: &ChatMessage) -> String {
+    if app.config.timestamps == TimestampStyle::Absolute {
+        return msg.timestamp.format("%H:%M:%S").to_string();
+    }
+    let age = chrono::Utc::now().signed_duration_since(msg.timestamp);
+    let ago = if age.num_days() > 0 {
+        format!("{}d", age.num_days())
+    } else if age.num_hours() > 0 {
+        format!("{}h", age.num_hours())
+    } else if age.num_minutes() > 0 {
+        format!("{}m", age.num_minutes())
+    } else {
+        return format!("{:>8}", app.strings.time_now);
+    };
+    format!("{:>8}", fill(app.strings.time_ago, &[&ago]))
+}
+
+/// Relay message of the day or compatibility warning as a multi-line banner
+fn banner<'a>(app: &App, msg: &'a ChatMessage, title: &str, color: Color) -> Vec<Line<'a>> {
+    let style = Style::default().fg(color);
+    let mut lines = vec![Line::from(vec![
+        Span::styled(
+            format!("[{}] ", message_time(app, msg)),
+            Style::default().fg(Color::DarkGray),
+        ),
+        Span::styled(
+            format!("== {} ==", title),
+            style.add_modifier(Modifier::BOLD),
+        ),
+    ])];
+    lines.extend(msg.content.lines().map(|line| {
+        Line::from(vec![Span::styled("  | ", style), Span::styled(line, style)])
+    }));
+    lines
+}
 
-    f.render_widget(messages_list, area);
+/// Whether `msg` continues the block started by `prev`: same sender, neither
+/// a system notice, and sent within `GROUP_WINDOW_SECS` of each other
+fn continues_group(prev: &ChatMessage, msg: &ChatMessage) -> bool {
+    !prev.is_system
+        && !msg.is_system
+        && !prev.action
+        && !msg.action
+        && prev.sender == msg.sender
+        && (msg.timestamp - prev.timestamp).num_seconds().abs() <= GROUP_WINDOW_SECS
 }
 
 /// Render input box
 fn render_input(f: &mut Frame, app: &App, area: Rect) {
+    // Announcement channels we may not post in are greyed out, with the
+    // reason in place of an empty draft; commands still work
+    let read_only = app.input_mode != InputMode::Command
+        && app.passphrase_prompt.is_none()
+        && !app.may_post(&app.active_channel, &app.username);
     let input_style = match app.input_mode {
+        _ if read_only => Style::default().fg(Color::DarkGray),
         InputMode::Normal => Style::default().fg(Color::Green),
         InputMode::Editing => Style::default().fg(Color::Yellow),
+        InputMode::Command => Style::default().fg(Color::Magenta),
     };
 
     let mode_indicator = match app.input_mode {
-        InputMode::Normal => " [NORMAL] ",
-        InputMode::Editing => " [EDIT] ",
+        _ if read_only => app.strings.mode_read_only,
+        InputMode::Normal => app.strings.mode_normal,
+        InputMode::Editing if app.passphrase_prompt.is_some() => app.strings.mode_passphrase,
+        InputMode::Editing => app.strings.mode_edit,
+        InputMode::Command => app.strings.mode_command,
+    };
+
+    // Passphrases are shown as one dot per character
+    let shown;
+    let (text, cursor_col) = if app.input_mode == InputMode::Command {
+        // The command line has its own buffer, so a draft message is kept
+        let line = &app.command_line;
+        shown = format!(":{}", line.text);
+        (shown.as_str(), 1 + line.text[..line.cursor].width() as u16)
+    } else if read_only && app.input.is_empty() {
+        (app.strings.announce_read_only, 0)
+    } else if app.passphrase_prompt.is_some() {
+        shown = app.glyphs().mask.repeat(app.input.chars().count());
+        (shown.as_str(), app.input[..app.input_cursor].chars().count() as u16)
+    } else {
+        // Terminal columns, not chars: CJK and emoji take two cells. Line
+        // breaks show as a glyph so the box stays one line high.
+        let newline = app.glyphs().newline;
+        shown = app.input.replace('\n', newline);
+        let before_cursor = app.input[..app.input_cursor].replace('\n', newline);
+        (shown.as_str(), before_cursor.width() as u16)
     };
 
-    let input = Paragraph::new(app.input.as_str())
+    // Scroll horizontally so the cursor stays inside the box
+    let inner_width = area.width.saturating_sub(2).max(1);
+    let scroll = cursor_col.saturating_sub(inner_width - 1);
+
+    let input = Paragraph::new(text)
         .style(input_style)
+        .scroll((0, scroll))
         .block(
             Block::default()
-                .title(mode_indicator)
+                .title(format!(" {} ", mode_indicator))
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
                 .border_style(input_style),
@@ -297,11 +844,11 @@ fn render_input(f: &mut Frame, app: &App, area: Rect) {
 
     f.render_widget(input, area);
 
-    // Show cursor in edit mode
-    if app.input_mode == InputMode::Editing {
+    // Show cursor in edit and command mode
+    if app.input_mode != InputMode::Normal {
         // Calculate cursor position
         f.set_cursor(
-            area.x + app.input_cursor as u16 + 1,
+            area.x + cursor_col - scroll + 1,
             area.y + 1,
         );
     }
@@ -315,9 +862,10 @@ fn render_telemetry(f: &mut Frame, app: &App, area: Rect) {
         .constraints([
             Constraint::Length(3),   // Connection uptime
             Constraint::Length(3),   // Latency
-            Constraint::Length(7),   // Statistics (expanded)
+            Constraint::Length(11),  // Statistics + most active channels
             Constraint::Min(3),      // Network activity chart
-            Constraint::Length(3),   // Server time
+            Constraint::Length(4),   // Latency sparkline
+            Constraint::Length(5),   // Server time, clock offset, relay version
         ])
         .split(area);
 
@@ -334,7 +882,7 @@ fn render_telemetry(f: &mut Frame, app: &App, area: Rect) {
     .alignment(Alignment::Center)
     .block(
         Block::default()
-            .title(" Uptime ")
+            .title(format!(" {} ", app.strings.uptime_title))
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded)
             .border_style(Style::default().fg(Color::Green)),
@@ -354,7 +902,7 @@ fn render_telemetry(f: &mut Frame, app: &App, area: Rect) {
     let latency = Gauge::default()
         .block(
             Block::default()
-                .title(format!(" Latency: {}ms ", app.telemetry.latency_ms))
+                .title(format!(" {} ", fill(app.strings.latency_title, &[&app.telemetry.latency_ms])))
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
                 .border_style(Style::default().fg(Color::Green)),
@@ -365,25 +913,60 @@ fn render_telemetry(f: &mut Frame, app: &App, area: Rect) {
 
     // Expanded statistics
     let active_channel_name = app.channels.get(&app.active_channel)
-        .map(|ch| ch.display_name())
-        .unwrap_or_else(|| "Unknown".to_string());
+        .map(|ch| app.channel_name(ch))
+        .unwrap_or_else(|| app.strings.unknown.to_string());
+    
+    let active_channel_count = app.telemetry.channel_counts
+        .get(&app.active_channel)
+        .copied()
+        .unwrap_or(0);
     
-    let stats_text = format!(
-        "↑ Sent: {}\n↓ Recv: {}\n📊 Bytes: {} / {}\n📺 Channel: {}\n👥 Users: {} | Channels: {}",
+    let glyphs = app.glyphs();
+    let s = app.strings;
+    let mut stats_text = format!(
+        "{}{}: {}\n{}{}: {}\n{}{}: {} / {}\n{}{}: {} ({})\n{}{}: {} | {}: {}\n{}:",
+        glyphs.sent,
+        s.stat_sent,
         app.telemetry.messages_sent,
+        glyphs.received,
+        s.stat_received,
         app.telemetry.messages_received,
+        glyphs.bytes,
+        s.stat_bytes,
         format_bytes(app.telemetry.bytes_sent),
         format_bytes(app.telemetry.bytes_received),
+        glyphs.channel,
+        s.stat_channel,
         active_channel_name,
+        active_channel_count,
+        glyphs.users,
+        s.stat_users,
         app.users.len(),
+        s.stat_channels,
         app.channels.len(),
+        s.stat_most_active,
     );
     
+    let most_active = app.telemetry.most_active_channels(3);
+    if most_active.is_empty() {
+        stats_text.push_str(&format!("\n  {}", s.stat_no_activity));
+    }
+    for (rank, (channel_id, count)) in most_active.into_iter().enumerate() {
+        let name = app.channels.get(channel_id)
+            .map(|ch| app.channel_name(ch))
+            .unwrap_or_else(|| channel_id.to_string());
+        stats_text.push_str(&format!("\n  {}. {} ({})", rank + 1, name, count));
+    }
+    // Only worth the space once the relay has tried something
+    if app.telemetry.replays_dropped > 0 {
+        stats_text.push_str(&format!("\n{} {}: {}", glyphs.warning, s.stat_replays, app.telemetry.replays_dropped));
+    }
+    
     let stats = Paragraph::new(stats_text)
         .style(Style::default().fg(Color::Green))
         .block(
             Block::default()
-                .title(" Statistics ")
+                .title(format!(" {} ", app.strings.stats_title))
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
                 .border_style(Style::default().fg(Color::Green)),
@@ -403,7 +986,7 @@ fn render_telemetry(f: &mut Frame, app: &App, area: Rect) {
         .map(|&val| ("", val))
         .collect();
     
-    let title = format!(" Activity (max: {}/s) ", max_activity);
+    let title = format!(" {} ", fill(app.strings.activity_title, &[&max_activity]));
     
     let barchart = ratatui::widgets::BarChart::default()
         .block(
@@ -414,6 +997,7 @@ fn render_telemetry(f: &mut Frame, app: &App, area: Rect) {
                 .border_style(Style::default().fg(Color::Green)),
         )
         .data(&recent_data)
+        .bar_set(app.glyphs().bars.clone())
         .bar_width(2)
         .bar_gap(0)
         .bar_style(Style::default().fg(Color::Green))
@@ -421,22 +1005,449 @@ fn render_telemetry(f: &mut Frame, app: &App, area: Rect) {
     
     f.render_widget(barchart, chunks[3]);
     
-    // Server time
-    use chrono::Utc;
-    let now = Utc::now();
-    let time_str = now.format("%H:%M:%S UTC").to_string();
+    // Latency history, newest sample on the right
+    let history: Vec<u64> = app.telemetry.latency_history.iter().copied().collect();
+    let summary = match (history.iter().min(), history.iter().max()) {
+        (Some(min), Some(max)) => {
+            let avg = history.iter().sum::<u64>() / history.len() as u64;
+            fill(app.strings.latency_summary, &[min, &avg, max])
+        }
+        _ => "-".to_string(),
+    };
+    let visible = chunks[4].width.saturating_sub(2) as usize;
+    let sparkline = Sparkline::default()
+        .block(
+            Block::default()
+                .title(format!(" {} ", app.strings.latency_history_title))
+                .title(
+                    Title::from(Span::styled(
+                        format!(" {} ", summary),
+                        Style::default().fg(Color::DarkGray),
+                    ))
+                    .position(Position::Bottom),
+                )
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(Color::Green)),
+        )
+        .data(&history[history.len().saturating_sub(visible)..])
+        .bar_set(app.glyphs().bars.clone())
+        .style(Style::default().fg(Color::Yellow));
+    f.render_widget(sparkline, chunks[4]);
+    
+    // Server time, corrected by the offset measured during the heartbeat,
+    // and the relay build from its HELLO
+    let relay = match &app.server_info {
+        Some(info) => format!("{} v{}", info.name, info.version),
+        None => app.strings.relay_unknown.to_string(),
+    };
+    let time_str = format!(
+        "{}\n{}\n{}",
+        app.server_now().format("%H:%M:%S UTC"),
+        fill(app.strings.clock_offset, &[&format!("{:+}", app.clock_offset_ms)]),
+        relay,
+    );
     
     let time_widget = Paragraph::new(time_str)
         .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
         .alignment(Alignment::Center)
         .block(
             Block::default()
-                .title(" Server Time ")
+                .title(format!(" {} ", app.strings.server_time_title))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(Color::Green)),
+        );
+    f.render_widget(time_widget, chunks[5]);
+}
+
+/// Render the raw message inspector popup
+fn render_inspector(f: &mut Frame, app: &App) {
+    let area = centered_rect(70, 70, f.size());
+    let text = app
+        .selected_message()
+        .map(inspector_text)
+        .unwrap_or_else(|| app.strings.no_message_selected.to_string());
+
+    let inspector = Paragraph::new(text)
+        .style(Style::default().fg(Color::Green))
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .title(format!(" {} ", app.strings.inspector_title))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(Color::Cyan)),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_widget(inspector, area);
+}
+
+/// Render the popup listing pinned messages of the active channel
+fn render_pins(f: &mut Frame, app: &App) {
+    let area = centered_rect(70, 60, f.size());
+    let pinned = app
+        .channels
+        .get(&app.active_channel)
+        .map(|channel| channel.pinned_messages())
+        .unwrap_or_default();
+
+    let lines: Vec<Line> = if pinned.is_empty() {
+        vec![Line::styled(app.strings.no_pins, Style::default().fg(Color::DarkGray))]
+    } else {
+        pinned
+            .iter()
+            .map(|msg| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("[{}] ", msg.timestamp.format("%Y-%m-%d %H:%M")),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(
+                        format!("{}: ", app.display_name(&msg.sender)),
+                        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(&*msg.content, Style::default().fg(Color::White)),
+                ])
+            })
+            .collect()
+    };
+
+    let pins = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .title(format!(" {} ", fill(app.strings.pins_title, &[&pinned.len()])))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(Color::Yellow)),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_widget(pins, area);
+}
+
+/// Render the `/verify` popup: the code to compare and the choices
+fn render_verify(f: &mut Frame, app: &App, user: &str, code: &[usize; verify::SAS_LEN]) {
+    let area = centered_rect(60, 40, f.size());
+    let symbols = verify::emoji(code);
+    let code_line = if app.config.ascii_only {
+        symbols.iter().map(|(_, word)| *word).collect::<Vec<_>>().join("  ")
+    } else {
+        symbols.iter().map(|(emoji, _)| *emoji).collect::<Vec<_>>().join("  ")
+    };
+    let words = symbols.iter().map(|(_, word)| *word).collect::<Vec<_>>().join(", ");
+
+    let mut lines = vec![
+        Line::raw(fill(app.strings.verify_instructions, &[&user])),
+        Line::raw(""),
+        Line::styled(code_line, Style::default().fg(Color::White).add_modifier(Modifier::BOLD)),
+    ];
+    if !app.config.ascii_only {
+        lines.push(Line::styled(words, Style::default().fg(Color::DarkGray)));
+    }
+    lines.push(Line::raw(""));
+    lines.push(Line::styled(app.strings.verify_keys, Style::default().fg(Color::Yellow)));
+
+    let popup = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .title(format!(" {} ", fill(app.strings.verify_title, &[&user])))
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
                 .border_style(Style::default().fg(Color::Green)),
         );
-    f.render_widget(time_widget, chunks[4]);
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+/// Warning before a message goes out in plaintext where messages were encrypted
+fn render_downgrade(f: &mut Frame, app: &App, channel_id: &str) {
+    let area = centered_rect(60, 30, f.size());
+    let channel = app.channels.get(channel_id);
+    let name = channel
+        .map(|channel| app.channel_name(channel))
+        .unwrap_or_else(|| channel_id.to_string());
+
+    let mut lines = vec![Line::raw(fill(app.strings.downgrade_warning, &[&name]))];
+    if let Some(ChannelType::Room { name, .. }) = channel.map(|channel| &channel.channel_type) {
+        lines.push(Line::raw(""));
+        lines.push(Line::raw(fill(app.strings.downgrade_room_hint, &[&name])));
+    }
+    lines.push(Line::raw(""));
+    lines.push(Line::styled(app.strings.downgrade_keys, Style::default().fg(Color::Yellow)));
+
+    let popup = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .title(format!(" {} {} ", app.glyphs().warning, app.strings.downgrade_title))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(Color::Red)),
+        );
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+/// Profile card of a user: presence, identity key, shared channels and the
+/// keys for quick actions
+fn render_profile(f: &mut Frame, app: &App, username: &str) {
+    let area = centered_rect(60, 50, f.size());
+    let glyphs = app.glyphs();
+    let label = Style::default().fg(Color::DarkGray);
+    let user = app.users.iter().find(|u| u.username == username);
+
+    let (icon, status, color) = match user.map(User::status) {
+        Some(UserStatus::Active) => (glyphs.online, app.strings.roster_active, Color::Green),
+        Some(UserStatus::Idle) => (glyphs.idle, app.strings.roster_idle, Color::Yellow),
+        Some(UserStatus::Offline) | None => (glyphs.offline, app.strings.roster_offline, Color::DarkGray),
+    };
+    let suffix = user.map(|user| last_seen_suffix(app, user)).unwrap_or_default();
+    let mut lines = vec![Line::from(vec![
+        Span::styled(app.strings.profile_status, label),
+        Span::styled(format!("{} {}{}", icon, status, suffix), Style::default().fg(color)),
+    ])];
+    if let Some(user) = user {
+        lines.push(Line::from(vec![
+            Span::styled(app.strings.profile_last_seen, label),
+            Span::raw(user.last_seen.format("%Y-%m-%d %H:%M:%S UTC").to_string()),
+        ]));
+    }
+
+    if let Some(info) = app.whois.as_ref().filter(|info| info.username == username) {
+        let relay = match info.connected_at.and_then(|secs| chrono::DateTime::from_timestamp(secs, 0)) {
+            Some(since) => {
+                let age = chrono::Utc::now().signed_duration_since(since);
+                let ago = match (age.num_hours(), age.num_minutes()) {
+                    (0, minutes) => format!("{}m", minutes),
+                    (hours, minutes) => format!("{}h {}m", hours, minutes % 60),
+                };
+                let since = since.format("%Y-%m-%d %H:%M UTC");
+                fill(app.strings.profile_connected_since, &[&since, &ago])
+            }
+            None if info.online => app.strings.roster_active.to_string(),
+            None => app.strings.profile_not_connected.to_string(),
+        };
+        lines.push(Line::from(vec![Span::styled(app.strings.profile_relay, label), Span::raw(relay)]));
+        if let Some(version) = &info.client_version {
+            lines.push(Line::from(vec![Span::styled(app.strings.profile_client, label), Span::raw(version.clone())]));
+        }
+    }
+
+    let key = match app.e2e.identities(username) {
+        Some((_, theirs)) if app.e2e.is_verified(username) => Span::styled(
+            format!("{}  {} {}", verify::fingerprint(&theirs), glyphs.verified, app.strings.profile_verified),
+            Style::default().fg(Color::Green),
+        ),
+        Some((_, theirs)) => Span::styled(
+            format!("{}  ({})", verify::fingerprint(&theirs), app.strings.profile_unverified),
+            Style::default().fg(Color::White),
+        ),
+        None => Span::styled(app.strings.profile_no_key, Style::default().fg(Color::DarkGray)),
+    };
+    lines.push(Line::from(vec![Span::styled(app.strings.profile_key, label), key]));
+
+    let channels = app.shared_channels(username);
+    let channels = if channels.is_empty() {
+        app.strings.profile_no_channels.to_string()
+    } else {
+        channels.join(", ")
+    };
+    lines.push(Line::from(vec![Span::styled(app.strings.profile_channels, label), Span::raw(channels)]));
+
+    let blocked = app.blocked.contains(username);
+    if blocked {
+        lines.push(Line::raw(""));
+        lines.push(Line::styled(app.strings.profile_blocked, Style::default().fg(Color::Red)));
+    }
+    lines.push(Line::raw(""));
+    let actions = if blocked { app.strings.profile_actions_blocked } else { app.strings.profile_actions };
+    lines.push(Line::styled(actions, Style::default().fg(Color::Yellow)));
+
+    let title = match app.display_name(username) {
+        name if name == username => format!(" {} ", username),
+        name => format!(" {} ({}) ", name, username),
+    };
+    let popup = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Cyan)),
+    );
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+/// Render the command palette: the search line, the matching actions and
+/// commands with their keys or usage, and the palette's own keys
+fn render_palette(f: &mut Frame, app: &App, query: &str, selected: usize) {
+    let area = centered_rect(60, 60, f.size());
+    let block = Block::default()
+        .title(format!(" {} ", app.strings.palette_title))
+        .title(
+            Title::from(format!(" {} ", app.strings.palette_keys))
+                .position(Position::Bottom)
+                .alignment(Alignment::Center),
+        )
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Magenta));
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(0)])
+        .split(inner);
+    let search = Paragraph::new(Line::from(vec![
+        Span::styled("> ", Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)),
+        Span::raw(query),
+    ]));
+    f.render_widget(search, chunks[0]);
+    let cursor_col = (2 + query.width() as u16).min(chunks[0].width.saturating_sub(1));
+    f.set_cursor(chunks[0].x + cursor_col, chunks[0].y);
+
+    let matches = palette::search(query);
+    if matches.is_empty() {
+        let empty = Paragraph::new(Line::styled(app.strings.palette_no_matches, Style::default().fg(Color::DarkGray)));
+        f.render_widget(empty, chunks[1]);
+        return;
+    }
+
+    // Descriptions on the left, keys or usage right-aligned after them
+    let width = usize::from(chunks[1].width);
+    let items: Vec<ListItem> = matches
+        .iter()
+        .map(|entry| {
+            let hint_style = match entry.target {
+                palette::Target::Action(_) => Style::default().fg(Color::Yellow),
+                palette::Target::Command(_) => Style::default().fg(Color::Cyan),
+            };
+            let gap = width.saturating_sub(entry.description.width() + entry.hint.width() + 1).max(2);
+            ListItem::new(Line::from(vec![
+                Span::raw(entry.description),
+                Span::raw(" ".repeat(gap)),
+                Span::styled(entry.hint, hint_style),
+            ]))
+        })
+        .collect();
+    let list = List::new(items).highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD));
+    let mut state = ListState::default().with_selected(Some(selected.min(matches.len() - 1)));
+    f.render_stateful_widget(list, chunks[1], &mut state);
+}
+
+/// Render `/search` results: time, channel, sender and text of each hit
+fn render_search(f: &mut Frame, app: &App, query: &str, hits: &[SearchHit], selected: usize) {
+    let area = centered_rect(80, 70, f.size());
+    let block = Block::default()
+        .title(format!(" {} ", fill(app.strings.search_title, &[&query, &hits.len()])))
+        .title(
+            Title::from(format!(" {} ", app.strings.search_keys))
+                .position(Position::Bottom)
+                .alignment(Alignment::Center),
+        )
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    if hits.is_empty() {
+        let empty = Paragraph::new(Line::styled(app.strings.search_no_hits, Style::default().fg(Color::DarkGray)));
+        f.render_widget(empty, inner);
+        return;
+    }
+
+    let items: Vec<ListItem> = hits
+        .iter()
+        .map(|hit| {
+            let channel = app
+                .channels
+                .get(&hit.channel_id)
+                .map(|channel| app.channel_name(channel))
+                .unwrap_or_else(|| hit.channel_id.clone());
+            // One line per hit; the rest of a multi-line message is on the jump
+            let first_line = hit.content.lines().next().unwrap_or_default();
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("[{}] ", hit.timestamp.format("%Y-%m-%d %H:%M")),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(format!("{} ", channel), Style::default().fg(Color::Green)),
+                Span::styled(
+                    format!("{}: ", app.display_name(&hit.sender)),
+                    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(first_line),
+            ]))
+        })
+        .collect();
+    let list = List::new(items).highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD));
+    let mut state = ListState::default().with_selected(Some(selected.min(hits.len() - 1)));
+    f.render_stateful_widget(list, inner, &mut state);
+}
+
+/// Build the inspector body for a message: decoded fields followed by the raw frame
+fn inspector_text(msg: &ChatMessage) -> String {
+    let Some(raw) = &msg.raw else {
+        return format!(
+            "Sender:     {}\nTimestamp:  {}\n\nNo wire frame recorded (local message)",
+            msg.sender,
+            msg.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+        );
+    };
+
+    let decoded = match decode_frame(raw) {
+        Ok(wire) => format!(
+            "Type:       {:?}\nChannel:    {}\nSender:     {}\nTimestamp:  {} ({}){}\nSignature:  none (unsigned)\nEncryption: none (plaintext)",
+            wire.msg_type,
+            wire.channel,
+            wire.meta.sender,
+            wire.meta.timestamp,
+            msg.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+            if msg.clock_skewed { " - sender clock skewed, showing receive time" } else { "" },
+        ),
+        Err(e) => format!("Decode error: {}", e),
+    };
+
+    // Pretty-print the frame, falling back to the raw text if it is not valid JSON
+    let pretty = serde_json::from_str::<serde_json::Value>(raw)
+        .and_then(|value| serde_json::to_string_pretty(&value))
+        .unwrap_or_else(|_| raw.clone());
+
+    format!("{}\n\nRaw frame ({} bytes):\n{}", decoded, raw.len(), pretty)
+}
+
+/// Compute a rectangle centered in `r` using percentages of its size
+fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(r);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
 }
 
 /// Format bytes into human-readable format
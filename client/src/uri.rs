@@ -0,0 +1,89 @@
+// GhostWire Client - ghostwire:// Links
+// A `ghostwire://<server>/<channel>` link given in place of the username or
+// relay URL connects to that relay and opens the channel, so links on web
+// pages are clickable once the installer registered the URI handler. The
+// channel is `global`, a room name (`lobby` or `room:lobby`) or `dm/<user>`;
+// `?passphrase` asks for a room's passphrase instead of joining it in
+// plaintext. Links never carry usernames, passphrases or keys.
+
+use anyhow::{bail, Context};
+use ghostwire_protocol::validate::{validate_group_name, validate_username};
+
+/// Scheme of GhostWire links
+const SCHEME: &str = "ghostwire://";
+
+/// Channel a link opens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkTarget {
+    Global,
+    Room { name: String, passphrase: bool },
+    Dm(String),
+}
+
+/// A parsed `ghostwire://` link
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// Relay WebSocket URL
+    pub server_url: String,
+    /// Channel to open once connected; `None` leaves the last one open
+    pub target: Option<LinkTarget>,
+}
+
+/// Whether a command-line argument is a `ghostwire://` link
+pub fn is_link(arg: &str) -> bool {
+    arg.get(..SCHEME.len()).is_some_and(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+}
+
+/// Relay URL for a link's host: plain `ws://` for this machine, `wss://`
+/// for anything else
+fn server_url(authority: &str) -> String {
+    let host = match authority.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or_default(),
+        None => authority.split(':').next().unwrap_or_default(),
+    };
+    let scheme = match host {
+        "localhost" | "127.0.0.1" | "::1" => "ws",
+        _ => "wss",
+    };
+    format!("{}://{}/ws", scheme, authority)
+}
+
+/// Parse a `ghostwire://<server>[/<channel>][?passphrase]` link
+pub fn parse(link: &str) -> anyhow::Result<Link> {
+    if !is_link(link) {
+        bail!("Not a ghostwire:// link: {}", link);
+    }
+    let rest = &link[SCHEME.len()..];
+    // Fragments are for web pages; a trailing `#...` is ignored
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (rest, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+    if authority.is_empty() || authority.contains(['@', ' ']) {
+        bail!("Invalid server in link {}", link);
+    }
+
+    let passphrase = query.split('&').any(|param| param == "passphrase");
+    let path = path.trim_end_matches('/');
+    let target = match path.split('/').collect::<Vec<_>>().as_slice() {
+        [""] => None,
+        ["global"] => Some(LinkTarget::Global),
+        ["dm", user] => {
+            validate_username(user).with_context(|| format!("Invalid username in link {}", link))?;
+            Some(LinkTarget::Dm(user.to_string()))
+        }
+        [room] | ["room", room] => {
+            let name = room.strip_prefix("room:").unwrap_or(room);
+            validate_group_name(name).with_context(|| format!("Invalid room name in link {}", link))?;
+            Some(LinkTarget::Room {
+                name: name.to_string(),
+                passphrase,
+            })
+        }
+        _ => bail!("Unknown channel in link {} (use global, <room> or dm/<user>)", link),
+    };
+
+    Ok(Link {
+        server_url: server_url(authority),
+        target,
+    })
+}
@@ -0,0 +1,58 @@
+// GhostWire Client - Identity Verification
+// Short authentication strings for `/verify`: both sides hash the two identity
+// keys into seven emoji (with words for `ascii_only`) and compare them out of
+// band. A relay swapping keys would have to produce a matching 42-bit code.
+
+use crate::ratchet::Key;
+use sha2::{Digest, Sha256};
+
+/// Number of symbols in a code
+pub const SAS_LEN: usize = 7;
+
+/// 64 symbols, so each carries 6 bits of the hash
+const SYMBOLS: [(&str, &str); 64] = [
+    ("🐶", "Dog"), ("🐱", "Cat"), ("🦁", "Lion"), ("🐎", "Horse"),
+    ("🦄", "Unicorn"), ("🐷", "Pig"), ("🐘", "Elephant"), ("🐰", "Rabbit"),
+    ("🐼", "Panda"), ("🐓", "Rooster"), ("🐧", "Penguin"), ("🐢", "Turtle"),
+    ("🐟", "Fish"), ("🐙", "Octopus"), ("🦋", "Butterfly"), ("🌷", "Flower"),
+    ("🌳", "Tree"), ("🌵", "Cactus"), ("🍄", "Mushroom"), ("🌏", "Globe"),
+    ("🌙", "Moon"), ("☁️", "Cloud"), ("🔥", "Fire"), ("🍌", "Banana"),
+    ("🍎", "Apple"), ("🍓", "Strawberry"), ("🌽", "Corn"), ("🍕", "Pizza"),
+    ("🎂", "Cake"), ("❤️", "Heart"), ("😀", "Smiley"), ("🤖", "Robot"),
+    ("🎩", "Hat"), ("👓", "Glasses"), ("🔧", "Spanner"), ("🎅", "Santa"),
+    ("👍", "Thumbs up"), ("☂️", "Umbrella"), ("⌛", "Hourglass"), ("⏰", "Clock"),
+    ("🎁", "Gift"), ("💡", "Light bulb"), ("📕", "Book"), ("✏️", "Pencil"),
+    ("📎", "Paperclip"), ("✂️", "Scissors"), ("🔒", "Lock"), ("🔑", "Key"),
+    ("🔨", "Hammer"), ("☎️", "Telephone"), ("🏁", "Flag"), ("🚂", "Train"),
+    ("🚲", "Bicycle"), ("✈️", "Aeroplane"), ("🚀", "Rocket"), ("🏆", "Trophy"),
+    ("⚽", "Ball"), ("🎸", "Guitar"), ("🎺", "Trumpet"), ("🔔", "Bell"),
+    ("⚓", "Anchor"), ("🎧", "Headphones"), ("📁", "Folder"), ("📌", "Pin"),
+];
+
+/// The code for two identity public keys; the same on both sides, since the
+/// keys are hashed in sorted order
+pub fn sas(ours: &Key, theirs: &Key) -> [usize; SAS_LEN] {
+    let (first, second) = if ours.0 <= theirs.0 { (ours, theirs) } else { (theirs, ours) };
+    let digest = Sha256::new()
+        .chain_update(b"GhostWire SAS")
+        .chain_update(first.0)
+        .chain_update(second.0)
+        .finalize();
+    let bits = digest[..6].iter().fold(0u64, |acc, &byte| (acc << 8) | u64::from(byte));
+    std::array::from_fn(|i| ((bits >> (48 - 6 * (i + 1))) & 0x3F) as usize)
+}
+
+/// Emoji of a code, each followed by its word
+pub fn emoji(code: &[usize; SAS_LEN]) -> Vec<(&'static str, &'static str)> {
+    code.iter().map(|&i| SYMBOLS[i]).collect()
+}
+
+/// Short hex fingerprint of an identity key, in groups of four for reading aloud
+pub fn fingerprint(key: &Key) -> String {
+    let digest = Sha256::digest(key.0);
+    digest[..16]
+        .chunks(2)
+        .map(|pair| format!("{:02x}{:02x}", pair[0], pair[1]))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
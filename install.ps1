@@ -74,6 +74,15 @@ if ($USER_PATH -notlike "*$INSTALL_DIR*") {
     Write-Host "✅ $INSTALL_DIR already in PATH" -ForegroundColor Green
 }
 
+# Register the ghostwire:// link handler for this user; links open the
+# client in a console window
+$PROTOCOL_KEY = "HKCU:\Software\Classes\ghostwire"
+New-Item -Path "$PROTOCOL_KEY\shell\open\command" -Force | Out-Null
+Set-ItemProperty -Path $PROTOCOL_KEY -Name "(Default)" -Value "URL:GhostWire Protocol"
+Set-ItemProperty -Path $PROTOCOL_KEY -Name "URL Protocol" -Value ""
+Set-ItemProperty -Path "$PROTOCOL_KEY\shell\open\command" -Name "(Default)" -Value "`"$INSTALL_DIR\$BINARY_NAME`" `"%1`""
+Write-Host "✅ Registered the ghostwire:// link handler" -ForegroundColor Green
+
 Write-Host ""
 Write-Host "✅ GhostWire Installed Successfully!" -ForegroundColor Green
 Write-Host "Installed to: $INSTALL_DIR\$BINARY_NAME" -ForegroundColor Cyan
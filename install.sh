@@ -89,5 +89,29 @@ else
     fi
 fi
 
+# Register the ghostwire:// link handler (freedesktop desktops); links open
+# the client in a terminal. macOS needs an app bundle, so it is skipped there.
+if [ "$PLATFORM" == "linux" ]; then
+    APPS_DIR="${XDG_DATA_HOME:-$HOME/.local/share}/applications"
+    mkdir -p "$APPS_DIR"
+    cat > "$APPS_DIR/ghostwire.desktop" <<DESKTOP
+[Desktop Entry]
+Type=Application
+Name=GhostWire
+Comment=Ephemeral terminal chat
+Exec=$FINAL_PATH %u
+Terminal=true
+NoDisplay=true
+MimeType=x-scheme-handler/ghostwire;
+DESKTOP
+    if command -v xdg-mime >/dev/null 2>&1; then
+        xdg-mime default ghostwire.desktop x-scheme-handler/ghostwire
+    fi
+    if command -v update-desktop-database >/dev/null 2>&1; then
+        update-desktop-database "$APPS_DIR" >/dev/null 2>&1 || true
+    fi
+    echo "Registered the ghostwire:// link handler"
+fi
+
 echo -e "${GREEN}✅ GhostWire Installed Successfully!${NC}"
 echo -e "Run with: ${GREEN}ghostwire${NC}"
@@ -0,0 +1,409 @@
+// GhostWire Load Generator
+// Opens many WebSocket connections to a relay, has each one send chat frames
+// at a steady rate, and measures how long the relay takes to fan every frame
+// out to the other connections. Every connection both sends and receives, so
+// a run exercises the broadcast path the way a busy channel does.
+
+use anyhow::Context;
+use clap::Parser;
+use futures_util::future::join_all;
+use futures_util::{SinkExt, StreamExt};
+use ghostwire_protocol::chunk::MAX_FRAME_LEN;
+use ghostwire_protocol::decode::decode_frame;
+use ghostwire_protocol::validate::validate_channel_id;
+use ghostwire_protocol::{MessageMeta, MessageType, WireMessage};
+use tokio::net::TcpStream;
+use tokio::time::{interval_at, timeout, timeout_at, Duration, Instant, MissedTickBehavior};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// How long a connection may take to open and be greeted by the relay
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Username prefix, so other clients on the relay can tell test traffic apart
+const NAME_PREFIX: &str = "loadgen";
+
+#[derive(Parser, Debug)]
+#[command(name = "ghostwire-loadgen")]
+#[command(about = "Measure relay broadcast latency and drops under load", long_about = None)]
+struct Args {
+    /// Relay WebSocket URL
+    #[arg(default_value = "ws://127.0.0.1:8080/ws")]
+    url: String,
+
+    /// Connections to open
+    #[arg(short, long, default_value_t = 10)]
+    clients: usize,
+
+    /// Messages per second, across all connections
+    #[arg(short, long, default_value_t = 10.0)]
+    rate: f64,
+
+    /// Payload size in bytes, or a range such as 64-1024 to vary it
+    #[arg(short, long, default_value = "64", value_parser = parse_size)]
+    size: (usize, usize),
+
+    /// Seconds to send for
+    #[arg(short, long, default_value_t = 10)]
+    duration: u64,
+
+    /// Seconds to keep receiving after the last send
+    #[arg(long, default_value_t = 2)]
+    drain: u64,
+
+    /// Channel to send in
+    #[arg(long, default_value = "global")]
+    channel: String,
+}
+
+/// Parse `N` or `MIN-MAX`
+fn parse_size(s: &str) -> Result<(usize, usize), String> {
+    let parse = |n: &str| n.trim().parse::<usize>().map_err(|_| format!("invalid size \"{}\"", n));
+    let (min, max) = match s.split_once('-') {
+        Some((min, max)) => (parse(min)?, parse(max)?),
+        None => (parse(s)?, parse(s)?),
+    };
+    if min > max {
+        return Err(format!("size range {} is backwards", s));
+    }
+    Ok((min, max))
+}
+
+/// What one connection saw during the run
+struct Report {
+    sent: u64,
+    received: u64,
+    /// SYS frames from the relay, usually rejections
+    notices: u64,
+    first_notice: Option<String>,
+    /// Whether the relay closed the connection before the run ended
+    closed_early: bool,
+    latency: Histogram,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    if args.clients < 2 {
+        anyhow::bail!("--clients must be at least 2: a broadcast needs someone to receive it");
+    }
+    if !args.rate.is_finite() || args.rate <= 0.0 {
+        anyhow::bail!("--rate must be positive");
+    }
+    validate_channel_id(&args.channel).context("--channel")?;
+    // Fail now rather than have the relay refuse every frame
+    let largest = frame_text(&format!("{}-{}", NAME_PREFIX, args.clients), &args.channel, u64::MAX, 0, args.size.1);
+    if largest.len() > MAX_FRAME_LEN {
+        anyhow::bail!(
+            "--size {} makes {}-byte frames; the relay accepts at most {}",
+            args.size.1,
+            largest.len(),
+            MAX_FRAME_LEN
+        );
+    }
+
+    eprintln!("Connecting {} clients to {}...", args.clients, args.url);
+    let sockets = join_all((0..args.clients).map(|i| connect(&args.url, username(i))))
+        .await
+        .into_iter()
+        .enumerate()
+        .map(|(i, socket)| socket.with_context(|| format!("connection {} failed", i)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    // Connections take turns, so the relay sees an even stream of frames
+    let period = Duration::from_secs_f64(args.clients as f64 / args.rate);
+    let stagger = period / args.clients as u32;
+    let epoch = Instant::now();
+    let stop = epoch + Duration::from_secs(args.duration);
+    let drained = stop + Duration::from_secs(args.drain);
+    eprintln!("Sending {:.1} msg/s for {}s...", args.rate, args.duration);
+
+    let tasks = sockets.into_iter().enumerate().map(|(i, socket)| {
+        let run = Run {
+            username: username(i),
+            channel: args.channel.clone(),
+            size: args.size,
+            epoch,
+            start: epoch + stagger * i as u32,
+            period,
+            stop,
+            drained,
+        };
+        tokio::spawn(run.drive(socket))
+    });
+    let reports = join_all(tasks)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .context("a connection task panicked")?;
+
+    print_summary(&args, &reports);
+    Ok(())
+}
+
+fn username(index: usize) -> String {
+    format!("{}-{}", NAME_PREFIX, index)
+}
+
+/// Open a connection, authenticate, and wait for the relay's HELLO, which
+/// it sends once the connection is registered for broadcasts
+async fn connect(url: &str, username: String) -> anyhow::Result<Socket> {
+    let (mut socket, _) = timeout(CONNECT_TIMEOUT, connect_async(url)).await.context("timed out")??;
+    let auth = WireMessage {
+        msg_type: MessageType::Auth,
+        payload: username.clone(),
+        channel: "global".to_string(),
+        meta: MessageMeta {
+            sender: username,
+            timestamp: 0,
+            id: None,
+            seq: None,
+            delayed: false,
+            encrypted: false,
+            action: false,
+        },
+    };
+    socket.send(Message::Text(serde_json::to_string(&auth)?)).await?;
+
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        let message = timeout_at(deadline, socket.next())
+            .await
+            .context("no HELLO from the relay")?
+            .context("the relay closed the connection")??;
+        if let Message::Text(text) = message {
+            if decode_frame(&text).is_ok_and(|frame| matches!(frame.msg_type, MessageType::Hello)) {
+                return Ok(socket);
+            }
+        }
+    }
+}
+
+/// One connection's part in the run
+struct Run {
+    username: String,
+    channel: String,
+    size: (usize, usize),
+    /// Send times are measured from here, the same for every connection
+    epoch: Instant,
+    /// When this connection sends its first frame
+    start: Instant,
+    period: Duration,
+    /// When sending ends
+    stop: Instant,
+    /// When receiving ends
+    drained: Instant,
+}
+
+impl Run {
+    async fn drive(self, socket: Socket) -> Report {
+        let (mut write, mut read) = socket.split();
+
+        let send = async {
+            let mut ticks = interval_at(self.start, self.period);
+            // A connection that falls behind sends less rather than bursting
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            let mut sent = 0;
+            while ticks.tick().await < self.stop {
+                let sent_us = self.epoch.elapsed().as_micros() as u64;
+                let size = pick_size(self.size, sent);
+                let text = frame_text(&self.username, &self.channel, sent_us, sent + 1, size);
+                if write.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+                sent += 1;
+            }
+            sent
+        };
+
+        let receive = async {
+            let mut report = Report {
+                sent: 0,
+                received: 0,
+                notices: 0,
+                first_notice: None,
+                closed_early: false,
+                latency: Histogram::default(),
+            };
+            // Ok(None): the relay closed the connection; Err: the run is over
+            while let Ok(message) = timeout_at(self.drained, read.next()).await {
+                let Some(Ok(message)) = message else {
+                    report.closed_early = true;
+                    break;
+                };
+                let Message::Text(text) = message else { continue };
+                let Ok(frame) = decode_frame(&text) else { continue };
+                match frame.msg_type {
+                    MessageType::Message if frame.meta.sender.starts_with(NAME_PREFIX) => {
+                        let Some(sent_us) = frame.payload.split(' ').next().and_then(|t| t.parse::<u64>().ok())
+                        else {
+                            continue;
+                        };
+                        let now_us = self.epoch.elapsed().as_micros() as u64;
+                        report.latency.record(now_us.saturating_sub(sent_us));
+                        report.received += 1;
+                    }
+                    MessageType::System | MessageType::Kick => {
+                        report.notices += 1;
+                        report.first_notice.get_or_insert(frame.payload);
+                    }
+                    _ => {}
+                }
+            }
+            report
+        };
+
+        let (sent, mut report) = tokio::join!(send, receive);
+        report.sent = sent;
+        let _ = write.send(Message::Close(None)).await;
+        report
+    }
+}
+
+/// Payload size for the `n`th frame: fixed, or spread over the range
+fn pick_size((min, max): (usize, usize), n: u64) -> usize {
+    let spread = (n.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32) as usize;
+    min + spread % (max - min + 1)
+}
+
+/// A chat frame whose payload starts with its send time in microseconds
+/// since the epoch, padded to `size` bytes
+fn frame_text(sender: &str, channel: &str, sent_us: u64, seq: u64, size: usize) -> String {
+    let mut payload = format!("{} ", sent_us);
+    let padding = size.saturating_sub(payload.len());
+    payload.push_str(&"x".repeat(padding));
+    let frame = WireMessage {
+        msg_type: MessageType::Message,
+        payload,
+        channel: channel.to_string(),
+        meta: MessageMeta {
+            sender: sender.to_string(),
+            timestamp: 0,
+            id: Some(format!("{}:{}", sender, seq)),
+            seq: Some(seq),
+            delayed: false,
+            encrypted: false,
+            action: false,
+        },
+    };
+    serde_json::to_string(&frame).unwrap_or_default()
+}
+
+fn print_summary(args: &Args, reports: &[Report]) {
+    let sent: u64 = reports.iter().map(|r| r.sent).sum();
+    let received: u64 = reports.iter().map(|r| r.received).sum();
+    let notices: u64 = reports.iter().map(|r| r.notices).sum();
+    let closed = reports.iter().filter(|r| r.closed_early).count();
+    // The relay sends every frame to every connection except its sender
+    let expected = sent * (reports.len() as u64 - 1);
+    let dropped = expected.saturating_sub(received);
+    let mut latency = Histogram::default();
+    for report in reports {
+        latency.merge(&report.latency);
+    }
+    let percent = |n: u64| if expected == 0 { 0.0 } else { n as f64 * 100.0 / expected as f64 };
+    let size = match args.size {
+        (min, max) if min == max => format!("{} bytes", min),
+        (min, max) => format!("{}-{} bytes", min, max),
+    };
+
+    println!("Relay:         {}", args.url);
+    println!("Connections:   {} ({} closed by the relay)", reports.len(), closed);
+    println!("Sent:          {} messages ({:.1}/s, {})", sent, sent as f64 / args.duration as f64, size);
+    println!("Expected:      {} deliveries", expected);
+    println!("Delivered:     {} ({:.2}%)", received, percent(received));
+    println!("Dropped:       {} ({:.2}%)", dropped, percent(dropped));
+    println!("Relay notices: {}", notices);
+    if let Some(notice) = reports.iter().find_map(|r| r.first_notice.as_deref()) {
+        println!("  first:       {}", notice);
+    }
+    if latency.count() == 0 {
+        println!("Latency:       no deliveries");
+        return;
+    }
+    println!(
+        "Latency:       p50 {}  p90 {}  p99 {}  p99.9 {}  max {}",
+        millis(latency.percentile(50.0)),
+        millis(latency.percentile(90.0)),
+        millis(latency.percentile(99.0)),
+        millis(latency.percentile(99.9)),
+        millis(latency.max),
+    );
+}
+
+fn millis(us: u64) -> String {
+    format!("{:.2}ms", us as f64 / 1000.0)
+}
+
+/// Values below this are counted exactly; above it each power of two is
+/// split into `SUB_BUCKETS` buckets, so a percentile is within about 1.5%
+const EXACT: u64 = 128;
+const SUB_BUCKETS: u64 = 64;
+
+/// Latency histogram in microseconds, small enough to keep per connection
+/// however long the run
+#[derive(Default)]
+struct Histogram {
+    counts: Vec<u64>,
+    max: u64,
+}
+
+impl Histogram {
+    fn record(&mut self, us: u64) {
+        let index = Self::index(us);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+        self.max = self.max.max(us);
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.max = self.max.max(other.max);
+    }
+
+    fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Smallest recorded value (rounded up to its bucket) that `p` percent
+    /// of values are at or below
+    fn percentile(&self, p: f64) -> u64 {
+        let rank = ((p / 100.0) * self.count() as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Self::upper_bound(index).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    fn index(us: u64) -> usize {
+        if us < EXACT {
+            return us as usize;
+        }
+        // Shift that brings the value into [SUB_BUCKETS, 2 * SUB_BUCKETS)
+        let shift = 63 - us.leading_zeros() as u64 - SUB_BUCKETS.trailing_zeros() as u64;
+        (EXACT + (shift - 1) * SUB_BUCKETS + ((us >> shift) - SUB_BUCKETS)) as usize
+    }
+
+    fn upper_bound(index: usize) -> u64 {
+        let index = index as u64;
+        if index < EXACT {
+            return index;
+        }
+        let shift = (index - EXACT) / SUB_BUCKETS + 1;
+        let mantissa = (index - EXACT) % SUB_BUCKETS + SUB_BUCKETS;
+        ((mantissa + 1) << shift) - 1
+    }
+}
@@ -0,0 +1,70 @@
+// GhostWire Protocol - Wire Format Benchmarks
+// Encoding and decoding a MSG frame as JSON, the format on the wire today,
+// and as MessagePack, the obvious binary alternative, at a short message, a
+// long one and a full chunk. `decode_frame` is measured next to plain serde
+// so the cost of tolerant decoding stays visible.
+//
+// cargo bench -p ghostwire-protocol
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ghostwire_protocol::chunk::CHUNK_DATA_LEN;
+use ghostwire_protocol::decode::decode_frame;
+use ghostwire_protocol::{MessageMeta, MessageType, WireMessage};
+
+/// Payload sizes measured, in bytes
+const PAYLOAD_SIZES: &[usize] = &[64, 1024, CHUNK_DATA_LEN];
+
+fn frame(payload_len: usize) -> WireMessage {
+    WireMessage {
+        msg_type: MessageType::Message,
+        payload: "x".repeat(payload_len),
+        channel: "dm:alice:bob".to_string(),
+        meta: MessageMeta {
+            sender: "alice".to_string(),
+            timestamp: 1_700_000_000,
+            id: Some("0b6c1f9e-4a2d-4c6e-9b1a-3f1e2d4c5b6a".to_string()),
+            seq: Some(42),
+            delayed: false,
+            encrypted: false,
+            action: false,
+        },
+    }
+}
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for &size in PAYLOAD_SIZES {
+        let msg = frame(size);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("json", size), &msg, |b, msg| {
+            b.iter(|| serde_json::to_string(black_box(msg)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("msgpack", size), &msg, |b, msg| {
+            b.iter(|| rmp_serde::to_vec_named(black_box(msg)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for &size in PAYLOAD_SIZES {
+        let msg = frame(size);
+        let json = serde_json::to_string(&msg).unwrap();
+        let msgpack = rmp_serde::to_vec_named(&msg).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("json", size), &json, |b, json| {
+            b.iter(|| serde_json::from_str::<WireMessage>(black_box(json)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("json_tolerant", size), &json, |b, json| {
+            b.iter(|| decode_frame(black_box(json)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("msgpack", size), &msgpack, |b, msgpack| {
+            b.iter(|| rmp_serde::from_slice::<WireMessage>(black_box(msgpack)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encode, decode);
+criterion_main!(benches);
@@ -0,0 +1,149 @@
+// GhostWire Protocol - Large Message Chunking
+// A MSG frame longer than `MAX_FRAME_LEN` is sent as CHUNK frames instead,
+// each carrying a slice of the serialized MSG frame. The receiving client
+// joins the slices and handles the result as if the MSG had arrived whole,
+// so the relay can refuse big frames without breaking long pastes.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Longest text frame the relay accepts, in bytes
+pub const MAX_FRAME_LEN: usize = 16 * 1024;
+
+/// Most bytes of a chunk's `data` once JSON-escaped, leaving room in the
+/// CHUNK frame for its envelope
+pub const CHUNK_DATA_LEN: usize = 8 * 1024;
+
+/// Most chunks in one message, so a reassembled frame is at most 512 KiB
+pub const MAX_CHUNKS: u32 = 64;
+
+/// How long a partly received message waits for its remaining chunks
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Most partly received messages kept at once; the oldest go first
+const MAX_PENDING: usize = 32;
+
+/// A CHUNK payload: slice `index` of `total` of the frame whose message ID is `id`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    pub id: String,
+    pub index: u32,
+    pub total: u32,
+    pub data: String,
+}
+
+impl Chunk {
+    /// Encode as a CHUNK payload
+    pub fn to_payload(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Decode a CHUNK payload
+    pub fn from_payload(payload: &str) -> Option<Self> {
+        serde_json::from_str(payload).ok()
+    }
+}
+
+/// Length of a character once escaped in a JSON string
+fn escaped_len(c: char) -> usize {
+    match c {
+        '"' | '\\' | '\n' | '\r' | '\t' | '\u{8}' | '\u{c}' => 2,
+        c if c < ' ' => 6,
+        c => c.len_utf8(),
+    }
+}
+
+/// Split a serialized frame into chunks of at most `CHUNK_DATA_LEN` escaped
+/// bytes. `None` if that takes more than `MAX_CHUNKS`.
+pub fn split(id: &str, frame: &str) -> Option<Vec<Chunk>> {
+    let mut slices = vec![String::new()];
+    let mut len = 0;
+    for c in frame.chars() {
+        if len + escaped_len(c) > CHUNK_DATA_LEN {
+            slices.push(String::new());
+            len = 0;
+        }
+        len += escaped_len(c);
+        slices.last_mut().expect("starts non-empty").push(c);
+    }
+
+    let total = u32::try_from(slices.len()).ok().filter(|&total| total <= MAX_CHUNKS)?;
+    Some(
+        slices
+            .into_iter()
+            .zip(0..)
+            .map(|(data, index)| Chunk {
+                id: id.to_string(),
+                index,
+                total,
+                data,
+            })
+            .collect(),
+    )
+}
+
+/// Chunks received so far for one message
+struct Partial {
+    parts: Vec<Option<String>>,
+    missing: usize,
+    started: Instant,
+}
+
+/// Collects chunks per sender and message ID until each message is complete
+#[derive(Default)]
+pub struct Reassembler {
+    pending: HashMap<(String, String), Partial>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a chunk from `sender`, returning the whole frame once its last
+    /// missing chunk arrives. Malformed or inconsistent chunks are ignored.
+    pub fn add(&mut self, sender: &str, chunk: Chunk) -> Option<String> {
+        self.pending.retain(|_, partial| partial.started.elapsed() < REASSEMBLY_TIMEOUT);
+
+        if chunk.total == 0 || chunk.total > MAX_CHUNKS || chunk.index >= chunk.total {
+            return None;
+        }
+        // A slice can be no longer than its escaped form
+        if chunk.data.len() > CHUNK_DATA_LEN {
+            return None;
+        }
+
+        let key = (sender.to_string(), chunk.id);
+        if !self.pending.contains_key(&key) && self.pending.len() >= MAX_PENDING {
+            let oldest = self
+                .pending
+                .iter()
+                .min_by_key(|(_, partial)| partial.started)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.pending.remove(&oldest);
+            }
+        }
+        let partial = self.pending.entry(key.clone()).or_insert_with(|| Partial {
+            parts: vec![None; chunk.total as usize],
+            missing: chunk.total as usize,
+            started: Instant::now(),
+        });
+        if partial.parts.len() != chunk.total as usize {
+            return None;
+        }
+
+        let slot = &mut partial.parts[chunk.index as usize];
+        if slot.is_none() {
+            partial.missing -= 1;
+        }
+        *slot = Some(chunk.data);
+        if partial.missing > 0 {
+            return None;
+        }
+
+        let partial = self.pending.remove(&key)?;
+        Some(partial.parts.into_iter().flatten().collect())
+    }
+}
@@ -0,0 +1,26 @@
+// GhostWire Protocol - Clock Exchange
+// Heartbeat ping payloads carry Unix-epoch milliseconds as 8 big-endian bytes.
+// The relay stamps its own pings with its wall clock, and clients stamp theirs
+// with the send time so the echoed pong yields a round-trip time. Peers that
+// predate this just echo or ignore the payload.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current wall-clock time in Unix-epoch milliseconds
+pub fn unix_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// Encode a millisecond timestamp as a ping payload
+pub fn encode_millis(millis: i64) -> Vec<u8> {
+    millis.to_be_bytes().to_vec()
+}
+
+/// Decode a ping/pong payload, if it carries a timestamp
+pub fn decode_millis(payload: &[u8]) -> Option<i64> {
+    let bytes: [u8; 8] = payload.try_into().ok()?;
+    Some(i64::from_be_bytes(bytes))
+}
@@ -0,0 +1,361 @@
+// GhostWire Protocol - Tolerant Frame Decoding
+// Frames come from other people's clients, so decoding takes what it can and
+// says exactly why it gave up when it cannot. It accepts everything the
+// strict serde form accepts, plus a few variations older or hand-written
+// clients produce: `"type": "MSG"` instead of `"type": {"type": "MSG"}`,
+// lowercase type names, and numeric strings for the timestamp. Fields with
+// the wrong type that are optional (`id`, `seq`, flags) are ignored rather
+// than failing the whole frame.
+
+use crate::chunk::{CHUNK_DATA_LEN, MAX_CHUNKS};
+use crate::validate::MAX_NAME_LEN;
+use crate::{MessageMeta, MessageType, WireMessage};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Longest frame decoded, in bytes: a MSG reassembled from `MAX_CHUNKS`
+/// chunks can be this long, and relay frames such as WHO are far shorter
+pub const MAX_DECODE_LEN: usize = MAX_CHUNKS as usize * CHUNK_DATA_LEN;
+
+/// Longest channel ID, in characters: `dm:` and two usernames
+pub const MAX_CHANNEL_LEN: usize = 3 + 2 * MAX_NAME_LEN + 1;
+
+/// Longest message ID, in characters (UUIDs take 36)
+pub const MAX_ID_LEN: usize = 64;
+
+/// Why a frame could not be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Frame is longer than `MAX_DECODE_LEN` bytes
+    TooLarge { len: usize },
+    /// Frame is not valid JSON
+    NotJson(String),
+    /// Frame is JSON but not an object
+    NotObject,
+    /// `type` names no frame type this build knows
+    UnknownType(String),
+    /// The `meta` object is missing
+    MissingMeta,
+    /// A required field is missing
+    MissingField(&'static str),
+    /// A required field has the wrong JSON type
+    InvalidField { field: &'static str, expected: &'static str },
+    /// A field is longer than the protocol allows
+    OversizedField { field: &'static str, len: usize, max: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::TooLarge { len } => {
+                write!(f, "frame is {} bytes (limit {})", len, MAX_DECODE_LEN)
+            }
+            DecodeError::NotJson(e) => write!(f, "not valid JSON: {}", e),
+            DecodeError::NotObject => write!(f, "frame is not a JSON object"),
+            DecodeError::UnknownType(name) => write!(f, "unknown frame type \"{}\"", name),
+            DecodeError::MissingMeta => write!(f, "frame has no meta"),
+            DecodeError::MissingField(field) => write!(f, "missing field `{}`", field),
+            DecodeError::InvalidField { field, expected } => {
+                write!(f, "field `{}` should be {}", field, expected)
+            }
+            DecodeError::OversizedField { field, len, max } => {
+                write!(f, "field `{}` is {} characters (limit {})", field, len, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Decode a text frame
+pub fn decode_frame(text: &str) -> Result<WireMessage, DecodeError> {
+    if text.len() > MAX_DECODE_LEN {
+        return Err(DecodeError::TooLarge { len: text.len() });
+    }
+    let value: Value = serde_json::from_str(text).map_err(|e| DecodeError::NotJson(e.to_string()))?;
+    let Value::Object(frame) = value else {
+        return Err(DecodeError::NotObject);
+    };
+
+    let msg_type = frame_type(frame.get("type"))?;
+    let payload = required_str(&frame, "payload", "payload")?;
+    let channel = match frame.get("channel") {
+        None | Some(Value::Null) => "global".to_string(),
+        Some(Value::String(channel)) => channel.clone(),
+        Some(_) => return Err(DecodeError::InvalidField { field: "channel", expected: "a string" }),
+    };
+    check_len("channel", &channel, MAX_CHANNEL_LEN)?;
+
+    let meta = match frame.get("meta") {
+        None | Some(Value::Null) => return Err(DecodeError::MissingMeta),
+        Some(Value::Object(meta)) => meta,
+        Some(_) => return Err(DecodeError::InvalidField { field: "meta", expected: "an object" }),
+    };
+    let sender = required_str(meta, "sender", "meta.sender")?;
+    check_len("meta.sender", &sender, MAX_NAME_LEN)?;
+    let timestamp = timestamp(meta.get("timestamp"))?;
+    let id = match meta.get("id") {
+        Some(Value::String(id)) => {
+            check_len("meta.id", id, MAX_ID_LEN)?;
+            Some(id.clone())
+        }
+        _ => None,
+    };
+    let flag = |name: &str| meta.get(name).and_then(Value::as_bool).unwrap_or(false);
+
+    Ok(WireMessage {
+        msg_type,
+        payload,
+        channel,
+        meta: MessageMeta {
+            sender,
+            timestamp,
+            id,
+            seq: meta.get("seq").and_then(Value::as_u64),
+            delayed: flag("delayed"),
+            encrypted: flag("encrypted"),
+            action: flag("action"),
+        },
+    })
+}
+
+/// The frame type, from `{"type": "MSG"}` or plain `"MSG"`, any case
+fn frame_type(value: Option<&Value>) -> Result<MessageType, DecodeError> {
+    let name = match value {
+        None | Some(Value::Null) => return Err(DecodeError::MissingField("type")),
+        Some(Value::String(name)) => name,
+        Some(Value::Object(tagged)) => match tagged.get("type") {
+            Some(Value::String(name)) => name,
+            None => return Err(DecodeError::MissingField("type.type")),
+            Some(_) => return Err(DecodeError::InvalidField { field: "type.type", expected: "a string" }),
+        },
+        Some(_) => return Err(DecodeError::InvalidField { field: "type", expected: "a string or object" }),
+    };
+    let tagged = serde_json::json!({ "type": name.to_ascii_uppercase() });
+    serde_json::from_value(tagged).map_err(|_| DecodeError::UnknownType(name.clone()))
+}
+
+/// Seconds since the epoch, as an integer, a float or a numeric string
+fn timestamp(value: Option<&Value>) -> Result<i64, DecodeError> {
+    let invalid = DecodeError::InvalidField { field: "meta.timestamp", expected: "a number" };
+    match value {
+        None | Some(Value::Null) => Err(DecodeError::MissingField("meta.timestamp")),
+        Some(Value::Number(n)) => n.as_i64().or_else(|| n.as_f64().map(|f| f as i64)).ok_or(invalid),
+        Some(Value::String(s)) => s.trim().parse().map_err(|_| invalid),
+        Some(_) => Err(invalid),
+    }
+}
+
+fn required_str(
+    object: &Map<String, Value>,
+    key: &str,
+    field: &'static str,
+) -> Result<String, DecodeError> {
+    match object.get(key) {
+        None | Some(Value::Null) => Err(DecodeError::MissingField(field)),
+        Some(Value::String(s)) => Ok(s.clone()),
+        Some(_) => Err(DecodeError::InvalidField { field, expected: "a string" }),
+    }
+}
+
+fn check_len(field: &'static str, value: &str, max: usize) -> Result<(), DecodeError> {
+    let len = value.chars().count();
+    if len > max {
+        return Err(DecodeError::OversizedField { field, len, max });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Cases per property; the seed is fixed so a failure reproduces
+    const CASES: usize = 500;
+
+    const TYPES: &[&str] = &[
+        "MSG", "AUTH", "SYS", "WHO", "RENAME", "PIN", "UNPIN", "HELLO", "KICK", "GROUP",
+        "DELIVERED", "READ", "KEYX", "CHUNK", "WHOIS",
+    ];
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(0x6057_3172)
+    }
+
+    /// Any string: ASCII, control characters, quotes and non-BMP characters
+    fn any_string(rng: &mut StdRng, max_len: usize) -> String {
+        let len = rng.gen_range(0..=max_len);
+        (0..len)
+            .map(|_| match rng.gen_range(0..6) {
+                0 => rng.gen_range(0u8..0x20) as char,
+                1 => ['"', '\\', '{', '}', ':', ','][rng.gen_range(0..6)],
+                2 => char::from_u32(rng.gen_range(0x80..0x3000)).unwrap_or('?'),
+                3 => char::from_u32(rng.gen_range(0x1F300..0x1F600)).unwrap_or('?'),
+                _ => rng.gen_range(b' '..=b'~') as char,
+            })
+            .collect()
+    }
+
+    fn any_name(rng: &mut StdRng) -> String {
+        const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_-.";
+        let len = rng.gen_range(1..=MAX_NAME_LEN);
+        (0..len).map(|_| CHARS[rng.gen_range(0..CHARS.len())] as char).collect()
+    }
+
+    /// A frame the strict serde form produces
+    fn any_frame(rng: &mut StdRng) -> WireMessage {
+        let tagged = serde_json::json!({ "type": TYPES[rng.gen_range(0..TYPES.len())] });
+        let channel = match rng.gen_range(0..3) {
+            0 => "global".to_string(),
+            1 => format!("dm:{}:{}", any_name(rng), any_name(rng)),
+            _ => format!("group:{}", any_name(rng)),
+        };
+        WireMessage {
+            msg_type: serde_json::from_value(tagged).expect("known type"),
+            payload: any_string(rng, 200),
+            channel,
+            meta: MessageMeta {
+                sender: any_name(rng),
+                timestamp: rng.gen(),
+                id: rng.gen_bool(0.7).then(|| any_string(rng, MAX_ID_LEN)),
+                seq: rng.gen_bool(0.5).then(|| rng.gen()),
+                delayed: rng.gen(),
+                encrypted: rng.gen(),
+                action: rng.gen(),
+            },
+        }
+    }
+
+    fn as_json(msg: &WireMessage) -> Value {
+        serde_json::to_value(msg).expect("serialize")
+    }
+
+    /// Anything JSON-shaped, nested a few levels
+    fn any_value(rng: &mut StdRng, depth: u32) -> Value {
+        match rng.gen_range(0..if depth == 0 { 4 } else { 6 }) {
+            0 => Value::Null,
+            1 => Value::Bool(rng.gen()),
+            2 => serde_json::json!(rng.gen::<i64>()),
+            3 => Value::String(any_string(rng, 20)),
+            4 => Value::Array((0..rng.gen_range(0..4)).map(|_| any_value(rng, depth - 1)).collect()),
+            _ => {
+                let keys = ["type", "payload", "channel", "meta", "sender", "timestamp", "id", "seq"];
+                let object = (0..rng.gen_range(0..5))
+                    .map(|_| (keys[rng.gen_range(0..keys.len())].to_string(), any_value(rng, depth - 1)))
+                    .collect();
+                Value::Object(object)
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_every_strict_frame() {
+        let mut rng = rng();
+        for _ in 0..CASES {
+            let msg = any_frame(&mut rng);
+            let text = serde_json::to_string(&msg).expect("serialize");
+            let decoded = decode_frame(&text).unwrap_or_else(|e| panic!("{}: {}", e, text));
+            assert_eq!(as_json(&decoded), as_json(&msg));
+        }
+    }
+
+    #[test]
+    fn accepts_whatever_serde_accepts() {
+        let mut rng = rng();
+        for _ in 0..CASES {
+            let mut value = as_json(&any_frame(&mut rng));
+            // Drop optional fields and add unknown ones
+            let frame = value.as_object_mut().expect("object");
+            if rng.gen() {
+                frame.remove("channel");
+            }
+            frame.insert("extra".to_string(), any_value(&mut rng, 2));
+            let meta = frame.get_mut("meta").and_then(Value::as_object_mut).expect("meta");
+            for key in ["id", "seq", "delayed", "encrypted", "action"] {
+                if rng.gen() {
+                    meta.remove(key);
+                }
+            }
+            let text = value.to_string();
+            let strict = serde_json::from_str::<WireMessage>(&text).expect("strict decode");
+            let tolerant = decode_frame(&text).unwrap_or_else(|e| panic!("{}: {}", e, text));
+            assert_eq!(as_json(&tolerant), as_json(&strict));
+        }
+    }
+
+    #[test]
+    fn never_panics_on_mangled_frames() {
+        let mut rng = rng();
+        for _ in 0..CASES {
+            let text = serde_json::to_string(&any_frame(&mut rng)).expect("serialize");
+            let mut bytes = text.into_bytes();
+            match rng.gen_range(0..3) {
+                0 => bytes.truncate(rng.gen_range(0..=bytes.len())),
+                1 => {
+                    for _ in 0..rng.gen_range(1..4) {
+                        let at = rng.gen_range(0..bytes.len());
+                        bytes[at] = rng.gen();
+                    }
+                }
+                _ => {
+                    let at = rng.gen_range(0..=bytes.len());
+                    let noise: Vec<u8> = (0..rng.gen_range(1..8)).map(|_| rng.gen()).collect();
+                    bytes.splice(at..at, noise);
+                }
+            }
+            let _ = decode_frame(&String::from_utf8_lossy(&bytes));
+        }
+    }
+
+    #[test]
+    fn never_panics_on_arbitrary_json() {
+        let mut rng = rng();
+        for _ in 0..CASES {
+            let _ = decode_frame(&any_value(&mut rng, 4).to_string());
+        }
+    }
+
+    #[test]
+    fn reports_why_a_frame_was_rejected() {
+        let frame = |value: Value| decode_frame(&value.to_string()).map(|_| ()).unwrap_err();
+        let meta = serde_json::json!({ "sender": "bob", "timestamp": 1 });
+
+        assert!(matches!(decode_frame("{not json"), Err(DecodeError::NotJson(_))));
+        assert_eq!(frame(serde_json::json!([1, 2])), DecodeError::NotObject);
+        assert_eq!(
+            frame(serde_json::json!({ "type": "NOPE", "payload": "", "meta": meta })),
+            DecodeError::UnknownType("NOPE".to_string())
+        );
+        assert_eq!(frame(serde_json::json!({ "payload": "", "meta": meta })), DecodeError::MissingField("type"));
+        assert_eq!(frame(serde_json::json!({ "type": "MSG", "payload": "" })), DecodeError::MissingMeta);
+        assert_eq!(
+            frame(serde_json::json!({ "type": "MSG", "payload": 5, "meta": meta })),
+            DecodeError::InvalidField { field: "payload", expected: "a string" }
+        );
+        assert_eq!(
+            frame(serde_json::json!({ "type": "MSG", "payload": "", "meta": { "sender": "bob" } })),
+            DecodeError::MissingField("meta.timestamp")
+        );
+        let long_name = "b".repeat(MAX_NAME_LEN + 1);
+        assert_eq!(
+            frame(serde_json::json!({
+                "type": "MSG", "payload": "", "meta": { "sender": long_name, "timestamp": 1 }
+            })),
+            DecodeError::OversizedField { field: "meta.sender", len: MAX_NAME_LEN + 1, max: MAX_NAME_LEN }
+        );
+        let huge = format!("\"{}\"", "x".repeat(MAX_DECODE_LEN));
+        assert_eq!(decode_frame(&huge).unwrap_err(), DecodeError::TooLarge { len: MAX_DECODE_LEN + 2 });
+    }
+
+    #[test]
+    fn tolerates_loose_type_and_timestamp_forms() {
+        let text = r#"{"type":"msg","payload":"hi","meta":{"sender":"bob","timestamp":"1700000000","seq":"x"}}"#;
+        let msg = decode_frame(text).expect("decode");
+        assert!(matches!(msg.msg_type, MessageType::Message));
+        assert_eq!(msg.channel, "global");
+        assert_eq!(msg.meta.timestamp, 1_700_000_000);
+        assert_eq!(msg.meta.seq, None);
+    }
+}
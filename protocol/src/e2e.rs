@@ -0,0 +1,98 @@
+// GhostWire Protocol - End-to-End Encryption Frames
+// DMs and passphrase rooms are encrypted by the clients. The relay forwards
+// KEYX handshake frames and MSG frames whose payload is an `Envelope` or
+// `RoomEnvelope` without being able to read them. Keys and ciphertext travel
+// as standard base64.
+
+use serde::{Deserialize, Serialize};
+
+/// A KEYX payload: one step of the handshake that starts a DM session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum KeyExchange {
+    /// Initiator's long-term identity key and a fresh ephemeral key
+    Init { identity: String, ephemeral: String },
+    /// Responder's identity key and its first ratchet key, answering the
+    /// init whose ephemeral key is `init`
+    Reply {
+        identity: String,
+        ephemeral: String,
+        init: String,
+    },
+    /// Initiator's first ratchet message, with an empty plaintext, proving
+    /// both sides derived the same keys
+    Confirm { envelope: Envelope },
+}
+
+/// Encrypted payload of a MSG frame with `meta.encrypted` set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Sender's current ratchet public key
+    pub dh: String,
+    /// Number of messages in the sender's previous sending chain
+    pub pn: u32,
+    /// Number of this message in the current sending chain
+    pub n: u32,
+    pub nonce: String,
+    /// Ciphertext with Poly1305 tag
+    pub ciphertext: String,
+}
+
+/// Encrypted payload of a MSG frame with `meta.encrypted` set in a `room:`
+/// channel, sealed under the key derived from the room's passphrase
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomEnvelope {
+    pub nonce: String,
+    /// Ciphertext with Poly1305 tag
+    pub ciphertext: String,
+    /// Unix time the sender sealed it, authenticated with the ciphertext so
+    /// receivers can refuse old messages replayed by the relay (absent from
+    /// older clients)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sent: Option<i64>,
+}
+
+impl KeyExchange {
+    /// Encode as a KEYX payload
+    pub fn to_payload(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Decode a KEYX payload
+    pub fn from_payload(payload: &str) -> Option<Self> {
+        serde_json::from_str(payload).ok()
+    }
+}
+
+impl Envelope {
+    /// Encode as a MSG payload
+    pub fn to_payload(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Decode a MSG payload
+    pub fn from_payload(payload: &str) -> Option<Self> {
+        serde_json::from_str(payload).ok()
+    }
+
+    /// Header bytes authenticated along with the ciphertext
+    pub fn header_bytes(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(self.dh.len() + 8);
+        header.extend_from_slice(self.dh.as_bytes());
+        header.extend_from_slice(&self.pn.to_be_bytes());
+        header.extend_from_slice(&self.n.to_be_bytes());
+        header
+    }
+}
+
+impl RoomEnvelope {
+    /// Encode as a MSG payload
+    pub fn to_payload(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Decode a MSG payload
+    pub fn from_payload(payload: &str) -> Option<Self> {
+        serde_json::from_str(payload).ok()
+    }
+}
@@ -0,0 +1,230 @@
+// GhostWire Protocol - Group Membership
+// Group channels (`group:<name>`) have members and operators. Changes travel as
+// GROUP frames whose payload is a JSON `GroupEvent`; the relay only forwards
+// them, so every member applies the same rules to arrive at the same group.
+// That includes announcement mode, where only operators and designated
+// announcers may post: the relay does not route by channel yet, so each
+// member drops posts from anyone else. These rules trust `meta.sender`, which
+// the relay binds to the name each connection authenticated as.
+
+use crate::validate::{validate_username, ValidationError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Maximum length of a group topic, in characters
+pub const MAX_TOPIC_LEN: usize = 200;
+
+/// Longest slow-mode interval, in seconds
+pub const MAX_SLOW_MODE_SECS: u32 = 3600;
+
+/// A group's membership and settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupInfo {
+    pub members: BTreeSet<String>,
+    /// Members who may invite, kick, set the topic and toggle slow and
+    /// announcement mode
+    pub operators: BTreeSet<String>,
+    #[serde(default)]
+    pub topic: Option<String>,
+    /// Minimum seconds between messages from members who are not operators; 0 is off
+    #[serde(default)]
+    pub slow_mode_secs: u32,
+    /// Announcement mode: only operators and `announcers` may post
+    #[serde(default)]
+    pub announce: bool,
+    /// Members who may post in announcement mode without being operators
+    #[serde(default)]
+    pub announcers: BTreeSet<String>,
+}
+
+/// A change to a group, sent as the JSON payload of a GROUP frame
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum GroupEvent {
+    /// Start a group; the sender becomes its operator
+    Create { members: Vec<String> },
+    /// Add a member. Carries the group as it stands after the invite, so the
+    /// new member can join without having seen the earlier events.
+    Invite { user: String, group: GroupInfo },
+    /// Remove a member who is not an operator
+    Kick { user: String },
+    /// Set the topic; an empty topic clears it
+    Topic { topic: String },
+    /// Set slow mode in seconds; 0 turns it off
+    SlowMode { secs: u32 },
+    /// Turn announcement mode on or off
+    Announce { on: bool },
+    /// Let a member post in announcement mode, or stop letting them
+    Announcer { user: String, allowed: bool },
+}
+
+/// Why a group event was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupError {
+    /// A member name is not a valid username
+    InvalidUser(ValidationError),
+    /// Only operators may do this
+    NotOperator(String),
+    /// The user is not a member of the group
+    NotMember(String),
+    /// The user is already a member of the group
+    AlreadyMember(String),
+    /// Operators cannot be kicked
+    KickOperator(String),
+    /// The group already exists
+    AlreadyExists,
+    /// The topic is longer than `MAX_TOPIC_LEN`
+    TopicTooLong,
+    /// The slow-mode interval is longer than `MAX_SLOW_MODE_SECS`
+    SlowModeTooLong,
+    /// An invite's group snapshot does not match the invite
+    InvalidInvite,
+    /// The user is not an announcer of the group
+    NotAnnouncer(String),
+}
+
+impl fmt::Display for GroupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupError::InvalidUser(e) => write!(f, "invalid member: {}", e),
+            GroupError::NotOperator(user) => write!(f, "{} is not a group operator", user),
+            GroupError::NotMember(user) => write!(f, "{} is not a member", user),
+            GroupError::AlreadyMember(user) => write!(f, "{} is already a member", user),
+            GroupError::KickOperator(user) => write!(f, "{} is an operator and cannot be kicked", user),
+            GroupError::AlreadyExists => write!(f, "the group already exists"),
+            GroupError::TopicTooLong => {
+                write!(f, "topic must be at most {} characters", MAX_TOPIC_LEN)
+            }
+            GroupError::SlowModeTooLong => {
+                write!(f, "slow mode must be at most {} seconds", MAX_SLOW_MODE_SECS)
+            }
+            GroupError::InvalidInvite => write!(f, "malformed invite"),
+            GroupError::NotAnnouncer(user) => write!(f, "{} is not an announcer", user),
+        }
+    }
+}
+
+impl std::error::Error for GroupError {}
+
+impl GroupInfo {
+    /// A new group with `creator` as its only operator
+    pub fn new(creator: &str, members: &[String]) -> Result<Self, GroupError> {
+        let mut group = Self::default();
+        for member in members.iter().map(String::as_str).chain([creator]) {
+            validate_username(member).map_err(GroupError::InvalidUser)?;
+            group.members.insert(member.to_string());
+        }
+        group.operators.insert(creator.to_string());
+        Ok(group)
+    }
+
+    /// The group described by an invite from `sender`, if it is consistent:
+    /// the inviter is an operator and the invitee a member
+    pub fn from_invite(sender: &str, user: &str, group: &GroupInfo) -> Result<Self, GroupError> {
+        if !group.operators.contains(sender)
+            || !group.members.contains(user)
+            || !group.operators.is_subset(&group.members)
+            || !group.announcers.is_subset(&group.members)
+        {
+            return Err(GroupError::InvalidInvite);
+        }
+        for member in &group.members {
+            validate_username(member).map_err(GroupError::InvalidUser)?;
+        }
+        check_topic(group.topic.as_deref().unwrap_or_default())?;
+        check_slow_mode(group.slow_mode_secs)?;
+        Ok(group.clone())
+    }
+
+    pub fn is_member(&self, user: &str) -> bool {
+        self.members.contains(user)
+    }
+
+    pub fn is_operator(&self, user: &str) -> bool {
+        self.operators.contains(user)
+    }
+
+    /// Whether `user` may post: any member, or in announcement mode only
+    /// operators and announcers
+    pub fn may_post(&self, user: &str) -> bool {
+        self.is_member(user) && (!self.announce || self.is_operator(user) || self.announcers.contains(user))
+    }
+
+    /// Apply an event sent by `sender` to an existing group. `sender` must be
+    /// the frame's sender as the relay delivered it, not a name from the payload.
+    pub fn apply(&mut self, sender: &str, event: &GroupEvent) -> Result<(), GroupError> {
+        if let GroupEvent::Create { .. } = event {
+            return Err(GroupError::AlreadyExists);
+        }
+        if !self.is_operator(sender) {
+            return Err(GroupError::NotOperator(sender.to_string()));
+        }
+
+        match event {
+            GroupEvent::Create { .. } => unreachable!("handled above"),
+            GroupEvent::Invite { user, .. } => {
+                validate_username(user).map_err(GroupError::InvalidUser)?;
+                if !self.members.insert(user.clone()) {
+                    return Err(GroupError::AlreadyMember(user.clone()));
+                }
+            }
+            GroupEvent::Kick { user } => {
+                if self.is_operator(user) {
+                    return Err(GroupError::KickOperator(user.clone()));
+                }
+                if !self.members.remove(user) {
+                    return Err(GroupError::NotMember(user.clone()));
+                }
+                self.announcers.remove(user);
+            }
+            GroupEvent::Topic { topic } => {
+                check_topic(topic)?;
+                self.topic = (!topic.is_empty()).then(|| topic.clone());
+            }
+            GroupEvent::SlowMode { secs } => {
+                check_slow_mode(*secs)?;
+                self.slow_mode_secs = *secs;
+            }
+            GroupEvent::Announce { on } => self.announce = *on,
+            GroupEvent::Announcer { user, allowed: true } => {
+                if !self.is_member(user) {
+                    return Err(GroupError::NotMember(user.clone()));
+                }
+                self.announcers.insert(user.clone());
+            }
+            GroupEvent::Announcer { user, allowed: false } => {
+                if !self.announcers.remove(user) {
+                    return Err(GroupError::NotAnnouncer(user.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GroupEvent {
+    /// Encode as a GROUP payload
+    pub fn to_payload(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Decode a GROUP payload
+    pub fn from_payload(payload: &str) -> Option<Self> {
+        serde_json::from_str(payload).ok()
+    }
+}
+
+fn check_topic(topic: &str) -> Result<(), GroupError> {
+    if topic.chars().count() > MAX_TOPIC_LEN {
+        return Err(GroupError::TopicTooLong);
+    }
+    Ok(())
+}
+
+fn check_slow_mode(secs: u32) -> Result<(), GroupError> {
+    if secs > MAX_SLOW_MODE_SECS {
+        return Err(GroupError::SlowModeTooLong);
+    }
+    Ok(())
+}
@@ -0,0 +1,91 @@
+// GhostWire Protocol - Relay Handshake
+// The relay opens every connection with a HELLO frame describing itself,
+// including the protocol version it speaks and the oldest client it supports,
+// so clients can warn when one side needs an update. Clients send their own
+// version in the `x-ghostwire-client` upgrade header.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Major version of the wire protocol, raised only for changes older
+/// clients cannot follow
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Protocol version of relays that predate the field
+fn first_protocol() -> u32 {
+    1
+}
+
+/// Relay identity and capabilities, sent as the JSON payload of HELLO
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    /// Human-readable relay name
+    pub name: String,
+    /// Relay build version
+    pub version: String,
+    /// Optional protocol features this relay supports (e.g. "who", "motd")
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Wire protocol major version the relay speaks
+    #[serde(default = "first_protocol")]
+    pub protocol: u32,
+    /// Oldest client version the relay supports, e.g. "0.2.0"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_client_version: Option<String>,
+}
+
+/// Why a client and relay may not work together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incompatibility {
+    /// The client is older than the relay's minimum
+    ClientTooOld { minimum: String },
+    /// The relay speaks a newer protocol major version than the client
+    NewerProtocol { relay: u32 },
+}
+
+/// Compare dotted versions numerically ("0.10.0" > "0.9.1"), ignoring a
+/// leading `v` and any `-pre`/`+build` suffix; `None` if either does not parse
+pub fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    fn parse(version: &str) -> Option<Vec<u64>> {
+        let version = version.trim().trim_start_matches('v');
+        let version = version.split(['-', '+']).next()?;
+        version.split('.').map(|part| part.parse().ok()).collect()
+    }
+    let (mut a, mut b) = (parse(a)?, parse(b)?);
+    // "1.2" equals "1.2.0"
+    let len = a.len().max(b.len());
+    a.resize(len, 0);
+    b.resize(len, 0);
+    Some(a.cmp(&b))
+}
+
+impl ServerInfo {
+    /// Encode as a HELLO payload
+    pub fn to_payload(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Decode a HELLO payload
+    pub fn from_payload(payload: &str) -> Option<Self> {
+        serde_json::from_str(payload).ok()
+    }
+
+    /// Whether the relay advertises `feature`
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// Whether a client at `client_version` should warn about this relay
+    pub fn compatibility(&self, client_version: &str) -> Option<Incompatibility> {
+        if self.protocol > PROTOCOL_VERSION {
+            return Some(Incompatibility::NewerProtocol { relay: self.protocol });
+        }
+        let minimum = self.min_client_version.as_ref()?;
+        match compare_versions(client_version, minimum) {
+            Some(Ordering::Less) => Some(Incompatibility::ClientTooOld {
+                minimum: minimum.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,151 @@
+// GhostWire Protocol - Shared Wire Types
+// Everything both the client and the relay need to agree on lives here
+
+use serde::{Deserialize, Serialize};
+
+pub mod chunk;
+pub mod clock;
+pub mod decode;
+pub mod e2e;
+pub mod group;
+pub mod hello;
+pub mod validate;
+pub mod whois;
+
+/// Message types for the GhostWire protocol
+///
+/// Note: because this enum is internally tagged and `WireMessage` renames the
+/// field to `type`, the encoded form is `"type": {"type": "MSG"}`. Deployed
+/// clients depend on that shape, so it must not change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum MessageType {
+    #[serde(rename = "MSG")]
+    Message,
+    #[serde(rename = "AUTH")]
+    Auth,
+    #[serde(rename = "SYS")]
+    System,
+    /// Roster request (empty payload) or, from the relay, the roster itself
+    #[serde(rename = "WHO")]
+    Who,
+    /// Nickname change: `meta.sender` is the old name, the payload the new one
+    #[serde(rename = "RENAME")]
+    Rename,
+    /// Pin a message in `channel`; the payload is the message's `meta.id`
+    #[serde(rename = "PIN")]
+    Pin,
+    /// Unpin a message in `channel`; the payload is the message's `meta.id`
+    #[serde(rename = "UNPIN")]
+    Unpin,
+    /// Relay handshake; the payload is a JSON `hello::ServerInfo`
+    #[serde(rename = "HELLO")]
+    Hello,
+    /// Relay-initiated disconnect; the payload is the reason shown to the user.
+    /// Only the relay sends KICK, and clients must not reconnect on their own.
+    #[serde(rename = "KICK")]
+    Kick,
+    /// Group membership or settings change in a `group:` channel; the
+    /// payload is a JSON `group::GroupEvent`
+    #[serde(rename = "GROUP")]
+    Group,
+    /// DM receipt: the recipient got the message whose `meta.id` is the payload
+    #[serde(rename = "DELIVERED")]
+    Delivered,
+    /// DM read receipt: the recipient has seen every message up to and
+    /// including the one whose `meta.id` is the payload
+    #[serde(rename = "READ")]
+    Read,
+    /// DM session handshake; the payload is a JSON `e2e::KeyExchange`
+    #[serde(rename = "KEYX")]
+    KeyExchange,
+    /// One slice of a MSG frame too long to send whole; the payload is a
+    /// JSON `chunk::Chunk`
+    #[serde(rename = "CHUNK")]
+    Chunk,
+    /// Connection info request (the payload is a username) or, from the
+    /// relay, the answer as a JSON `whois::WhoisInfo`
+    #[serde(rename = "WHOIS")]
+    Whois,
+}
+
+/// Metadata for each message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageMeta {
+    pub sender: String,
+    pub timestamp: i64,
+    /// Unique message ID (UUID v4), used to drop duplicates; absent on relay
+    /// frames and on frames from older clients
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Sequence number of a sender's plaintext chat messages in this channel,
+    /// starting at 1 and increasing by one per message, so receivers can spot
+    /// ones they never got. Encrypted messages carry none, so cover traffic
+    /// cannot be told apart by it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    /// Set by the relay on a DM it held while the recipient was offline, so
+    /// the timestamp is the original send time rather than a clock sample
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub delayed: bool,
+    /// The payload is a JSON `e2e::Envelope` only the recipient can open
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
+    /// An action (`/roll`, `/flip`, `/shrug`) rather than speech: shown as
+    /// "* sender payload" instead of after the sender's name
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub action: bool,
+}
+
+/// Wire protocol message structure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireMessage {
+    #[serde(rename = "type")]
+    pub msg_type: MessageType,
+    pub payload: String,
+    /// Channel ID: "global", "dm:user1:user2", or "group:name"
+    #[serde(default = "default_channel")]
+    pub channel: String,
+    pub meta: MessageMeta,
+}
+
+/// Sender of the relay's message-of-the-day SYS frame
+pub const MOTD_SENDER: &str = "MOTD";
+
+/// Sender of the relay's SYS notice that a DM was not delivered because the
+/// recipient is offline; `meta.id` is the undelivered message's ID
+pub const UNDELIVERED_SENDER: &str = "UNDELIVERED";
+
+/// Sender of the relay's SYS notice that a DM is being held until its
+/// recipient reconnects; `meta.id` is the held message's ID
+pub const QUEUED_SENDER: &str = "QUEUED";
+
+/// WebSocket upgrade header carrying the client build, e.g. `ghostwire/0.3.0`
+pub const CLIENT_VERSION_HEADER: &str = "x-ghostwire-client";
+
+/// WebSocket close code a relay sends when it drops a connection that has
+/// been idle too long (from the 4000-4999 private-use range)
+pub const CLOSE_IDLE_TIMEOUT: u16 = 4000;
+
+/// Encode a roster as a WHO payload. Validated usernames never contain commas.
+pub fn encode_roster<S: AsRef<str>>(usernames: &[S]) -> String {
+    usernames
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Decode a WHO payload into usernames
+pub fn decode_roster(payload: &str) -> Vec<String> {
+    payload
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Default channel is global for backward compatibility
+fn default_channel() -> String {
+    "global".to_string()
+}
@@ -0,0 +1,103 @@
+// GhostWire Protocol - Name Validation
+// Usernames are embedded in channel IDs (`dm:user1:user2`), so they must never
+// contain the separator or anything else that breaks parsing or display.
+
+use std::fmt;
+
+/// Maximum length of a username or group name, in characters
+pub const MAX_NAME_LEN: usize = 32;
+
+/// Names that would impersonate the relay or system messages (case-insensitive)
+pub const RESERVED_USERNAMES: &[&str] = &["system", "server", "relay", "admin", "everyone", "here", "motd", "undelivered", "queued"];
+
+/// Why a username or channel ID was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// Name is empty
+    Empty,
+    /// Name is longer than `MAX_NAME_LEN`
+    TooLong,
+    /// Name contains a character outside `[A-Za-z0-9_.-]`
+    InvalidChar(char),
+    /// Name is reserved for the relay or system messages
+    Reserved(String),
+    /// Channel ID is not `global`, `dm:<user>:<user>`, `group:<name>` or `room:<name>`
+    InvalidChannel(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::Empty => write!(f, "name must not be empty"),
+            ValidationError::TooLong => {
+                write!(f, "name must be at most {} characters", MAX_NAME_LEN)
+            }
+            ValidationError::InvalidChar(c) => write!(
+                f,
+                "invalid character {:?} (use letters, digits, '_', '-' or '.')",
+                c
+            ),
+            ValidationError::Reserved(name) => write!(f, "\"{}\" is a reserved name", name),
+            ValidationError::InvalidChannel(id) => write!(f, "invalid channel ID \"{}\"", id),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Check length and charset shared by usernames and group names
+fn validate_name(name: &str) -> Result<(), ValidationError> {
+    if name.is_empty() {
+        return Err(ValidationError::Empty);
+    }
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(ValidationError::TooLong);
+    }
+    match name
+        .chars()
+        .find(|&c| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')))
+    {
+        Some(c) => Err(ValidationError::InvalidChar(c)),
+        None => Ok(()),
+    }
+}
+
+/// Validate a username
+pub fn validate_username(name: &str) -> Result<(), ValidationError> {
+    validate_name(name)?;
+    if RESERVED_USERNAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(name))
+    {
+        return Err(ValidationError::Reserved(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Validate a group name (the part after `group:`)
+pub fn validate_group_name(name: &str) -> Result<(), ValidationError> {
+    validate_name(name)
+}
+
+/// Validate a channel ID: `global`, `dm:<user1>:<user2>`, `group:<name>` or
+/// `room:<name>`
+pub fn validate_channel_id(id: &str) -> Result<(), ValidationError> {
+    let invalid = || ValidationError::InvalidChannel(id.to_string());
+
+    if id == "global" {
+        return Ok(());
+    }
+    if let Some(users) = id.strip_prefix("dm:") {
+        let (user1, user2) = users.split_once(':').ok_or_else(invalid)?;
+        validate_username(user1).map_err(|_| invalid())?;
+        validate_username(user2).map_err(|_| invalid())?;
+        if user1 == user2 {
+            return Err(invalid());
+        }
+        return Ok(());
+    }
+    if let Some(name) = id.strip_prefix("group:").or_else(|| id.strip_prefix("room:")) {
+        return validate_name(name).map_err(|_| invalid());
+    }
+    Err(invalid())
+}
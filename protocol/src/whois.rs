@@ -0,0 +1,43 @@
+// GhostWire Protocol - Who-is Lookups
+// A client sends WHOIS with a username as payload; the relay answers with a
+// WHOIS frame whose payload is what it is willing to share about that user's
+// connection. Addresses and traffic counters stay with the admin API.
+
+use serde::{Deserialize, Serialize};
+
+/// The relay's answer to a WHOIS request, sent as its JSON payload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WhoisInfo {
+    /// The username that was looked up
+    pub username: String,
+    /// Whether a client is authenticated under that name
+    pub online: bool,
+    /// Unix seconds the user's connection was opened
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connected_at: Option<i64>,
+    /// Client build from the `x-ghostwire-client` header, e.g. "ghostwire/0.1.2"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_version: Option<String>,
+}
+
+impl WhoisInfo {
+    /// Answer for a user with no connection
+    pub fn offline(username: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            online: false,
+            connected_at: None,
+            client_version: None,
+        }
+    }
+
+    /// Encode as a WHOIS payload
+    pub fn to_payload(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Decode a WHOIS payload
+    pub fn from_payload(payload: &str) -> Option<Self> {
+        serde_json::from_str(payload).ok()
+    }
+}
@@ -0,0 +1,80 @@
+// GhostWire Server - Broadcast Benchmarks
+// `RelayState::broadcast` fanning one chat frame out to 10, 100 and 1000
+// registered clients. Only the broadcast is timed: the frames it queues are
+// drained between iterations, outside the measurement, so the receivers'
+// queues do not grow for the length of the run.
+//
+// cargo bench -p ghostwire-server
+
+// The server is a binary crate, so the relay module is compiled into the
+// benchmark directly; most of it goes unused here
+#[allow(dead_code)]
+#[path = "../src/relay.rs"]
+mod relay;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ghostwire_protocol::{MessageMeta, MessageType, WireMessage};
+use relay::{BroadcastMessage, ConnectionInfo, RelayState};
+use std::time::{Duration, Instant};
+
+/// Connected clients measured
+const CLIENT_COUNTS: &[usize] = &[10, 100, 1000];
+
+/// A typical chat frame, as the relay receives it
+fn chat_frame() -> String {
+    let msg = WireMessage {
+        msg_type: MessageType::Message,
+        payload: "The quick brown fox jumps over the lazy dog".to_string(),
+        channel: "global".to_string(),
+        meta: MessageMeta {
+            sender: "alice".to_string(),
+            timestamp: 1_700_000_000,
+            id: Some("0b6c1f9e-4a2d-4c6e-9b1a-3f1e2d4c5b6a".to_string()),
+            seq: Some(1),
+            delayed: false,
+            encrypted: false,
+            action: false,
+        },
+    };
+    serde_json::to_string(&msg).unwrap()
+}
+
+fn broadcast(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let content = chat_frame();
+    let mut group = c.benchmark_group("broadcast");
+
+    for &clients in CLIENT_COUNTS {
+        let state = RelayState::new();
+        let mut receivers = runtime.block_on(async {
+            let mut receivers = Vec::with_capacity(clients);
+            for _ in 0..clients {
+                receivers.push(state.register_client(ConnectionInfo::default()).await);
+            }
+            receivers
+        });
+        let from = receivers[0].0;
+
+        // Frames delivered: everyone but the sender
+        group.throughput(Throughput::Elements(clients as u64 - 1));
+        group.bench_function(BenchmarkId::from_parameter(clients), |b| {
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iters {
+                    let msg = BroadcastMessage { from, content: content.clone() };
+                    let start = Instant::now();
+                    runtime.block_on(state.broadcast(msg));
+                    elapsed += start.elapsed();
+                    for (_, rx) in &mut receivers {
+                        while rx.try_recv().is_ok() {}
+                    }
+                }
+                elapsed
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, broadcast);
+criterion_main!(benches);
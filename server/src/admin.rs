@@ -0,0 +1,74 @@
+// GhostWire Server - Stats and Admin API
+// `/stats` reports aggregate numbers anyone may see; `/admin/...` routes list
+// per-client metadata (including IPs) or act on clients, and require the
+// admin bearer token.
+
+use crate::relay::{ClientId, ClientInfo, RelayState, RelayStats};
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use tracing::warn;
+
+/// Reason sent with a kick when the admin gives none
+const DEFAULT_KICK_REASON: &str = "Disconnected by the relay operator";
+
+/// Optional body of a kick request
+#[derive(Debug, Default, Deserialize)]
+pub struct KickRequest {
+    pub reason: Option<String>,
+}
+
+/// Check the admin bearer token, producing the error status on failure
+fn authorize(state: &RelayState, headers: &HeaderMap) -> Result<(), StatusCode> {
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    match state.admin_authorized(authorization) {
+        // Without a configured token the admin API does not exist
+        None => Err(StatusCode::NOT_FOUND),
+        Some(false) => {
+            warn!("Rejected admin request with a missing or wrong token");
+            Err(StatusCode::UNAUTHORIZED)
+        }
+        Some(true) => Ok(()),
+    }
+}
+
+/// Aggregate relay statistics
+pub async fn stats(State(state): State<RelayState>) -> Json<RelayStats> {
+    Json(state.stats().await)
+}
+
+/// Metadata of every connected client
+pub async fn clients(State(state): State<RelayState>, headers: HeaderMap) -> Response {
+    if let Err(status) = authorize(&state, &headers) {
+        return status.into_response();
+    }
+    Json::<Vec<ClientInfo>>(state.client_list().await).into_response()
+}
+
+/// Disconnect a client with a KICK carrying the given (or a default) reason
+pub async fn kick(
+    State(state): State<RelayState>,
+    Path(id): Path<ClientId>,
+    headers: HeaderMap,
+    body: Option<Json<KickRequest>>,
+) -> Response {
+    if let Err(status) = authorize(&state, &headers) {
+        return status.into_response();
+    }
+    let reason = body
+        .and_then(|Json(request)| request.reason)
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty())
+        .unwrap_or_else(|| DEFAULT_KICK_REASON.to_string());
+    if state.kick(id, &reason).await {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        StatusCode::NOT_FOUND.into_response()
+    }
+}
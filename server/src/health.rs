@@ -0,0 +1,66 @@
+// GhostWire Server - Health Probes
+// `/healthz` (liveness) answers as long as the process serves HTTP, so an
+// orchestrator only restarts a relay that is truly stuck. `/readyz`
+// (readiness) also checks the listener, the backing store when there is one,
+// and the connection threshold, and answers 503 while any of them fails so
+// load balancers send new clients elsewhere.
+
+use crate::relay::RelayState;
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use std::time::Duration;
+
+/// How long the backing store gets to answer a readiness probe
+const STORE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// One readiness check
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Body of `/readyz`
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<Check>,
+}
+
+/// Liveness: the process is up and serving requests
+pub async fn liveness() -> &'static str {
+    "ok"
+}
+
+/// Readiness: 200 when the relay should get new clients, 503 otherwise
+pub async fn readiness(State(state): State<RelayState>) -> (StatusCode, Json<Readiness>) {
+    let mut checks = vec![Check {
+        name: "listener",
+        ok: state.is_listening(),
+        detail: None,
+    }];
+
+    if let Some(probe) = state.store_probe() {
+        let (ok, detail) = match tokio::time::timeout(STORE_TIMEOUT, probe()).await {
+            Ok(Ok(())) => (true, None),
+            Ok(Err(e)) => (false, Some(format!("{:#}", e))),
+            Err(_) => (false, Some(format!("no answer within {}s", STORE_TIMEOUT.as_secs()))),
+        };
+        checks.push(Check { name: "store", ok, detail });
+    }
+
+    if let Some(max) = state.max_connections() {
+        let clients = state.client_count().await;
+        checks.push(Check {
+            name: "connections",
+            ok: clients < max,
+            detail: Some(format!("{}/{}", clients, max)),
+        });
+    }
+
+    let ready = checks.iter().all(|check| check.ok);
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(Readiness { ready, checks }))
+}
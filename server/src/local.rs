@@ -1,19 +1,24 @@
 // GhostWire Server - Local Development Entry Point
 // This binary is used for local development without Shuttle runtime
 
+mod admin;
+mod health;
 mod relay;
+#[cfg(feature = "web")]
+mod web;
 
 use axum::{
-    extract::{ws::WebSocketUpgrade, State},
-    response::{Html, IntoResponse},
-    routing::get,
+    extract::{ws::WebSocketUpgrade, ConnectInfo, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
     Router,
 };
-use relay::RelayState;
+use relay::{ConnectionInfo, RelayState};
 use std::net::SocketAddr;
-use tracing_subscriber::EnvFilter;
+use tower_http::sensitive_headers::SetSensitiveRequestHeadersLayer;
 use tower_http::trace::{DefaultMakeSpan, TraceLayer};
-use tracing::info;
+use tracing::{error, info, warn};
 
 /// Health check endpoint
 async fn health_check() -> &'static str {
@@ -77,47 +82,310 @@ async fn root(State(state): State<RelayState>) -> Html<String> {
 /// WebSocket upgrade handler
 async fn ws_handler(
     ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    peer: Option<ConnectInfo<SocketAddr>>,
     State(state): State<RelayState>,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| relay::handle_websocket(socket, state))
+) -> Response {
+    // Browsers can be pointed at the relay from any page; only allowed origins may connect
+    let origin = headers.get(header::ORIGIN).map(|value| value.to_str().unwrap_or("<invalid>"));
+    if !state.origin_allowed(origin) {
+        warn!("Rejected WebSocket upgrade from origin {:?}", origin);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let peer = peer.map(|ConnectInfo(addr)| addr);
+    let connection = ConnectionInfo::from_request(&headers, peer, state.trusted_proxies());
+    ws.on_upgrade(move |socket| relay::handle_websocket(socket, state, connection))
 }
 
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| {
-                EnvFilter::new("info")
-                    .add_directive("ghostwire_server=debug".parse().expect("Invalid tracing directive"))
-                    .add_directive("tower_http=debug".parse().expect("Invalid tracing directive"))
-            }),
-        )
-        .init();
+    // Initialize tracing; the guard flushes file logs on exit
+    let _log_guard = match logging::init() {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("Failed to set up logging: {:#}", e);
+            return;
+        }
+    };
 
     info!("🚀 Starting GhostWire Relay Server (Local Mode)");
 
-    // Create shared state
-    let state = RelayState::new();
+    // Create shared state; GHOSTWIRE_MOTD sets the message of the day,
+    // GHOSTWIRE_ALLOWED_ORIGINS the browser origins allowed to connect and
+    // GHOSTWIRE_ADMIN_TOKEN enables the admin API; GHOSTWIRE_OFFLINE_QUEUE
+    // holds that many DMs per offline user for GHOSTWIRE_OFFLINE_TTL_SECS;
+    // GHOSTWIRE_MAX_CONNECTIONS is where /readyz starts reporting not ready;
+    // GHOSTWIRE_MIN_CLIENT_VERSION is the oldest client version supported;
+    // GHOSTWIRE_IDLE_TIMEOUT_SECS closes connections that stay silent that long
+    let state = RelayState::new()
+        .with_motd(std::env::var("GHOSTWIRE_MOTD").ok())
+        .with_allowed_origins(std::env::var("GHOSTWIRE_ALLOWED_ORIGINS").ok())
+        .with_admin_token(std::env::var("GHOSTWIRE_ADMIN_TOKEN").ok())
+        .with_offline_queue(
+            std::env::var("GHOSTWIRE_OFFLINE_QUEUE").ok(),
+            std::env::var("GHOSTWIRE_OFFLINE_TTL_SECS").ok(),
+        )
+        .with_daily_quota(std::env::var("GHOSTWIRE_DAILY_QUOTA_BYTES").ok())
+        .with_max_connections(std::env::var("GHOSTWIRE_MAX_CONNECTIONS").ok())
+        .with_trusted_proxies(std::env::var("GHOSTWIRE_TRUSTED_PROXIES").ok())
+        .with_idle_timeout(std::env::var("GHOSTWIRE_IDLE_TIMEOUT_SECS").ok())
+        .with_min_client_version(std::env::var("GHOSTWIRE_MIN_CLIENT_VERSION").ok());
 
     // Build the router
     let app = Router::new()
         .route("/", get(root))
         .route("/health", get(health_check))
+        .route("/healthz", get(health::liveness))
+        .route("/readyz", get(health::readiness))
         .route("/ws", get(ws_handler))
-        .with_state(state)
+        .route("/stats", get(admin::stats))
+        .route("/admin/clients", get(admin::clients))
+        .route("/admin/clients/:id/kick", post(admin::kick));
+    // The browser client at /app
+    #[cfg(feature = "web")]
+    let app = app.merge(web::routes());
+    let app = app
+        .with_state(state.clone())
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(DefaultMakeSpan::default().include_headers(true)),
-        );
+        )
+        // Applied before tracing, so relay tokens are logged as "Sensitive"
+        .layer(SetSensitiveRequestHeadersLayer::new([axum::http::header::AUTHORIZATION]));
 
-    // Bind to address
+    // Bind to address. With GHOSTWIRE_TLS_CERT and GHOSTWIRE_TLS_KEY set
+    // (PEM files), TLS is terminated here and clients use wss://
     let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
-    info!("👻 GhostWire Relay listening on http://{}", addr);
-    info!("📡 WebSocket endpoint: ws://{}/ws", addr);
-    info!("🌐 Status page: http://{}", addr);
+    let tls = match (std::env::var("GHOSTWIRE_TLS_CERT"), std::env::var("GHOSTWIRE_TLS_KEY")) {
+        (Ok(cert), Ok(key)) => match listeners::tls_acceptor(&cert, &key) {
+            Ok(acceptor) => Some(acceptor),
+            Err(e) => {
+                error!("Failed to load TLS certificate {} / key {}: {:#}", cert, key, e);
+                return;
+            }
+        },
+        _ => None,
+    };
+    let (http, ws) = if tls.is_some() { ("https", "wss") } else { ("http", "ws") };
+    info!("👻 GhostWire Relay listening on {}://{}", http, addr);
+    info!("📡 WebSocket endpoint: {}://{}/ws", ws, addr);
+    info!("🌐 Status page: {}://{}", http, addr);
+
+    // Optionally also listen on a Unix socket, e.g. behind a reverse proxy
+    #[cfg(unix)]
+    if let Ok(path) = std::env::var("GHOSTWIRE_UNIX_SOCKET") {
+        match listeners::bind_unix(&path) {
+            Ok(listener) => {
+                info!("🔌 Unix socket: {}", path);
+                tokio::spawn(listeners::serve_unix(listener, app.clone()));
+            }
+            Err(e) => error!("Failed to bind Unix socket {}: {}", path, e),
+        }
+    }
 
     // Start server
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    state.set_listening();
+    let server = async move {
+        match tls {
+            Some(acceptor) => listeners::serve_tls(listener, acceptor, app).await,
+            None => axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap(),
+        }
+    };
+
+    // Tell clients the relay is going away, so they reconnect to its successor
+    tokio::select! {
+        _ = server => {}
+        _ = state.shutdown_on_signal() => {}
+    }
+}
+
+/// Listeners `axum::serve` does not cover: Unix sockets and TLS over TCP.
+/// Each accepted stream is served with HTTP/1.1 plus upgrades for WebSockets.
+mod listeners {
+    use anyhow::Context;
+    use axum::{
+        extract::{ConnectInfo, Request},
+        Router,
+    };
+    use hyper::body::Incoming;
+    use hyper_util::rt::TokioIo;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tokio::io::{AsyncRead, AsyncWrite};
+    use tokio::net::TcpListener;
+    use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+    use tokio_rustls::TlsAcceptor;
+    use tower::Service;
+    use tracing::{debug, error};
+
+    /// Serve one connection until it closes. `peer` is exposed to handlers
+    /// as `ConnectInfo`, as `axum::serve` does for plain TCP.
+    async fn serve_connection<S>(stream: S, peer: Option<SocketAddr>, app: Router)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let service = hyper::service::service_fn(move |mut request: Request<Incoming>| {
+            if let Some(peer) = peer {
+                request.extensions_mut().insert(ConnectInfo(peer));
+            }
+            app.clone().call(request)
+        });
+        let connection = hyper::server::conn::http1::Builder::new()
+            .serve_connection(TokioIo::new(stream), service)
+            .with_upgrades();
+        if let Err(e) = connection.await {
+            debug!("Connection ended: {}", e);
+        }
+    }
+
+    /// Bind a Unix socket, replacing a stale socket file left by a previous run
+    #[cfg(unix)]
+    pub fn bind_unix(path: &str) -> std::io::Result<tokio::net::UnixListener> {
+        let path = std::path::Path::new(path);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        tokio::net::UnixListener::bind(path)
+    }
+
+    /// Accept Unix socket connections forever
+    #[cfg(unix)]
+    pub async fn serve_unix(listener: tokio::net::UnixListener, app: Router) {
+        loop {
+            match listener.accept().await {
+                Ok((socket, _)) => {
+                    tokio::spawn(serve_connection(socket, None, app.clone()));
+                }
+                Err(e) => error!("Unix socket accept failed: {}", e),
+            }
+        }
+    }
+
+    /// Build a TLS acceptor from PEM certificate chain and private key files
+    pub fn tls_acceptor(cert_path: &str, key_path: &str) -> anyhow::Result<TlsAcceptor> {
+        let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?))?
+            .into_iter()
+            .map(Certificate)
+            .collect::<Vec<_>>();
+        anyhow::ensure!(!certs.is_empty(), "no certificates in {}", cert_path);
+
+        let key = rustls_pemfile::read_all(&mut BufReader::new(File::open(key_path)?))?
+            .into_iter()
+            .find_map(|item| match item {
+                rustls_pemfile::Item::PKCS8Key(key)
+                | rustls_pemfile::Item::RSAKey(key)
+                | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+                _ => None,
+            })
+            .with_context(|| format!("no private key in {}", key_path))?;
+
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+
+    /// Accept TCP connections forever, terminating TLS on each
+    pub async fn serve_tls(listener: TcpListener, acceptor: TlsAcceptor, app: Router) {
+        loop {
+            let (socket, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    error!("TCP accept failed: {}", e);
+                    continue;
+                }
+            };
+            let acceptor = acceptor.clone();
+            let app = app.clone();
+            // Handshake off the accept loop so a slow client cannot stall it
+            tokio::spawn(async move {
+                match acceptor.accept(socket).await {
+                    Ok(stream) => serve_connection(stream, Some(peer), app).await,
+                    Err(e) => debug!("TLS handshake failed: {}", e),
+                }
+            });
+        }
+    }
+}
+
+/// Tracing setup for the local binary, configured through the environment:
+///
+/// - `GHOSTWIRE_LOG_LEVEL`: filter directives (falls back to `RUST_LOG`, then
+///   `info` with debug output from the relay and HTTP layer)
+/// - `GHOSTWIRE_LOG_FORMAT`: `json` for one JSON object per line, otherwise text
+/// - `GHOSTWIRE_LOG_FILE`: write to this file instead of stdout, rotated per
+///   `GHOSTWIRE_LOG_ROTATION` (`daily` by default, `hourly` or `never`)
+mod logging {
+    use anyhow::Context;
+    use std::path::Path;
+    use tracing_appender::non_blocking::WorkerGuard;
+    use tracing_appender::rolling::{RollingFileAppender, Rotation};
+    use tracing_subscriber::fmt::writer::BoxMakeWriter;
+    use tracing_subscriber::EnvFilter;
+
+    fn filter() -> EnvFilter {
+        EnvFilter::try_from_env("GHOSTWIRE_LOG_LEVEL")
+            .or_else(|_| EnvFilter::try_from_default_env())
+            .unwrap_or_else(|_| {
+                EnvFilter::new("info")
+                    .add_directive("ghostwire_server=debug".parse().expect("Invalid tracing directive"))
+                    .add_directive("ghostwire_local=debug".parse().expect("Invalid tracing directive"))
+                    .add_directive("tower_http=debug".parse().expect("Invalid tracing directive"))
+            })
+    }
+
+    fn rotation() -> anyhow::Result<Rotation> {
+        match std::env::var("GHOSTWIRE_LOG_ROTATION").as_deref() {
+            Err(_) | Ok("daily") => Ok(Rotation::DAILY),
+            Ok("hourly") => Ok(Rotation::HOURLY),
+            Ok("never") => Ok(Rotation::NEVER),
+            Ok(other) => anyhow::bail!("unknown GHOSTWIRE_LOG_ROTATION {:?}", other),
+        }
+    }
+
+    /// Install the global subscriber. Returns the guard of the background
+    /// file writer, which must be kept alive until shutdown.
+    pub fn init() -> anyhow::Result<Option<WorkerGuard>> {
+        let (writer, guard) = match std::env::var("GHOSTWIRE_LOG_FILE") {
+            Ok(path) => {
+                let path = Path::new(&path);
+                let file_name = path
+                    .file_name()
+                    .with_context(|| format!("no file name in {}", path.display()))?;
+                let directory = match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent,
+                    _ => Path::new("."),
+                };
+                let appender = RollingFileAppender::new(rotation()?, directory, file_name);
+                let (writer, guard) = tracing_appender::non_blocking(appender);
+                (BoxMakeWriter::new(writer), Some(guard))
+            }
+            Err(_) => (BoxMakeWriter::new(std::io::stdout), None),
+        };
+        let to_file = guard.is_some();
+
+        let builder = tracing_subscriber::fmt()
+            .with_env_filter(filter())
+            .with_writer(writer);
+        let json = std::env::var("GHOSTWIRE_LOG_FORMAT")
+            .map(|format| format.eq_ignore_ascii_case("json"))
+            .unwrap_or(false);
+        if json {
+            builder
+                .json()
+                .with_current_span(true)
+                .with_span_list(false)
+                .try_init()
+        } else {
+            builder.with_ansi(!to_file).try_init()
+        }
+        .map_err(|e| anyhow::anyhow!(e))?;
+        Ok(guard)
+    }
 }
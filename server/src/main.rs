@@ -1,20 +1,32 @@
 // GhostWire Server - Shuttle Entry Point
 // This is the "dumb relay" server that knows nothing about message content
 
+mod admin;
+mod health;
 mod relay;
+#[cfg(feature = "web")]
+mod web;
+#[cfg(feature = "postgres")]
+mod store;
 
 use axum::{
     extract::{
         ws::WebSocketUpgrade,
+        ConnectInfo,
         State,
     },
-    response::{Html, IntoResponse},
-    routing::get,
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
     Router,
 };
-use relay::RelayState;
+use relay::{ConnectionInfo, RelayState};
+use std::net::SocketAddr;
+#[cfg(feature = "postgres")]
+use std::sync::Arc;
+use tower_http::sensitive_headers::SetSensitiveRequestHeadersLayer;
 use tower_http::trace::{DefaultMakeSpan, TraceLayer};
-use tracing::info;
+use tracing::warn;
 
 /// Health check endpoint
 async fn health_check() -> &'static str {
@@ -78,9 +90,19 @@ async fn root(State(state): State<RelayState>) -> Html<String> {
 /// WebSocket upgrade handler
 async fn ws_handler(
     ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    peer: Option<ConnectInfo<SocketAddr>>,
     State(state): State<RelayState>,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| relay::handle_websocket(socket, state))
+) -> Response {
+    // Browsers can be pointed at the relay from any page; only allowed origins may connect
+    let origin = headers.get(header::ORIGIN).map(|value| value.to_str().unwrap_or("<invalid>"));
+    if !state.origin_allowed(origin) {
+        warn!("Rejected WebSocket upgrade from origin {:?}", origin);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let peer = peer.map(|ConnectInfo(addr)| addr);
+    let connection = ConnectionInfo::from_request(&headers, peer, state.trusted_proxies());
+    ws.on_upgrade(move |socket| relay::handle_websocket(socket, state, connection))
 }
 
 /// Redirect to the install script
@@ -93,26 +115,88 @@ async fn install_ps1_redirect() -> impl IntoResponse {
     axum::response::Redirect::temporary("https://raw.githubusercontent.com/jcyrus/GhostWire/main/install.ps1")
 }
 
-/// Main Shuttle entry point
-#[shuttle_runtime::main]
-async fn main() -> shuttle_axum::ShuttleAxum {
-    // Shuttle handles tracing initialization, so we don't need to do it here
-    
-    // Create shared state
-    let state = RelayState::new();
+/// Relay state configured from the secrets (Secrets.toml): MOTD and
+/// ALLOWED_ORIGINS set the message of the day and the browser origins allowed
+/// to connect, ADMIN_TOKEN enables the admin API, OFFLINE_QUEUE holds that
+/// many DMs per offline user for OFFLINE_TTL_SECS, DAILY_QUOTA_BYTES caps
+/// each username's daily traffic, MAX_CONNECTIONS is where /readyz starts
+/// reporting not ready, TRUSTED_PROXIES is how many proxies' X-Forwarded-For
+/// entries to believe, IDLE_TIMEOUT_SECS closes connections that stay silent
+/// that long, and MIN_CLIENT_VERSION is the oldest client supported
+fn relay_state(secrets: &shuttle_runtime::SecretStore) -> RelayState {
+    let state = RelayState::new()
+        .with_motd(secrets.get("MOTD"))
+        .with_allowed_origins(secrets.get("ALLOWED_ORIGINS"))
+        .with_admin_token(secrets.get("ADMIN_TOKEN"))
+        .with_offline_queue(secrets.get("OFFLINE_QUEUE"), secrets.get("OFFLINE_TTL_SECS"))
+        .with_daily_quota(secrets.get("DAILY_QUOTA_BYTES"))
+        .with_max_connections(secrets.get("MAX_CONNECTIONS"))
+        .with_trusted_proxies(secrets.get("TRUSTED_PROXIES"))
+        .with_idle_timeout(secrets.get("IDLE_TIMEOUT_SECS"))
+        .with_min_client_version(secrets.get("MIN_CLIENT_VERSION"));
+    // Shuttle binds the listener once we return the router, and requests
+    // can only reach the probes through it
+    state.set_listening();
+    // Listening for the stop signal replaces its default action, so exit
+    // once clients are told the relay is going away
+    let closing = state.clone();
+    tokio::spawn(async move {
+        closing.shutdown_on_signal().await;
+        std::process::exit(0);
+    });
+    state
+}
 
-    // Build the router
+/// Routes served by the relay
+fn router(state: RelayState) -> Router {
     let router = Router::new()
         .route("/", get(root))
         .route("/health", get(health_check))
+        .route("/healthz", get(health::liveness))
+        .route("/readyz", get(health::readiness))
         .route("/ws", get(ws_handler))
+        .route("/stats", get(admin::stats))
+        .route("/admin/clients", get(admin::clients))
+        .route("/admin/clients/:id/kick", post(admin::kick))
         .route("/install", get(install_redirect))
-        .route("/install.ps1", get(install_ps1_redirect))
+        .route("/install.ps1", get(install_ps1_redirect));
+    // The browser client at /app
+    #[cfg(feature = "web")]
+    let router = router.merge(web::routes());
+    router
         .with_state(state)
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(DefaultMakeSpan::default().include_headers(true)),
-        );
+        )
+        // Applied before tracing, so relay tokens are logged as "Sensitive"
+        .layer(SetSensitiveRequestHeadersLayer::new([axum::http::header::AUTHORIZATION]))
+}
 
-    Ok(router.into())
+/// Main Shuttle entry point
+#[cfg(not(feature = "postgres"))]
+#[shuttle_runtime::main]
+async fn main(#[shuttle_runtime::Secrets] secrets: shuttle_runtime::SecretStore) -> shuttle_axum::ShuttleAxum {
+    // Shuttle handles tracing initialization, so we don't need to do it here
+    Ok(router(relay_state(&secrets)).into())
+}
+
+/// Main Shuttle entry point, keeping held DMs in the shared Postgres
+/// database so they survive redeploys
+#[cfg(feature = "postgres")]
+#[shuttle_runtime::main]
+async fn main(
+    #[shuttle_runtime::Secrets] secrets: shuttle_runtime::SecretStore,
+    #[shuttle_shared_db::Postgres] pool: sqlx::PgPool,
+) -> shuttle_axum::ShuttleAxum {
+    let store = store::Store::open(pool).await?;
+    let held = store.held().await?;
+    let probe = store.clone();
+    let state = relay_state(&secrets)
+        .with_held_store(held, store.spawn())
+        .with_store_probe(Arc::new(move || {
+            let store = probe.clone();
+            Box::pin(async move { store.ping().await })
+        }));
+    Ok(router(state).into())
 }
@@ -1,12 +1,46 @@
 // GhostWire Server - WebSocket Relay
 // This module implements the "dumb relay" - it broadcasts messages without understanding them
 
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket};
+use axum::http::HeaderMap;
+use futures::future::BoxFuture;
 use futures::{stream::StreamExt, SinkExt};
-use std::collections::HashMap;
+use ghostwire_protocol::chunk::{Chunk, MAX_FRAME_LEN};
+use ghostwire_protocol::clock;
+use ghostwire_protocol::decode::decode_frame;
+use ghostwire_protocol::hello::{compare_versions, ServerInfo, PROTOCOL_VERSION};
+use ghostwire_protocol::validate::{validate_channel_id, validate_username};
+use ghostwire_protocol::whois::WhoisInfo;
+use ghostwire_protocol::{
+    encode_roster, MessageMeta, MessageType, WireMessage, CLIENT_VERSION_HEADER, CLOSE_IDLE_TIMEOUT, MOTD_SENDER,
+    QUEUED_SENDER, UNDELIVERED_SENDER,
+};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, RwLock};
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, field, info, info_span, warn, Instrument, Span};
+
+/// Relay name advertised in HELLO
+const RELAY_NAME: &str = "GhostWire Relay";
+
+/// How long DMs are held for offline users unless configured otherwise
+const DEFAULT_OFFLINE_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Share of the daily quota, in percent, at which a client is warned
+const QUOTA_WARN_PERCENT: u64 = 80;
+
+/// How long to wait for queued frames (e.g. a rejection notice) to flush on disconnect
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Senders of frames the relay itself writes; clients may not use them
+const RELAY_SENDERS: &[&str] = &["SYSTEM", MOTD_SENDER, UNDELIVERED_SENDER, QUEUED_SENDER];
+
+/// Longest close reason a WebSocket close frame can carry, in bytes
+const MAX_CLOSE_REASON: usize = 123;
 
 /// Unique identifier for each connected client
 pub type ClientId = usize;
@@ -20,13 +54,470 @@ pub struct BroadcastMessage {
     pub content: String,
 }
 
+/// What the upgrade request told us about a connecting client
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionInfo {
+    /// Peer address, or the `X-Forwarded-For` hop added by the outermost
+    /// trusted proxy
+    pub remote_ip: Option<IpAddr>,
+    /// Client build from the `x-ghostwire-client` header
+    pub client_version: Option<String>,
+}
+
+impl ConnectionInfo {
+    /// Gather connection details from the upgrade request. `peer` is absent
+    /// where the listener does not report it (Unix sockets, Shuttle's proxy).
+    ///
+    /// Clients can send any `X-Forwarded-For` they like, so it is only read
+    /// behind `trusted_proxies` proxies, each of which appends the address it
+    /// got the request from: the client's is then that many hops from the
+    /// right, and anything further left is the client's own claim.
+    pub fn from_request(headers: &HeaderMap, peer: Option<SocketAddr>, trusted_proxies: usize) -> Self {
+        let forwarded = || {
+            if trusted_proxies == 0 {
+                return None;
+            }
+            let hops: Vec<&str> = headers
+                .get_all("x-forwarded-for")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .collect();
+            hops.len().checked_sub(trusted_proxies).and_then(|index| hops[index].parse().ok())
+        };
+        let client_version = headers
+            .get(CLIENT_VERSION_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|version| version.chars().take(64).collect());
+        Self {
+            remote_ip: forwarded().or(peer.map(|peer| peer.ip())),
+            client_version,
+        }
+    }
+}
+
+/// Per-client metadata, served by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientInfo {
+    pub id: ClientId,
+    /// Username from AUTH (or the latest rename); `None` until authenticated
+    pub username: Option<String>,
+    /// Unix seconds
+    pub connected_at: i64,
+    pub remote_ip: Option<IpAddr>,
+    pub client_version: Option<String>,
+    /// Text frames received from this client
+    pub messages_received: u64,
+    pub bytes_received: u64,
+    /// Bytes of text frames sent to this client
+    pub bytes_sent: u64,
+    /// Bytes this username moved through the relay today (UTC), in and
+    /// out; only counted when a daily quota is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_today: Option<u64>,
+}
+
+/// Aggregate relay statistics, served by `/stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayStats {
+    pub version: &'static str,
+    pub uptime_secs: u64,
+    pub clients: usize,
+    /// Clients that have sent AUTH
+    pub authenticated: usize,
+    /// Text frames received since startup
+    pub messages_received: u64,
+    pub bytes_received: u64,
+    /// Bytes of text frames sent to clients since startup
+    pub bytes_sent: u64,
+    /// Per-username daily byte quota, if one is enforced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daily_quota_bytes: Option<u64>,
+}
+
+/// Why a client frame was not relayed
+#[derive(Debug)]
+struct Rejection {
+    /// Explanation sent back to the client as a SYS message
+    reason: String,
+    /// Whether the client should be disconnected
+    disconnect: bool,
+}
+
+/// Validate the envelope of a client frame (never the payload).
+///
+/// Frames are decoded as leniently as clients decode them, so nothing a
+/// client would accept slips past these checks. Frames that do not decode are
+/// relayed untouched, keeping the relay dumb about message types it does not
+/// know. Valid frames are returned parsed so the caller can act on AUTH and WHO.
+fn validate_frame(text: &str) -> Result<Option<WireMessage>, Rejection> {
+    // Long messages arrive as CHUNK frames; anything bigger is refused
+    if text.len() > MAX_FRAME_LEN {
+        return Err(Rejection {
+            reason: format!("Message rejected: frames are limited to {} bytes", MAX_FRAME_LEN),
+            disconnect: false,
+        });
+    }
+
+    let Ok(msg) = decode_frame(text) else {
+        return Ok(None);
+    };
+
+    // A relayed KICK would disconnect every other client
+    if let MessageType::Kick = msg.msg_type {
+        return Err(Rejection {
+            reason: "Message rejected: KICK is reserved for the relay".to_string(),
+            disconnect: false,
+        });
+    }
+
+    // Clients take HELLO as the relay's banner, minimum version included
+    if let MessageType::Hello = msg.msg_type {
+        return Err(Rejection {
+            reason: "Message rejected: HELLO is reserved for the relay".to_string(),
+            disconnect: false,
+        });
+    }
+
+    // Clients tell the MOTD, delivery notices and relay messages apart by
+    // sender, whatever the frame type
+    if RELAY_SENDERS.iter().any(|reserved| reserved.eq_ignore_ascii_case(&msg.meta.sender)) {
+        return Err(Rejection {
+            reason: format!("Message rejected: the sender {} is reserved for the relay", msg.meta.sender),
+            disconnect: false,
+        });
+    }
+
+    // Receipts only make sense between the two ends of a DM
+    if matches!(msg.msg_type, MessageType::Delivered | MessageType::Read) && !msg.channel.starts_with("dm:") {
+        return Err(Rejection {
+            reason: "Message rejected: receipts are only sent in DMs".to_string(),
+            disconnect: false,
+        });
+    }
+
+    // End-to-end encryption is only used in DMs and passphrase rooms; key
+    // exchanges only in DMs
+    let dm = msg.channel.starts_with("dm:");
+    let encryptable = dm || msg.channel.starts_with("room:");
+    if (matches!(msg.msg_type, MessageType::KeyExchange) && !dm) || (msg.meta.encrypted && !encryptable) {
+        return Err(Rejection {
+            reason: "Message rejected: encryption is only used in DMs and passphrase rooms".to_string(),
+            disconnect: false,
+        });
+    }
+
+    // Only the relay marks frames as held, which exempts them from clock checks
+    if msg.meta.delayed {
+        return Err(Rejection {
+            reason: "Message rejected: delayed is set by the relay".to_string(),
+            disconnect: false,
+        });
+    }
+
+    if let MessageType::Auth = msg.msg_type {
+        validate_username(&msg.meta.sender).map_err(|e| Rejection {
+            reason: format!("Invalid username: {}", e),
+            disconnect: true,
+        })?;
+    }
+
+    if let MessageType::Rename = msg.msg_type {
+        validate_username(&msg.payload).map_err(|e| Rejection {
+            reason: format!("Invalid nickname: {}", e),
+            disconnect: false,
+        })?;
+    }
+
+    validate_channel_id(&msg.channel).map_err(|e| Rejection {
+        reason: format!("Message rejected: {}", e),
+        disconnect: false,
+    })?;
+
+    Ok(Some(msg))
+}
+
+/// Build a SYS frame from the relay itself
+pub fn system_frame(content: String) -> String {
+    relay_frame(MessageType::System, content)
+}
+
+/// What a client's send task writes to its socket
+#[derive(Debug)]
+pub(crate) enum Outbound {
+    Frame(String),
+    /// Close the connection with this code and reason; frames queued
+    /// before it are sent first
+    Close(u16, String),
+}
+
+/// Cut a close reason to what fits in a close frame, on a character boundary
+fn close_reason(mut reason: String) -> String {
+    if reason.len() > MAX_CLOSE_REASON {
+        let mut end = MAX_CLOSE_REASON;
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        reason.truncate(end);
+    }
+    reason
+}
+
+/// Build a KICK frame carrying the reason for the disconnect
+fn kick_frame(reason: &str) -> String {
+    relay_frame(MessageType::Kick, reason.to_string())
+}
+
+/// Build the message-of-the-day SYS frame
+fn motd_frame(motd: &str) -> String {
+    frame_from(MOTD_SENDER, MessageType::System, motd.to_string())
+}
+
+/// Build a notice about a DM to an offline user: `UNDELIVERED_SENDER` if it
+/// was dropped, `QUEUED_SENDER` if it is held for the recipient
+fn delivery_frame(
+    sender: &str,
+    channel: &str,
+    notice: String,
+    message_id: Option<String>,
+) -> String {
+    let msg = WireMessage {
+        msg_type: MessageType::System,
+        payload: notice,
+        channel: channel.to_string(),
+        meta: MessageMeta {
+            sender: sender.to_string(),
+            timestamp: clock::unix_millis() / 1000,
+            id: message_id,
+            seq: None,
+            delayed: false,
+            encrypted: false,
+            action: false,
+        },
+    };
+    serde_json::to_string(&msg).unwrap_or_default()
+}
+
+/// Check that a frame speaks for the client that sent it: nothing but AUTH
+/// before it has authenticated, then only under the name it authenticated
+/// as, and only in DMs it is part of. `msg` is `None` for frames that did not
+/// decode, which are checked by the sender they claim, if any.
+fn check_sender(msg: Option<&WireMessage>, text: &str, username: Option<&str>) -> Result<(), String> {
+    let Some(username) = username else {
+        return match msg {
+            Some(WireMessage { msg_type: MessageType::Auth, .. }) => Ok(()),
+            _ => Err("Message rejected: send AUTH first".to_string()),
+        };
+    };
+    let (sender, channel) = match msg {
+        // A second AUTH is answered by the caller
+        Some(WireMessage { msg_type: MessageType::Auth, .. }) => return Ok(()),
+        Some(msg) => (msg.meta.sender.clone(), Some(msg.channel.as_str())),
+        None => match claimed_sender(text) {
+            Some(sender) => (sender, None),
+            None => return Ok(()),
+        },
+    };
+    if sender != username {
+        return Err(format!("Message rejected: you are {}, not {}", username, sender));
+    }
+    if let Some((user1, user2)) = channel.and_then(|c| c.strip_prefix("dm:")).and_then(|u| u.split_once(':')) {
+        if user1 != username && user2 != username {
+            return Err("Message rejected: you are not part of this DM".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// `meta.sender` of a frame that did not decode, e.g. one of a type newer
+/// than this relay
+fn claimed_sender(text: &str) -> Option<String> {
+    let frame: serde_json::Value = serde_json::from_str(text).ok()?;
+    frame.get("meta")?.get("sender")?.as_str().map(str::to_string)
+}
+
+/// The other participant of a `dm:user1:user2` channel
+fn dm_recipient<'a>(channel: &'a str, sender: &str) -> Option<&'a str> {
+    let (user1, user2) = channel.strip_prefix("dm:")?.split_once(':')?;
+    Some(if user1 == sender { user2 } else { user1 })
+}
+
+/// Build a frame of the given type sent by the relay itself
+fn relay_frame(msg_type: MessageType, content: String) -> String {
+    frame_from("SYSTEM", msg_type, content)
+}
+
+/// Build a relay-originated frame with the given sender
+fn frame_from(sender: &str, msg_type: MessageType, content: String) -> String {
+    let timestamp = clock::unix_millis() / 1000;
+    let msg = WireMessage {
+        msg_type,
+        payload: content,
+        channel: "global".to_string(),
+        meta: MessageMeta {
+            sender: sender.to_string(),
+            timestamp,
+            id: None,
+            seq: None,
+            delayed: false,
+            encrypted: false,
+            action: false,
+        },
+    };
+    serde_json::to_string(&msg).unwrap_or_default()
+}
+
+/// Store-and-forward limits for DMs to offline users
+#[derive(Debug, Clone, Copy)]
+struct OfflineQueueConfig {
+    /// Most DMs held per recipient; further DMs are refused
+    capacity: usize,
+    /// How long a held DM is kept before it is discarded
+    ttl: Duration,
+}
+
+/// A DM frame held for an offline recipient, content untouched
+#[derive(Debug)]
+struct HeldFrame {
+    frame: String,
+    held_at: Instant,
+}
+
+/// What a daily quota is charged to. Usernames are only claimed, so each
+/// frame is charged to the client's address as well, or to its connection
+/// when the address is unknown.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum QuotaKey {
+    User(String),
+    Ip(IpAddr),
+    Connection(ClientId),
+}
+
+impl ClientInfo {
+    /// Everything this client's traffic is charged to
+    fn quota_keys(&self) -> Vec<QuotaKey> {
+        let mut keys: Vec<QuotaKey> = self.username.iter().cloned().map(QuotaKey::User).collect();
+        keys.push(match self.remote_ip {
+            Some(ip) => QuotaKey::Ip(ip),
+            None => QuotaKey::Connection(self.id),
+        });
+        keys
+    }
+}
+
+/// Bytes a username or address moved through the relay on one UTC day
+#[derive(Debug, Clone, Copy)]
+struct DailyUsage {
+    /// Days since the Unix epoch
+    day: i64,
+    bytes: u64,
+    /// Whether the client was already warned it is close to the quota
+    warned: bool,
+}
+
+/// Where a client stands against the daily quota after a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quota {
+    Within,
+    /// Crossed the warning threshold for the first time today
+    Nearing(u64),
+    /// Reached the quota; the client is disconnected
+    Exceeded(u64),
+}
+
+impl Quota {
+    /// The standing that calls for more action
+    fn worse(self, other: Quota) -> Quota {
+        match (self, other) {
+            (Quota::Exceeded(quota), _) | (_, Quota::Exceeded(quota)) => Quota::Exceeded(quota),
+            (Quota::Nearing(a), Quota::Nearing(b)) => Quota::Nearing(a.max(b)),
+            (Quota::Nearing(used), _) | (_, Quota::Nearing(used)) => Quota::Nearing(used),
+            _ => Quota::Within,
+        }
+    }
+}
+
+/// Days since the Unix epoch, in UTC
+fn today() -> i64 {
+    clock::unix_millis().div_euclid(86_400_000)
+}
+
+/// SYS warning for a client that used most of its daily quota
+fn quota_warning(used: u64, quota: u64) -> String {
+    format!(
+        "You have used {} of your {} byte daily quota; you will be disconnected at the limit (resets 00:00 UTC)",
+        used, quota
+    )
+}
+
+/// KICK reason for a client over its daily quota
+fn quota_reason(quota: u64) -> String {
+    format!("Daily quota of {} bytes reached; try again after 00:00 UTC", quota)
+}
+
+/// A change to the DMs held for offline users, for a persistent store to
+/// mirror (see `store.rs`)
+#[derive(Debug)]
+#[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+pub enum HeldChange {
+    /// A DM was held for `recipient` at `held_at` (Unix seconds)
+    Held { recipient: String, frame: String, held_at: i64 },
+    /// Everything held for `recipient` was delivered on AUTH
+    Taken { recipient: String },
+    /// Frames held before this Unix second are past the TTL
+    Expired { before: i64 },
+}
+
+/// Readiness check of a backing store: resolves once it answered
+pub type StoreProbe = Arc<dyn Fn() -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync>;
+
 /// Shared state for the relay server
 #[derive(Clone)]
 pub struct RelayState {
     /// Map of client IDs to their broadcast channels
-    clients: Arc<RwLock<HashMap<ClientId, mpsc::UnboundedSender<String>>>>,
+    clients: Arc<RwLock<HashMap<ClientId, mpsc::UnboundedSender<Outbound>>>>,
+    /// Metadata of connected clients; usernames answer WHO
+    metadata: Arc<RwLock<HashMap<ClientId, ClientInfo>>>,
     /// Counter for generating unique client IDs
     next_client_id: Arc<RwLock<ClientId>>,
+    /// Message of the day, sent to each client right after AUTH
+    motd: Option<Arc<str>>,
+    /// Oldest client version supported, advertised in HELLO
+    min_client_version: Option<String>,
+    /// Origins allowed to open a WebSocket; empty allows any
+    allowed_origins: Arc<[String]>,
+    /// Bearer token for the admin API; the API is disabled without one
+    admin_token: Option<Arc<str>>,
+    /// Store-and-forward settings; DMs to offline users are dropped without them
+    offline_queue: Option<OfflineQueueConfig>,
+    /// DMs held per offline recipient, oldest first
+    held: Arc<RwLock<HashMap<String, VecDeque<HeldFrame>>>>,
+    /// Where changes to `held` are mirrored, when a persistent store is set
+    held_sink: Option<mpsc::UnboundedSender<HeldChange>>,
+    /// Checks the store behind `held_sink` for `/readyz`
+    store_probe: Option<StoreProbe>,
+    /// Whether the entry point has its listener bound
+    listening: Arc<AtomicBool>,
+    /// Connected clients at which `/readyz` reports not ready
+    max_connections: Option<usize>,
+    /// When the relay started, in Unix seconds
+    started_at: i64,
+    /// Text frames and bytes received since startup
+    messages_received: Arc<AtomicU64>,
+    bytes_received: Arc<AtomicU64>,
+    /// Bytes of text frames sent since startup
+    bytes_sent: Arc<AtomicU64>,
+    /// Bytes each username may move per UTC day; unlimited without one
+    daily_quota: Option<u64>,
+    /// Today's usage per username and address, kept across reconnects
+    usage: Arc<RwLock<HashMap<QuotaKey, DailyUsage>>>,
+    /// Reverse proxies in front of the relay whose `X-Forwarded-For` entries
+    /// are believed; none by default
+    trusted_proxies: usize,
+    /// How long a client may go without sending a frame before it is
+    /// closed with `CLOSE_IDLE_TIMEOUT`; never without one
+    idle_timeout: Option<Duration>,
 }
 
 impl RelayState {
@@ -34,7 +525,269 @@ impl RelayState {
     pub fn new() -> Self {
         Self {
             clients: Arc::new(RwLock::new(HashMap::new())),
+            metadata: Arc::new(RwLock::new(HashMap::new())),
             next_client_id: Arc::new(RwLock::new(0)),
+            motd: None,
+            min_client_version: None,
+            allowed_origins: Arc::from([]),
+            admin_token: None,
+            offline_queue: None,
+            held: Arc::new(RwLock::new(HashMap::new())),
+            held_sink: None,
+            store_probe: None,
+            listening: Arc::new(AtomicBool::new(false)),
+            max_connections: None,
+            started_at: clock::unix_millis() / 1000,
+            messages_received: Arc::new(AtomicU64::new(0)),
+            bytes_received: Arc::new(AtomicU64::new(0)),
+            bytes_sent: Arc::new(AtomicU64::new(0)),
+            daily_quota: None,
+            usage: Arc::new(RwLock::new(HashMap::new())),
+            trusted_proxies: 0,
+            idle_timeout: None,
+        }
+    }
+
+    /// Enable the admin API with this bearer token; blank leaves it disabled
+    pub fn with_admin_token(mut self, token: Option<String>) -> Self {
+        self.admin_token = token
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty())
+            .map(Arc::from);
+        self
+    }
+
+    /// Hold up to `capacity` DMs per offline recipient for `ttl_secs`
+    /// (default one day). A missing, zero or invalid capacity leaves
+    /// store-and-forward off.
+    pub fn with_offline_queue(mut self, capacity: Option<String>, ttl_secs: Option<String>) -> Self {
+        let Some(capacity) = capacity.filter(|c| !c.trim().is_empty()) else {
+            return self;
+        };
+        let capacity = match capacity.trim().parse::<usize>() {
+            Ok(0) => return self,
+            Ok(capacity) => capacity,
+            Err(e) => {
+                warn!("Ignoring offline queue capacity {:?}: {}", capacity, e);
+                return self;
+            }
+        };
+        let ttl_secs = match ttl_secs.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+            None => DEFAULT_OFFLINE_TTL_SECS,
+            Some(ttl) => match ttl.parse::<u64>() {
+                Ok(ttl) if ttl > 0 => ttl,
+                _ => {
+                    warn!("Ignoring offline queue TTL {:?}; using {}s", ttl, DEFAULT_OFFLINE_TTL_SECS);
+                    DEFAULT_OFFLINE_TTL_SECS
+                }
+            },
+        };
+        self.offline_queue = Some(OfflineQueueConfig {
+            capacity,
+            ttl: Duration::from_secs(ttl_secs),
+        });
+        self
+    }
+
+    /// Limit each username to `bytes` of traffic (received and sent) per
+    /// UTC day. A missing, zero or invalid value leaves traffic unlimited.
+    pub fn with_daily_quota(mut self, bytes: Option<String>) -> Self {
+        let Some(bytes) = bytes.filter(|b| !b.trim().is_empty()) else {
+            return self;
+        };
+        match bytes.trim().parse::<u64>() {
+            Ok(0) => {}
+            Ok(quota) => self.daily_quota = Some(quota),
+            Err(e) => warn!("Ignoring daily quota {:?}: {}", bytes, e),
+        }
+        self
+    }
+
+    /// Mirror held DMs into a persistent store: `restored` are the frames it
+    /// kept (recipient, frame, Unix seconds held at, oldest first) and
+    /// `sink` receives every later change. Needs the offline queue, so set
+    /// this after `with_offline_queue`.
+    #[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+    pub fn with_held_store(
+        mut self,
+        restored: Vec<(String, String, i64)>,
+        sink: mpsc::UnboundedSender<HeldChange>,
+    ) -> Self {
+        let Some(config) = self.offline_queue else {
+            return self;
+        };
+        let now = clock::unix_millis() / 1000;
+        let mut held: HashMap<String, VecDeque<HeldFrame>> = HashMap::new();
+        let mut count = 0;
+        for (recipient, frame, held_at) in restored {
+            let age = Duration::from_secs((now - held_at).max(0) as u64);
+            if age >= config.ttl {
+                continue;
+            }
+            let held_at = Instant::now().checked_sub(age).unwrap_or_else(Instant::now);
+            held.entry(recipient).or_default().push_back(HeldFrame { frame, held_at });
+            count += 1;
+        }
+        info!("Restored {} held DMs for {} users", count, held.len());
+        self.held = Arc::new(RwLock::new(held));
+        self.held_sink = Some(sink);
+        self
+    }
+
+    /// Believe `X-Forwarded-For` entries added by this many reverse proxies
+    /// in front of the relay; blank, zero or invalid ignores the header
+    pub fn with_trusted_proxies(mut self, proxies: Option<String>) -> Self {
+        let Some(proxies) = proxies.filter(|p| !p.trim().is_empty()) else {
+            return self;
+        };
+        match proxies.trim().parse::<usize>() {
+            Ok(proxies) => self.trusted_proxies = proxies,
+            Err(e) => warn!("Ignoring trusted proxy count {:?}: {}", proxies, e),
+        }
+        self
+    }
+
+    /// Close connections that send no text frame for `secs` seconds; pings
+    /// and pongs do not count. A missing, zero or invalid value never does.
+    pub fn with_idle_timeout(mut self, secs: Option<String>) -> Self {
+        let Some(secs) = secs.filter(|s| !s.trim().is_empty()) else {
+            return self;
+        };
+        match secs.trim().parse::<u64>() {
+            Ok(0) => {}
+            Ok(secs) => self.idle_timeout = Some(Duration::from_secs(secs)),
+            Err(e) => warn!("Ignoring idle timeout {:?}: {}", secs, e),
+        }
+        self
+    }
+
+    /// Reverse proxies whose `X-Forwarded-For` entries are believed
+    pub fn trusted_proxies(&self) -> usize {
+        self.trusted_proxies
+    }
+
+    /// Report not ready in `/readyz` once this many clients are connected;
+    /// blank, zero or invalid leaves the count out of readiness
+    pub fn with_max_connections(mut self, max: Option<String>) -> Self {
+        let Some(max) = max.filter(|m| !m.trim().is_empty()) else {
+            return self;
+        };
+        match max.trim().parse::<usize>() {
+            Ok(0) => {}
+            Ok(max) => self.max_connections = Some(max),
+            Err(e) => warn!("Ignoring max connections {:?}: {}", max, e),
+        }
+        self
+    }
+
+    /// Check the backing store before reporting ready
+    #[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+    pub fn with_store_probe(mut self, probe: StoreProbe) -> Self {
+        self.store_probe = Some(probe);
+        self
+    }
+
+    /// Record that the listener is bound and accepting connections
+    pub fn set_listening(&self) {
+        self.listening.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the listener is bound
+    pub fn is_listening(&self) -> bool {
+        self.listening.load(Ordering::Relaxed)
+    }
+
+    /// The backing store check, if a store is configured
+    pub fn store_probe(&self) -> Option<StoreProbe> {
+        self.store_probe.clone()
+    }
+
+    /// Connected clients at which the relay reports not ready
+    pub fn max_connections(&self) -> Option<usize> {
+        self.max_connections
+    }
+
+    /// Whether an `Authorization` header grants admin access.
+    /// `None` means the admin API is disabled altogether.
+    pub fn admin_authorized(&self, authorization: Option<&str>) -> Option<bool> {
+        let expected = self.admin_token.as_deref()?;
+        let given = authorization
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        Some(constant_time_eq(given.as_bytes(), expected.as_bytes()))
+    }
+
+    /// Restrict upgrades to a comma-separated list of origins
+    /// (e.g. `https://chat.example.com`); blank allows any origin
+    pub fn with_allowed_origins(mut self, origins: Option<String>) -> Self {
+        let origins: Vec<String> = origins
+            .unwrap_or_default()
+            .split(',')
+            .map(|origin| origin.trim().trim_end_matches('/').to_string())
+            .filter(|origin| !origin.is_empty())
+            .collect();
+        self.allowed_origins = origins.into();
+        self
+    }
+
+    /// Whether a WebSocket upgrade with this `Origin` header may proceed.
+    ///
+    /// Native clients send no Origin and are always allowed; browsers always
+    /// send one, which must match the allow-list when it is configured.
+    pub fn origin_allowed(&self, origin: Option<&str>) -> bool {
+        match origin {
+            None => true,
+            Some(origin) => {
+                self.allowed_origins.is_empty()
+                    || self
+                        .allowed_origins
+                        .iter()
+                        .any(|allowed| allowed.eq_ignore_ascii_case(origin))
+            }
+        }
+    }
+
+    /// Set the message of the day; blank messages are ignored
+    pub fn with_motd(mut self, motd: Option<String>) -> Self {
+        self.motd = motd
+            .map(|motd| motd.trim().to_string())
+            .filter(|motd| !motd.is_empty())
+            .map(Arc::from);
+        self
+    }
+
+    /// Advertise the oldest supported client version (e.g. `0.2.0`) so older
+    /// clients warn their users; blank or unparsable values are ignored
+    pub fn with_min_client_version(mut self, version: Option<String>) -> Self {
+        let Some(version) = version.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) else {
+            return self;
+        };
+        if compare_versions(&version, &version).is_none() {
+            warn!("Ignoring minimum client version {:?}: expected e.g. 0.2.0", version);
+            return self;
+        }
+        self.min_client_version = Some(version);
+        self
+    }
+
+    /// Identity and capabilities advertised in HELLO
+    pub fn server_info(&self) -> ServerInfo {
+        let mut features = vec!["channels", "who", "rename", "pins", "chunks", "whois"];
+        if self.motd.is_some() {
+            features.push("motd");
+        }
+        if self.offline_queue.is_some() {
+            features.push("offline-queue");
+        }
+        if self.daily_quota.is_some() {
+            features.push("quota");
+        }
+        ServerInfo {
+            name: RELAY_NAME.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            features: features.into_iter().map(str::to_string).collect(),
+            protocol: PROTOCOL_VERSION,
+            min_client_version: self.min_client_version.clone(),
         }
     }
 
@@ -47,11 +800,36 @@ impl RelayState {
     }
 
     /// Register a new client and return their ID and receiver
-    async fn register_client(&self) -> (ClientId, mpsc::UnboundedReceiver<String>) {
+    pub(crate) async fn register_client(
+        &self,
+        connection: ConnectionInfo,
+    ) -> (ClientId, mpsc::UnboundedReceiver<Outbound>) {
         let id = self.next_id().await;
         let (tx, rx) = mpsc::unbounded_channel();
+
+        // The client warns its user itself once it reads HELLO; this is for the operator
+        if let (Some(minimum), Some(version)) = (&self.min_client_version, &connection.client_version) {
+            let bare = version.rsplit('/').next().unwrap_or(version);
+            if compare_versions(bare, minimum).is_some_and(|order| order.is_lt()) {
+                info!("Client {} runs {}, older than the supported minimum {}", id, version, minimum);
+            }
+        }
         
         self.clients.write().await.insert(id, tx);
+        self.metadata.write().await.insert(
+            id,
+            ClientInfo {
+                id,
+                username: None,
+                connected_at: clock::unix_millis() / 1000,
+                remote_ip: connection.remote_ip,
+                client_version: connection.client_version,
+                messages_received: 0,
+                bytes_received: 0,
+                bytes_sent: 0,
+                bytes_today: None,
+            },
+        );
         info!("Client {} connected. Total clients: {}", id, self.clients.read().await.len());
         
         (id, rx)
@@ -60,11 +838,84 @@ impl RelayState {
     /// Unregister a client
     async fn unregister_client(&self, id: ClientId) {
         self.clients.write().await.remove(&id);
+        self.metadata.write().await.remove(&id);
         info!("Client {} disconnected. Total clients: {}", id, self.clients.read().await.len());
     }
 
+    /// Disconnect a client, telling it why with a KICK frame and a policy
+    /// close (1008), so it does not reconnect on its own.
+    /// Returns false if no such client is connected.
+    pub async fn kick(&self, id: ClientId, reason: &str) -> bool {
+        // Queue the KICK and the close, then drop the client's sender: its
+        // send task flushes the frame, closes the socket and ends the connection
+        let Some(tx) = self.clients.write().await.remove(&id) else {
+            return false;
+        };
+        let _ = tx.send(Outbound::Frame(kick_frame(reason)));
+        let _ = tx.send(Outbound::Close(close_code::POLICY, reason.to_string()));
+        self.metadata.write().await.remove(&id);
+        info!("Kicked client {}: {}", id, reason);
+        true
+    }
+
+    /// Disconnect a client with a close code, after the frames already
+    /// queued for it
+    async fn close(&self, id: ClientId, code: u16, reason: String) {
+        if let Some(tx) = self.clients.write().await.remove(&id) {
+            let _ = tx.send(Outbound::Close(code, reason));
+        }
+        self.metadata.write().await.remove(&id);
+    }
+
+    /// Wait for Ctrl+C, or SIGTERM (what service managers, `docker stop`
+    /// and Shuttle send) on Unix, then close every connection as in `shutdown`
+    pub async fn shutdown_on_signal(&self) {
+        let ctrl_c = async {
+            if let Err(e) = tokio::signal::ctrl_c().await {
+                error!("Failed to listen for Ctrl+C: {}", e);
+                std::future::pending::<()>().await;
+            }
+        };
+        #[cfg(unix)]
+        let terminate = async {
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(mut signal) => {
+                    signal.recv().await;
+                }
+                Err(e) => {
+                    error!("Failed to listen for SIGTERM: {}", e);
+                    std::future::pending::<()>().await;
+                }
+            }
+        };
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
+
+        tokio::select! {
+            _ = ctrl_c => {}
+            _ = terminate => {}
+        }
+        info!("🛑 Shutting down");
+        self.shutdown().await;
+    }
+
+    /// Close every connection as going away (1001), which clients take as
+    /// a cue to reconnect, and give the close frames a moment to go out
+    async fn shutdown(&self) {
+        let clients: Vec<_> = self.clients.write().await.drain().collect();
+        self.metadata.write().await.clear();
+        if clients.is_empty() {
+            return;
+        }
+        info!("Closing {} connections for shutdown", clients.len());
+        for (_, tx) in clients {
+            let _ = tx.send(Outbound::Close(close_code::AWAY, String::new()));
+        }
+        tokio::time::sleep(FLUSH_TIMEOUT).await;
+    }
+
     /// Broadcast a message to all clients except the sender
-    async fn broadcast(&self, msg: BroadcastMessage) {
+    pub(crate) async fn broadcast(&self, msg: BroadcastMessage) {
         let clients = self.clients.read().await;
         let mut failed_clients = Vec::new();
 
@@ -75,74 +926,533 @@ impl RelayState {
             }
 
             // Try to send, track failures
-            if let Err(e) = tx.send(msg.content.clone()) {
+            if let Err(e) = tx.send(Outbound::Frame(msg.content.clone())) {
                 warn!("Failed to send to client {}: {}", client_id, e);
                 failed_clients.push(client_id);
             }
         }
 
-        // Clean up failed clients
+        // Clean up failed clients, metadata included, so they leave the
+        // roster, WHOIS and the admin API too
         drop(clients);
         if !failed_clients.is_empty() {
             let mut clients = self.clients.write().await;
+            let mut metadata = self.metadata.write().await;
             for client_id in failed_clients {
                 clients.remove(&client_id);
+                metadata.remove(&client_id);
                 debug!("Removed dead client {}", client_id);
             }
         }
     }
 
+    /// Send a frame to a single client
+    async fn send_to(&self, id: ClientId, content: String) {
+        if let Some(tx) = self.clients.read().await.get(&id) {
+            let _ = tx.send(Outbound::Frame(content));
+        }
+    }
+
+    /// The username a client authenticated as, if it has
+    async fn username_of(&self, id: ClientId) -> Option<String> {
+        self.metadata.read().await.get(&id).and_then(|info| info.username.clone())
+    }
+
+    /// Record the username a client authenticated as, refusing a name
+    /// another client is using, like `rename` does
+    async fn claim_username(&self, id: ClientId, username: &str) -> Result<(), String> {
+        let mut metadata = self.metadata.write().await;
+        let taken = metadata
+            .values()
+            .any(|info| info.id != id && info.username.as_deref() == Some(username));
+        if taken {
+            return Err(format!("Username {} is already in use", username));
+        }
+        if let Some(info) = metadata.get_mut(&id) {
+            info.username = Some(username.to_string());
+        }
+        Ok(())
+    }
+
+    /// Count a text frame received from a client against its totals and
+    /// daily quota
+    async fn record_frame(&self, id: ClientId, bytes: usize) -> Quota {
+        let bytes = bytes as u64;
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received.fetch_add(bytes, Ordering::Relaxed);
+        let keys = match self.metadata.write().await.get_mut(&id) {
+            Some(info) => {
+                info.messages_received += 1;
+                info.bytes_received += bytes;
+                info.quota_keys()
+            }
+            None => Vec::new(),
+        };
+        self.charge(keys, bytes).await
+    }
+
+    /// Count a text frame sent to a client against its totals and daily quota
+    async fn record_sent(&self, id: ClientId, bytes: usize) -> Quota {
+        let bytes = bytes as u64;
+        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+        let keys = match self.metadata.write().await.get_mut(&id) {
+            Some(info) => {
+                info.bytes_sent += bytes;
+                info.quota_keys()
+            }
+            None => Vec::new(),
+        };
+        self.charge(keys, bytes).await
+    }
+
+    /// Add traffic to today's usage of every key, returning the worst
+    /// standing among them. Frames before AUTH are charged to the address
+    /// alone.
+    async fn charge(&self, keys: Vec<QuotaKey>, bytes: u64) -> Quota {
+        let Some(quota) = self.daily_quota else {
+            return Quota::Within;
+        };
+        let day = today();
+        let mut usage = self.usage.write().await;
+        let mut standing = Quota::Within;
+        for key in keys {
+            let entry = usage.entry(key).or_insert(DailyUsage {
+                day,
+                bytes: 0,
+                warned: false,
+            });
+            if entry.day != day {
+                *entry = DailyUsage {
+                    day,
+                    bytes: 0,
+                    warned: false,
+                };
+            }
+            entry.bytes = entry.bytes.saturating_add(bytes);
+            let this = if entry.bytes >= quota {
+                Quota::Exceeded(quota)
+            } else if !entry.warned && entry.bytes.saturating_mul(100) >= quota.saturating_mul(QUOTA_WARN_PERCENT) {
+                entry.warned = true;
+                Quota::Nearing(entry.bytes)
+            } else {
+                Quota::Within
+            };
+            standing = standing.worse(this);
+        }
+        standing
+    }
+
+    /// The quota `username` already used up today, if any
+    async fn quota_exhausted(&self, username: &str) -> Option<u64> {
+        let quota = self.daily_quota?;
+        let usage = self.usage.read().await;
+        let used = usage.get(&QuotaKey::User(username.to_string())).filter(|u| u.day == today())?;
+        (used.bytes >= quota).then_some(quota)
+    }
+
+    /// Forget the usage of earlier days, so the map only holds today's
+    async fn expire_usage(&self) {
+        let day = today();
+        self.usage.write().await.retain(|_, u| u.day == day);
+    }
+
+    /// Act on a client's standing against the quota: warn it once when it
+    /// nears the limit, disconnect it at the limit. Returns true if the
+    /// client was disconnected.
+    async fn enforce_quota(&self, id: ClientId, quota: Quota) -> bool {
+        match quota {
+            Quota::Within => false,
+            Quota::Nearing(used) => {
+                info!("Client {} is nearing its daily quota", id);
+                if let Some(quota) = self.daily_quota {
+                    self.send_to(id, system_frame(quota_warning(used, quota))).await;
+                }
+                false
+            }
+            Quota::Exceeded(quota) => {
+                warn!("Client {} reached its daily quota", id);
+                self.kick(id, &quota_reason(quota)).await;
+                true
+            }
+        }
+    }
+
+    /// Change a client's username, refusing to rename anyone else or to take
+    /// a name another client is using
+    async fn rename(&self, id: ClientId, old: &str, new: &str) -> Result<(), String> {
+        let mut metadata = self.metadata.write().await;
+        if metadata.get(&id).and_then(|info| info.username.as_deref()) != Some(old) {
+            return Err(format!("Rename rejected: you are not {}", old));
+        }
+        let taken = metadata
+            .values()
+            .any(|info| info.id != id && info.username.as_deref() == Some(new));
+        if taken {
+            return Err(format!("Rename rejected: {} is already in use", new));
+        }
+        if let Some(info) = metadata.get_mut(&id) {
+            info.username = Some(new.to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether any connected client is authenticated as `username`
+    async fn is_online(&self, username: &str) -> bool {
+        self.metadata
+            .read()
+            .await
+            .values()
+            .any(|info| info.username.as_deref() == Some(username))
+    }
+
+    /// Hold a DM frame for an offline recipient. Returns false if
+    /// store-and-forward is off or the recipient's queue is full.
+    async fn hold(&self, recipient: &str, frame: String) -> bool {
+        let Some(config) = self.offline_queue else {
+            return false;
+        };
+        let mut held = self.held.write().await;
+        // Expire old frames everywhere, so abandoned queues do not pile up
+        held.retain(|_, queue| {
+            queue.retain(|f| f.held_at.elapsed() < config.ttl);
+            !queue.is_empty()
+        });
+        let queue = held.entry(recipient.to_string()).or_default();
+        if queue.len() >= config.capacity {
+            return false;
+        }
+        if let Some(sink) = &self.held_sink {
+            let now = clock::unix_millis() / 1000;
+            let _ = sink.send(HeldChange::Expired {
+                before: now - config.ttl.as_secs() as i64,
+            });
+            let _ = sink.send(HeldChange::Held {
+                recipient: recipient.to_string(),
+                frame: frame.clone(),
+                held_at: now,
+            });
+        }
+        queue.push_back(HeldFrame {
+            frame,
+            held_at: Instant::now(),
+        });
+        true
+    }
+
+    /// Take every unexpired DM held for `username`, oldest first
+    async fn take_held(&self, username: &str) -> Vec<String> {
+        let Some(config) = self.offline_queue else {
+            return Vec::new();
+        };
+        let queue = self.held.write().await.remove(username).unwrap_or_default();
+        if let (Some(sink), false) = (&self.held_sink, queue.is_empty()) {
+            let _ = sink.send(HeldChange::Taken {
+                recipient: username.to_string(),
+            });
+        }
+        queue
+            .into_iter()
+            .filter(|f| f.held_at.elapsed() < config.ttl)
+            .map(|f| f.frame)
+            .collect()
+    }
+
+    /// What we share about `username`'s connection with other users; the
+    /// earliest connection wins if several clients use the name
+    async fn whois(&self, username: &str) -> WhoisInfo {
+        self.metadata
+            .read()
+            .await
+            .values()
+            .filter(|info| info.username.as_deref() == Some(username))
+            .min_by_key(|info| info.connected_at)
+            .map(|info| WhoisInfo {
+                username: username.to_string(),
+                online: true,
+                connected_at: Some(info.connected_at),
+                client_version: info.client_version.clone(),
+            })
+            .unwrap_or_else(|| WhoisInfo::offline(username))
+    }
+
+    /// Usernames of all authenticated clients, sorted and deduplicated
+    async fn roster(&self) -> Vec<String> {
+        let mut roster: Vec<String> = self
+            .metadata
+            .read()
+            .await
+            .values()
+            .filter_map(|info| info.username.clone())
+            .collect();
+        roster.sort();
+        roster.dedup();
+        roster
+    }
+
     /// Get the current number of connected clients
     pub async fn client_count(&self) -> usize {
         self.clients.read().await.len()
     }
+
+    /// Metadata of every connected client, ordered by ID
+    pub async fn client_list(&self) -> Vec<ClientInfo> {
+        let mut clients: Vec<ClientInfo> = self.metadata.read().await.values().cloned().collect();
+        clients.sort_by_key(|info| info.id);
+        if self.daily_quota.is_some() {
+            let day = today();
+            let usage = self.usage.read().await;
+            for info in &mut clients {
+                info.bytes_today = Some(
+                    info.username
+                        .as_ref()
+                        .and_then(|name| usage.get(&QuotaKey::User(name.clone())))
+                        .filter(|u| u.day == day)
+                        .map_or(0, |u| u.bytes),
+                );
+            }
+        }
+        clients
+    }
+
+    /// Aggregate statistics, free of anything identifying a client
+    pub async fn stats(&self) -> RelayStats {
+        let metadata = self.metadata.read().await;
+        RelayStats {
+            version: env!("CARGO_PKG_VERSION"),
+            uptime_secs: (clock::unix_millis() / 1000 - self.started_at).max(0) as u64,
+            clients: metadata.len(),
+            authenticated: metadata.values().filter(|info| info.username.is_some()).count(),
+            messages_received: self.messages_received.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            daily_quota_bytes: self.daily_quota,
+        }
+    }
+}
+
+/// Compare secrets without leaking the position of the first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
 }
 
 /// Handle a WebSocket connection
-pub async fn handle_websocket(socket: WebSocket, state: RelayState) {
+///
+/// Everything logged for the connection happens inside a `client` span
+/// carrying its client ID, IP and (once authenticated) username.
+pub async fn handle_websocket(socket: WebSocket, state: RelayState, connection: ConnectionInfo) {
+    let span = info_span!(
+        "client",
+        client_id = field::Empty,
+        ip = connection.remote_ip.map(field::display),
+        username = field::Empty,
+    );
+    serve_client(socket, state, connection).instrument(span).await
+}
+
+/// Relay frames for one client until it disconnects
+async fn serve_client(socket: WebSocket, state: RelayState, connection: ConnectionInfo) {
     // Register this client
-    let (client_id, mut broadcast_rx) = state.register_client().await;
+    let (client_id, mut broadcast_rx) = state.register_client(connection).await;
+    let span = Span::current();
+    span.record("client_id", client_id);
+
+    // Introduce the relay before anything else is queued
+    let hello = relay_frame(MessageType::Hello, state.server_info().to_payload());
+    state.send_to(client_id, hello).await;
 
     // Split the WebSocket into sender and receiver
     let (mut ws_tx, mut ws_rx) = socket.split();
 
     // Spawn a task to forward broadcast messages to this client
     // Also send periodic pings to keep the connection alive
+    let send_state = state.clone();
     let mut send_task = tokio::spawn(async move {
+        // The first tick completes immediately, so clients get a clock sample on connect
         let mut heartbeat = tokio::time::interval(std::time::Duration::from_secs(30));
-        heartbeat.tick().await; // First tick completes immediately
         
         loop {
             tokio::select! {
-                // Send heartbeat ping
+                // Send heartbeat ping carrying the relay's clock
                 _ = heartbeat.tick() => {
-                    if ws_tx.send(Message::Ping(vec![])).await.is_err() {
+                    let now = clock::encode_millis(clock::unix_millis());
+                    if ws_tx.send(Message::Ping(now)).await.is_err() {
                         // Client disconnected
                         break;
                     }
                 }
                 
                 // Forward broadcast messages
-                Some(msg) = broadcast_rx.recv() => {
+                msg = broadcast_rx.recv() => {
+                    let msg = match msg {
+                        Some(Outbound::Frame(msg)) => msg,
+                        // Kicks, idle timeouts and shutdown say why
+                        Some(Outbound::Close(code, reason)) => {
+                            let frame = CloseFrame { code, reason: close_reason(reason).into() };
+                            let _ = ws_tx.send(Message::Close(Some(frame))).await;
+                            break;
+                        }
+                        // None: client was unregistered and every queued frame is flushed
+                        None => {
+                            let _ = ws_tx.send(Message::Close(None)).await;
+                            break;
+                        }
+                    };
+                    let len = msg.len();
                     if ws_tx.send(Message::Text(msg)).await.is_err() {
                         // Client disconnected
                         break;
                     }
+                    // A kick leaves the receiver to drain, then close, above
+                    let quota = send_state.record_sent(client_id, len).await;
+                    send_state.enforce_quota(client_id, quota).await;
                 }
-                
-                // Channel closed
-                else => break,
             }
         }
-    });
+    }.instrument(span.clone()));
 
     // Handle incoming messages from this client
     let state_clone = state.clone();
     let mut recv_task = tokio::spawn(async move {
-        while let Some(result) = ws_rx.next().await {
+        let mut last_frame = Instant::now();
+        loop {
+            let next = match state_clone.idle_timeout {
+                Some(idle) => match tokio::time::timeout(idle.saturating_sub(last_frame.elapsed()), ws_rx.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        info!("Client {} idle for {}s, closing", client_id, idle.as_secs());
+                        let reason = format!("No messages for {} seconds", idle.as_secs());
+                        state_clone.close(client_id, CLOSE_IDLE_TIMEOUT, reason).await;
+                        break;
+                    }
+                },
+                None => ws_rx.next().await,
+            };
+            let Some(result) = next else {
+                break;
+            };
             match result {
                 Ok(Message::Text(text)) => {
+                    last_frame = Instant::now();
                     debug!("Client {} sent: {} bytes", client_id, text.len());
+                    let quota = state_clone.record_frame(client_id, text.len()).await;
+                    if state_clone.enforce_quota(client_id, quota).await {
+                        break;
+                    }
+                    
+                    let msg = match validate_frame(&text) {
+                        Ok(msg) => msg,
+                        Err(rejection) => {
+                            warn!("Rejected frame from client {}: {}", client_id, rejection.reason);
+                            if rejection.disconnect {
+                                state_clone.kick(client_id, &rejection.reason).await;
+                                break;
+                            }
+                            state_clone.send_to(client_id, system_frame(rejection.reason)).await;
+                            continue;
+                        }
+                    };
+
+                    // Nobody speaks for anyone else
+                    let username = state_clone.username_of(client_id).await;
+                    if let Err(reason) = check_sender(msg.as_ref(), &text, username.as_deref()) {
+                        warn!("Rejected frame from client {}: {}", client_id, reason);
+                        state_clone.send_to(client_id, system_frame(reason)).await;
+                        continue;
+                    }
+                    
+                    match msg {
+                        Some(WireMessage { msg_type: MessageType::Auth, meta, .. }) => {
+                            // Switching names goes through RENAME, which checks ownership
+                            if let Some(current) = username {
+                                warn!("Client {}: second AUTH as {} ignored", client_id, meta.sender);
+                                let reason = format!("AUTH rejected: already authenticated as {}", current);
+                                state_clone.send_to(client_id, system_frame(reason)).await;
+                                continue;
+                            }
+                            Span::current().record("username", meta.sender.as_str());
+                            // Reconnecting does not reset the quota
+                            state_clone.expire_usage().await;
+                            if let Some(quota) = state_clone.quota_exhausted(&meta.sender).await {
+                                warn!("Client {}: {} is over its daily quota", client_id, meta.sender);
+                                state_clone.kick(client_id, &quota_reason(quota)).await;
+                                break;
+                            }
+                            if let Err(reason) = state_clone.claim_username(client_id, &meta.sender).await {
+                                warn!("Client {}: {}", client_id, reason);
+                                state_clone.kick(client_id, &reason).await;
+                                break;
+                            }
+                            let held = state_clone.take_held(&meta.sender).await;
+                            if let Some(motd) = &state_clone.motd {
+                                state_clone.send_to(client_id, motd_frame(motd)).await;
+                            }
+                            // DMs that arrived while this user was away
+                            if !held.is_empty() {
+                                info!("Client {}: delivering {} held DMs", client_id, held.len());
+                            }
+                            for frame in held {
+                                state_clone.send_to(client_id, frame).await;
+                            }
+                        }
+                        // Roster requests are answered directly, never broadcast
+                        Some(WireMessage { msg_type: MessageType::Who, .. }) => {
+                            let roster = encode_roster(&state_clone.roster().await);
+                            state_clone.send_to(client_id, relay_frame(MessageType::Who, roster)).await;
+                            continue;
+                        }
+                        // So are who-is lookups
+                        Some(WireMessage { msg_type: MessageType::Whois, payload, .. }) => {
+                            let whois = state_clone.whois(payload.trim()).await;
+                            state_clone.send_to(client_id, relay_frame(MessageType::Whois, whois.to_payload())).await;
+                            continue;
+                        }
+                        // Confirm renames to the sender too, so it only switches names once accepted
+                        Some(WireMessage { msg_type: MessageType::Rename, meta, payload, .. }) => {
+                            if let Err(reason) = state_clone.rename(client_id, &meta.sender, &payload).await {
+                                warn!("Client {}: {}", client_id, reason);
+                                state_clone.send_to(client_id, system_frame(reason)).await;
+                                continue;
+                            }
+                            info!("Client {} renamed {} -> {}", client_id, meta.sender, payload);
+                            Span::current().record("username", payload.as_str());
+                            state_clone.send_to(client_id, text.clone()).await;
+                        }
+                        // DMs to someone who is not connected would go nowhere: hold
+                        // them if store-and-forward is on, and tell the sender
+                        Some(mut msg @ WireMessage { msg_type: MessageType::Message | MessageType::Chunk, .. }) => {
+                            let recipient = dm_recipient(&msg.channel, &msg.meta.sender).map(str::to_string);
+                            if let Some(recipient) = recipient {
+                                if !state_clone.is_online(&recipient).await {
+                                    debug!("Client {}: DM recipient {} is offline", client_id, recipient);
+                                    let id = msg.meta.id.clone();
+                                    msg.meta.delayed = true;
+                                    let frame = serde_json::to_string(&msg).unwrap_or_default();
+                                    let held = state_clone.hold(&recipient, frame).await;
+                                    // One notice per chunked message, unless a later chunk is refused
+                                    let first_chunk = match msg.msg_type {
+                                        MessageType::Chunk => Chunk::from_payload(&msg.payload).is_some_and(|c| c.index == 0),
+                                        _ => true,
+                                    };
+                                    if held && !first_chunk {
+                                        continue;
+                                    }
+                                    let notice = if held {
+                                        let notice = format!(
+                                            "{} is offline; message will be delivered when they reconnect",
+                                            recipient
+                                        );
+                                        delivery_frame(QUEUED_SENDER, &msg.channel, notice, id)
+                                    } else {
+                                        let notice = format!("{} is offline; message not delivered", recipient);
+                                        delivery_frame(UNDELIVERED_SENDER, &msg.channel, notice, id)
+                                    };
+                                    state_clone.send_to(client_id, notice).await;
+                                    continue;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
                     
                     // Broadcast to all other clients
                     state_clone.broadcast(BroadcastMessage {
@@ -170,20 +1480,26 @@ pub async fn handle_websocket(socket: WebSocket, state: RelayState) {
                 }
             }
         }
-    });
+    }.instrument(span));
 
     // Wait for either task to finish (disconnect)
-    tokio::select! {
+    let recv_finished = tokio::select! {
         _ = &mut send_task => {
             debug!("Send task finished for client {}", client_id);
             recv_task.abort();
+            false
         }
         _ = &mut recv_task => {
             debug!("Recv task finished for client {}", client_id);
-            send_task.abort();
+            true
         }
-    }
+    };
 
-    // Unregister the client
+    // Unregister the client; this drops its sender so the send task can drain
     state.unregister_client(client_id).await;
+
+    // Give already-queued frames (such as a rejection notice) a moment to flush
+    if recv_finished && tokio::time::timeout(FLUSH_TIMEOUT, &mut send_task).await.is_err() {
+        send_task.abort();
+    }
 }
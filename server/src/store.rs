@@ -0,0 +1,89 @@
+// GhostWire Server - Postgres Persistence
+// Optional backend for the Shuttle build (`--features postgres`), on the
+// database `shuttle-shared-db` provisions. DMs held for offline users are
+// mirrored into it and restored on startup, so a redeploy does not drop them.
+// The relay keeps serving from memory; one task writes the changes behind it,
+// in order, and a failed write is logged rather than failing the relay.
+
+use crate::relay::HeldChange;
+use anyhow::Context;
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Held DM frames, still opaque, with their recipient and Unix hold time
+const SCHEMA: [&str; 2] = [
+    "CREATE TABLE IF NOT EXISTS held_dms (
+        id BIGSERIAL PRIMARY KEY,
+        recipient TEXT NOT NULL,
+        frame TEXT NOT NULL,
+        held_at BIGINT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS held_dms_recipient ON held_dms (recipient)",
+];
+
+/// Relay state kept in Postgres
+#[derive(Clone)]
+pub struct Store {
+    pool: PgPool,
+}
+
+impl Store {
+    /// Use `pool`, creating the tables on first start
+    pub async fn open(pool: PgPool) -> anyhow::Result<Self> {
+        for statement in SCHEMA {
+            sqlx::query(statement)
+                .execute(&pool)
+                .await
+                .context("Failed to create the held DM table")?;
+        }
+        Ok(Self { pool })
+    }
+
+    /// Whether the database answers, for readiness probes
+    pub async fn ping(&self) -> anyhow::Result<()> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .context("Postgres is unreachable")?;
+        Ok(())
+    }
+
+    /// Every held DM as (recipient, frame, held at), oldest first
+    pub async fn held(&self) -> anyhow::Result<Vec<(String, String, i64)>> {
+        sqlx::query_as("SELECT recipient, frame, held_at FROM held_dms ORDER BY id")
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to load held DMs")
+    }
+
+    /// Write one change to the held DMs
+    async fn apply(&self, change: HeldChange) -> sqlx::Result<()> {
+        let query = match change {
+            HeldChange::Held { recipient, frame, held_at } => {
+                sqlx::query("INSERT INTO held_dms (recipient, frame, held_at) VALUES ($1, $2, $3)")
+                    .bind(recipient)
+                    .bind(frame)
+                    .bind(held_at)
+            }
+            HeldChange::Taken { recipient } => {
+                sqlx::query("DELETE FROM held_dms WHERE recipient = $1").bind(recipient)
+            }
+            HeldChange::Expired { before } => sqlx::query("DELETE FROM held_dms WHERE held_at < $1").bind(before),
+        };
+        query.execute(&self.pool).await.map(|_| ())
+    }
+
+    /// Start mirroring changes; the task ends when the relay drops the sender
+    pub fn spawn(self) -> mpsc::UnboundedSender<HeldChange> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(change) = rx.recv().await {
+                if let Err(e) = self.apply(change).await {
+                    warn!("Failed to persist held DMs: {}", e);
+                }
+            }
+        });
+        tx
+    }
+}
@@ -0,0 +1,48 @@
+// GhostWire Server - Web Client
+// Serves the minimal browser client in `web/` (plain HTML, CSS and
+// JavaScript, embedded at build time) from `/app`, so users without the TUI
+// can join #global from a browser. It speaks the same wire protocol over
+// `/ws` as every other client. Left out when built without the `web` feature.
+
+use axum::{
+    http::header,
+    response::{IntoResponse, Redirect},
+    routing::get,
+    Router,
+};
+
+const INDEX_HTML: &str = include_str!("../../web/index.html");
+const APP_JS: &str = include_str!("../../web/app.js");
+const STYLE_CSS: &str = include_str!("../../web/style.css");
+
+/// Only our own scripts, styles and WebSocket; nothing inline or third-party
+const CONTENT_SECURITY_POLICY: &str =
+    "default-src 'none'; script-src 'self'; style-src 'self'; connect-src 'self' ws: wss:; base-uri 'none'; form-action 'none'";
+
+/// The client page
+async fn index() -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "text/html; charset=utf-8"),
+            (header::CONTENT_SECURITY_POLICY, CONTENT_SECURITY_POLICY),
+        ],
+        INDEX_HTML,
+    )
+}
+
+async fn script() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/javascript; charset=utf-8")], APP_JS)
+}
+
+async fn style() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/css; charset=utf-8")], STYLE_CSS)
+}
+
+/// Routes of the web client, merged into the relay's router
+pub fn routes<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new()
+        .route("/app", get(index))
+        .route("/app/", get(|| async { Redirect::permanent("/app") }))
+        .route("/app/app.js", get(script))
+        .route("/app/style.css", get(style))
+}
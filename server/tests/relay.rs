@@ -0,0 +1,286 @@
+// GhostWire Server - Relay Network Tests
+// The relay serves its WebSocket route on an ephemeral port; each test
+// connects clients to it, speaks the wire protocol and checks what the relay
+// answers and what the other clients receive, so the rules on who may send
+// what are checked end to end.
+//
+// cargo test -p ghostwire-server
+
+// The server is a binary crate, so the relay module is compiled into the
+// test directly; most of it goes unused here
+#[allow(dead_code)]
+#[path = "../src/relay.rs"]
+mod relay;
+
+use axum::extract::{State, WebSocketUpgrade};
+use axum::routing::get;
+use axum::Router;
+use futures::{SinkExt, StreamExt};
+use ghostwire_protocol::group::{GroupError, GroupEvent, GroupInfo};
+use ghostwire_protocol::{MessageMeta, MessageType, WireMessage};
+use relay::{ConnectionInfo, RelayState};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+/// How long a test waits for a connection or frame
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Start a relay with default settings and return its WebSocket URL
+async fn start_relay() -> String {
+    let app = Router::new()
+        .route(
+            "/ws",
+            get(|ws: WebSocketUpgrade, State(state): State<RelayState>| async move {
+                ws.on_upgrade(move |socket| relay::handle_websocket(socket, state, ConnectionInfo::default()))
+            }),
+        )
+        .with_state(RelayState::new());
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let url = format!("ws://{}/ws", listener.local_addr().expect("local addr"));
+    tokio::spawn(async move { axum::serve(listener, app).await.expect("serve") });
+    url
+}
+
+/// A frame as a client would send it
+fn frame(msg_type: MessageType, sender: &str, channel: &str, payload: &str) -> WireMessage {
+    WireMessage {
+        msg_type,
+        payload: payload.to_string(),
+        channel: channel.to_string(),
+        meta: MessageMeta {
+            sender: sender.to_string(),
+            timestamp: 1_700_000_000,
+            id: Some(uuid::Uuid::new_v4().to_string()),
+            seq: None,
+            delayed: false,
+            encrypted: false,
+            action: false,
+        },
+    }
+}
+
+/// One connection to the relay, seen from a client
+struct Client {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl Client {
+    /// Connect and read past the relay's HELLO
+    async fn connect(url: &str) -> Self {
+        let (ws, _) = timeout(TIMEOUT, connect_async(url))
+            .await
+            .expect("relay never answered")
+            .expect("connect");
+        let mut client = Self { ws };
+        let hello = client.recv().await;
+        assert!(matches!(hello.msg_type, MessageType::Hello));
+        client
+    }
+
+    /// Connect and authenticate as `username`. The roster request after AUTH
+    /// is answered once the AUTH is handled, so the name is claimed on return.
+    async fn authenticated(url: &str, username: &str) -> Self {
+        let mut client = Self::connect(url).await;
+        client.send(&frame(MessageType::Auth, username, "global", username)).await;
+        client.send(&frame(MessageType::Who, username, "global", "")).await;
+        let who = client.recv().await;
+        assert!(matches!(who.msg_type, MessageType::Who), "expected WHO, got {:?}", who);
+        client
+    }
+
+    async fn send(&mut self, msg: &WireMessage) {
+        self.send_text(serde_json::to_string(msg).expect("serialize")).await;
+    }
+
+    async fn send_text(&mut self, text: String) {
+        self.ws.send(Message::Text(text)).await.expect("send to relay");
+    }
+
+    /// Next text frame from the relay; heartbeats are skipped
+    async fn recv(&mut self) -> WireMessage {
+        loop {
+            let message = timeout(TIMEOUT, self.ws.next())
+                .await
+                .expect("no frame from relay")
+                .expect("relay closed the connection")
+                .expect("WebSocket error");
+            if let Message::Text(text) = message {
+                return ghostwire_protocol::decode::decode_frame(&text).expect("relay sent an invalid frame");
+            }
+        }
+    }
+
+    /// Next frame, which must be the relay refusing one of ours
+    async fn rejected(&mut self) -> String {
+        let notice = self.recv().await;
+        assert!(matches!(notice.msg_type, MessageType::System), "expected a rejection, got {:?}", notice);
+        assert_eq!(notice.meta.sender, "SYSTEM");
+        notice.payload
+    }
+}
+
+/// Connect alice and bob, and read bob's AUTH off alice's connection
+async fn alice_and_bob(url: &str) -> (Client, Client) {
+    let mut alice = Client::authenticated(url, "alice").await;
+    let bob = Client::authenticated(url, "bob").await;
+    let auth = alice.recv().await;
+    assert!(matches!(auth.msg_type, MessageType::Auth));
+    assert_eq!(auth.meta.sender, "bob");
+    (alice, bob)
+}
+
+/// Have bob send a chat message and check it is the next thing alice gets,
+/// i.e. nothing bob sent before it reached her
+async fn next_for_alice_is_bobs(alice: &mut Client, bob: &mut Client) {
+    bob.send(&frame(MessageType::Message, "bob", "global", "hello")).await;
+    let msg = alice.recv().await;
+    assert!(matches!(msg.msg_type, MessageType::Message));
+    assert_eq!(msg.meta.sender, "bob");
+    assert_eq!(msg.payload, "hello");
+}
+
+#[tokio::test]
+async fn drops_frames_before_auth() {
+    let url = start_relay().await;
+    let (mut alice, mut bob) = alice_and_bob(&url).await;
+
+    let mut carol = Client::connect(&url).await;
+    carol.send(&frame(MessageType::Message, "carol", "global", "anyone here?")).await;
+    assert!(carol.rejected().await.contains("AUTH"));
+
+    next_for_alice_is_bobs(&mut alice, &mut bob).await;
+}
+
+#[tokio::test]
+async fn refuses_frames_sent_under_another_name() {
+    let url = start_relay().await;
+    let (mut alice, mut bob) = alice_and_bob(&url).await;
+    let mut carol = Client::authenticated(&url, "carol").await;
+    // carol's AUTH reached both of them
+    assert_eq!(alice.recv().await.meta.sender, "carol");
+    assert_eq!(bob.recv().await.meta.sender, "carol");
+
+    carol.send(&frame(MessageType::Message, "bob", "global", "I am bob")).await;
+    assert!(carol.rejected().await.contains("you are carol"));
+
+    // Spelled the way lenient decoders accept it, too
+    let lowercase = r#"{"type":"msg","payload":"I am bob","channel":"global","meta":{"sender":"bob","timestamp":"1700000000"}}"#;
+    carol.send_text(lowercase.to_string()).await;
+    assert!(carol.rejected().await.contains("you are carol"));
+
+    // Nor post in a DM she is not part of, where the relay would hold it
+    carol.send(&frame(MessageType::Message, "carol", "dm:alice:bob", "psst")).await;
+    assert!(carol.rejected().await.contains("not part of this DM"));
+
+    next_for_alice_is_bobs(&mut alice, &mut bob).await;
+}
+
+#[tokio::test]
+async fn refuses_hello_from_clients() {
+    let url = start_relay().await;
+    let (mut alice, mut bob) = alice_and_bob(&url).await;
+
+    // A forged banner would show everyone an "Update needed" warning
+    let banner = r#"{"name":"GhostWire Relay","version":"9.9.9","features":[],"protocol":1,"min_client_version":"99.0.0"}"#;
+    for sender in ["SYSTEM", "bob"] {
+        bob.send(&frame(MessageType::Hello, sender, "global", banner)).await;
+        assert!(bob.rejected().await.contains("HELLO is reserved"));
+    }
+
+    next_for_alice_is_bobs(&mut alice, &mut bob).await;
+}
+
+#[tokio::test]
+async fn refuses_the_motd_sender_from_clients() {
+    let url = start_relay().await;
+    let (mut alice, mut bob) = alice_and_bob(&url).await;
+
+    // Clients show SYS frames from MOTD as the relay's message of the day
+    for sender in ["MOTD", "motd"] {
+        bob.send(&frame(MessageType::System, sender, "global", "Relay moved, connect to evil.example")).await;
+        assert!(bob.rejected().await.contains("reserved for the relay"));
+    }
+
+    next_for_alice_is_bobs(&mut alice, &mut bob).await;
+}
+
+/// Have bob forge a relay delivery notice from `sender` about alice's
+/// DM to him, which would mark it undelivered or queued on her side, and
+/// check the relay refuses it
+async fn forge_delivery_notice(sender: &str) {
+    let url = start_relay().await;
+    let (mut alice, mut bob) = alice_and_bob(&url).await;
+
+    let dm = frame(MessageType::Message, "alice", "dm:alice:bob", "ciphertext");
+    alice.send(&dm).await;
+    let received = bob.recv().await;
+    assert_eq!(received.meta.id, dm.meta.id);
+
+    let mut notice = frame(MessageType::System, sender, "dm:alice:bob", "bob is offline");
+    notice.meta.id = dm.meta.id.clone();
+    bob.send(&notice).await;
+    assert!(bob.rejected().await.contains("reserved for the relay"));
+
+    next_for_alice_is_bobs(&mut alice, &mut bob).await;
+}
+
+#[tokio::test]
+async fn refuses_forged_queued_notices() {
+    forge_delivery_notice("QUEUED").await;
+}
+
+#[tokio::test]
+async fn refuses_forged_undelivered_notices() {
+    forge_delivery_notice("UNDELIVERED").await;
+}
+
+#[tokio::test]
+async fn group_events_carry_the_real_sender() {
+    let url = start_relay().await;
+    let (mut alice, mut bob) = alice_and_bob(&url).await;
+
+    let members = vec!["bob".to_string()];
+    let create = GroupEvent::Create { members: members.clone() };
+    alice.send(&frame(MessageType::Group, "alice", "group:ops", &create.to_payload())).await;
+    assert_eq!(bob.recv().await.meta.sender, "alice");
+    let mut group = GroupInfo::new("alice", &members).expect("group");
+
+    // Members check operator rights against the sender, so bob may not
+    // borrow alice's name to change the group
+    let topic = GroupEvent::Topic { topic: "bob runs this now".to_string() };
+    bob.send(&frame(MessageType::Group, "alice", "group:ops", &topic.to_payload())).await;
+    assert!(bob.rejected().await.contains("you are bob"));
+
+    // Under his own name the event arrives as his, and alice's client drops it
+    bob.send(&frame(MessageType::Group, "bob", "group:ops", &topic.to_payload())).await;
+    let received = alice.recv().await;
+    assert_eq!(received.meta.sender, "bob");
+    let event = GroupEvent::from_payload(&received.payload).expect("group event");
+    assert!(matches!(group.apply(&received.meta.sender, &event), Err(GroupError::NotOperator(_))));
+    assert_eq!(group.topic, None);
+}
+
+#[tokio::test]
+async fn announcements_carry_the_real_sender() {
+    let url = start_relay().await;
+    let (mut alice, mut bob) = alice_and_bob(&url).await;
+    let mut carol = Client::authenticated(&url, "carol").await;
+    assert_eq!(alice.recv().await.meta.sender, "carol");
+    assert_eq!(bob.recv().await.meta.sender, "carol");
+
+    let mut group = GroupInfo::new("alice", &["bob".to_string(), "carol".to_string()]).expect("group");
+    group.apply("alice", &GroupEvent::Announce { on: true }).expect("announce");
+
+    // Only alice may post, and carol may not post under her name
+    carol.send(&frame(MessageType::Message, "alice", "group:news", "all hands at noon")).await;
+    assert!(carol.rejected().await.contains("you are carol"));
+
+    // Under her own name the post arrives as hers, and members drop it
+    carol.send(&frame(MessageType::Message, "carol", "group:news", "all hands at noon")).await;
+    let received = bob.recv().await;
+    assert_eq!(received.meta.sender, "carol");
+    assert!(!group.may_post(&received.meta.sender));
+}
@@ -0,0 +1,298 @@
+// GhostWire Web Client
+// A minimal browser client for #global, served by the relay from /app. It
+// speaks the same wire protocol as the terminal client over /ws: AUTH on
+// connect, WHO for the roster, MSG to chat, and CHUNK reassembly for long
+// messages. Encrypted frames and other channels are left to the TUI.
+
+'use strict';
+
+// Must match protocol/src/validate.rs and protocol/src/chunk.rs
+const MAX_NAME_LEN = 32;
+const RESERVED_USERNAMES = ['system', 'server', 'relay', 'admin', 'everyone', 'here', 'motd', 'undelivered', 'queued'];
+const MAX_FRAME_LEN = 16 * 1024;
+const MAX_CHUNKS = 64;
+
+// Frames from the relay itself carry this sender
+const RELAY_SENDER = 'SYSTEM';
+const MOTD_SENDER = 'MOTD';
+
+const CHANNEL = 'global';
+const WHO_INTERVAL_MS = 30 * 1000;
+const MAX_RECONNECT_DELAY_MS = 30 * 1000;
+const MAX_MESSAGES = 1000;
+
+const $ = (id) => document.getElementById(id);
+
+const state = {
+    username: null,
+    socket: null,
+    seq: 0,
+    roster: new Set(),
+    seen: new Set(),
+    chunks: new Map(),
+    kicked: false,
+    reconnectDelay: 1000,
+    whoTimer: null,
+};
+
+// Why a username is refused, or null if it is valid
+function usernameError(name) {
+    if (!name) return 'Username must not be empty';
+    if (name.length > MAX_NAME_LEN) return `Username must be at most ${MAX_NAME_LEN} characters`;
+    if (!/^[A-Za-z0-9_.-]+$/.test(name)) return "Use letters, digits, '_', '-' or '.'";
+    if (RESERVED_USERNAMES.includes(name.toLowerCase())) return `"${name}" is a reserved name`;
+    return null;
+}
+
+// Strip control and bidi override characters, as the TUI does
+function sanitize(text) {
+    return String(text).replace(/[\u0000-\u0008\u000b-\u001f\u007f-\u009f\u202a-\u202e\u2066-\u2069]/g, '');
+}
+
+// A wire frame of `type` from us
+function frame(type, payload, meta = {}) {
+    return JSON.stringify({
+        type: { type },
+        payload,
+        channel: CHANNEL,
+        meta: {
+            sender: state.username,
+            timestamp: Math.floor(Date.now() / 1000),
+            id: crypto.randomUUID(),
+            ...meta,
+        },
+    });
+}
+
+function send(text) {
+    if (state.socket && state.socket.readyState === WebSocket.OPEN) {
+        state.socket.send(text);
+    }
+}
+
+function setStatus(text, online) {
+    const status = $('status');
+    status.textContent = text;
+    status.className = online ? 'online' : 'offline';
+}
+
+// Append a line to the message log, dropping the oldest past the limit
+function appendLine(parts, className) {
+    const log = $('messages');
+    const atBottom = log.scrollHeight - log.scrollTop - log.clientHeight < 4;
+    const line = document.createElement('p');
+    if (className) line.className = className;
+    for (const [text, partClass] of parts) {
+        const span = document.createElement('span');
+        if (partClass) span.className = partClass;
+        span.textContent = text;
+        line.append(span);
+    }
+    log.append(line);
+    while (log.childElementCount > MAX_MESSAGES) {
+        log.firstElementChild.remove();
+    }
+    if (atBottom) log.scrollTop = log.scrollHeight;
+}
+
+function timeOf(seconds) {
+    const date = seconds ? new Date(seconds * 1000) : new Date();
+    return date.toTimeString().slice(0, 5);
+}
+
+function systemLine(text) {
+    appendLine([[`${timeOf()} `, 'time'], [`*** ${sanitize(text)}`, null]], 'system');
+}
+
+function chatLine(msg) {
+    const sender = sanitize(msg.meta.sender);
+    const text = sanitize(msg.payload);
+    const self = sender === state.username ? 'self' : null;
+    const time = [`${timeOf(msg.meta.timestamp)} `, 'time'];
+    if (msg.meta.action) {
+        appendLine([time, ['* ', null], [sender, 'sender'], [` ${text}`, null]], self);
+    } else {
+        appendLine([time, [sender, 'sender'], [`: ${text}`, null]], self);
+    }
+}
+
+function renderRoster() {
+    const list = $('roster');
+    list.replaceChildren(
+        ...[...state.roster].sort().map((name) => {
+            const item = document.createElement('li');
+            item.textContent = name === state.username ? `${name} (you)` : name;
+            return item;
+        }),
+    );
+    $('roster-count').textContent = `(${state.roster.size})`;
+}
+
+// Handle a CHUNK payload; returns the reassembled MSG frame once complete
+function reassemble(payload) {
+    let chunk;
+    try {
+        chunk = JSON.parse(payload);
+    } catch {
+        return null;
+    }
+    if (!chunk.id || !(chunk.total > 0 && chunk.total <= MAX_CHUNKS) || !(chunk.index < chunk.total)) {
+        return null;
+    }
+    const parts = state.chunks.get(chunk.id) || new Array(chunk.total);
+    parts[chunk.index] = chunk.data;
+    state.chunks.set(chunk.id, parts);
+    // Sparse arrays skip holes in filter, so this counts received slices
+    if (parts.filter((part) => part !== undefined).length < chunk.total) {
+        return null;
+    }
+    state.chunks.delete(chunk.id);
+    return parts.join('');
+}
+
+function handleFrame(text) {
+    let msg;
+    try {
+        msg = JSON.parse(text);
+    } catch {
+        return;
+    }
+    const type = msg.type && msg.type.type;
+    if (!type || !msg.meta) return;
+
+    switch (type) {
+        case 'HELLO': {
+            if (msg.meta.sender !== RELAY_SENDER) return;
+            try {
+                const info = JSON.parse(msg.payload);
+                setStatus(`${sanitize(info.name)} ${sanitize(info.version)}`, true);
+            } catch {
+                setStatus('CONNECTED', true);
+            }
+            break;
+        }
+        case 'KICK':
+            if (msg.meta.sender !== RELAY_SENDER) return;
+            state.kicked = true;
+            systemLine(`Disconnected by the relay: ${msg.payload}`);
+            break;
+        case 'SYS':
+            if (msg.meta.sender === MOTD_SENDER) {
+                for (const line of msg.payload.split('\n')) systemLine(line);
+            } else if (msg.channel === CHANNEL || msg.meta.sender === RELAY_SENDER) {
+                systemLine(msg.payload);
+            }
+            break;
+        case 'WHO':
+            if (msg.meta.sender !== RELAY_SENDER) return;
+            state.roster = new Set(msg.payload.split(',').filter(Boolean));
+            state.roster.add(state.username);
+            renderRoster();
+            break;
+        case 'AUTH':
+            if (!usernameError(msg.meta.sender) && !state.roster.has(msg.meta.sender)) {
+                state.roster.add(msg.meta.sender);
+                renderRoster();
+                systemLine(`${msg.meta.sender} joined`);
+            }
+            break;
+        case 'RENAME':
+            if (!usernameError(msg.payload)) {
+                state.roster.delete(msg.meta.sender);
+                state.roster.add(msg.payload);
+                renderRoster();
+                systemLine(`${msg.meta.sender} is now ${msg.payload}`);
+            }
+            break;
+        case 'CHUNK': {
+            if (msg.channel !== CHANNEL) return;
+            const whole = reassemble(msg.payload);
+            if (whole) handleFrame(whole);
+            break;
+        }
+        case 'MSG':
+            // Encrypted payloads only open in the terminal client
+            if (msg.channel !== CHANNEL || msg.meta.encrypted) return;
+            // Relays can repeat a message; its CHUNKs share its ID, so only
+            // whole messages are checked
+            if (msg.meta.id) {
+                if (state.seen.has(msg.meta.id)) return;
+                state.seen.add(msg.meta.id);
+            }
+            chatLine(msg);
+            break;
+        default:
+            break;
+    }
+}
+
+function connect() {
+    const scheme = location.protocol === 'https:' ? 'wss' : 'ws';
+    const socket = new WebSocket(`${scheme}://${location.host}/ws`);
+    state.socket = socket;
+    setStatus('CONNECTING', false);
+
+    socket.addEventListener('open', () => {
+        state.reconnectDelay = 1000;
+        send(frame('AUTH', state.username));
+        send(frame('WHO', ''));
+        clearInterval(state.whoTimer);
+        state.whoTimer = setInterval(() => send(frame('WHO', '')), WHO_INTERVAL_MS);
+        $('input').focus();
+    });
+
+    socket.addEventListener('message', (event) => {
+        if (typeof event.data === 'string') handleFrame(event.data);
+    });
+
+    socket.addEventListener('close', () => {
+        clearInterval(state.whoTimer);
+        state.roster.clear();
+        renderRoster();
+        // Like the TUI, never reconnect on our own after a KICK
+        if (state.kicked) {
+            setStatus('KICKED', false);
+            return;
+        }
+        const delay = state.reconnectDelay;
+        state.reconnectDelay = Math.min(delay * 2, MAX_RECONNECT_DELAY_MS);
+        setStatus(`RECONNECTING in ${Math.round(delay / 1000)}s`, false);
+        setTimeout(connect, delay);
+    });
+}
+
+$('login').addEventListener('submit', (event) => {
+    event.preventDefault();
+    const name = $('username').value.trim();
+    const error = usernameError(name);
+    $('login-error').textContent = error || '';
+    if (error) return;
+    state.username = name;
+    $('login').hidden = true;
+    $('chat').hidden = false;
+    connect();
+});
+
+$('composer').addEventListener('submit', (event) => {
+    event.preventDefault();
+    const input = $('input');
+    const text = input.value.trim();
+    if (!text) return;
+    if (!state.socket || state.socket.readyState !== WebSocket.OPEN) {
+        systemLine('Not connected; message not sent');
+        return;
+    }
+    state.seq += 1;
+    const msg = frame('MSG', text, { seq: state.seq });
+    if (new TextEncoder().encode(msg).length > MAX_FRAME_LEN) {
+        state.seq -= 1;
+        systemLine(`Message too long (frames are limited to ${MAX_FRAME_LEN} bytes)`);
+        return;
+    }
+    send(msg);
+    // The relay does not echo our own frames back
+    const sent = JSON.parse(msg);
+    state.seen.add(sent.meta.id);
+    chatLine(sent);
+    input.value = '';
+});